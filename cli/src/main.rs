@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::process::ExitCode;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Instant;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use hex_brains_engine::arena::{Arena, Genome};
+use hex_brains_engine::core::{OUTPUT_NAMES, RngResource, SENSOR_NAMES};
+use hex_brains_engine::experiments::{Experiment, ParameterRange};
+use hex_brains_engine::events::{event_channel, EventCapacity};
+#[cfg(feature = "history_db")]
+use hex_brains_engine::history_db::{self, HistoryDb};
+use hex_brains_engine::navigation::NavigationBenchmark;
+use hex_brains_engine::neural::{Activation, InnovationTracker, NeuralNetwork, SensorInput};
+use hex_brains_engine::simulation::{EngineState, Simulation, SimulationConfig, WallLayout};
+use hex_brains_engine::snapshot::{diff_snapshots, WorldSnapshot};
+
+#[derive(Parser)]
+#[command(name = "hex_brains", about = "Headless tools for the hex brains simulation")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs every combination of the swept parameters with several seeds and prints a summary table
+    Experiment {
+        /// Parameter to sweep, as name=start..end:step or name=value, can be repeated
+        #[arg(long = "sweep", value_name = "RANGE")]
+        sweeps: Vec<ParameterRange>,
+        /// Steps simulated in every run
+        #[arg(long, default_value_t = 5_000)]
+        steps: u32,
+        /// Number of seeds every combination is run with
+        #[arg(long, default_value_t = 3)]
+        seeds: u64,
+        /// First seed, the following runs use consecutive seeds
+        #[arg(long, default_value_t = 1)]
+        first_seed: u64,
+        /// Width and height of the world
+        #[arg(long, default_value_t = 100)]
+        size: usize,
+        #[arg(long, default_value_t = 10)]
+        starting_snakes: usize,
+        /// Walls laid out in the world: none, bars, maze, rooms or spiral
+        #[arg(long, default_value = "none")]
+        walls: WallLayout,
+        /// Seed of the maze, rooms and spiral layouts
+        #[arg(long, default_value_t = 0)]
+        wall_seed: u64,
+        /// Also write the summary as csv to this file
+        #[arg(long)]
+        csv: Option<String>,
+    },
+    /// Puts saved genomes into the same world with a fixed seed and ranks them by survival and food eaten
+    Arena {
+        /// Genome files saved from the networks window
+        #[arg(required = true)]
+        genomes: Vec<PathBuf>,
+        /// Snakes spawned from every genome
+        #[arg(long, default_value_t = 5)]
+        copies: usize,
+        #[arg(long, default_value_t = 5_000)]
+        steps: u32,
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+        /// Width and height of the world
+        #[arg(long, default_value_t = 100)]
+        size: usize,
+        /// Walls laid out in the world: none, bars, maze, rooms or spiral
+        #[arg(long, default_value = "none")]
+        walls: WallLayout,
+        /// Seed of the maze, rooms and spiral layouts
+        #[arg(long, default_value_t = 0)]
+        wall_seed: u64,
+        /// Also write the ranking as csv to this file
+        #[arg(long)]
+        csv: Option<String>,
+    },
+    /// Times the decisions of many random snake brains, the first decision after a change also orders the network
+    Bench {
+        /// Networks decided with, one for every snake
+        #[arg(long, default_value_t = 5_000)]
+        networks: usize,
+        /// Random leaders the networks are copied from, copies of one leader can be run as a batch like a specie
+        #[arg(long, default_value_t = 20)]
+        species: usize,
+        /// Hidden nodes put into every network by splitting its connections
+        #[arg(long, default_value_t = 10)]
+        hidden: usize,
+        /// Decisions of every network after the first one
+        #[arg(long, default_value_t = 100)]
+        runs: usize,
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+    /// Evolves a single snake until it finds the food at the far end of a maze and prints how many generations it took
+    Maze {
+        /// Generations tried before giving up
+        #[arg(long, default_value_t = 200)]
+        generations: u32,
+        /// Mutated children tried in every generation
+        #[arg(long, default_value_t = 10)]
+        candidates: usize,
+        /// Steps every child gets to find the food
+        #[arg(long, default_value_t = 300)]
+        steps: u32,
+        /// Width and height of the world
+        #[arg(long, default_value_t = 24)]
+        size: usize,
+        /// Seed of the maze and the mutations
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+        /// Settings saved as a preset from the gui, e.g. to compare their mutation settings
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Compares two world snapshots saved from the gui and prints what changed between them
+    Diff {
+        /// The older snapshot
+        before: PathBuf,
+        /// The newer snapshot
+        after: PathBuf,
+    },
+    /// Saves and loads a snapshot and a genome of a simulated world as toml and compact binary and compares their sizes and times
+    Formats {
+        /// Snakes created, every one starts as its own specie with a leader genome in the snapshot
+        #[arg(long, default_value_t = 500)]
+        snakes: usize,
+        /// Steps simulated before the snapshot is taken
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+        /// Width and height of the world
+        #[arg(long, default_value_t = 300)]
+        size: usize,
+        /// Saves and loads timed for every format
+        #[arg(long, default_value_t = 10)]
+        repeats: usize,
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+    /// Runs a simulation and writes its stats and the births, deaths and species to a sqlite database
+    #[cfg(feature = "history_db")]
+    Record {
+        /// The database, runs written to an existing one replace the frames they repeat
+        database: PathBuf,
+        #[arg(long, default_value_t = 10_000)]
+        steps: u32,
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+        /// Frames between two rows of stats
+        #[arg(long, default_value_t = 10)]
+        stats_interval: u32,
+        /// Settings saved as a preset from the gui
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Runs sql on a database written by record and prints the rows tab separated, the tables are frames and events
+    #[cfg(feature = "history_db")]
+    Query {
+        database: PathBuf,
+        sql: String,
+    },
+}
+
+// Genomes and snapshots can be toml or compact binary
+fn load_genome(path: &PathBuf) -> Result<(String, Genome), String> {
+    let content = std::fs::read(path).map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    let genome = Genome::from_bytes(&content).map_err(|error| format!("Invalid genome {}: {}", path.display(), error))?;
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_else(|| path.display().to_string());
+    Ok((name, genome))
+}
+
+fn load_config(path: &PathBuf) -> Result<SimulationConfig, String> {
+    let content = std::fs::read_to_string(path).map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    let mut config = SimulationConfig::from_toml(&content).map_err(|error| format!("Invalid settings {}: {}", path.display(), error))?;
+    // every run of the config clamps it, this only says so before it starts
+    for problem in config.clamp() {
+        eprintln!("{}: {}, using the closest valid value", path.display(), problem);
+    }
+    Ok(config)
+}
+
+fn load_snapshot(path: &PathBuf) -> Result<WorldSnapshot, String> {
+    let content = std::fs::read(path).map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    WorldSnapshot::from_bytes(&content).map_err(|error| format!("Invalid snapshot {}: {}", path.display(), error))
+}
+
+fn write_csv(path: &str, csv: String) -> ExitCode {
+    if let Err(error) = std::fs::write(path, csv) {
+        eprintln!("Unable to write {}: {}", path, error);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn bench(networks: usize, species: usize, hidden: usize, runs: usize, seed: u64) {
+    let mut rng = RngResource::from_seed(seed).rng;
+    let mut innovation_tracker = InnovationTracker::default();
+    let leaders: Vec<NeuralNetwork> = (0..species.max(1)).map(|_| {
+        let mut network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), OUTPUT_NAMES.len(), 0.5, &mut innovation_tracker, &mut rng);
+        for _ in 0..hidden {
+            let enabled: Vec<usize> = network.connections().iter().enumerate().filter(|(_, connection)| connection.enabled).map(|(index, _)| index).collect();
+            if let Some(index) = enabled.choose(&mut rng) {
+                network.split_connection(*index, Activation::Relu, &mut innovation_tracker);
+            }
+        }
+        network
+    }).collect();
+    let networks: Vec<NeuralNetwork> = (0..networks).map(|index| leaders[index % leaders.len()].clone()).collect();
+    let inputs: Vec<SensorInput> = (0..SENSOR_NAMES.len()).map(|index| SensorInput { value: rng.gen_range(0.0..1.0), index }).collect();
+    let start = Instant::now();
+    for network in &networks {
+        network.run(inputs.clone());
+    }
+    let first = start.elapsed().as_secs_f64() / networks.len() as f64;
+    let start = Instant::now();
+    for _ in 0..runs {
+        for network in &networks {
+            network.run(inputs.clone());
+        }
+    }
+    let following = start.elapsed().as_secs_f64() / (networks.len() * runs).max(1) as f64;
+    println!("First decision after a change: {:.2} µs", first * 1e6);
+    println!("Following decisions:           {:.2} µs ({:.1}x faster)", following * 1e6, first / following);
+    let mut batches: HashMap<u64, Vec<&NeuralNetwork>> = HashMap::new();
+    for network in &networks {
+        batches.entry(network.topology()).or_default().push(network);
+    }
+    // every snake senses its inputs anyway, so they are not part of the time
+    let values: Vec<f32> = inputs.iter().map(|input| input.value).collect();
+    let slices = vec![values.as_slice(); networks.len()];
+    let start = Instant::now();
+    for _ in 0..runs {
+        for batch in batches.values() {
+            NeuralNetwork::run_batch(batch, &slices[..batch.len()]);
+        }
+    }
+    let batched = start.elapsed().as_secs_f64() / (networks.len() * runs).max(1) as f64;
+    println!("Batched decisions:             {:.2} µs ({:.1}x faster, {} batches)", batched * 1e6, following / batched, batches.len());
+}
+
+// Average seconds of a save or load
+fn time<T>(repeats: usize, mut run: impl FnMut() -> T) -> f64 {
+    let start = Instant::now();
+    for _ in 0..repeats.max(1) {
+        run();
+    }
+    start.elapsed().as_secs_f64() / repeats.max(1) as f64
+}
+
+fn formats(snakes: usize, steps: u32, size: usize, repeats: usize, seed: u64) -> Result<(), String> {
+    let (engine_events, _) = event_channel(EventCapacity::default());
+    let mut simulation = Simulation::new(format!("Formats seed {}", seed), engine_events, None, SimulationConfig { rows: size, columns: size, ..SimulationConfig::default() });
+    simulation.insert_resource(RngResource::from_seed(seed));
+    simulation.insert_resource(EngineState::default());
+    simulation.create_snakes(snakes);
+    for _ in 0..steps {
+        simulation.step();
+    }
+    let snapshot = simulation.snapshot();
+    let genome = snapshot.species.first().map(|specie| specie.leader.clone()).ok_or("Every snake died before the snapshot")?;
+    println!("{} snakes and {} species after {} steps", snapshot.snakes.len(), snapshot.species.len(), steps);
+    println!("{:<16} | {:>10} | {:>10} | {:>10}", "", "bytes", "save ms", "load ms");
+    let row = |name: &str, bytes: usize, save: f64, load: f64| println!("{:<16} | {:>10} | {:>10.3} | {:>10.3}", name, bytes, save * 1e3, load * 1e3);
+    let toml = snapshot.to_toml()?;
+    row("snapshot toml", toml.len(), time(repeats, || snapshot.to_toml()), time(repeats, || WorldSnapshot::from_toml(&toml)));
+    let binary = snapshot.to_binary()?;
+    row("snapshot binary", binary.len(), time(repeats, || snapshot.to_binary()), time(repeats, || WorldSnapshot::from_binary(&binary)));
+    let toml = genome.to_toml();
+    row("genome toml", toml.len(), time(repeats, || genome.to_toml()), time(repeats, || Genome::from_toml(&toml)));
+    let binary = genome.to_binary();
+    row("genome binary", binary.len(), time(repeats, || genome.to_binary()), time(repeats, || Genome::from_binary(&binary)));
+    Ok(())
+}
+
+#[cfg(feature = "history_db")]
+fn record(database: &std::path::Path, config: SimulationConfig, steps: u32, seed: u64) -> Result<(), String> {
+    let history = HistoryDb::create(database)?;
+    // nothing is sent while stepping manually, so the receiver can be dropped right away
+    let (engine_events, _) = event_channel(EventCapacity::default());
+    let mut simulation = Simulation::new(format!("Record seed {}", seed), engine_events, None, config.clone());
+    simulation.insert_resource(RngResource::from_seed(seed));
+    simulation.insert_resource(EngineState::default());
+    let history = history.attach(&mut simulation);
+    simulation.create_snakes(config.starting_snakes);
+    for step in 1..=steps {
+        simulation.step();
+        if step % 1000 == 0 {
+            eprintln!("{} of {} steps", step, steps);
+        }
+    }
+    let result = history.lock().unwrap().flush();
+    result
+}
+
+#[cfg(feature = "history_db")]
+fn query(database: &std::path::Path, sql: &str) -> Result<(), String> {
+    let rows = HistoryDb::open(database)?.query(sql, &[])?;
+    println!("{}", rows.columns.join("\t"));
+    for row in rows.rows {
+        println!("{}", row.iter().map(history_db::to_text).collect::<Vec<_>>().join("\t"));
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match Cli::parse().command {
+        Command::Experiment { sweeps, steps, seeds, first_seed, size, starting_snakes, walls, wall_seed, csv } => {
+            let experiment = Experiment {
+                base_config: SimulationConfig { rows: size, columns: size, starting_snakes, wall_layout: walls, wall_seed, ..SimulationConfig::default() },
+                ranges: sweeps,
+                steps,
+                seeds: (first_seed..first_seed + seeds).collect(),
+            };
+            eprintln!("Running {} combinations with {} seeds for {} steps", experiment.combinations().len(), seeds, steps);
+            let summary = experiment.run();
+            print!("{}", summary);
+            match csv {
+                Some(path) => write_csv(&path, summary.to_csv()),
+                None => ExitCode::SUCCESS,
+            }
+        }
+        Command::Arena { genomes, copies, steps, seed, size, walls, wall_seed, csv } => {
+            let genomes = match genomes.iter().map(load_genome).collect::<Result<Vec<_>, _>>() {
+                Ok(genomes) => genomes,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let arena = Arena {
+                config: SimulationConfig { rows: size, columns: size, wall_layout: walls, wall_seed, ..SimulationConfig::default() },
+                genomes,
+                copies,
+                steps,
+                seed,
+            };
+            eprintln!("Running {} genomes with {} copies each for {} steps", arena.genomes.len(), copies, steps);
+            let report = arena.run();
+            print!("{}", report);
+            match csv {
+                Some(path) => write_csv(&path, report.to_csv()),
+                None => ExitCode::SUCCESS,
+            }
+        }
+        Command::Bench { networks, species, hidden, runs, seed } => {
+            eprintln!("Running {} networks of {} species with {} hidden nodes {} times", networks, species, hidden, runs + 1);
+            bench(networks, species, hidden, runs, seed);
+            ExitCode::SUCCESS
+        }
+        Command::Maze { generations, candidates, steps, size, seed, config } => {
+            let config = match config.as_ref().map(load_config).transpose() {
+                Ok(config) => config.unwrap_or_default(),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let benchmark = NavigationBenchmark { config: SimulationConfig { rows: size, columns: size, ..config }, seed, generations, candidates, steps };
+            eprintln!("Evolving a snake through a {}x{} maze for up to {} generations of {} children", size, size, generations, candidates);
+            match benchmark.run() {
+                Ok(report) => {
+                    print!("{}", report);
+                    ExitCode::SUCCESS
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Diff { before, after } => {
+            match load_snapshot(&before).and_then(|before| load_snapshot(&after).map(|after| diff_snapshots(&before, &after))) {
+                Ok(diff) => {
+                    print!("{}", diff);
+                    ExitCode::SUCCESS
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Formats { snakes, steps, size, repeats, seed } => {
+            eprintln!("Simulating {} snakes in a {}x{} world for {} steps", snakes, size, size, steps);
+            match formats(snakes, steps, size, repeats, seed) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        #[cfg(feature = "history_db")]
+        Command::Record { database, steps, seed, stats_interval, config } => {
+            let result = config.as_ref().map(load_config).transpose().and_then(|config| {
+                let config = SimulationConfig { stats_interval, ..config.unwrap_or_default() };
+                eprintln!("Recording {} steps with seed {} to {}", steps, seed, database.display());
+                record(&database, config, steps, seed)
+            });
+            match result {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        #[cfg(feature = "history_db")]
+        Command::Query { database, sql } => {
+            match query(&database, &sql) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::events::{event_channel, EventCapacity};
+use bevy_ecs::prelude::{Component, Entity};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::compact;
+use crate::core::{RngResource, Snake, OUTPUT_NAMES};
+use crate::dna::Dna;
+use crate::lineage::MutationKind;
+use crate::neural::{NeuralNetwork, NodeType};
+use crate::simulation::{EngineState, MutationConfig, Simulation, SimulationConfig};
+
+const GENOME_TAG: &[u8; 4] = b"HXGN";
+
+// Everything needed to recreate a snake, saved and loaded as toml or compact binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Genome {
+    pub network: NeuralNetwork,
+    pub dna: Dna,
+}
+
+impl Genome {
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("genomes only contain serializable values")
+    }
+
+    // Networks saved before sensors were added still work, the new inputs are just not connected
+    pub fn from_toml(content: &str) -> Result<Genome, String> {
+        toml::from_str(content).map_err(|error| error.to_string()).and_then(Genome::validate)
+    }
+
+    // A few times smaller than toml, for saving many genomes at once
+    pub fn to_binary(&self) -> Vec<u8> {
+        compact::encode(GENOME_TAG, self).expect("genomes only contain serializable values")
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Genome, String> {
+        compact::decode(GENOME_TAG, bytes).and_then(Genome::validate)
+    }
+
+    // Reads a genome file of either format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Genome, String> {
+        if compact::is_compact(bytes, GENOME_TAG) {
+            return Genome::from_binary(bytes);
+        }
+        Genome::from_toml(std::str::from_utf8(bytes).map_err(|error| error.to_string())?)
+    }
+
+    fn validate(genome: Genome) -> Result<Genome, String> {
+        let nodes = genome.network.get_nodes();
+        let outputs = nodes.iter().filter(|node| node.node_type == NodeType::Output).count();
+        // networks saved before the transfer and signal outputs were added have fewer outputs
+        if !(4..=OUTPUT_NAMES.len()).contains(&outputs) {
+            return Err(format!("Expected 4 to {} output nodes, found {}", OUTPUT_NAMES.len(), outputs));
+        }
+        if genome.network.connections().iter().any(|connection| connection.in_node >= nodes.len() || connection.out_node >= nodes.len()) {
+            return Err("Connection to a node that does not exist".to_string());
+        }
+        if genome.dna.genes.is_empty() || genome.dna.genes.iter().any(|gene| gene.jump >= genome.dna.genes.len()) || genome.dna.current_gene >= genome.dna.genes.len() {
+            return Err("Dna genes are empty or jump outside of the dna".to_string());
+        }
+        Ok(genome)
+    }
+
+    // The child a snake gets when it splits, the genome itself stays as it is so mutations can also be previewed outside a world
+    pub fn mutate(&self, mutation: &MutationConfig, rng: &mut impl Rng) -> (Genome, Vec<MutationKind>) {
+        let mut network = self.network.clone();
+        let mut mutations = vec![];
+        if rng.gen_bool(mutation.connection_flip_chance) {
+            network.flip_random_connection(rng);
+            mutations.push(MutationKind::ConnectionFlip);
+        }
+        if rng.gen_bool(mutation.weight_perturbation_chance) {
+            network.mutate_perturb_random_connection_weight(mutation.weight_perturbation_range, mutation.perturb_disabled_connections, rng);
+            mutations.push(MutationKind::WeightPerturbation);
+        }
+        if rng.gen_bool(mutation.weight_reset_chance) {
+            network.mutate_reset_random_connection_weight(mutation.weight_reset_range, mutation.perturb_reset_connections, rng);
+            mutations.push(MutationKind::WeightReset);
+        }
+        if rng.gen_bool(mutation.prune_chance) {
+            network.prune();
+            mutations.push(MutationKind::Prune);
+        }
+        let mut dna = self.dna.clone();
+        if rng.gen_bool(mutation.dna_mutation_chance) {
+            mutations.push(MutationKind::Dna(dna.mutate(mutation, rng)));
+        }
+        // without the check the disabled default would still draw from the rng and change every run
+        if mutation.trait_mutation_chance > 0.0 && rng.gen_bool(mutation.trait_mutation_chance) {
+            mutations.push(MutationKind::Trait(dna.mutate_traits(mutation, rng)));
+        }
+        (Genome { network, dna }, mutations)
+    }
+
+    // A single line that is easy to paste into a chat
+    pub fn to_share_code(&self) -> String {
+        format!("{}{}", SHARE_CODE_PREFIX, encode_base64(self.to_toml().as_bytes()))
+    }
+
+    // Accepts share codes as well as the toml of saved genome files
+    pub fn from_share_code(content: &str) -> Result<Genome, String> {
+        let content = content.trim();
+        match content.strip_prefix(SHARE_CODE_PREFIX) {
+            Some(code) => {
+                let toml = String::from_utf8(decode_base64(code)?).map_err(|_| "Share code does not contain text".to_string())?;
+                Genome::from_toml(&toml)
+            }
+            None => Genome::from_toml(content),
+        }
+    }
+}
+
+const SHARE_CODE_PREFIX: &str = "hexbrains:";
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (index, byte)| value | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(value >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Whitespace is skipped, chat programs like to break long lines
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text.bytes().filter(|byte| !byte.is_ascii_whitespace()).take_while(|byte| *byte != b'=')
+        .map(|byte| BASE64_ALPHABET.iter().position(|digit| *digit == byte).map(|digit| digit as u32).ok_or(format!("'{}' is not valid in a share code", byte as char)))
+        .collect::<Result<Vec<u32>, String>>()?;
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (index, digit)| value | digit << (18 - 6 * index));
+        for index in 0..chunk.len().saturating_sub(1) {
+            bytes.push((value >> (16 - 8 * index)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+// Marks the snakes spawned from a genome, their offspring are not scored
+#[derive(Component)]
+struct Contestant;
+
+pub struct Arena {
+    pub config: SimulationConfig,
+    pub genomes: Vec<(String, Genome)>,
+    // snakes spawned from every genome
+    pub copies: usize,
+    pub steps: u32,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArenaScore {
+    pub name: String,
+    pub spawned: usize,
+    pub survivors: usize,
+    pub mean_survival: f32,
+    pub mean_food_eaten: f32,
+}
+
+pub struct ArenaReport {
+    pub steps: u32,
+    // best genome first
+    pub scores: Vec<ArenaScore>,
+}
+
+#[derive(Clone, Copy)]
+struct ContestantRecord {
+    survived: u32,
+    food_eaten: f32,
+    alive: bool,
+}
+
+impl Arena {
+    // All genomes share one world, so they compete for the same food
+    pub fn run(&self) -> ArenaReport {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new(format!("Arena seed {}", self.seed), engine_events, None, self.config.clone());
+        simulation.insert_resource(RngResource::from_seed(self.seed));
+        simulation.insert_resource(EngineState::default());
+        let mut records: HashMap<Entity, (usize, ContestantRecord)> = HashMap::new();
+        // copies are interleaved so no genome gets the first pick of the free places
+        for _ in 0..self.copies {
+            for (index, (_, genome)) in self.genomes.iter().enumerate() {
+                if let Some(entity) = simulation.spawn_snake(genome.network.clone(), genome.dna.clone()) {
+                    simulation.world_mut().entity_mut(entity).insert(Contestant);
+                    records.insert(entity, (index, ContestantRecord { survived: 0, food_eaten: 0.0, alive: true }));
+                }
+            }
+        }
+        let mut steps = 0;
+        while steps < self.steps && records.values().any(|(_, record)| record.alive) {
+            simulation.step();
+            steps += 1;
+            for (_, record) in records.values_mut() {
+                record.alive = false;
+            }
+            let world = simulation.world_mut();
+            for (entity, snake, _) in world.query::<(Entity, &Snake, &Contestant)>().iter(world) {
+                if let Some((_, record)) = records.get_mut(&entity) {
+                    *record = ContestantRecord { survived: steps, food_eaten: snake.energy.food_eaten, alive: true };
+                }
+            }
+        }
+        self.report(steps, records.into_values().collect())
+    }
+
+    fn report(&self, steps: u32, records: Vec<(usize, ContestantRecord)>) -> ArenaReport {
+        let mut scores: Vec<ArenaScore> = self.genomes.iter().enumerate().map(|(index, (name, _))| {
+            let records: Vec<&ContestantRecord> = records.iter().filter(|(genome, _)| *genome == index).map(|(_, record)| record).collect();
+            let mean = |value: &dyn Fn(&ContestantRecord) -> f32| if records.is_empty() { 0.0 } else { records.iter().map(|record| value(record)).sum::<f32>() / records.len() as f32 };
+            ArenaScore {
+                name: name.clone(),
+                spawned: records.len(),
+                survivors: records.iter().filter(|record| record.alive).count(),
+                mean_survival: mean(&|record| record.survived as f32),
+                mean_food_eaten: mean(&|record| record.food_eaten),
+            }
+        }).collect();
+        scores.sort_by(|a, b| b.mean_survival.total_cmp(&a.mean_survival).then(b.mean_food_eaten.total_cmp(&a.mean_food_eaten)));
+        ArenaReport { steps, scores }
+    }
+}
+
+impl ArenaReport {
+    pub fn to_csv(&self) -> String {
+        let mut csv = "rank,genome,spawned,survivors,mean_survival,mean_food_eaten\n".to_string();
+        for (rank, score) in self.scores.iter().enumerate() {
+            csv.push_str(&format!("{},{},{},{},{:.1},{:.1}\n", rank + 1, score.name, score.spawned, score.survivors, score.mean_survival, score.mean_food_eaten));
+        }
+        csv
+    }
+}
+
+impl fmt::Display for ArenaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name_width = self.scores.iter().map(|score| score.name.len()).chain(["genome".len()]).max().unwrap_or(0);
+        writeln!(f, "{:>4} | {:<name_width$} | {:>7} | {:>9} | {:>8} | {:>10}", "rank", "genome", "spawned", "survivors", "survival", "food eaten", name_width = name_width)?;
+        for (rank, score) in self.scores.iter().enumerate() {
+            writeln!(f, "{:>4} | {:<name_width$} | {:>7} | {:>9} | {:>8.1} | {:>10.1}", rank + 1, score.name, score.spawned, score.survivors, score.mean_survival, score.mean_food_eaten, name_width = name_width)?;
+        }
+        writeln!(f, "after {} steps", self.steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::SENSOR_NAMES;
+    use crate::neural::InnovationTracker;
+    use crate::dna::Traits;
+    use crate::simulation::MutationConfig;
+    use super::*;
+
+    fn random_genome(seed: u64) -> Genome {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), OUTPUT_NAMES.len(), 0.5, &mut InnovationTracker::new(), &mut rng);
+        Genome { network, dna: Dna::random(8, &MutationConfig::default(), &mut rng) }
+    }
+
+    #[test]
+    fn genome_survives_toml_round_trip() {
+        let genome = random_genome(1);
+        let loaded = Genome::from_toml(&genome.to_toml()).unwrap();
+        assert_eq!(loaded.network.connections(), genome.network.connections());
+        assert_eq!(loaded.dna.genes.len(), genome.dna.genes.len());
+        assert!(Genome::from_toml("network = 1").is_err());
+    }
+
+    #[test]
+    fn genome_survives_binary_round_trip() {
+        let genome = random_genome(2);
+        let binary = genome.to_binary();
+        assert!(binary.len() * 3 < genome.to_toml().len());
+        let loaded = Genome::from_bytes(&binary).unwrap();
+        assert_eq!(loaded.network.connections(), genome.network.connections());
+        assert_eq!(loaded.dna.genes.len(), genome.dna.genes.len());
+        assert_eq!(Genome::from_bytes(genome.to_toml().as_bytes()).unwrap().network.connections(), genome.network.connections());
+        assert!(Genome::from_binary(&binary[..binary.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn genome_survives_share_code_round_trip() {
+        let genome = random_genome(3);
+        let code = genome.to_share_code();
+        assert!(!code.contains('\n'));
+        let loaded = Genome::from_share_code(&format!("  {}\n", code)).unwrap();
+        assert_eq!(loaded.network.connections(), genome.network.connections());
+        assert_eq!(Genome::from_share_code(&genome.to_toml()).unwrap().network.connections(), genome.network.connections());
+        assert!(Genome::from_share_code("hexbrains:!!").is_err());
+        for text in ["", "a", "ab", "abc", "abcd"] {
+            assert_eq!(decode_base64(&encode_base64(text.as_bytes())).unwrap(), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn mutating_a_genome_leaves_the_parent_untouched() {
+        let genome = random_genome(4);
+        let certain = MutationConfig { connection_flip_chance: 1.0, weight_perturbation_chance: 1.0, weight_reset_chance: 1.0, prune_chance: 0.0, dna_mutation_chance: 1.0, ..MutationConfig::default() };
+        let (child, mutations) = genome.mutate(&certain, &mut StdRng::seed_from_u64(1));
+        assert_eq!(mutations[..3], [MutationKind::ConnectionFlip, MutationKind::WeightPerturbation, MutationKind::WeightReset]);
+        assert!(matches!(mutations[3], MutationKind::Dna(_)));
+        assert_ne!(child.network.connections(), genome.network.connections());
+        assert_eq!(genome.network.connections(), random_genome(4).network.connections());
+        let never = MutationConfig { connection_flip_chance: 0.0, weight_perturbation_chance: 0.0, weight_reset_chance: 0.0, prune_chance: 0.0, dna_mutation_chance: 0.0, ..MutationConfig::default() };
+        let (clone, mutations) = genome.mutate(&never, &mut StdRng::seed_from_u64(1));
+        assert!(mutations.is_empty());
+        assert_eq!(clone.network.connections(), genome.network.connections());
+    }
+
+    #[test]
+    fn trait_mutations_stay_within_their_ranges() {
+        let mut genome = random_genome(5);
+        let traits_only = MutationConfig { connection_flip_chance: 0.0, weight_perturbation_chance: 0.0, weight_reset_chance: 0.0, dna_mutation_chance: 0.0, trait_mutation_chance: 1.0, trait_mutation_range: 0.5, mobility_range: (0.8, 1.2), ..MutationConfig::default() };
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..50 {
+            let (child, mutations) = genome.mutate(&traits_only, &mut rng);
+            assert!(matches!(mutations[..], [MutationKind::Trait(_)]));
+            genome = child;
+        }
+        assert_ne!(genome.dna.traits, Traits::default());
+        assert!((0.8..=1.2).contains(&genome.dna.traits.mobility));
+        assert!((0.5..=2.0).contains(&genome.dna.traits.max_energy));
+    }
+
+    #[test]
+    fn arena_ranks_every_genome_and_is_reproducible() {
+        let arena = Arena {
+            // the chaos input is not seeded
+            config: SimulationConfig { rows: 20, columns: 20, mutation: MutationConfig { chaos_input_enabled: false, ..MutationConfig::default() }, ..SimulationConfig::default() },
+            genomes: vec![("first".to_string(), random_genome(1)), ("second".to_string(), random_genome(2))],
+            copies: 3,
+            steps: 50,
+            seed: 7,
+        };
+        let report = arena.run();
+        assert_eq!(report.scores.len(), 2);
+        assert!(report.scores.iter().all(|score| score.spawned == 3));
+        assert!(report.scores[0].mean_survival >= report.scores[1].mean_survival);
+        assert_eq!(arena.run().to_csv(), report.to_csv());
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::Read;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// Compact saves start with a tag telling what they hold and the version of the layout, then follows deflated bincode
+// bincode does not know field names, so every change to a saved struct needs a new version, older saves are then only read as toml
+const FORMAT_VERSION: u8 = 1;
+
+pub(crate) fn is_compact(bytes: &[u8], tag: &[u8; 4]) -> bool {
+    bytes.starts_with(tag)
+}
+
+pub(crate) fn encode<T: Serialize>(tag: &[u8; 4], value: &T) -> Result<Vec<u8>, String> {
+    let mut bytes = tag.to_vec();
+    bytes.push(FORMAT_VERSION);
+    let mut encoder = DeflateEncoder::new(bytes, Compression::fast());
+    bincode::serialize_into(&mut encoder, value).map_err(|error| error.to_string())?;
+    encoder.finish().map_err(|error| error.to_string())
+}
+
+pub(crate) fn decode<T: DeserializeOwned>(tag: &[u8; 4], bytes: &[u8]) -> Result<T, String> {
+    if !is_compact(bytes, tag) {
+        return Err(format!("Not a compact save of this kind, expected it to start with {}", String::from_utf8_lossy(tag)));
+    }
+    match bytes.get(tag.len()) {
+        Some(&FORMAT_VERSION) => {}
+        Some(version) => return Err(format!("Compact save of format version {}, this version of hex brains reads {}, save it as toml instead", version, FORMAT_VERSION)),
+        None => return Err("Compact save ends after its tag".to_string()),
+    }
+    let mut decoder = DeflateDecoder::new(&bytes[tag.len() + 1..]);
+    let value = bincode::deserialize_from(&mut decoder).map_err(|error| error.to_string())?;
+    // anything after the value means the save was not written by encode
+    if decoder.read(&mut [0]).map_err(|error| error.to_string())? != 0 {
+        return Err("Compact save has data after its end".to_string());
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_saves_roundtrip_and_reject_other_tags_and_versions() {
+        let value = (7u32, vec![1.5f32; 1000], "snake".to_string());
+        let bytes = encode(b"TEST", &value).unwrap();
+        // the repeated values deflate to a fraction of their 4000 bytes
+        assert!(bytes.len() < 200);
+        assert_eq!(decode::<(u32, Vec<f32>, String)>(b"TEST", &bytes).unwrap(), value);
+        assert!(decode::<(u32, Vec<f32>, String)>(b"ELSE", &bytes).is_err());
+        let mut newer = bytes.clone();
+        newer[4] = FORMAT_VERSION + 1;
+        assert!(decode::<(u32, Vec<f32>, String)>(b"TEST", &newer).unwrap_err().contains("format version"));
+        assert!(decode::<(u32, Vec<f32>, String)>(b"TEST", &bytes[..bytes.len() / 2]).is_err());
+    }
+}
@@ -1,16 +1,28 @@
 use rand::prelude::SliceRandom;
-use std::cell::RefCell;
-use std::collections::{HashMap, LinkedList, VecDeque};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use bevy_tasks::ComputeTaskPool;
 use bevy_ecs::prelude::*;
 use std::clone::Clone;
 use std::fmt::Debug;
-use bevy_ecs::query::QueryParIter;
-use tracing::{debug, info, warn};
-use crate::neural::{ConnectionGene, InnovationTracker, NeuralNetwork, SensorInput};
-use crate::simulation::{SimulationConfig, Stats};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use crate::neural::{InnovationTracker, NeuralNetwork, NodeType, SensorInput};
+use crate::simulation::{BehaviorStats, CollisionPolicy, DeathCause, EngineState, EntityLimit, FoodControl, FoodDistribution, MAX_ACTION_TEMPERATURE, MIN_EFFICIENCY, OverpopulationPolicy, RegionStats, SimulationConfig, TemperatureLayout, SimulationEventKind, SimulationEvents, Stats};
 use rand::Rng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use crate::core::Direction::{East, NorthEast, NorthWest, SouthEast, SouthWest, West};
 use crate::dna::{Dna, SegmentType};
+use crate::arena::Genome;
+use crate::lineage::MutationKind;
+use crate::profile_system;
+use crate::time::{AGING_INTERVAL, SimTime};
+use crate::disease::Infection;
+use crate::minerals::{can_build_solid, MineralMap, see_minerals};
+use crate::water::{can_enter, see_water, WaterMap};
+use crate::stats_worker::{SnakeSample, StatsSample, StatsWorker};
 
 #[derive(Component, Clone, Default)]
 #[derive(Debug)]
@@ -25,7 +37,7 @@ impl Position {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     NorthEast,
     East,
@@ -36,8 +48,9 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn random() -> Self {
-        let mut rng = rand::thread_rng();
+    pub const ALL: [Direction; 6] = [NorthEast, East, SouthEast, SouthWest, West, NorthWest];
+
+    pub fn random(rng: &mut impl Rng) -> Self {
         match rng.gen_range(0..=5) {
             0 => NorthEast,
             1 => East,
@@ -47,18 +60,134 @@ impl Direction {
             _ => NorthWest
         }
     }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            NorthEast => SouthWest,
+            East => West,
+            SouthEast => NorthWest,
+            SouthWest => NorthEast,
+            West => East,
+            NorthWest => SouthEast,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Decision {
     MoveForward,
     MoveLeft,
     MoveRight,
     Wait,
+    // stays in place and gives energy to the snake in front, only chosen when energy transfer is enabled
+    TransferEnergy,
+    // the tail leads and the whole snake crawls one hex back
+    MoveBackward,
+    // two hexes forward for twice the energy
+    Sprint,
+    // stays in place and builds a nest there, only chosen when nests are enabled
+    BuildNest,
+}
+
+impl Decision {
+    fn index(&self) -> usize {
+        match self {
+            Decision::MoveForward => 0,
+            Decision::MoveLeft => 1,
+            Decision::MoveRight => 2,
+            Decision::Wait => 3,
+            Decision::TransferEnergy => 4,
+            Decision::MoveBackward => 5,
+            Decision::Sprint => 6,
+            Decision::BuildNest => 7,
+        }
+    }
+
+    fn from_output(output: usize) -> Decision {
+        match output {
+            0 => Decision::MoveForward,
+            1 => Decision::MoveLeft,
+            2 => Decision::MoveRight,
+            4 => Decision::TransferEnergy,
+            6 => Decision::MoveBackward,
+            7 => Decision::Sprint,
+            8 => Decision::BuildNest,
+            _ => Decision::Wait
+        }
+    }
+}
+
+// Actions a brain may choose on top of moving forward, turning and waiting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActionSet {
+    pub transfer: bool,
+    pub backward: bool,
+    pub sprint: bool,
+    pub nest: bool,
+}
+
+impl ActionSet {
+    pub fn from_config(config: &SimulationConfig) -> Self {
+        ActionSet { transfer: config.energy_transfer_enabled, backward: config.backward_move_enabled, sprint: config.sprint_enabled, nest: config.nests_enabled }
+    }
+
+    // Outputs new brains get, the layer only grows past the signal output when the extra moves are enabled
+    pub fn outputs(&self) -> usize {
+        if self.nest {
+            9
+        } else if self.sprint {
+            8
+        } else if self.backward {
+            7
+        } else {
+            6
+        }
+    }
+
+    fn allows(&self, output: usize) -> bool {
+        match output {
+            0..=3 => true,
+            4 => self.transfer,
+            6 => self.backward,
+            7 => self.sprint,
+            8 => self.nest,
+            _ => false,
+        }
+    }
+}
+
+// Collected between two statistics updates and turned into BehaviorStats
+#[derive(Resource, Default)]
+pub struct BehaviorCounters {
+    decisions: [usize; 8],
+    collisions: usize,
+    head_positions: HashMap<Entity, Position>,
+}
+
+// Frames are played in rounds, every snake acts in the first one and only snakes with move potential left act in the following ones
+#[derive(Resource, Default)]
+pub struct ActionRound {
+    pub round: u32,
+}
+
+impl ActionRound {
+    fn skips(&self, snake: &Snake) -> bool {
+        self.round > 0 && snake.energy.move_potential < 1.0
+    }
+}
+
+// Where snakes died since the previous statistics update, turned into RegionStats
+#[derive(Resource, Default)]
+pub struct RegionCounters {
+    deaths: Vec<Position>,
 }
 
 pub trait Brain: Sync + Send + Debug {
-    fn decide(&self, sensory_input: Vec<f32>) -> Decision;
+    // the decision and the signal shown to other snakes, outputs of actions that are not allowed are ignored
+    // above a temperature of 0 weaker outputs get a chance too, drawn from the generator of the snake
+    fn decide(&self, sensory_input: Vec<f32>, actions: ActionSet, temperature: f32, rng: &mut StdRng) -> (Decision, f32);
+    // the same for outputs of the network that were already calculated, e.g. by a batched run
+    fn choose(&self, outputs: &[f32], actions: ActionSet, temperature: f32, rng: &mut StdRng) -> (Decision, f32);
     fn get_neural_network(&self) -> Option<&NeuralNetwork>;
 }
 
@@ -69,6 +198,7 @@ pub struct Specie {
     pub id: u32,
     pub leader: Entity,
     pub leader_network: NeuralNetwork,
+    pub leader_dna: Dna,
     pub members: VecDeque<Entity>,
 }
 
@@ -82,6 +212,9 @@ pub struct Species {
 pub struct Snake {
     pub direction: Direction,
     pub decision: Decision,
+    // what the snake did in the previous tick and whether it wanted to move but couldn't
+    pub last_decision: Decision,
+    pub last_move_blocked: bool,
     pub brain: Box<dyn Brain>,
     pub new_position: (i32, i32),
     pub last_position: (i32, i32),
@@ -92,6 +225,18 @@ pub struct Snake {
     pub dna: Dna,
     pub metabolism: Metabolism,
     pub energy: Energy,
+    // unique for the whole simulation unlike entities, 0 until the lineage registers the snake
+    pub id: u64,
+    pub parent_id: Option<u64>,
+    pub birth_mutations: Vec<MutationKind>,
+    // set by the signal output, other snakes see it when their front vision ray hits this snake
+    pub signal: f32,
+    // own action temperature once it evolves, none follows the settings
+    pub action_temperature: Option<f32>,
+    // frames spent below zero energy, the snake starves once it is past the starvation grace
+    pub starving: u32,
+    // network outputs of the last think in the order of OUTPUT_NAMES, empty for brains without a network
+    pub outputs: Vec<f32>,
 }
 
 // those change after eating or moving
@@ -102,6 +247,10 @@ pub struct Energy {
     pub plant_in_stomach: f32,
     pub energy: f32,
     pub accumulated_meat_matter_for_growth: f32,
+    // plants and meat eaten during the whole life
+    pub food_eaten: f32,
+    // picked up from mineral deposits, spent on solid segments
+    pub minerals: f32,
 }
 
 impl Default for Energy {
@@ -114,6 +263,8 @@ impl Default for Energy {
             plant_in_stomach: 0.0,
             energy: 100.0,
             accumulated_meat_matter_for_growth: 0.0,
+            food_eaten: 0.0,
+            minerals: 0.0,
         }
     }
 }
@@ -131,6 +282,10 @@ pub struct Metabolism {
     pub max_meat_in_stomach: f32,
     pub max_energy: f32,
     pub meat_matter_for_growth_production_speed: f32,
+    // a single fin lets the snake enter water
+    pub swims: bool,
+    pub eyes: u32,
+    pub noses: u32,
 }
 
 impl Default for Metabolism {
@@ -148,6 +303,9 @@ impl Default for Metabolism {
             max_meat_in_stomach: 0.0,
             max_energy: 400.0,
             meat_matter_for_growth_production_speed: 5.0,
+            swims: false,
+            eyes: 0,
+            noses: 0,
         }
     }
 }
@@ -158,6 +316,12 @@ pub struct Solid;
 #[derive(Component)]
 pub struct JustBorn;
 
+// Snake steered by the player, its brain is skipped and the decision comes from the gui
+#[derive(Component)]
+pub struct Controlled {
+    pub decision: Decision,
+}
+
 #[derive(Debug)]
 pub struct RandomBrain;
 
@@ -173,14 +337,19 @@ pub struct Age {
 }
 
 impl Brain for RandomBrain {
-    fn decide(&self, _: Vec<f32>) -> Decision {
+    fn decide(&self, _: Vec<f32>, _: ActionSet, _: f32, _: &mut StdRng) -> (Decision, f32) {
         let mut rng = rand::thread_rng();
-        match rng.gen_range(0..=3) {
+        let decision = match rng.gen_range(0..=3) {
             0 => Decision::MoveForward,
             1 => Decision::MoveLeft,
             2 => Decision::MoveRight,
             _ => Decision::Wait
-        }
+        };
+        (decision, 0.0)
+    }
+
+    fn choose(&self, _: &[f32], actions: ActionSet, temperature: f32, rng: &mut StdRng) -> (Decision, f32) {
+        self.decide(vec![], actions, temperature, rng)
     }
 
     fn get_neural_network(&self) -> Option<&NeuralNetwork> {
@@ -189,8 +358,8 @@ impl Brain for RandomBrain {
 }
 
 impl RandomNeuralBrain {
-    pub(crate) fn new(innovation_tracker: &mut InnovationTracker) -> Self {
-        let neural_network = NeuralNetwork::random_brain(18, 0.1, innovation_tracker);
+    pub(crate) fn new(outputs: usize, innovation_tracker: &mut InnovationTracker, rng: &mut impl Rng) -> Self {
+        let neural_network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), outputs, 0.1, innovation_tracker, rng);
         Self {
             neural_network
         }
@@ -202,29 +371,49 @@ impl RandomNeuralBrain {
     }
 }
 
+// Picks an output with a chance growing with its value, the higher the temperature the closer the chances get to each other
+fn softmax_choice(outputs: &[(usize, f32)], temperature: f32, rng: &mut StdRng) -> usize {
+    let max_value = outputs.iter().map(|(_, value)| *value).fold(f32::MIN, f32::max);
+    let weights: Vec<f32> = outputs.iter().map(|(_, value)| ((value - max_value) / temperature).exp()).collect();
+    let mut pick = rng.gen_range(0.0..weights.iter().sum::<f32>());
+    for ((index, _), weight) in outputs.iter().zip(weights) {
+        if pick < weight {
+            return *index;
+        }
+        pick -= weight;
+    }
+    outputs.last().map_or(0, |(index, _)| *index)
+}
+
 impl Brain for RandomNeuralBrain {
-    fn decide(&self, sensor_input: Vec<f32>) -> Decision {
+    fn decide(&self, sensor_input: Vec<f32>, actions: ActionSet, temperature: f32, rng: &mut StdRng) -> (Decision, f32) {
         debug!("Neural network input: {:?}", sensor_input);
         let sensor_input = sensor_input.iter().enumerate().map(|(index, value)| SensorInput { index, value: *value }).collect();
         let output = self.neural_network.run(sensor_input);
-        // return the index with the maximum value of the output vector
-        let mut max_index = 0;
-        let mut max_value = 0.0;
-        for (index, value) in output.iter().enumerate() {
-            if *value > max_value {
-                max_value = *value;
-                max_index = index;
+        self.choose(&output, actions, temperature, rng)
+    }
+
+    fn choose(&self, output: &[f32], actions: ActionSet, temperature: f32, rng: &mut StdRng) -> (Decision, f32) {
+        let allowed: Vec<(usize, f32)> = output.iter().copied().enumerate().filter(|(index, _)| actions.allows(*index)).collect();
+        let chosen = if temperature > 0.0 {
+            softmax_choice(&allowed, temperature, rng)
+        } else {
+            // return the index with the maximum value of the output vector
+            let mut max_index = 0;
+            let mut max_value = 0.0;
+            for (index, value) in allowed {
+                if value > max_value {
+                    max_value = value;
+                    max_index = index;
+                }
             }
-        }
-        let decision = match max_index {
-            0 => Decision::MoveForward,
-            1 => Decision::MoveLeft,
-            2 => Decision::MoveRight,
-            _ => Decision::Wait
+            max_index
         };
+        let decision = Decision::from_output(chosen);
         debug!("Network architecture: {:?}", self.neural_network.get_active_connections());
         debug!("Output: {:?}, decision: {:?}", output, decision);
-        decision
+        // networks saved before the signal output was added never signal
+        (decision, output.get(5).copied().unwrap_or(0.0))
     }
 
     fn get_neural_network(&self) -> Option<&NeuralNetwork> {
@@ -232,6 +421,17 @@ impl Brain for RandomNeuralBrain {
     }
 }
 
+// Cells are stored row by row, positions outside of the map are either rejected (checked getters) or wrapped around like the world (wrapped getters)
+fn cell_index(width: usize, height: usize, position: &Position) -> Option<usize> {
+    let inside = (0..width as i32).contains(&position.x) && (0..height as i32).contains(&position.y);
+    inside.then(|| position.y as usize * width + position.x as usize)
+}
+
+pub(crate) fn wrap_position(width: usize, height: usize, position: &Position) -> Position {
+    Position { x: position.x.rem_euclid(width as i32), y: position.y.rem_euclid(height as i32) }
+}
+
+#[derive(Clone)]
 pub struct Map2d<T> {
     pub map: Vec<T>,
     pub width: usize,
@@ -252,21 +452,49 @@ impl<T: Default + Clone> Map2d<T> {
         }
     }
 
+    // a position outside of the map would silently land in another row, so it is a bug of the caller
     fn index(&self, position: &Position) -> usize {
-        (position.x * self.width as i32 + position.y) as usize
+        cell_index(self.width, self.height, position).unwrap_or_else(|| panic!("Position {:?} is outside of the {}x{} map", position, self.width, self.height))
+    }
+
+    pub fn contains(&self, position: &Position) -> bool {
+        cell_index(self.width, self.height, position).is_some()
+    }
+
+    // The same position on the map, going around the edges like the world does
+    pub fn wrap(&self, position: &Position) -> Position {
+        wrap_position(self.width, self.height, position)
     }
+
     // Get a reference to the value at a given position
     pub fn get(&self, position: &Position) -> &T {
         let index = self.index(position);
         &self.map[index]
     }
 
+    pub fn get_checked(&self, position: &Position) -> Option<&T> {
+        cell_index(self.width, self.height, position).map(|index| &self.map[index])
+    }
+
+    pub fn get_wrapped(&self, position: &Position) -> &T {
+        self.get(&self.wrap(position))
+    }
+
     // Get a mutable reference to the value at a given position
     pub fn get_mut(&mut self, position: &Position) -> &mut T {
         let index = self.index(position);
         &mut self.map[index]
     }
 
+    pub fn get_checked_mut(&mut self, position: &Position) -> Option<&mut T> {
+        cell_index(self.width, self.height, position).map(|index| &mut self.map[index])
+    }
+
+    pub fn get_wrapped_mut(&mut self, position: &Position) -> &mut T {
+        let position = self.wrap(position);
+        self.get_mut(&position)
+    }
+
     // Set the value at a given position
     pub fn set(&mut self, position: &Position, value: T) {
         let index = self.index(position);
@@ -276,6 +504,11 @@ impl<T: Default + Clone> Map2d<T> {
     pub fn clear(&mut self) {
         self.map = vec![T::default(); self.width * self.height];
     }
+
+    // Every cell together with its position, row by row
+    pub fn iter(&self) -> impl Iterator<Item = (Position, &T)> {
+        self.map.iter().enumerate().map(|(index, value)| (Position { x: (index % self.width) as i32, y: (index / self.width) as i32 }, value))
+    }
 }
 
 pub struct Map3d<T> {
@@ -299,14 +532,31 @@ impl<T: Clone> Map3d<T> {
     }
 
     fn index(&self, position: &Position) -> usize {
-        (position.x * self.width as i32 + position.y) as usize
+        cell_index(self.width, self.height, position).unwrap_or_else(|| panic!("Position {:?} is outside of the {}x{} map", position, self.width, self.height))
+    }
+
+    pub fn contains(&self, position: &Position) -> bool {
+        cell_index(self.width, self.height, position).is_some()
+    }
+
+    pub fn wrap(&self, position: &Position) -> Position {
+        wrap_position(self.width, self.height, position)
     }
+
     // Get a reference to the value at a given position
     pub fn get(&self, position: &Position) -> &Vec<T> {
         let index = self.index(position);
         &self.map[index]
     }
 
+    pub fn get_checked(&self, position: &Position) -> Option<&Vec<T>> {
+        cell_index(self.width, self.height, position).map(|index| &self.map[index])
+    }
+
+    pub fn get_wrapped(&self, position: &Position) -> &Vec<T> {
+        self.get(&self.wrap(position))
+    }
+
     // Get a mutable reference to the value at a given position
     pub fn get_mut(&mut self, position: &Position) -> &mut Vec<T> {
         let index = self.index(position);
@@ -366,7 +616,7 @@ impl Food {
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 pub struct FoodMap {
     pub map: Map2d<Food>,
 }
@@ -379,63 +629,229 @@ pub struct SolidsMap {
 #[derive(Component)]
 pub struct Scent {}
 
+// Every random decision outside of the systems goes through here, so a seed reproduces a run
+#[derive(Resource)]
+pub struct RngResource {
+    pub rng: StdRng,
+    // kept so every run can be repeated, even the ones started from entropy
+    pub seed: u64,
+}
+
+// Systems draw from their own stream instead of sharing one generator, so they only read the RngResource and can run in parallel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngStream {
+    Temperature,
+    // snakes act several times in one frame, every round gets another stream
+    Think { round: u32 },
+    Scents,
+    Food,
+    Split,
+    Disease,
+    Minerals,
+    Aging,
+}
+
+impl RngStream {
+    fn id(&self) -> u64 {
+        match self {
+            RngStream::Temperature => 0,
+            RngStream::Think { round } => 1 + ((*round as u64) << 8),
+            RngStream::Scents => 2,
+            RngStream::Food => 3,
+            RngStream::Split => 4,
+            RngStream::Disease => 5,
+            RngStream::Minerals => 6,
+            RngStream::Aging => 7,
+        }
+    }
+}
+
+// SplitMix64, neighbouring seeds, streams and ticks end up far apart
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl RngResource {
+    pub fn from_seed(seed: u64) -> Self {
+        RngResource { rng: StdRng::seed_from_u64(seed), seed }
+    }
+
+    // Generator of one system in the current frame, only depends on the seed of the run, the stream and the tick
+    pub fn stream(&self, stream: RngStream, time: &SimTime) -> StdRng {
+        StdRng::seed_from_u64(split_mix(split_mix(split_mix(self.seed) ^ stream.id()) ^ time.ticks))
+    }
+
+    // the seed stays below 2^63 so it fits into a toml integer
+    pub fn from_entropy() -> Self {
+        RngResource::from_seed(StdRng::from_entropy().gen_range(0..=i64::MAX as u64))
+    }
+}
+
 #[derive(Resource)]
 pub struct ScentMap {
     pub map: Map2d<f32>,
 }
 
+// Signal of the snake on every hex, only kept up to date while signaling is enabled
+#[derive(Resource)]
+pub struct SignalMap {
+    pub map: Map2d<f32>,
+}
+
 #[derive(Resource)]
 pub struct SegmentMap {
     pub map: Map3d<Entity>,
 }
 
-pub fn incease_move_potential(mut snakes: Query<(&mut Snake, &Age)>) {
-    puffin::profile_function!();
+pub const NEUTRAL_TEMPERATURE: f32 = 0.5;
+// size in hexes of the random blobs of the noise layout
+const TEMPERATURE_NOISE_SCALE: usize = 10;
+
+// Temperatures go from 0 (cold) to 1 (hot), regenerated whenever the layout or the world size changes
+#[derive(Resource)]
+pub struct TemperatureMap {
+    pub map: Map2d<f32>,
+    pub layout: Option<TemperatureLayout>,
+}
+
+impl Default for TemperatureMap {
+    fn default() -> Self {
+        TemperatureMap { map: Map2d::new(0, 0, NEUTRAL_TEMPERATURE), layout: None }
+    }
+}
+
+impl TemperatureMap {
+    pub fn temperature(&self, position: &Position) -> f32 {
+        if position.x < self.map.width as i32 && position.y < self.map.height as i32 {
+            *self.map.get(position)
+        } else {
+            NEUTRAL_TEMPERATURE
+        }
+    }
+}
+
+// from -strength on the coldest hexes to +strength on the hottest ones
+pub fn temperature_effect(temperature: f32, strength: f32) -> f32 {
+    (temperature - NEUTRAL_TEMPERATURE) * 2.0 * strength
+}
+
+fn smoothstep(value: f32) -> f32 {
+    value * value * (3.0 - 2.0 * value)
+}
+
+// Value noise on a coarse lattice, the lattice wraps around just like the world does
+fn temperature_noise(rng: &mut impl Rng, columns: usize, rows: usize) -> Map2d<f32> {
+    let lattice_columns = columns.div_ceil(TEMPERATURE_NOISE_SCALE).max(1);
+    let lattice_rows = rows.div_ceil(TEMPERATURE_NOISE_SCALE).max(1);
+    let lattice: Vec<f32> = (0..lattice_columns * lattice_rows).map(|_| rng.gen_range(0.0..1.0)).collect();
+    let at = |x: usize, y: usize| lattice[(y % lattice_rows) * lattice_columns + x % lattice_columns];
+    let mut map = Map2d::new(columns, rows, NEUTRAL_TEMPERATURE);
+    for y in 0..rows {
+        for x in 0..columns {
+            let (cell_x, cell_y) = (x / TEMPERATURE_NOISE_SCALE, y / TEMPERATURE_NOISE_SCALE);
+            let fraction_x = smoothstep((x % TEMPERATURE_NOISE_SCALE) as f32 / TEMPERATURE_NOISE_SCALE as f32);
+            let fraction_y = smoothstep((y % TEMPERATURE_NOISE_SCALE) as f32 / TEMPERATURE_NOISE_SCALE as f32);
+            let top = at(cell_x, cell_y) * (1.0 - fraction_x) + at(cell_x + 1, cell_y) * fraction_x;
+            let bottom = at(cell_x, cell_y + 1) * (1.0 - fraction_x) + at(cell_x + 1, cell_y + 1) * fraction_x;
+            map.set(&Position { x: x as i32, y: y as i32 }, top * (1.0 - fraction_y) + bottom * fraction_y);
+        }
+    }
+    map
+}
+
+pub fn update_temperature(mut temperature_map: ResMut<TemperatureMap>, rng: Res<RngResource>, time: Res<SimTime>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let up_to_date = temperature_map.layout == Some(config.temperature_layout) && temperature_map.map.width == config.columns && temperature_map.map.height == config.rows;
+    if up_to_date {
+        return;
+    }
+    let map = match config.temperature_layout {
+        TemperatureLayout::Uniform => Map2d::new(config.columns, config.rows, NEUTRAL_TEMPERATURE),
+        TemperatureLayout::Gradient => {
+            let mut map = Map2d::new(config.columns, config.rows, NEUTRAL_TEMPERATURE);
+            for y in 0..config.rows {
+                for x in 0..config.columns {
+                    map.set(&Position { x: x as i32, y: y as i32 }, y as f32 / (config.rows - 1).max(1) as f32);
+                }
+            }
+            map
+        }
+        TemperatureLayout::Noise => temperature_noise(&mut rng.stream(RngStream::Temperature, &time), config.columns, config.rows),
+    };
+    *temperature_map = TemperatureMap { map, layout: Some(config.temperature_layout) };
+}
+
+pub fn incease_move_potential(mut snakes: Query<(&mut Snake, &Age)>, config: Res<SimulationConfig>) {
+    profile_system!();
     for (mut snake, age) in &mut snakes {
         if snake.energy.move_potential < 1.0 {
-            snake.energy.move_potential += snake.metabolism.mobility * age.efficiency_factor;
+            snake.energy.move_potential += snake.metabolism.mobility * age.efficiency_factor * config.speed_factor;
         }
     }
 }
 
 // This system moves each entity with a Position and Velocity component
-pub fn movement(mut snakes: Query<(Entity, &mut Snake, &Position, &Age)>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub fn movement(mut snakes: Query<(Entity, &mut Snake, &Position, &Age)>, temperature_map: Res<TemperatureMap>, water_map: Res<WaterMap>, mut counters: ResMut<BehaviorCounters>, action_round: Res<ActionRound>, config: Res<SimulationConfig>) {
+    profile_system!();
 
     for (_, mut snake, head_position, age) in &mut snakes {
+        if action_round.skips(&snake) {
+            snake.decision = Decision::Wait;
+            continue;
+        }
+        // starving snakes are too weak to do anything
+        if snake.starving > 0 {
+            snake.decision = Decision::Wait;
+        }
+        counters.decisions[snake.decision.index()] += 1;
         debug!("Energy before move: {:?}, (eff: {}, age: {})", snake.energy.energy, age.efficiency_factor, age.age);
+        // heat makes moving more expensive but boosts energy production, cold does the opposite
+        let heat = temperature_effect(temperature_map.temperature(head_position), config.temperature_strength);
+        snake.last_decision = snake.decision;
+        snake.last_move_blocked = matches!(snake.decision, Decision::MoveForward | Decision::MoveLeft | Decision::MoveRight | Decision::MoveBackward | Decision::Sprint) && snake.energy.move_potential < 1.0;
         if snake.energy.move_potential >= 1.0 {
-            let move_cost = snake.metabolism.segment_move_cost / age.efficiency_factor;
+            let move_cost = snake.metabolism.segment_move_cost * (1.0 + heat) / age.efficiency_factor;
             match snake.decision {
                 Decision::MoveForward => {
-                    snake.energy.energy -= move_cost;
-                    let new_position = position_at_direction(&snake.direction, &head_position, &config);
-                    snake.new_position.0 = new_position.x;
-                    snake.new_position.1 = new_position.y;
+                    let new_position = position_at_direction(&snake.direction, head_position, &config);
+                    step(&mut snake, new_position, move_cost, &water_map, &config);
                 }
                 Decision::MoveLeft => {
-                    snake.energy.energy -= move_cost;
                     snake.direction = turn_left(&snake.direction);
-                    let new_position = position_at_direction(&snake.direction, &head_position, &config);
-                    snake.new_position.0 = new_position.x;
-                    snake.new_position.1 = new_position.y;
+                    let new_position = position_at_direction(&snake.direction, head_position, &config);
+                    step(&mut snake, new_position, move_cost, &water_map, &config);
                 }
                 Decision::MoveRight => {
-                    snake.energy.energy -= move_cost;
                     snake.direction = turn_right(&snake.direction);
-                    let new_position = position_at_direction(&snake.direction, &head_position, &config);
-                    snake.new_position.0 = new_position.x;
-                    snake.new_position.1 = new_position.y;
+                    let new_position = position_at_direction(&snake.direction, head_position, &config);
+                    step(&mut snake, new_position, move_cost, &water_map, &config);
+                }
+                // the segments behind the head are needed to crawl back, update_positions does the move
+                Decision::MoveBackward => {
+                    snake.energy.energy -= move_cost;
                 }
-                Decision::Wait => {}
+                // only the first hex is set here, the second step is taken in update_positions
+                Decision::Sprint => {
+                    let new_position = position_at_direction(&snake.direction, head_position, &config);
+                    step(&mut snake, new_position, 2.0 * move_cost, &water_map, &config);
+                }
+                Decision::Wait | Decision::TransferEnergy | Decision::BuildNest => {}
             }
             snake.energy.move_potential -= 1.0;
         }
-        snake.energy.energy -= snake.metabolism.segment_basic_cost / age.efficiency_factor;
+        // upkeep and production happen once per frame, no matter how often the snake acts
+        if action_round.round > 0 {
+            continue;
+        }
+        let efficiency = if snake.starving > 0 { age.efficiency_factor * config.starving_efficiency } else { age.efficiency_factor };
+        snake.energy.energy -= snake.metabolism.segment_basic_cost / efficiency;
         // snake.energy.energy -= snake.brain.get_neural_network().unwrap().run_cost();
         // very old snakes wont produce energy anymore
         if age.efficiency_factor > 0.2 {
-            snake.energy.energy += snake.metabolism.segment_energy_production * age.efficiency_factor;
+            snake.energy.energy += snake.metabolism.segment_energy_production * (1.0 + heat) * efficiency;
         } else {
             debug!("Snake {:#?} is too old to produce energy", snake);
         }
@@ -443,32 +859,142 @@ pub fn movement(mut snakes: Query<(Entity, &mut Snake, &Position, &Age)>, config
     }
 }
 
+// Snakes that can't swim stay where they are and keep their energy, swimming through water is cheaper than crawling
+fn step(snake: &mut Snake, new_position: Position, move_cost: f32, water_map: &WaterMap, config: &SimulationConfig) {
+    if !can_enter(snake, &new_position, water_map) {
+        snake.last_move_blocked = true;
+        return;
+    }
+    let in_water = *water_map.map.get(&new_position);
+    snake.energy.energy -= if in_water { move_cost * config.water_move_cost } else { move_cost };
+    snake.new_position = new_position.as_pair();
+}
+
+// Snakes that decided to transfer energy give some of it to whichever snake is on the hex in front of them
+pub fn transfer_energy(mut snakes: Query<(Entity, &mut Snake, &Position)>, segment_map: Res<SegmentMap>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if !config.energy_transfer_enabled {
+        return;
+    }
+    let givers: Vec<(Entity, Position)> = snakes.iter()
+        .filter(|(_, snake, _)| snake.decision == Decision::TransferEnergy)
+        .map(|(head_id, snake, position)| (head_id, position_at_direction(&snake.direction, position, &config)))
+        .collect();
+    if givers.is_empty() {
+        return;
+    }
+    let owners: HashMap<Entity, Entity> = snakes.iter().flat_map(|(head_id, snake, _)| snake.segments.iter().map(move |segment_id| (*segment_id, head_id))).collect();
+    for (giver_id, faced) in givers {
+        let Some(receiver_id) = segment_map.map.get(&faced).iter().find_map(|segment_id| owners.get(segment_id)).copied() else {
+            continue;
+        };
+        // a snake facing its own body can't feed itself
+        if let Ok([(_, mut giver, _), (_, mut receiver, _)]) = snakes.get_many_mut([giver_id, receiver_id]) {
+            let amount = config.energy_transfer_amount.min(giver.energy.energy.max(0.0));
+            giver.energy.energy -= amount;
+            receiver.energy.energy += amount;
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct DiedFromCollision {}
 
-pub fn update_positions(mut commands: Commands, mut positions: Query<&mut Position>, mut snakes: Query<(Entity, &mut Snake)>, mut solids_map: ResMut<SolidsMap>) {
-    puffin::profile_function!();
+pub fn update_positions(mut commands: Commands, mut positions: Query<&mut Position>, mut snakes: Query<(Entity, &mut Snake)>, solids_map: ResMut<SolidsMap>, water_map: Res<WaterMap>, mut counters: ResMut<BehaviorCounters>, config: Res<SimulationConfig>) {
+    profile_system!();
     for (head_id, mut snake) in &mut snakes {
+        if snake.decision == Decision::MoveBackward {
+            if !snake.last_move_blocked {
+                crawl_backward(&mut commands, &mut positions, head_id, &mut snake, &solids_map, &water_map, &mut counters, &config);
+            }
+            continue;
+        }
         let new_position = snake.new_position;
         let last_position = positions.get_mut(*snake.segments.last().unwrap()).unwrap().clone();
-        let mut head_position = positions.get_mut(head_id).unwrap();
+        let head_position = positions.get_mut(head_id).unwrap();
         debug!("Snake {:?} with {} segements is moving from {:?} to {:?} (last tail position: {:?})", head_id, snake.segments.len(), head_position, new_position, snake.last_position);
         let old_head_position = head_position.clone();
         if new_position == old_head_position.as_pair() {
             debug!("Snake is not moving");
             continue;
         }
-        if *solids_map.map.get(&Position { x: new_position.0, y: new_position.1 }) {
-            debug!("Snake has hit something, he will soon die");
-            commands.entity(head_id).insert(DiedFromCollision {});
+        if *solids_map.map.get(&Position { x: new_position.0, y: new_position.1 }) && collide(&mut commands, &mut counters, head_id, &mut snake, &config) {
+            snake.new_position = old_head_position.as_pair();
+            continue;
         }
         update_segment_positions(&mut positions, Position { x: new_position.0, y: new_position.1 }, &snake.segments);
         debug!("Removing snake head {:?} from position {:?}", head_id, old_head_position);
         snake.last_position = last_position.as_pair();
+        if snake.decision == Decision::Sprint {
+            let second_position = position_at_direction(&snake.direction, &Position { x: new_position.0, y: new_position.1 }, &config);
+            // the sprint ends on the shore
+            if !can_enter(&snake, &second_position, &water_map) {
+                continue;
+            }
+            // a bounce ends the sprint after the first hex
+            if *solids_map.map.get(&second_position) && collide(&mut commands, &mut counters, head_id, &mut snake, &config) {
+                continue;
+            }
+            let last_position = positions.get(*snake.segments.last().unwrap()).unwrap().as_pair();
+            update_segment_positions(&mut positions, second_position.clone(), &snake.segments);
+            snake.new_position = second_position.as_pair();
+            snake.last_position = last_position;
+        }
+    }
+}
+
+// True when the snake bounced off and the move is cancelled, otherwise it dies in the next frame
+fn collide(commands: &mut Commands, counters: &mut BehaviorCounters, head_id: Entity, snake: &mut Snake, config: &SimulationConfig) -> bool {
+    match config.collision_policy {
+        CollisionPolicy::Die => {
+            debug!("Snake has hit something, he will soon die");
+            commands.entity(head_id).insert(DiedFromCollision {});
+            false
+        }
+        CollisionPolicy::Bounce => {
+            // dying snakes are counted in die_from_collisions
+            counters.collisions += 1;
+            snake.energy.energy -= config.bounce_cost;
+            snake.last_move_blocked = true;
+            true
+        }
+    }
+}
+
+// Every segment takes the place of the one behind it and the tail moves on in the direction it came from, the head keeps facing forward
+#[allow(clippy::too_many_arguments)]
+fn crawl_backward(commands: &mut Commands, positions: &mut Query<&mut Position>, head_id: Entity, snake: &mut Mut<Snake>, solids_map: &SolidsMap, water_map: &WaterMap, counters: &mut BehaviorCounters, config: &SimulationConfig) {
+    let segment_positions: Vec<Position> = snake.segments.iter().map(|segment| positions.get(*segment).unwrap().clone()).collect();
+    let tail = segment_positions.last().unwrap();
+    let away_from_body = match segment_positions.len() {
+        1 => turn_left(&turn_left(&turn_left(&snake.direction))),
+        length => {
+            let before_tail = &segment_positions[length - 2];
+            let Some(direction) = [NorthEast, East, SouthEast, SouthWest, West, NorthWest].into_iter().find(|direction| position_at_direction(direction, before_tail, config).as_pair() == tail.as_pair()) else {
+                return;
+            };
+            direction
+        }
+    };
+    let new_tail = position_at_direction(&away_from_body, tail, config);
+    if !can_enter(snake, &new_tail, water_map) {
+        snake.last_move_blocked = true;
+        return;
+    }
+    if *solids_map.map.get(&new_tail) && collide(commands, counters, head_id, snake, config) {
+        return;
+    }
+    for (segment, position) in snake.segments.iter().zip(segment_positions.iter().skip(1).chain(std::iter::once(&new_tail))) {
+        let mut segment_position = positions.get_mut(*segment).unwrap();
+        segment_position.x = position.x;
+        segment_position.y = position.y;
     }
+    snake.new_position = segment_positions.get(1).unwrap_or(&new_tail).as_pair();
+    // the hex behind the tail is taken now, so growing waits for a move forward
+    snake.last_position = new_tail.as_pair();
 }
 
-fn update_segment_positions(mut positions: &mut Query<&mut Position>, new_position: Position, segments: &Vec<Entity>) {
+fn update_segment_positions(positions: &mut Query<&mut Position>, new_position: Position, segments: &Vec<Entity>) {
     let mut new_position = new_position.clone();
     for segment in segments {
         let mut position = positions.get_mut(*segment).unwrap();
@@ -502,18 +1028,7 @@ fn turn_right(direction: &Direction) -> Direction {
     }
 }
 
-fn flip_direction(direction: &Direction) -> Direction {
-    match direction {
-        NorthEast => SouthWest,
-        East => West,
-        SouthEast => NorthWest,
-        SouthWest => NorthEast,
-        West => East,
-        NorthWest => SouthEast,
-    }
-}
-
-fn position_at_direction(direction: &Direction, position: &Position, config: &Res<SimulationConfig>) -> Position {
+pub(crate) fn position_at_direction(direction: &Direction, position: &Position, config: &SimulationConfig) -> Position {
     let mut x = position.x;
     let mut y = position.y;
     match direction {
@@ -555,45 +1070,338 @@ fn position_at_direction(direction: &Direction, position: &Position, config: &Re
     Position { x, y }
 }
 
-pub fn think(mut heads: Query<(&Position, &mut Snake, &Age)>, food_map: Res<FoodMap>, solids_map: Res<SolidsMap>, scent_map: Res<ScentMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+// Order of the inputs passed to the brain in think
+pub const SENSOR_NAMES: [&str; 39] = [
+    "Bias",
+    "Chaos",
+    "Scent front",
+    "Scent left",
+    "Scent right",
+    "Plants front",
+    "Plants left",
+    "Plants right",
+    "Meat front",
+    "Meat left",
+    "Meat right",
+    "Obstacle front",
+    "Obstacle left",
+    "Obstacle right",
+    "Plants in stomach",
+    "Meat in stomach",
+    "Energy",
+    "Age",
+    "Moved forward",
+    "Turned left",
+    "Turned right",
+    "Move blocked",
+    "Facing north",
+    "Facing east",
+    "Food ahead",
+    "Food to the left",
+    "Touching obstacle front",
+    "Touching obstacle left",
+    "Touching obstacle right",
+    "Touching food front",
+    "Touching food left",
+    "Touching food right",
+    "Signal front",
+    "Minerals front",
+    "Minerals carried",
+    "Water front",
+    "Hunger",
+    "Length",
+    "Growth",
+];
+
+// Order of the brain outputs, older networks may have fewer of them
+pub const OUTPUT_NAMES: [&str; 9] = ["Move forward", "Move left", "Move right", "Wait", "Transfer energy", "Signal", "Move backward", "Sprint", "Build nest"];
+
+// Everything the snake saw and thought during its last think, only recorded for the snake being debugged
+#[derive(Component, Debug, Clone, Default)]
+pub struct ThoughtTrace {
+    pub inputs: Vec<f32>,
+    // values of all network nodes in the order of get_nodes, empty for brains without a network
+    pub node_values: Vec<f32>,
+    pub node_types: Vec<NodeType>,
+    pub decision: Option<Decision>,
+    pub signal: f32,
+    pub age: u32,
+    pub efficiency: f32,
+}
+
+// distance between hex rows relative to the distance of neighbours in a row
+const HEX_ROW_HEIGHT: f32 = 0.866;
+
+// counterclockwise from east, north is up
+fn direction_angle(direction: &Direction) -> f32 {
+    let degrees: f32 = match direction {
+        East => 0.0,
+        NorthEast => 60.0,
+        NorthWest => 120.0,
+        West => 180.0,
+        SouthWest => 240.0,
+        SouthEast => 300.0,
+    };
+    degrees.to_radians()
+}
+
+// Cartesian offset between two hexes going the shortest way around the wrapping world
+fn hex_offset(from: &Position, to: &Position, config: &SimulationConfig) -> (f32, f32) {
+    let shortest = |delta: f32, size: f32| if delta > size / 2.0 { delta - size } else if delta < -size / 2.0 { delta + size } else { delta };
+    let row_shift = |position: &Position| if position.y % 2 == 0 { 0.5 } else { 0.0 };
+    let dx = shortest(to.x as f32 + row_shift(to) - from.x as f32 - row_shift(from), config.columns as f32);
+    let dy = shortest((to.y - from.y) as f32, config.rows as f32);
+    (dx, -dy * HEX_ROW_HEIGHT)
+}
+
+fn nearest_food(position: &Position, range: u32, food_map: &FoodMap, config: &SimulationConfig) -> Option<(f32, f32)> {
+    let range = range as i32;
+    let mut nearest: Option<(f32, f32)> = None;
+    for dy in -range..=range {
+        for dx in -range..=range {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            let candidate = food_map.map.wrap(&Position { x: position.x + dx, y: position.y + dy });
+            if !food_map.map.get(&candidate).contains_food() {
+                continue;
+            }
+            let offset = hex_offset(position, &candidate, config);
+            let distance = offset.0 * offset.0 + offset.1 * offset.1;
+            if nearest.is_none_or(|(x, y)| distance < x * x + y * y) {
+                nearest = Some(offset);
+            }
+        }
+    }
+    nearest
+}
+
+// (ahead, to the left) as the cosine and sine of the angle to the nearest food, zeros when nothing is in range
+fn food_direction(direction: &Direction, position: &Position, range: u32, food_map: &FoodMap, config: &SimulationConfig) -> (f32, f32) {
+    match nearest_food(position, range, food_map, config) {
+        Some((x, y)) => {
+            let angle = y.atan2(x) - direction_angle(direction);
+            (angle.cos(), angle.sin())
+        }
+        None => (0.0, 0.0),
+    }
+}
+
+// What a snake feels and sees around its head, in the order of SENSOR_NAMES
+#[allow(clippy::too_many_arguments)]
+fn sense(position: &Position, head: &Snake, age: &Age, food_map: &Res<FoodMap>, solids_map: &Res<SolidsMap>, scent_map: &Res<ScentMap>, signal_map: &Res<SignalMap>, mineral_map: &Res<MineralMap>, water_map: &Res<WaterMap>, config: &Res<SimulationConfig>, rng: &mut StdRng) -> Vec<f32> {
     let bias = 1.0;
-    heads.par_iter_mut().for_each(|(position, mut head, age)| {
-        let mut rng = rand::thread_rng();
-        let chaos = if config.mutation.chaos_input_enabled {
-            rng.gen_range(0.0..1.0)
-        } else {
-            0.0
+    let chaos = if config.mutation.chaos_input_enabled {
+        rng.gen_range(0.0..1.0)
+    } else {
+        0.0
+    };
+    let direction_left = turn_left(&head.direction);
+    let direction_right = turn_right(&head.direction);
+    // eyes and noses add to the ranges of the settings
+    let sight = head.metabolism.eyes * config.mutation.eye_vision_bonus;
+    let smell = 1 + head.metabolism.noses * config.mutation.nose_smell_bonus;
+    let scent_front = scent(&head.direction, position, smell, scent_map, config);
+    let scent_left = scent(&direction_left, position, smell, scent_map, config);
+    let scent_right = scent(&direction_right, position, smell, scent_map, config);
+    let plant_vision_front = see_plants(&head.direction, position, config.mutation.plant_vision_front_range + sight, food_map, config);
+    let plant_vision_left = see_plants(&direction_left, position, config.mutation.plant_vision_left_range + sight, food_map, config);
+    let plant_vision_right = see_plants(&direction_right, position, config.mutation.plant_vision_right_range + sight, food_map, config);
+    let meat_vision_front = see_meat(&head.direction, position, config.mutation.meat_vision_front_range + sight, food_map, config);
+    let meat_vision_left = see_meat(&direction_left, position, config.mutation.meat_vision_left_range + sight, food_map, config);
+    let meat_vision_right = see_meat(&direction_right, position, config.mutation.meat_vision_right_range + sight, food_map, config);
+    let solid_vision_front = see_obstacles(&head.direction, position, config.mutation.obstacle_vision_front_range + sight, solids_map, config);
+    let solid_vision_left = see_obstacles(&direction_left, position, config.mutation.obstacle_vision_left_range + sight, solids_map, config);
+    let solid_vision_right = see_obstacles(&direction_right, position, config.mutation.obstacle_vision_right_range + sight, solids_map, config);
+    let plant_food_level = head.energy.plant_in_stomach / head.metabolism.max_plants_in_stomach;
+    let meat_food_level = head.energy.meat_in_stomach / head.metabolism.max_meat_in_stomach;
+    let energy_level = head.energy.energy / head.metabolism.max_energy;
+    let age_level = age.efficiency_factor;
+    let remembered = |active: bool| if config.mutation.memory_input_enabled && active { 1.0 } else { 0.0 };
+    let moved_forward = remembered(head.last_decision == Decision::MoveForward);
+    let turned_left = remembered(head.last_decision == Decision::MoveLeft);
+    let turned_right = remembered(head.last_decision == Decision::MoveRight);
+    let move_blocked = remembered(head.last_move_blocked);
+    let (facing_north, facing_east) = if config.mutation.compass_input_enabled {
+        let angle = direction_angle(&head.direction);
+        (angle.sin(), angle.cos())
+    } else {
+        (0.0, 0.0)
+    };
+    let (food_ahead, food_left) = if config.mutation.food_direction_enabled {
+        food_direction(&head.direction, position, config.mutation.food_direction_range, food_map, config)
+    } else {
+        (0.0, 0.0)
+    };
+    let (obstacle_touch_front, food_touch_front) = touch(&head.direction, position, food_map, solids_map, config);
+    let (obstacle_touch_left, food_touch_left) = touch(&direction_left, position, food_map, solids_map, config);
+    let (obstacle_touch_right, food_touch_right) = touch(&direction_right, position, food_map, solids_map, config);
+    let signal_front = see_signal(&head.direction, position, config.mutation.obstacle_vision_front_range + sight, solids_map, signal_map, config);
+    let minerals_front = see_minerals(&head.direction, position, mineral_map, config);
+    let minerals_carried = if config.minerals_enabled { head.energy.minerals / config.max_minerals_carried.max(f32::EPSILON) } else { 0.0 };
+    let water_front = see_water(&head.direction, position, config.mutation.obstacle_vision_front_range + sight, water_map, config);
+    // grows while the snake starves, 1 in its last frame
+    let hunger = if config.starvation_grace > 0 { head.starving as f32 / config.starvation_grace as f32 } else { 0.0 };
+    // 1 once the snake is long enough to split and once it has the meat for its next segment
+    let length = (head.segments.len() as f32 / config.size_to_split as f32).min(1.0);
+    let growth = (head.energy.accumulated_meat_matter_for_growth / config.new_segment_cost.max(f32::EPSILON)).min(1.0);
+    vec![bias, chaos, scent_front, scent_left, scent_right, plant_vision_front, plant_vision_left, plant_vision_right, meat_vision_front, meat_vision_left, meat_vision_right, solid_vision_front, solid_vision_left, solid_vision_right, plant_food_level, meat_food_level, energy_level, age_level, moved_forward, turned_left, turned_right, move_blocked, facing_north, facing_east, food_ahead, food_left, obstacle_touch_front, obstacle_touch_left, obstacle_touch_right, food_touch_front, food_touch_left, food_touch_right, signal_front, minerals_front, minerals_carried, water_front, hunger, length, growth]
+}
+
+// Only the snake being debugged has a trace
+#[allow(clippy::too_many_arguments)]
+fn record_thought(head: &mut Snake, trace: Option<Mut<ThoughtTrace>>, inputs: Vec<f32>, outputs: Vec<f32>, decision: Decision, signal: f32, age: &Age, config: &SimulationConfig) {
+    head.decision = decision;
+    head.outputs = outputs;
+    head.signal = if config.mutation.signaling_enabled { signal } else { 0.0 };
+    if let Some(mut trace) = trace {
+        let network = head.brain.get_neural_network();
+        trace.node_values = network.map_or(vec![], |network| network.evaluate(inputs.iter().enumerate().map(|(index, value)| SensorInput { index, value: *value }).collect()));
+        trace.node_types = network.map_or(vec![], |network| network.get_nodes().iter().map(|node| node.node_type.clone()).collect());
+        trace.inputs = inputs;
+        trace.decision = Some(decision);
+        trace.signal = head.signal;
+        trace.age = age.age;
+        trace.efficiency = age.efficiency_factor;
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn think(mut heads: Query<(Entity, &Position, &mut Snake, &Age, Option<&mut ThoughtTrace>), Without<Controlled>>, food_map: Res<FoodMap>, solids_map: Res<SolidsMap>, scent_map: Res<ScentMap>, signal_map: Res<SignalMap>, mineral_map: Res<MineralMap>, water_map: Res<WaterMap>, action_round: Res<ActionRound>, config: Res<SimulationConfig>, rng: Res<RngResource>, time: Res<SimTime>) {
+    profile_system!();
+    // every snake gets its own generator so seeded runs stay the same no matter which thread thinks for it
+    let frame_seed: u64 = rng.stream(RngStream::Think { round: action_round.round }, &time).gen();
+    if config.batched_thinking {
+        think_in_batches(&mut heads, &food_map, &solids_map, &scent_map, &signal_map, &mineral_map, &water_map, &action_round, &config, frame_seed);
+        return;
+    }
+    heads.par_iter_mut().for_each(|(_, position, mut head, age, trace)| {
+        if action_round.skips(&head) {
+            return;
+        }
+        let mut snake_rng = StdRng::seed_from_u64(frame_seed ^ head.id);
+        let inputs = sense(position, &head, age, &food_map, &solids_map, &scent_map, &signal_map, &mineral_map, &water_map, &config, &mut snake_rng);
+        let temperature = head.action_temperature.unwrap_or(config.action_temperature);
+        // the outputs are kept for the statistics, deciding from them is the same as letting the brain run its network
+        let outputs = head.brain.get_neural_network().map_or(vec![], |network| network.run(inputs.iter().enumerate().map(|(index, value)| SensorInput { index, value: *value }).collect()));
+        let (decision, signal) = match head.brain.get_neural_network() {
+            Some(_) => head.brain.choose(&outputs, ActionSet::from_config(&config), temperature, &mut snake_rng),
+            None => head.brain.decide(inputs.clone(), ActionSet::from_config(&config), temperature, &mut snake_rng),
         };
-        let direction_left = turn_left(&head.direction);
-        let direction_right = turn_right(&head.direction);
-        let scent_front = scent(&position_at_direction(&head.direction, &position, &config), &scent_map, &config);
-        let scent_left = scent(&position_at_direction(&direction_left, &position, &config), &scent_map, &config);
-        let scent_right = scent(&position_at_direction(&direction_right, &position, &config), &scent_map, &config);
-        let plant_vision_front = see_plants(&head.direction, &position, config.mutation.plant_vision_front_range, &food_map, &config);
-        let plant_vision_left = see_plants(&direction_left, &position, config.mutation.plant_vision_left_range, &food_map, &config);
-        let plant_vision_right = see_plants(&direction_right, &position, config.mutation.plant_vision_right_range, &food_map, &config);
-        let meat_vision_front = see_meat(&head.direction, &position, config.mutation.meat_vision_front_range, &food_map, &config);
-        let meat_vision_left = see_meat(&direction_left, &position, config.mutation.meat_vision_left_range, &food_map, &config);
-        let meat_vision_right = see_meat(&direction_right, &position, config.mutation.meat_vision_right_range, &food_map, &config);
-        let solid_vision_front = see_obstacles(&head.direction, &position, config.mutation.obstacle_vision_front_range, &solids_map, &config);
-        let solid_vision_left = see_obstacles(&direction_left, &position, config.mutation.obstacle_vision_left_range, &solids_map, &config);
-        let solid_vision_right = see_obstacles(&direction_right, &position, config.mutation.obstacle_vision_right_range, &solids_map, &config);
-        let plant_food_level = head.energy.plant_in_stomach / head.metabolism.max_plants_in_stomach;
-        let meat_food_level = head.energy.meat_in_stomach / head.metabolism.max_meat_in_stomach;
-        let energy_level = head.energy.energy / head.metabolism.max_energy;
-        let age_level = age.efficiency_factor;
-        head.decision = head.brain.decide(vec![bias.clone(), chaos, scent_front, scent_left, scent_right, plant_vision_front, plant_vision_left, plant_vision_right, meat_vision_front, meat_vision_left, meat_vision_right, solid_vision_front, solid_vision_left, solid_vision_right, plant_food_level, meat_food_level, energy_level, age_level]);
+        record_thought(&mut head, trace, inputs, outputs, decision, signal, age, &config);
     });
 }
 
-fn scent(scenting_position: &Position, scent_map: &Res<ScentMap>, config: &Res<SimulationConfig>) -> f32 {
-    if config.mutation.scent_sensing_enabled {
-        let scent = scent_map.map.get(scenting_position);
-        scent / 500.0
-    } else {
-        0.0
+// Snakes whose networks only differ in their weights, like most members of a specie, are run together by NeuralNetwork::run_batch
+// The decisions are exactly the ones think makes for every snake on its own
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn think_in_batches(heads: &mut Query<(Entity, &Position, &mut Snake, &Age, Option<&mut ThoughtTrace>), Without<Controlled>>, food_map: &Res<FoodMap>, solids_map: &Res<SolidsMap>, scent_map: &Res<ScentMap>, signal_map: &Res<SignalMap>, mineral_map: &Res<MineralMap>, water_map: &Res<WaterMap>, action_round: &Res<ActionRound>, config: &Res<SimulationConfig>, frame_seed: u64) {
+    let sensed = Mutex::new(vec![]);
+    heads.par_iter().for_each(|(head_id, position, head, age, _)| {
+        if action_round.skips(head) {
+            return;
+        }
+        let mut snake_rng = StdRng::seed_from_u64(frame_seed ^ head.id);
+        let inputs = sense(position, head, age, food_map, solids_map, scent_map, signal_map, mineral_map, water_map, config, &mut snake_rng);
+        let topology = head.brain.get_neural_network().map(|network| network.topology());
+        sensed.lock().unwrap().push((head_id, inputs, snake_rng, topology));
+    });
+    let mut sensed = sensed.into_inner().unwrap();
+    let mut outputs = vec![vec![]; sensed.len()];
+    {
+        let mut groups: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (index, (_, _, _, topology)) in sensed.iter().enumerate() {
+            if let Some(topology) = topology {
+                groups.entry(*topology).or_default().push(index);
+            }
+        }
+        let networks: Vec<Option<&NeuralNetwork>> = sensed.iter().map(|(head_id, ..)| heads.get(*head_id).ok().and_then(|(_, _, head, _, _)| head.brain.get_neural_network())).collect();
+        // the task pool of the schedule, rayon could deadlock when the worlds themselves run on rayon threads
+        let batches = ComputeTaskPool::get().scope(|scope| {
+            for group in groups.into_values() {
+                let (networks, sensed) = (&networks, &sensed);
+                scope.spawn(async move {
+                    let batch: Vec<&NeuralNetwork> = group.iter().filter_map(|index| networks[*index]).collect();
+                    let inputs: Vec<&[f32]> = group.iter().map(|index| sensed[*index].1.as_slice()).collect();
+                    let results = NeuralNetwork::run_batch(&batch, &inputs);
+                    (group, results)
+                });
+            }
+        });
+        for (group, results) in batches {
+            for (index, result) in group.into_iter().zip(results) {
+                outputs[index] = result;
+            }
+        }
     }
+    for ((head_id, inputs, snake_rng, _), output) in sensed.iter_mut().zip(outputs) {
+        let Ok((_, _, mut head, age, trace)) = heads.get_mut(*head_id) else {
+            continue;
+        };
+        let temperature = head.action_temperature.unwrap_or(config.action_temperature);
+        let (decision, signal) = head.brain.choose(&output, ActionSet::from_config(config), temperature, snake_rng);
+        record_thought(&mut head, trace, std::mem::take(inputs), output, decision, signal, age, config);
+    }
+}
+
+// Last positions of a snake head, oldest first, only recorded when trails are turned on
+#[derive(Component, Default)]
+pub struct Trail {
+    pub positions: VecDeque<Position>,
+}
+
+pub fn record_trails(mut commands: Commands, mut heads: Query<(Entity, &Position, Option<&mut Trail>), With<Snake>>, config: Res<SimulationConfig>) {
+    profile_system!();
+    for (head_id, position, trail) in &mut heads {
+        match trail {
+            Some(mut trail) => {
+                if trail.positions.back().map(Position::as_pair) != Some(position.as_pair()) {
+                    trail.positions.push_back(position.clone());
+                }
+                while trail.positions.len() > config.trail_length {
+                    trail.positions.pop_front();
+                }
+            }
+            None if config.trail_length > 0 => {
+                commands.entity(head_id).insert(Trail { positions: VecDeque::from([position.clone()]) });
+            }
+            None => {}
+        }
+    }
+}
+
+// Turns are applied once on the next move, afterwards the snake keeps going straight
+pub fn steer_controlled(mut heads: Query<(&mut Snake, &mut Controlled)>) {
+    profile_system!();
+    for (mut snake, mut controlled) in &mut heads {
+        snake.decision = controlled.decision;
+        if snake.energy.move_potential >= 1.0 && matches!(controlled.decision, Decision::MoveLeft | Decision::MoveRight) {
+            controlled.decision = Decision::MoveForward;
+        }
+    }
+}
+
+// (obstacle, food) in the neighbouring hex, walls and snake bodies both count as obstacles
+fn touch(direction: &Direction, position: &Position, food_map: &FoodMap, solids_map: &SolidsMap, config: &SimulationConfig) -> (f32, f32) {
+    if !config.mutation.touch_input_enabled {
+        return (0.0, 0.0);
+    }
+    let touched = position_at_direction(direction, position, config);
+    let obstacle = if *solids_map.map.get(&touched) { 1.0 } else { 0.0 };
+    let food = if food_map.map.get(&touched).contains_food() { 1.0 } else { 0.0 };
+    (obstacle, food)
+}
+
+// The strongest scent within range hexes into the direction, scents further away are weaker
+fn scent(direction: &Direction, position: &Position, range: u32, scent_map: &Res<ScentMap>, config: &Res<SimulationConfig>) -> f32 {
+    if !config.mutation.scent_sensing_enabled {
+        return 0.0;
+    }
+    let mut scenting_position = position.clone();
+    (1..=range).map(|distance| {
+        scenting_position = position_at_direction(direction, &scenting_position, config);
+        scent_map.map.get(&scenting_position) / 500.0 / distance as f32
+    }).reduce(f32::max).unwrap_or(0.0)
 }
 
 fn see_meat(head_direction: &Direction, position: &Position, range: u32, food_map: &Res<FoodMap>, config: &Res<SimulationConfig>) -> f32 {
@@ -601,7 +1409,7 @@ fn see_meat(head_direction: &Direction, position: &Position, range: u32, food_ma
         let current_vision_position = position;
         let mut current_range = 0;
         while current_range < range {
-            let current_vision_position = &position_at_direction(head_direction, &current_vision_position, &config).clone();
+            let current_vision_position = &position_at_direction(head_direction, current_vision_position, config).clone();
             if food_map.map.get(current_vision_position).is_meat() {
                 return (range - current_range) as f32 / range as f32;
             }
@@ -616,7 +1424,7 @@ fn see_plants(head_direction: &Direction, position: &Position, range: u32, food_
         let current_vision_position = position;
         let mut current_range = 0;
         while current_range < range {
-            let current_vision_position = &position_at_direction(head_direction, &current_vision_position, &config).clone();
+            let current_vision_position = &position_at_direction(head_direction, current_vision_position, config).clone();
             if food_map.map.get(current_vision_position).is_plant() {
                 return (range - current_range) as f32 / range as f32;
             }
@@ -631,7 +1439,7 @@ fn see_obstacles(head_direction: &Direction, position: &Position, range: u32, so
         let mut current_vision_position = position.clone();
         let mut current_range = 0;
         while current_range < range {
-            current_vision_position = position_at_direction(head_direction, &current_vision_position, &config).clone();
+            current_vision_position = position_at_direction(head_direction, &current_vision_position, config).clone();
             if *solids_map.map.get(&current_vision_position) {
                 return (range - current_range) as f32 / range as f32;
             }
@@ -641,14 +1449,46 @@ fn see_obstacles(head_direction: &Direction, position: &Position, range: u32, so
     0.0
 }
 
+// Signal of the first snake the ray hits, walls block the view and show no signal
+fn see_signal(head_direction: &Direction, position: &Position, range: u32, solids_map: &SolidsMap, signal_map: &SignalMap, config: &SimulationConfig) -> f32 {
+    if !config.mutation.signaling_enabled {
+        return 0.0;
+    }
+    let mut current_vision_position = position.clone();
+    for _ in 0..range {
+        current_vision_position = position_at_direction(head_direction, &current_vision_position, config);
+        if *solids_map.map.get(&current_vision_position) {
+            return *signal_map.map.get(&current_vision_position);
+        }
+    }
+    0.0
+}
 
-pub fn add_scents(mut commands: Commands, scent_source: Query<(&MeatMatter, &Position)>, mut scent_map: ResMut<ScentMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub fn update_signal_map(snakes: Query<&Snake>, positions: Query<&Position>, mut signal_map: ResMut<SignalMap>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if !config.mutation.signaling_enabled {
+        return;
+    }
+    signal_map.map.map.fill(0.0);
+    for snake in &snakes {
+        for position in snake.segments.iter().filter_map(|segment_id| positions.get(*segment_id).ok()) {
+            signal_map.map.set(position, snake.signal);
+        }
+    }
+}
+
+pub fn add_scents(mut commands: Commands, scent_source: Query<(&MeatMatter, &Position)>, mut scent_map: ResMut<ScentMap>, mut counts: ResMut<EntityCounts>, config: Res<SimulationConfig>) {
+    profile_system!();
     if config.create_scents {
         for (meat, position) in &scent_source {
             debug!("Adding scent at position {:?} with energy {}", position, meat.amount);
-            let mut current_scent = scent_map.map.get_mut(position);
+            let current_scent = scent_map.map.get_mut(position);
             if current_scent <= &mut 0.0 {
+                // a scent without its entity would never diffuse, so none is added at all
+                if counts.scents >= config.max_scents {
+                    continue;
+                }
+                counts.scents += 1;
                 debug!("Adding scent at position {:?} with energy {}", position, meat.amount);
                 commands.spawn((Scent {}, Position { x: position.x, y: position.y }));
             } else {
@@ -661,15 +1501,15 @@ pub fn add_scents(mut commands: Commands, scent_source: Query<(&MeatMatter, &Pos
     }
 }
 
-pub fn diffuse_scents(mut commands: Commands, scents: Query<(&Scent, &Position)>, mut scent_map: ResMut<ScentMap>, config: Res<SimulationConfig>) {
+pub fn diffuse_scents(mut commands: Commands, scents: Query<(&Scent, &Position)>, mut scent_map: ResMut<ScentMap>, rng: Res<RngResource>, time: Res<SimTime>, config: Res<SimulationConfig>) {
     let directions = [NorthEast, East, SouthEast, SouthWest, West, NorthWest];
-    let mut rng = rand::thread_rng();
+    let mut rng = rng.stream(RngStream::Scents, &time);
     for (_, position) in &scents {
         let random_direction = directions.choose(&mut rng).unwrap();
-        let new_position = &position_at_direction(random_direction, &position, &config);
+        let new_position = &position_at_direction(random_direction, position, &config);
         let diffused_scent = scent_map.map.get(position) * config.scent_diffusion_rate;
         *scent_map.map.get_mut(position) -= diffused_scent;
-        let mut new_scent = scent_map.map.get_mut(new_position);
+        let new_scent = scent_map.map.get_mut(new_position);
         if new_scent <= &mut 0.0 {
             debug!("Adding scent throuhg diffusion at position {:?} with energy {}", new_position, diffused_scent);
             commands.spawn((Scent {}, Position { x: new_position.x, y: new_position.y }));
@@ -682,9 +1522,9 @@ pub fn diffuse_scents(mut commands: Commands, scents: Query<(&Scent, &Position)>
 }
 
 pub fn disperse_scents(mut commands: Commands, scents: Query<(Entity, &Scent, &Position)>, mut scent_map: ResMut<ScentMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+    profile_system!();
     for (scent_id, _, position) in &scents {
-        let mut scent = scent_map.map.get_mut(position);
+        let scent = scent_map.map.get_mut(position);
         *scent -= config.scent_dispersion_per_step;
         if scent <= &mut 0.0 {
             debug!("Removing scent at position {:?} with energy {}", position, scent);
@@ -694,33 +1534,187 @@ pub fn disperse_scents(mut commands: Commands, scents: Query<(Entity, &Scent, &P
     }
 }
 
-pub fn create_food(mut commands: Commands, mut food_map: ResMut<FoodMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
-    let mut rng = rand::thread_rng();
-    let rows = config.rows as i32;
-    let columns = config.columns as i32;
-    for _ in 0..config.food_per_step {
-        let x = rng.gen_range(0..columns);
-        let y = rng.gen_range(0..rows);
-        let mut food = food_map.map.get_mut(&Position { x, y });
+// Centers of food patches and oases, recreated whenever they don't match the config
+#[derive(Resource, Default)]
+pub struct FoodSources {
+    pub centers: Vec<(f32, f32)>,
+}
+
+// State of the food controller, the rate is carried over between steps and the fraction of a food left over is spawned later
+#[derive(Resource, Default)]
+pub struct FoodController {
+    pub rate: f32,
+    integral: f32,
+    carry: f32,
+}
+
+impl FoodController {
+    // Food to spawn this step, the rate follows the relative distance of the measure from the target
+    fn update(&mut self, measured: f32, config: &SimulationConfig) -> usize {
+        let error = (config.food_control_target - measured) / config.food_control_target;
+        let max_rate = config.food_control_max_rate as f32;
+        let rate = |integral: f32| config.food_per_step as f32 * (1.0 + config.food_control_gain * error + config.food_control_integral_gain * integral);
+        // the sum only grows while the rate is not stuck at a limit, otherwise it takes forever to come back
+        let integral = self.integral + error;
+        if (0.0..=max_rate).contains(&rate(integral)) {
+            self.integral = integral;
+        }
+        self.rate = rate(self.integral).clamp(0.0, max_rate);
+        let food = self.rate + self.carry;
+        self.carry = food.fract();
+        food as usize
+    }
+}
+
+const MAX_SPAWN_ATTEMPTS: usize = 10;
+
+pub fn random_position(rng: &mut impl Rng, config: &SimulationConfig) -> Position {
+    Position { x: rng.gen_range(0..config.columns as i32), y: rng.gen_range(0..config.rows as i32) }
+}
+
+fn wrapped_position(x: f32, y: f32, config: &SimulationConfig) -> Position {
+    Position { x: (x.round() as i32).rem_euclid(config.columns as i32), y: (y.round() as i32).rem_euclid(config.rows as i32) }
+}
+
+// Box-Muller transform, rand_distr is not worth a dependency for this
+fn standard_normal(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen_range(0.0..1.0);
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos()
+}
+
+pub fn is_free(position: &Position, solids_map: &SolidsMap, segment_map: &SegmentMap) -> bool {
+    !*solids_map.map.get(position) && segment_map.map.get(position).is_empty()
+}
+
+// Gives up after a few attempts, so crowded worlds simply spawn less
+pub fn find_free_position<R: Rng>(rng: &mut R, solids_map: &SolidsMap, segment_map: &SegmentMap, mut sample: impl FnMut(&mut R) -> Position) -> Option<Position> {
+    (0..MAX_SPAWN_ATTEMPTS).map(|_| sample(rng)).find(|position| is_free(position, solids_map, segment_map))
+}
+
+fn sample_food_position(rng: &mut impl Rng, config: &SimulationConfig, centers: &[(f32, f32)]) -> Position {
+    let spread = config.food_patch_radius as f32;
+    match config.food_distribution {
+        FoodDistribution::Uniform => random_position(rng, config),
+        FoodDistribution::Patches | FoodDistribution::Oases => {
+            let (x, y) = centers[rng.gen_range(0..centers.len())];
+            wrapped_position(x + standard_normal(rng) * spread, y + standard_normal(rng) * spread, config)
+        }
+        FoodDistribution::Ring => {
+            let ring_radius = config.columns.min(config.rows) as f32 / 3.0;
+            let angle = rng.gen_range(0.0..2.0 * std::f32::consts::PI);
+            let distance = ring_radius + standard_normal(rng) * spread;
+            wrapped_position(config.columns as f32 / 2.0 + distance * angle.cos(), config.rows as f32 / 2.0 + distance * angle.sin(), config)
+        }
+        FoodDistribution::Gradient => {
+            // density grows linearly from the left edge to the right one
+            let x = config.columns as f32 * rng.gen_range(0.0f32..1.0).sqrt();
+            Position { x: (x as i32).min(config.columns as i32 - 1), y: rng.gen_range(0..config.rows as i32) }
+        }
+    }
+}
+
+// quadrants are numbered row by row: north west, north east, south west, south east
+fn region_rate(position: &Position, config: &SimulationConfig) -> f32 {
+    let east = position.x >= config.columns as i32 / 2;
+    let south = position.y >= config.rows as i32 / 2;
+    config.food_region_rates[south as usize * 2 + east as usize]
+}
+
+fn update_food_sources(food_sources: &mut FoodSources, rng: &mut impl Rng, config: &SimulationConfig) {
+    let patches = config.food_patches.max(1);
+    let out_of_bounds = food_sources.centers.iter().any(|(x, y)| *x >= config.columns as f32 || *y >= config.rows as f32);
+    if out_of_bounds || food_sources.centers.len() != patches {
+        food_sources.centers = (0..patches).map(|_| {
+            let position = random_position(rng, config);
+            (position.x as f32, position.y as f32)
+        }).collect();
+    }
+    if config.food_distribution == FoodDistribution::Patches {
+        for (x, y) in food_sources.centers.iter_mut() {
+            *x = (*x + standard_normal(rng) * config.food_patch_drift).rem_euclid(config.columns as f32);
+            *y = (*y + standard_normal(rng) * config.food_patch_drift).rem_euclid(config.rows as f32);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_food(mut commands: Commands, mut food_map: ResMut<FoodMap>, mut food_sources: ResMut<FoodSources>, mut controller: ResMut<FoodController>, snakes: Query<&Snake>, solids_map: Res<SolidsMap>, segment_map: Res<SegmentMap>, rng: Res<RngResource>, time: Res<SimTime>, mut counts: ResMut<EntityCounts>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let rng = &mut rng.stream(RngStream::Food, &time);
+    update_food_sources(&mut food_sources, rng, &config);
+    let food_per_step = match config.food_control {
+        FoodControl::Off => config.food_per_step,
+        FoodControl::Population => controller.update(counts.snakes as f32, &config),
+        FoodControl::Energy => controller.update(snakes.iter().map(|snake| snake.energy.energy).sum(), &config),
+    };
+    stats.food_per_step = match config.food_control {
+        FoodControl::Off => config.food_per_step as f32,
+        _ => controller.rate,
+    };
+    let max_rate = config.food_region_rates.iter().cloned().fold(0.0, f32::max);
+    if max_rate <= 0.0 {
+        return;
+    }
+    for _ in 0..food_per_step {
+        let Some(position) = find_free_position(rng, &solids_map, &segment_map, |rng| sample_food_position(rng, &config, &food_sources.centers)) else {
+            continue;
+        };
+        // regions with lower rates reject a part of the food that would land there
+        if !rng.gen_bool((region_rate(&position, &config) / max_rate).clamp(0.0, 1.0) as f64) {
+            continue;
+        }
+        let food = food_map.map.get_mut(&position);
         if !food.contains_food() {
-            commands.spawn((Position { x, y }, Food { plant: config.plant_matter_per_segment, meat: 0.0 }, Age { age: 0, efficiency_factor: 1.0 }));
+            if counts.food >= config.max_food {
+                continue;
+            }
+            counts.food += 1;
+            spawn_food_entity(&mut commands, position.clone());
         }
+        // plants already growing there are replaced, only the difference grows
+        stats.energy_flows.plants_grown += (config.plant_matter_per_segment - food.plant).max(0.0);
         *food = Food::from_plant(config.plant_matter_per_segment);
     }
 }
 
-pub fn destroy_old_food(mut commands: Commands, mut food: Query<(Entity, &Position, &Food, &Age)>, mut food_map: ResMut<FoodMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
-    for (food_id, postition, food, age) in &mut food {
+// below this amount the rest of the meat rots at once, so that corpses eventually disappear
+const MIN_MEAT_AMOUNT: f32 = 1.0;
+
+pub fn decay_meat(mut food_map: ResMut<FoodMap>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if config.meat_decay_rate <= 0.0 {
+        return;
+    }
+    let mut decayed = 0.0;
+    let mut composted = 0.0;
+    for food in food_map.map.map.iter_mut().filter(|food| food.meat > 0.0) {
+        let mut rotten = food.meat * config.meat_decay_rate;
+        if food.meat - rotten < MIN_MEAT_AMOUNT {
+            rotten = food.meat;
+        }
+        food.meat -= rotten;
+        food.plant += rotten * config.meat_compost_ratio;
+        decayed += rotten;
+        composted += rotten * config.meat_compost_ratio;
+    }
+    stats.total_decayed_meat += decayed;
+    stats.total_composted_plants += composted;
+    stats.energy_flows.meat_decayed += decayed;
+    stats.energy_flows.meat_composted += composted;
+}
+
+pub fn destroy_old_food(mut food: Query<(&Position, &Food, &Age)>, mut food_map: ResMut<FoodMap>) {
+    profile_system!();
+    for (postition, _, age) in &mut food {
         if age.age >= 5000 {
             food_map.map.set(postition, Food::default());
         }
     }
 }
 
-pub fn eat_food(mut snakes: Query<(&Position, &mut Snake)>, mut food_map: ResMut<FoodMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub fn eat_food(mut snakes: Query<(&Position, &mut Snake)>, mut food_map: ResMut<FoodMap>, mut stats: ResMut<Stats>) {
+    profile_system!();
     for (position, mut snake) in &mut snakes {
         let food = food_map.map.get_mut(position);
         let place_for_plants = snake.metabolism.max_plants_in_stomach - snake.energy.plant_in_stomach;
@@ -729,17 +1723,21 @@ pub fn eat_food(mut snakes: Query<(&Position, &mut Snake)>, mut food_map: ResMut
         let meat_to_eat = food.meat.min(place_for_meat);
         if snake.metabolism.plant_processing_speed > 0.0 {
             snake.energy.plant_in_stomach += plants_to_eat;
+            snake.energy.food_eaten += plants_to_eat;
             food.plant -= plants_to_eat;
+            stats.energy_flows.plants_eaten += plants_to_eat;
         }
         if snake.metabolism.meat_processing_speed > 0.0 {
             snake.energy.meat_in_stomach += meat_to_eat;
+            snake.energy.food_eaten += meat_to_eat;
             food.meat -= meat_to_eat;
+            stats.energy_flows.meat_eaten += meat_to_eat;
         }
     }
 }
 
-pub fn despawn_food(mut commands: Commands, food: Query<(Entity, &Position, &Food)>, mut food_map: ResMut<FoodMap>) {
-    puffin::profile_function!();
+pub fn despawn_food(mut commands: Commands, food: Query<(Entity, &Position, &Food)>, food_map: ResMut<FoodMap>) {
+    profile_system!();
     for (food_id, position, _) in &food {
         if !food_map.map.get(position).contains_food() {
             commands.entity(food_id).despawn();
@@ -747,68 +1745,146 @@ pub fn despawn_food(mut commands: Commands, food: Query<(Entity, &Position, &Foo
     }
 }
 
-pub fn starve(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake)>, positions: Query<&Position>, mut food_map: ResMut<FoodMap>, mut species: ResMut<Species>, mut solids_map: ResMut<SolidsMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
-    for (head_id, mut snake) in &mut snakes {
+#[allow(clippy::too_many_arguments)]
+pub fn starve(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake, &Age)>, infected: Query<(), With<Infection>>, positions: Query<&Position>, mut food_map: ResMut<FoodMap>, mut species: ResMut<Species>, mut solids_map: ResMut<SolidsMap>, mut simulation_events: ResMut<SimulationEvents>, mut region_counters: ResMut<RegionCounters>, mut stats: ResMut<Stats>, quotas: Res<SpeciesQuotas>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let mut starved = vec![];
+    for (head_id, mut snake, age) in &mut snakes {
         debug!("Snake {:?} has energy {} and plants {} and meat {} in stomach", head_id, snake.energy.energy, snake.energy.plant_in_stomach, snake.energy.meat_in_stomach);
-        if snake.energy.energy < 0.0 {
+        if snake.energy.energy >= 0.0 {
+            snake.starving = 0;
+            continue;
+        }
+        // elites are spared until they get old, so they can't live forever, they keep their debt so no energy comes from nothing
+        if config.protect_elites && quotas.elites.contains(&head_id) && age.age < config.snake_max_age {
+            snake.starving = 0;
+        } else if snake.starving < config.starvation_grace {
+            debug!("Snake {:?} is starving", head_id);
+            snake.starving += 1;
+        } else {
             debug!("Snake {:?} starved to death", head_id);
-            kill_snake(&mut commands, &positions, &mut food_map, &mut species, &mut solids_map, &config, head_id, &mut snake);
+            starved.push(head_id);
         }
     }
+    for head_id in starved {
+        // a sick snake running out of energy died of its illness
+        let cause = if infected.contains(head_id) {
+            stats.disease_deaths += 1;
+            DeathCause::Disease
+        } else {
+            DeathCause::Starvation
+        };
+        kill_snake(&mut commands, &snakes, &positions, &mut food_map, &mut species, &mut solids_map, &mut simulation_events, &mut region_counters, &mut stats, &config, head_id, cause);
+    }
 }
 
-fn remove_segment_and_transform_to_food(mut commands: &mut Commands, positions: &Query<&Position>, mut food_map: &mut ResMut<FoodMap>, mut solids_map: &mut ResMut<SolidsMap>, config: &Res<SimulationConfig>, segment_id: &Entity) {
+// The lower the efficiency of a snake the more likely it dies, snakes past the max lifespan always do
+#[allow(clippy::too_many_arguments)]
+pub fn die_of_old_age(mut commands: Commands, snakes: Query<(Entity, &mut Snake, &Age)>, positions: Query<&Position>, mut food_map: ResMut<FoodMap>, mut species: ResMut<Species>, mut solids_map: ResMut<SolidsMap>, mut simulation_events: ResMut<SimulationEvents>, mut region_counters: ResMut<RegionCounters>, mut stats: ResMut<Stats>, rng: Res<RngResource>, time: Res<SimTime>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if config.old_age_mortality <= 0.0 && config.max_lifespan == 0 {
+        return;
+    }
+    let mut rng = rng.stream(RngStream::Aging, &time);
+    let died: Vec<Entity> = snakes.iter().filter(|(_, _, age)| {
+        let too_old = config.max_lifespan > 0 && age.age >= config.max_lifespan;
+        let mortality = config.old_age_mortality * (1.0 - age.efficiency_factor) / (1.0 - MIN_EFFICIENCY);
+        too_old || (mortality > 0.0 && rng.gen_bool(mortality.min(1.0) as f64))
+    }).map(|(head_id, _, _)| head_id).collect();
+    for head_id in died {
+        debug!("Snake {:?} died of old age", head_id);
+        kill_snake(&mut commands, &snakes, &positions, &mut food_map, &mut species, &mut solids_map, &mut simulation_events, &mut region_counters, &mut stats, &config, head_id, DeathCause::OldAge);
+    }
+}
+
+fn remove_segment_and_transform_to_food(commands: &mut Commands, positions: &Query<&Position>, food_map: &mut ResMut<FoodMap>, solids_map: &mut ResMut<SolidsMap>, config: &Res<SimulationConfig>, segment_id: &Entity) {
     commands.entity(*segment_id).despawn();
     let position = positions.get(*segment_id).unwrap();
     solids_map.map.set(position, false);
     let added_food = Food::from_meat(config.new_segment_cost);
     debug!("Segment is becoming food now: {:?}", added_food);
-    food_map.map.set(position, added_food.clone());
-    commands.spawn((position.clone(), added_food, Age { age: 0, efficiency_factor: 1.0 }));
+    if !food_map.map.get(position).contains_food() {
+        spawn_food_entity(commands, position.clone());
+    }
+    food_map.map.set(position, added_food);
 }
 
-fn remove_snake_from_species(species: &mut ResMut<Species>, head_id: Entity, snake: &mut Mut<Snake>) {
+// Food entities only track how old the food on a hex is so it can rot away, the amounts live in the food map
+#[cfg(not(feature = "map_food"))]
+pub(crate) fn spawn_food_entity(commands: &mut Commands, position: Position) {
+    commands.spawn((position, Food::default(), Age { age: 0, efficiency_factor: 1.0 }));
+}
+
+// Large maps can skip the food entities entirely, food then never gets too old
+#[cfg(feature = "map_food")]
+pub(crate) fn spawn_food_entity(_commands: &mut Commands, _position: Position) {}
+
+// The next oldest member leads a specie when its leader dies, new members are compared to its genome from then on
+fn remove_snake_from_species(species: &mut ResMut<Species>, simulation_events: &mut ResMut<SimulationEvents>, snakes: &Query<(Entity, &mut Snake, &Age)>, head_id: Entity, snake: &Snake) {
     let specie = snake.species.unwrap();
-    if let Some(mut specie) = species.species.iter_mut().find(|s| s.id == specie) {
-        if specie.leader == head_id {
-            specie.members.retain(|s| *s != head_id);
-            if let Some(new_leader) = specie.members.pop_front() {
-                specie.leader = new_leader;
-                specie.leader_network = snake.brain.get_neural_network().unwrap().clone();
-                debug!("New leader for specie {:?}: {:?}", specie.id, specie.leader);
-            } else {
-                let specie_id = specie.id;
-                debug!("Specie {:?} is extinct", specie_id);
-                species.species.retain(|s| s.id != specie_id);
-            }
-        } else {
-            specie.members.retain(|s| *s != head_id);
+    if let Some(specie) = species.species.iter_mut().find(|s| s.id == specie) {
+        specie.members.retain(|s| *s != head_id);
+        if specie.leader != head_id {
             debug!("Snake {:?} died and was removed from specie {:?}", head_id, specie.id);
+            return;
         }
+        // the leader stays a member like the first leader of a specie does, members that are no snake anymore are dropped
+        while let Some(new_leader) = specie.members.pop_front() {
+            let Ok((_, leader, _)) = snakes.get(new_leader) else {
+                warn!("Member {:?} of specie {:?} is no snake anymore", new_leader, specie.id);
+                continue;
+            };
+            specie.members.push_front(new_leader);
+            specie.leader = new_leader;
+            specie.leader_network = leader.brain.get_neural_network().unwrap().clone();
+            specie.leader_dna = leader.dna.clone();
+            debug!("New leader for specie {:?}: {:?}", specie.id, specie.leader);
+            return;
+        }
+        let specie_id = specie.id;
+        debug!("Specie {:?} is extinct", specie_id);
+        species.species.retain(|s| s.id != specie_id);
+        simulation_events.push(SimulationEventKind::SpecieExtinct { specie: specie_id });
     } else {
         warn!("Snake {:?} died and was not found in any specie", head_id);
     }
 }
 
-pub fn die_from_collisions(mut commands: Commands, positions: Query<&Position>, mut snake: Query<(Entity, &mut Snake, &DiedFromCollision)>, mut food_map: ResMut<FoodMap>, mut species: ResMut<Species>, mut solids_map: ResMut<SolidsMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
-    for (head_id, mut snake, _) in &mut snake {
+#[allow(clippy::too_many_arguments)]
+pub fn die_from_collisions(mut commands: Commands, positions: Query<&Position>, snakes: Query<(Entity, &mut Snake, &Age)>, collided: Query<Entity, With<DiedFromCollision>>, mut food_map: ResMut<FoodMap>, mut species: ResMut<Species>, mut solids_map: ResMut<SolidsMap>, mut simulation_events: ResMut<SimulationEvents>, mut counters: ResMut<BehaviorCounters>, mut region_counters: ResMut<RegionCounters>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    for head_id in &collided {
+        if !snakes.contains(head_id) {
+            continue;
+        }
+        counters.collisions += 1;
         debug!("Snake {:?} collided with something solid", head_id);
-        kill_snake(&mut commands, &positions, &mut food_map, &mut species, &mut solids_map, &config, head_id, &mut snake);
+        kill_snake(&mut commands, &snakes, &positions, &mut food_map, &mut species, &mut solids_map, &mut simulation_events, &mut region_counters, &mut stats, &config, head_id, DeathCause::Collision);
     }
 }
 
-fn kill_snake(mut commands: &mut Commands, positions: &Query<&Position>, mut food_map: &mut ResMut<FoodMap>, mut species: &mut ResMut<Species>, mut solids_map: &mut ResMut<SolidsMap>, config: &Res<SimulationConfig>, head_id: Entity, mut snake: &mut Mut<Snake>) {
+#[allow(clippy::too_many_arguments)]
+fn kill_snake(commands: &mut Commands, snakes: &Query<(Entity, &mut Snake, &Age)>, positions: &Query<&Position>, food_map: &mut ResMut<FoodMap>, species: &mut ResMut<Species>, solids_map: &mut ResMut<SolidsMap>, simulation_events: &mut ResMut<SimulationEvents>, region_counters: &mut ResMut<RegionCounters>, stats: &mut ResMut<Stats>, config: &Res<SimulationConfig>, head_id: Entity, cause: DeathCause) {
+    let (_, snake, age) = snakes.get(head_id).unwrap();
     commands.entity(head_id).remove::<Snake>();
-    remove_snake_from_species(&mut species, head_id, &mut snake);
+    if let Ok(position) = positions.get(head_id) {
+        region_counters.deaths.push(position.clone());
+    }
+    simulation_events.push(SimulationEventKind::SnakeDied { snake: head_id, cause, age: age.age, generation: snake.generation });
+    if age.age > simulation_events.longest_lifespan {
+        simulation_events.longest_lifespan = age.age;
+        simulation_events.push(SimulationEventKind::LifespanRecord { snake: head_id, age: age.age });
+    }
+    remove_snake_from_species(species, simulation_events, snakes, head_id, snake);
     for segment_id in &snake.segments {
-        remove_segment_and_transform_to_food(&mut commands, &positions, &mut food_map, &mut solids_map, &config, segment_id);
+        remove_segment_and_transform_to_food(commands, positions, food_map, solids_map, config, segment_id);
+        stats.energy_flows.corpse_meat += config.new_segment_cost;
     }
+    stats.mutations.record_death(snake, age.age);
 }
 
-pub fn reproduce(mut commands: Commands, mut snakes: Query<(&mut MeatMatter, &Position)>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub fn reproduce(_commands: Commands, _snakes: Query<(&mut MeatMatter, &Position)>, _config: Res<SimulationConfig>) {
+    profile_system!();
     // for (mut energy, position) in &mut snakes {
     //     if energy.amount >= config.energy_to_breed {
     //         energy.amount -= config.energy_to_breed / 2;
@@ -819,58 +1895,61 @@ pub fn reproduce(mut commands: Commands, mut snakes: Query<(&mut MeatMatter, &Po
     // }
 }
 
-pub fn split(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake)>, segments: Query<&SegmentType>, positions: Query<&Position>, config: Res<SimulationConfig>, mut innovation_tracker: ResMut<InnovationTracker>) {
-    puffin::profile_function!();
-    for (head_id, mut snake) in &mut snakes {
+fn is_fertile(age: &Age, config: &SimulationConfig) -> bool {
+    age.age >= config.maturity_age && (config.menopause_age == 0 || age.age < config.menopause_age)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn split(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake, &Age)>, segments: Query<&SegmentType>, positions: Query<&Position>, mut simulation_events: ResMut<SimulationEvents>, rng: Res<RngResource>, time: Res<SimTime>, mut quotas: ResMut<SpeciesQuotas>, mut counts: ResMut<EntityCounts>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let rng = &mut rng.stream(RngStream::Split, &time);
+    for (head_id, mut snake, age) in &mut snakes {
         let snake_length = snake.segments.len();
-        if snake_length >= config.size_to_split {
+        if snake_length >= config.size_to_split && is_fertile(age, &config) && !counts.blocks_split(&config) && quotas.allows_split(snake.species) {
+            counts.snakes += 1;
             debug!("Snake splits: {:#?}, {:#?}", snake.metabolism, snake.energy);
-            let mut new_snake_segments = snake.segments.split_off(snake_length / 2);
+            let new_snake_segments = snake.segments.split_off(snake_length / 2);
             let new_head_id = new_snake_segments.first().unwrap();
             let new_head_position = positions.get(*new_head_id).unwrap();
             // new_snake_segments.reverse();
             let mut new_head;
             if let Some(neural_network) = snake.brain.get_neural_network() {
                 debug!("Snake {:?} is splitting with neural network", head_id);
-                let mut new_neural_network = neural_network.clone();
-                let mut rng = rand::thread_rng();
-                let mut mutations = snake.mutations;
-                if rng.gen_bool(config.mutation.connection_flip_chance) {
-                    new_neural_network.flip_random_connection();
-                    mutations += 1;
-                }
-                if rng.gen_bool(config.mutation.weight_perturbation_chance) {
-                    new_neural_network.mutate_perturb_random_connection_weight(config.mutation.weight_perturbation_range, config.mutation.perturb_disabled_connections);
-                    mutations += 1;
-                }
-                if rng.gen_bool(config.mutation.weight_reset_chance) {
-                    new_neural_network.mutate_reset_random_connection_weight(config.mutation.weight_reset_range, config.mutation.perturb_reset_connections);
-                    mutations += 1;
-                }
-                let mut dna = snake.dna.clone();
-                if rng.gen_bool(config.mutation.dna_mutation_chance) {
-                    dna.mutate();
-                    mutations += 1;
-                }
+                let parent = Genome { network: neural_network.clone(), dna: snake.dna.clone() };
+                let (Genome { network: new_neural_network, dna }, birth_mutations) = parent.mutate(&config.mutation, rng);
+                // pruning only removes what was not used anyway, so it does not count as a mutation
+                stats.mutations.record_birth(&birth_mutations);
+                let temperature_range = config.mutation.action_temperature_mutation_range;
+                let action_temperature = if temperature_range > 0.0 {
+                    let inherited = snake.action_temperature.unwrap_or(config.action_temperature);
+                    Some((inherited + rng.gen_range(-temperature_range..=temperature_range)).clamp(0.0, MAX_ACTION_TEMPERATURE))
+                } else {
+                    None
+                };
+                let mutations = snake.mutations + birth_mutations.iter().filter(|mutation| **mutation != MutationKind::Prune).count() as u32;
                 debug!("New neural network: {:?}", new_neural_network);
-                new_head = create_head((new_head_position.x, new_head_position.y), Box::new(RandomNeuralBrain::from_neural_network(new_neural_network.clone())), snake.generation + 1, mutations, dna);
+                let direction = if rng.gen_bool(0.5) {
+                    turn_left(&snake.direction)
+                } else {
+                    turn_right(&snake.direction)
+                };
+                new_head = create_head((new_head_position.x, new_head_position.y), direction, Box::new(RandomNeuralBrain::from_neural_network(new_neural_network.clone())), snake.generation + 1, mutations, dna);
                 new_head.0.segments = new_snake_segments;
+                new_head.0.parent_id = Some(snake.id);
+                new_head.0.birth_mutations = birth_mutations;
+                new_head.0.action_temperature = action_temperature;
                 new_head.0.energy.energy = snake.energy.energy / 2.0;
-                snake.energy.energy = snake.energy.energy / 2.0;
+                snake.energy.energy /= 2.0;
                 new_head.0.energy.plant_in_stomach = snake.energy.plant_in_stomach / 2.0;
-                snake.energy.plant_in_stomach = snake.energy.plant_in_stomach / 2.0;
+                snake.energy.plant_in_stomach /= 2.0;
                 new_head.0.energy.meat_in_stomach = snake.energy.meat_in_stomach / 2.0;
-                snake.energy.meat_in_stomach = snake.energy.meat_in_stomach / 2.0;
+                snake.energy.meat_in_stomach /= 2.0;
                 recalculate_snake_params(&mut snake, &segments, &config, None);
                 recalculate_snake_params(&mut new_head.0, &segments, &config, None);
                 debug!("Old snake after split: {:#?}, {:#?}", snake.metabolism, snake.energy);
                 debug!("New snake after split: {:#?}, {:#?}", new_head.0.metabolism, new_head.0.energy);
                 let new_head_id = new_head.0.segments[0];
-                if rng.gen_bool(0.5) {
-                    new_head.0.direction = turn_left(&snake.direction);
-                } else {
-                    new_head.0.direction = turn_right(&snake.direction);
-                }
+                simulation_events.push(SimulationEventKind::SnakeBorn { snake: new_head_id, generation: new_head.0.generation, mutations: new_head.0.mutations });
                 commands.entity(new_head_id).insert(new_head);
                 commands.entity(new_head_id).remove::<SegmentType>();
             } else {
@@ -880,11 +1959,17 @@ pub fn split(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake)>, se
     }
 }
 
+// Part of the plant digestion speed that is kept for a body with the given share of stomach segments
+fn plant_digestion_kept(stomach_share: f32, config: &SimulationConfig) -> f32 {
+    1.0 - config.diet_tradeoff_strength * stomach_share.powf(config.diet_tradeoff_exponent)
+}
+
 fn recalculate_snake_params(snake: &mut Snake, segments: &Query<&SegmentType>, config: &Res<SimulationConfig>, new_segment: Option<&SegmentType>) {
     let mut mobility = 0.0;
     let mut move_cost = 0.0;
     let mut segment_basic_cost = 0.0;
     let mut segment_energy_production = 0.0;
+    let mut stomachs = 0;
     snake.metabolism = Metabolism::default();
     for segment_id in &snake.segments {
         if *segment_id == snake.segments[0] {
@@ -900,17 +1985,29 @@ fn recalculate_snake_params(snake: &mut Snake, segments: &Query<&SegmentType>, c
         } else {
             segment_energy_production -= segment.energy_cost_always();
         }
+        if let SegmentType::Stomach(_) = segment {
+            // TODO: this should come from config
+            snake.metabolism.meat_processing_speed += 1.0;
+            snake.metabolism.max_meat_in_stomach += 200.0;
+            stomachs += 1;
+        }
         match segment {
-            SegmentType::Stomach(_) => {
-                // TODO: this should come from config
-                snake.metabolism.meat_processing_speed += 1.0;
-                snake.metabolism.max_meat_in_stomach += 200.0;
-            }
+            SegmentType::Fin(_) => snake.metabolism.swims = true,
+            SegmentType::Eye(_) => snake.metabolism.eyes += 1,
+            SegmentType::Nose(_) => snake.metabolism.noses += 1,
             _ => {}
         }
     }
+    // meat eaters give up some of their plant digestion so herbivores and carnivores can beat omnivores in their niche
+    if config.diet_tradeoff_strength > 0.0 && snake.segments.len() > 1 {
+        snake.metabolism.plant_processing_speed *= plant_digestion_kept(stomachs as f32 / (snake.segments.len() - 1) as f32, config);
+    }
     let len = snake.segments.len() as f32;
-    snake.metabolism.mobility = mobility / len;
+    let traits = snake.dna.traits;
+    snake.metabolism.mobility = mobility / len * traits.mobility;
+    snake.metabolism.max_energy *= traits.max_energy;
+    snake.metabolism.plant_processing_speed *= traits.digestion_speed;
+    snake.metabolism.meat_processing_speed *= traits.digestion_speed;
     snake.metabolism.segment_move_cost += move_cost;
     snake.metabolism.segment_basic_cost += segment_basic_cost;
     snake.metabolism.segment_energy_production += segment_energy_production;
@@ -928,39 +2025,92 @@ fn recalculate_snake_params(snake: &mut Snake, segments: &Query<&SegmentType>, c
 }
 
 pub fn increase_age(mut agables: Query<&mut Age>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+    profile_system!();
     for mut age in &mut agables {
-        age.age += 10;
-        age.efficiency_factor = (1.0 / (age.age as f32 / config.snake_max_age as f32)).min(1.0);
+        age.age = age.age.saturating_add(AGING_INTERVAL * config.aging_rate);
+        age.efficiency_factor = config.aging_curve.efficiency(age.age, config.snake_max_age);
         if age.efficiency_factor < 1.0 {
             debug!("Snake is getting old, efficiency factor is {}", age.efficiency_factor);
         }
     }
 }
-pub fn calculate_stats(entities: Query<Entity>, scents: Query<&Scent>, food: Query<&Food>, snakes: Query<(&Snake, &Age)>, segments: Query<&SegmentType>, mut stats: ResMut<Stats>, species: Res<Species>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
-    let max_age = snakes.iter().map(|(_, a)| a.age).reduce(|a, b| a.max(b));
-    let max_generation = snakes.iter().map(|(s, _)| s.generation).reduce(|a, b| a.max(b));
-    let max_mutation = snakes.iter().map(|(s, _)| s.mutations).reduce(|a, b| a.max(b));
-    stats.oldest_snake = max_age.unwrap_or(0);
-    stats.total_snakes = snakes.iter().count();
-    stats.total_food = food.iter().count();
-    stats.total_segments = segments.iter().count();
-    stats.total_scents = scents.iter().count();
-    stats.max_generation = max_generation.unwrap_or(0);
-    stats.max_mutations = max_mutation.unwrap_or(0);
-    stats.species = species.clone();
-    stats.total_entities = entities.iter().count();
-    stats.total_snake_energy = snakes.iter().map(|(s, _)| s.energy.energy).sum();
-    stats.total_plants_in_stomachs = snakes.iter().map(|(s, _)| s.energy.plant_in_stomach).sum();
-    stats.total_meat_in_stomachs = snakes.iter().map(|(s, _)| s.energy.meat_in_stomach).sum();
-    stats.total_plants = food.iter().map(|f| f.plant).sum();
-    stats.total_meat = food.iter().map(|f| f.meat).sum();
-    stats.total_energy = stats.total_snake_energy + stats.total_plants * config.plant_energy_content + stats.total_meat * config.meat_energy_content;
-}
-
-pub fn process_food(mut snake: Query<(&mut Snake, &Age)>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub(crate) const HISTOGRAM_BUCKETS: usize = 20;
+
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_stats(entities: Query<Entity>, scents: Query<&Scent>, food_map: Res<FoodMap>, snakes: Query<(&Snake, &Age)>, heads: Query<(Entity, &Position), With<Snake>>, mut counters: ResMut<BehaviorCounters>, mut region_counters: ResMut<RegionCounters>, segments: Query<&SegmentType>, mut stats: ResMut<Stats>, mut worker: ResMut<StatsWorker>, species: Res<Species>, engine_state: Res<EngineState>, time: Res<SimTime>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let sample = StatsSample {
+        frame: engine_state.frames,
+        time: *time,
+        snakes: snakes.iter().map(|(snake, age)| SnakeSample {
+            age: age.age,
+            generation: snake.generation,
+            mutations: snake.mutations,
+            species: snake.species,
+            energy: snake.energy.energy,
+            plants_in_stomach: snake.energy.plant_in_stomach,
+            meat_in_stomach: snake.energy.meat_in_stomach,
+            segments: snake.segments.len(),
+            connections: snake.brain.get_neural_network().map_or(0, |network| network.get_active_connections().len()),
+            outputs: snake.outputs.clone(),
+        }).collect(),
+        species: species.clone(),
+        food_map: Cow::Borrowed(&food_map),
+        heads: heads.iter().map(|(_, position)| position.clone()).collect(),
+        deaths: std::mem::take(&mut region_counters.deaths),
+        total_segments: segments.iter().count(),
+        total_scents: scents.iter().count(),
+        total_entities: entities.iter().count(),
+        behavior: counters.take_stats(heads.iter(), &config),
+        config: Cow::Borrowed(&config),
+    };
+    // only the worker needs its own copy of the food map and settings
+    if config.background_stats {
+        worker.send(sample.into_owned());
+    } else {
+        sample.summarize(&mut stats);
+    }
+}
+
+impl BehaviorCounters {
+    // Summarizes everything counted since the last call and starts counting again from the current head positions
+    fn take_stats<'a>(&mut self, heads: impl Iterator<Item = (Entity, &'a Position)>, config: &SimulationConfig) -> BehaviorStats {
+        let decisions: usize = self.decisions.iter().sum();
+        let share = |count: usize| if decisions > 0 { count as f32 / decisions as f32 } else { 0.0 };
+        let mut head_positions = HashMap::new();
+        let mut total_displacement = 0.0;
+        let mut displaced = 0;
+        for (head_id, position) in heads {
+            // snakes born since the last update have nothing to compare with
+            if let Some(previous) = self.head_positions.get(&head_id) {
+                let (dx, dy) = hex_offset(previous, position, config);
+                total_displacement += (dx * dx + dy * dy).sqrt();
+                displaced += 1;
+            }
+            head_positions.insert(head_id, position.clone());
+        }
+        let behavior = BehaviorStats {
+            decision_shares: self.decisions.map(share),
+            mean_displacement: if displaced > 0 { total_displacement / displaced as f32 } else { 0.0 },
+            collision_rate: share(self.collisions) * 1000.0,
+        };
+        *self = BehaviorCounters { head_positions, ..BehaviorCounters::default() };
+        behavior
+    }
+}
+
+// Counts snakes, food and deaths in every region
+pub(crate) fn region_stats(heads: &[Position], deaths: &[Position], food_map: &FoodMap, config: &SimulationConfig) -> Vec<RegionStats> {
+    config.regions.iter().map(|region| RegionStats {
+        name: region.name.clone(),
+        snakes: heads.iter().filter(|position| region.contains(position, config)).count(),
+        food: food_map.map.iter().filter(|(position, _)| region.contains(position, config)).map(|(_, food)| food.plant + food.meat).sum(),
+        deaths: deaths.iter().filter(|position| region.contains(position, config)).count(),
+    }).collect()
+}
+
+pub fn process_food(mut snake: Query<(&mut Snake, &Age)>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
     for (mut snake, age) in &mut snake {
         debug!("Snake energy at start: {}", snake.energy.energy);
         if snake.energy.energy < snake.metabolism.max_energy {
@@ -968,6 +2118,8 @@ pub fn process_food(mut snake: Query<(&mut Snake, &Age)>, config: Res<Simulation
             snake.energy.plant_in_stomach -= eaten_plants;
             let eaten_meat = snake.energy.meat_in_stomach.min(snake.metabolism.meat_processing_speed);
             snake.energy.meat_in_stomach -= eaten_meat;
+            stats.energy_flows.plants_digested += eaten_plants;
+            stats.energy_flows.meat_digested += eaten_meat;
             debug!("Snake ate {} plants and {} meat and now has {} plants and {} meat in stomach", eaten_plants, eaten_meat, snake.energy.plant_in_stomach, snake.energy.meat_in_stomach);
             let plant_energy_gain = eaten_plants * config.plant_energy_content * age.efficiency_factor;
             let meat_energy_gain = eaten_meat * config.meat_energy_content * age.efficiency_factor;
@@ -983,21 +2135,26 @@ pub fn process_food(mut snake: Query<(&mut Snake, &Age)>, config: Res<Simulation
     }
 }
 
-pub fn grow(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake)>, segment_map: Res<SegmentMap>, config: Res<SimulationConfig>, segments: Query<&SegmentType>) {
-    puffin::profile_function!();
-    for (snake_id, mut snake) in &mut snakes {
+pub fn grow(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake)>, segment_map: Res<SegmentMap>, mut counts: ResMut<EntityCounts>, config: Res<SimulationConfig>, segments: Query<&SegmentType>) {
+    profile_system!();
+    for (_snake_id, mut snake) in &mut snakes {
         // tail always takes energy from head when growing
         let position_empty = segment_map.map.get(&Position { x: snake.last_position.0, y: snake.last_position.1 }).is_empty();
-        if position_empty && snake.energy.accumulated_meat_matter_for_growth >= config.new_segment_cost {
+        let solid_next = matches!(snake.dna.get_current_gene().segment_type, SegmentType::Solid(_));
+        if solid_next && !can_build_solid(&snake, &config) {
+            continue;
+        }
+        if position_empty && snake.energy.accumulated_meat_matter_for_growth >= config.new_segment_cost && !counts.blocks_growth(&config) {
+            counts.segments += 1;
             let meat_for_tail = config.new_segment_cost;
             snake.energy.accumulated_meat_matter_for_growth -= meat_for_tail;
+            if solid_next && config.minerals_enabled {
+                snake.energy.minerals -= config.solid_segment_minerals;
+            }
             let segment_type = snake.dna.build_segment();
             let new_tail = commands.spawn((segment_type.clone(), Position { x: snake.last_position.0, y: snake.last_position.1 }, MeatMatter { amount: meat_for_tail })).id();
-            match segment_type {
-                SegmentType::Solid(_) => {
-                    commands.entity(new_tail).insert(Solid {});
-                }
-                _ => {}
+            if let SegmentType::Solid(_) = segment_type {
+                commands.entity(new_tail).insert(Solid {});
             }
             snake.segments.push(new_tail);
             recalculate_snake_params(&mut snake, &segments, &config, Some(&segment_type));
@@ -1007,16 +2164,16 @@ pub fn grow(mut commands: Commands, mut snakes: Query<(Entity, &mut Snake)>, seg
 
 
 pub fn assign_missing_segments(mut snakes: Query<(Entity, &mut Snake), Added<Snake>>) {
-    puffin::profile_function!();
+    profile_system!();
     for (snake_id, mut snake) in &mut snakes {
-        if snake.segments.len() == 0 {
+        if snake.segments.is_empty() {
             snake.segments.push(snake_id);
         }
     }
 }
 
-pub fn assign_solid_positions(mut solids: Query<(&Position, &Solid)>, mut solids_map: ResMut<SolidsMap>, segment_map: Res<SegmentMap>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub fn assign_solid_positions(mut solids: Query<(&Position, &Solid)>, mut solids_map: ResMut<SolidsMap>) {
+    profile_system!();
     solids_map.map.clear();
     for (position, _) in &mut solids {
         solids_map.map.set(position, true);
@@ -1024,20 +2181,20 @@ pub fn assign_solid_positions(mut solids: Query<(&Position, &Solid)>, mut solids
 }
 
 pub fn assign_segment_positions(mut segment_map: ResMut<SegmentMap>, segments: Query<(Entity, &Position, &SegmentType)>) {
-    puffin::profile_function!();
+    profile_system!();
     segment_map.map.clear();
     for (segment_id, position, _) in &segments {
         segment_map.map.add(position, segment_id);
     }
 }
 
-pub fn assign_species(new_borns: Query<Entity, Added<JustBorn>>, mut snakes: Query<(Entity, &mut Snake)>, mut species: ResMut<Species>, config: Res<SimulationConfig>) {
-    puffin::profile_function!();
+pub fn assign_species(new_borns: Query<Entity, Added<JustBorn>>, mut snakes: Query<(Entity, &mut Snake)>, mut species: ResMut<Species>, mut simulation_events: ResMut<SimulationEvents>, config: Res<SimulationConfig>) {
+    profile_system!();
     for baby_id in &new_borns {
         // let mut baby_snake = None;
         for specie in species.species.iter_mut() {
-            if let Ok([(snake_id, mut snake), (leader_id, leader_snake)]) = snakes.get_many_mut([baby_id, specie.leader]) {
-                let compatibility = calculate_gene_difference(&leader_snake.brain.get_neural_network().unwrap(), &snake.brain.get_neural_network().unwrap());
+            if let Ok([(snake_id, mut snake), (_leader_id, leader_snake)]) = snakes.get_many_mut([baby_id, specie.leader]) {
+                let compatibility = calculate_gene_difference(leader_snake.brain.get_neural_network().unwrap(), snake.brain.get_neural_network().unwrap(), &config);
                 debug!("Difference: {}", compatibility);
                 if compatibility < config.species_threshold {
                     debug!("Snake {:?} is in specie {:?}", snake_id, specie.id);
@@ -1054,50 +2211,171 @@ pub fn assign_species(new_borns: Query<Entity, Added<JustBorn>>, mut snakes: Que
         let (_, mut baby_snake) = snakes.get_mut(baby_id).unwrap();
         if baby_snake.species.is_none() {
             let baby_neural_network = baby_snake.brain.get_neural_network().unwrap().clone();
-            let mut new_specie = Specie { id: species.last_id + 1, leader: baby_id, members: VecDeque::new(), leader_network: baby_neural_network };
+            let mut new_specie = Specie { id: species.last_id + 1, leader: baby_id, members: VecDeque::new(), leader_network: baby_neural_network, leader_dna: baby_snake.dna.clone() };
             new_specie.members.push_back(baby_id);
             species.species.push(new_specie);
             species.last_id += 1;
             baby_snake.species = Some(species.last_id);
+            simulation_events.push(SimulationEventKind::SpecieCreated { specie: species.last_id });
             debug!("Snake {:?} is a new specie: {}", baby_id, species.last_id);
         }
     }
 }
 
-fn calculate_gene_difference(leader: &NeuralNetwork, new_snake: &NeuralNetwork) -> f32 {
-    let leader_genes = leader.connections.iter().filter(|c| c.enabled).map(|c| c).collect::<Vec<&ConnectionGene>>();
-    let new_snake_genes = new_snake.connections.iter().filter(|c| c.enabled).map(|c| c).collect::<Vec<&ConnectionGene>>();
-    let leader_innovations: Vec<_> = leader_genes.iter().map(|c| c.innovation_number).collect();
-    let new_snake_innovations: Vec<_> = new_snake_genes.iter().map(|c| c.innovation_number).collect();
-    // genes that both have in common
-    let matching_innovations: Vec<_> = leader_innovations.iter().filter(|i| new_snake_innovations.contains(i)).collect();
-    let matching_genes_count = matching_innovations.len();
-    // calculate weight difference on matching genes
-    let mut weight_difference = 0.0;
-    for innovation in matching_innovations {
-        let leader_weight = leader.connections.iter().find(|c| c.innovation_number == *innovation).unwrap().weight;
-        let new_snake_weight = new_snake.connections.iter().find(|c| c.innovation_number == *innovation).unwrap().weight;
-        weight_difference += (leader_weight - new_snake_weight).abs();
+// Splits each specie may still do this step and the best snake of each specie, only used with species quotas
+#[derive(Resource, Default)]
+pub struct SpeciesQuotas {
+    pub remaining_splits: HashMap<u32, usize>,
+    pub elites: HashSet<Entity>,
+}
+
+impl SpeciesQuotas {
+    // Uses up one split of the specie, species that appeared since the last update are not limited yet
+    fn allows_split(&mut self, specie: Option<u32>) -> bool {
+        match specie.and_then(|specie| self.remaining_splits.get_mut(&specie)) {
+            Some(0) => false,
+            Some(remaining) => {
+                *remaining -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+// Every specie gets a part of the quota population proportional to its adjusted fitness (food eaten shared by the members)
+pub fn update_species_quotas(snakes: Query<(Entity, &Snake)>, mut quotas: ResMut<SpeciesQuotas>, config: Res<SimulationConfig>) {
+    profile_system!();
+    quotas.remaining_splits.clear();
+    quotas.elites.clear();
+    if !config.species_quotas_enabled {
+        return;
+    }
+    // members, sum of food eaten, best member and its food eaten
+    let mut species: HashMap<u32, (usize, f32, Entity, f32)> = HashMap::new();
+    for (entity, snake) in &snakes {
+        let Some(specie) = snake.species else {
+            continue;
+        };
+        let fitness = snake.energy.food_eaten;
+        let entry = species.entry(specie).or_insert((0, 0.0, entity, f32::MIN));
+        entry.0 += 1;
+        entry.1 += fitness;
+        if fitness > entry.3 {
+            entry.2 = entity;
+            entry.3 = fitness;
+        }
+    }
+    let total_adjusted_fitness: f32 = species.values().map(|(members, fitness, _, _)| fitness / *members as f32).sum();
+    for (specie, (members, fitness, elite, _)) in species.iter() {
+        let share = if total_adjusted_fitness > 0.0 { fitness / *members as f32 / total_adjusted_fitness } else { 1.0 / species.len() as f32 };
+        let quota = ((share * config.quota_population as f32).round() as usize).max(1);
+        quotas.remaining_splits.insert(*specie, quota.saturating_sub(*members));
+        quotas.elites.insert(*elite);
+    }
+}
+
+// Entities counted at the start of every step and kept up to date by the systems that spawn them
+#[derive(Resource, Default)]
+pub struct EntityCounts {
+    pub snakes: usize,
+    pub segments: usize,
+    pub food: usize,
+    pub scents: usize,
+    // limits that were already reported, so every overflow is only warned about once
+    reached: HashSet<EntityLimit>,
+}
+
+impl EntityCounts {
+    fn blocks_split(&self, config: &SimulationConfig) -> bool {
+        config.overpopulation_policy == OverpopulationPolicy::BlockSplits && self.snakes >= config.max_snakes
+    }
+
+    fn blocks_growth(&self, config: &SimulationConfig) -> bool {
+        config.overpopulation_policy == OverpopulationPolicy::BlockSplits && self.segments >= config.max_segments
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn enforce_entity_limits(mut commands: Commands, snakes: Query<(Entity, &mut Snake, &Age)>, scents: Query<(), With<Scent>>, positions: Query<&Position>, mut food_map: ResMut<FoodMap>, mut species: ResMut<Species>, mut solids_map: ResMut<SolidsMap>, mut simulation_events: ResMut<SimulationEvents>, mut counts: ResMut<EntityCounts>, mut region_counters: ResMut<RegionCounters>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    counts.snakes = snakes.iter().count();
+    // freshly split snakes only get their head as segment in the next step
+    counts.segments = snakes.iter().map(|(_, snake, _)| snake.segments.len().max(1)).sum();
+    counts.food = food_map.map.map.iter().filter(|food| food.contains_food()).count();
+    counts.scents = scents.iter().count();
+    if config.overpopulation_policy == OverpopulationPolicy::CullWeakest && (counts.snakes > config.max_snakes || counts.segments > config.max_segments) {
+        let mut weakest: Vec<(Entity, f32)> = snakes.iter().map(|(entity, snake, _)| (entity, snake.energy.energy)).collect();
+        weakest.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for (head_id, _) in weakest {
+            if counts.snakes <= config.max_snakes && counts.segments <= config.max_segments {
+                break;
+            }
+            let (_, snake, _) = snakes.get(head_id).unwrap();
+            counts.snakes -= 1;
+            counts.segments = counts.segments.saturating_sub(snake.segments.len().max(1));
+            kill_snake(&mut commands, &snakes, &positions, &mut food_map, &mut species, &mut solids_map, &mut simulation_events, &mut region_counters, &mut stats, &config, head_id, DeathCause::Culled);
+        }
+    }
+    let limits = [
+        (EntityLimit::Snakes, counts.snakes, config.max_snakes),
+        (EntityLimit::Segments, counts.segments, config.max_segments),
+        (EntityLimit::Food, counts.food, config.max_food),
+        (EntityLimit::Scents, counts.scents, config.max_scents),
+    ];
+    for (limit, count, max) in limits {
+        if count < max {
+            counts.reached.remove(&limit);
+        } else if counts.reached.insert(limit) {
+            warn!("{:?} limit of {} reached", limit, max);
+            simulation_events.push(SimulationEventKind::EntityLimitReached { limit, count, max });
+        }
     }
-    // max number of genes
-    let max_genes = leader_genes.len().max(new_snake_genes.len());
-    let gene_difference = (max_genes - matching_genes_count) as f32 / max_genes as f32;
-    let weight_difference = weight_difference / matching_genes_count as f32;
-    debug!("Matching genes: {}, max genes: {}, gene difference: {}, weight difference: {}", matching_genes_count, max_genes, gene_difference, weight_difference);
-    0.6 * gene_difference + 0.4 * weight_difference
 }
-pub fn create_snake(meat_matter: f32, position: (i32, i32), brain: Box<dyn Brain>, dna: Dna) -> (Position, MeatMatter, Snake, Age, JustBorn) {
+
+// (excess, disjoint) genes missing in the other network, excess ones are newer than anything the other network has
+fn count_unmatched_genes(genes: &HashMap<usize, f32>, other: &HashMap<usize, f32>) -> (usize, usize) {
+    let other_newest = other.keys().max();
+    genes.keys().filter(|innovation| !other.contains_key(innovation)).fold((0, 0), |(excess, disjoint), innovation| {
+        if other_newest.is_none_or(|newest| innovation > newest) { (excess + 1, disjoint) } else { (excess, disjoint + 1) }
+    })
+}
+
+// NEAT compatibility distance: c1 * excess / n + c2 * disjoint / n + c3 * mean weight difference of the matching genes
+pub(crate) fn calculate_gene_difference(leader: &NeuralNetwork, new_snake: &NeuralNetwork, config: &SimulationConfig) -> f32 {
+    let leader_genes: HashMap<usize, f32> = leader.connections().iter().filter(|c| c.enabled).map(|c| (c.innovation_number, c.weight)).collect();
+    let new_snake_genes: HashMap<usize, f32> = new_snake.connections().iter().filter(|c| c.enabled).map(|c| (c.innovation_number, c.weight)).collect();
+    let max_genes = leader_genes.len().max(new_snake_genes.len());
+    if max_genes == 0 {
+        return 0.0;
+    }
+    let (leader_excess, leader_disjoint) = count_unmatched_genes(&leader_genes, &new_snake_genes);
+    let (new_snake_excess, new_snake_disjoint) = count_unmatched_genes(&new_snake_genes, &leader_genes);
+    let excess = leader_excess + new_snake_excess;
+    let disjoint = leader_disjoint + new_snake_disjoint;
+    let weight_differences: Vec<f32> = leader_genes.iter()
+        .filter_map(|(innovation, weight)| new_snake_genes.get(innovation).map(|other_weight| (weight - other_weight).abs()))
+        .collect();
+    let weight_difference = if weight_differences.is_empty() { 0.0 } else { weight_differences.iter().sum::<f32>() / weight_differences.len() as f32 };
+    debug!("Excess genes: {}, disjoint genes: {}, max genes: {}, weight difference: {}", excess, disjoint, max_genes, weight_difference);
+    let genes = max_genes as f32;
+    config.excess_gene_coefficient * excess as f32 / genes + config.disjoint_gene_coefficient * disjoint as f32 / genes + config.weight_difference_coefficient * weight_difference
+}
+
+pub fn create_snake(meat_matter: f32, position: (i32, i32), direction: Direction, brain: Box<dyn Brain>, dna: Dna) -> (Position, MeatMatter, Snake, Age, JustBorn) {
     if brain.get_neural_network().is_none() {
         panic!("Brain without neural network");
     }
-    let (head, age, just_born) = create_head(position, brain, 0, 0, dna);
+    let (head, age, just_born) = create_head(position, direction, brain, 0, 0, dna);
     (Position { x: position.0, y: position.1 }, MeatMatter { amount: meat_matter }, head, age, just_born)
 }
 
-fn create_head(position: (i32, i32), brain: Box<dyn Brain>, generation: u32, mutations: u32, dna: Dna) -> (Snake, Age, JustBorn) {
+fn create_head(position: (i32, i32), direction: Direction, brain: Box<dyn Brain>, generation: u32, mutations: u32, dna: Dna) -> (Snake, Age, JustBorn) {
     (Snake {
-        direction: Direction::random(),
+        direction,
         decision: Decision::Wait,
+        last_decision: Decision::Wait,
+        last_move_blocked: false,
         brain,
         new_position: position,
         segments: vec![],
@@ -1108,5 +2386,714 @@ fn create_head(position: (i32, i32), brain: Box<dyn Brain>, generation: u32, mut
         dna,
         metabolism: Metabolism::default(),
         energy: Energy::default(),
+        id: 0,
+        parent_id: None,
+        birth_mutations: vec![],
+        action_temperature: None,
+        signal: 0.0,
+        starving: 0,
+        outputs: vec![],
     }, Age { age: 0, efficiency_factor: 1.0 }, JustBorn)
-}
\ No newline at end of file
+}
+
+// The snake the tests start from, a random brain and four random genes
+#[cfg(test)]
+pub(crate) fn test_snake(position: (i32, i32), rng: &mut impl Rng) -> (Position, MeatMatter, Snake, Age, JustBorn) {
+    let brain = RandomNeuralBrain::new(ActionSet::default().outputs(), &mut InnovationTracker::new(), rng);
+    create_snake(100.0, position, Direction::East, Box::new(brain), Dna::random(4, &crate::simulation::MutationConfig::default(), rng))
+}
+
+#[cfg(test)]
+pub(crate) fn spawn_test_snake(world: &mut World, position: (i32, i32), rng: &mut impl Rng) -> Entity {
+    world.spawn(test_snake(position, rng)).id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::system::RunSystemOnce;
+    use rand::rngs::StdRng;
+    use crate::neural::{Activation, NeuralNetworkBuilder};
+    use crate::simulation::{EnergyFlows, MutationConfig, Region};
+
+    #[test]
+    fn stomachs_cost_plant_digestion_along_the_tradeoff_curve() {
+        let linear = SimulationConfig { diet_tradeoff_strength: 0.8, diet_tradeoff_exponent: 1.0, ..SimulationConfig::default() };
+        assert_eq!(plant_digestion_kept(0.0, &linear), 1.0);
+        assert!((plant_digestion_kept(0.5, &linear) - 0.6).abs() < 1e-6);
+        assert!((plant_digestion_kept(1.0, &linear) - 0.2).abs() < 1e-6);
+        let late = SimulationConfig { diet_tradeoff_exponent: 3.0, ..linear.clone() };
+        assert!(plant_digestion_kept(0.5, &late) > plant_digestion_kept(0.5, &linear));
+        assert_eq!(plant_digestion_kept(1.0, &SimulationConfig::default()), 1.0);
+    }
+
+    #[test]
+    fn spawn_positions_avoid_solids_and_segments() {
+        let config = SimulationConfig { rows: 4, columns: 4, ..SimulationConfig::default() };
+        let mut solids_map = SolidsMap { map: Map2d::new(4, 4, true) };
+        let mut segment_map = SegmentMap { map: Map3d::new(4, 4) };
+        solids_map.map.set(&Position { x: 1, y: 2 }, false);
+        solids_map.map.set(&Position { x: 3, y: 3 }, false);
+        segment_map.map.add(&Position { x: 3, y: 3 }, Entity::from_raw(1));
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            if let Some(position) = find_free_position(&mut rng, &solids_map, &segment_map, |rng| random_position(rng, &config)) {
+                assert_eq!(position.as_pair(), (1, 2));
+            }
+        }
+        let position = find_free_position(&mut rng, &solids_map, &segment_map, |_| Position { x: 1, y: 1 });
+        assert!(position.is_none());
+    }
+
+    #[test]
+    fn food_samples_stay_in_bounds() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for food_distribution in [FoodDistribution::Uniform, FoodDistribution::Patches, FoodDistribution::Oases, FoodDistribution::Ring, FoodDistribution::Gradient] {
+            let config = SimulationConfig { rows: 20, columns: 30, food_distribution, food_patch_radius: 15, ..SimulationConfig::default() };
+            let mut food_sources = FoodSources::default();
+            update_food_sources(&mut food_sources, &mut rng, &config);
+            for _ in 0..1000 {
+                let position = sample_food_position(&mut rng, &config, &food_sources.centers);
+                assert!(position.x >= 0 && position.x < 30 && position.y >= 0 && position.y < 20, "{:?} out of bounds for {:?}", position, food_distribution);
+            }
+        }
+    }
+
+    #[test]
+    fn food_controller_pushes_the_measure_back_to_its_target() {
+        let config = SimulationConfig { food_per_step: 4, food_control: FoodControl::Population, food_control_target: 100.0, food_control_gain: 1.0, food_control_integral_gain: 0.1, food_control_max_rate: 20, ..SimulationConfig::default() };
+        // a single step of half the target doubles the base rate with the gain alone
+        let mut controller = FoodController::default();
+        assert_eq!(controller.update(50.0, &config), 6);
+        assert!(controller.update(150.0, &config) < 4);
+        // far too few snakes saturate at the maximum without the sum winding up behind it
+        let mut starving = FoodController::default();
+        for _ in 0..1000 {
+            starving.update(0.0, &config);
+        }
+        assert_eq!(starving.update(0.0, &config), 20);
+        let mut steps = 0;
+        while starving.update(150.0, &config) >= 4 {
+            steps += 1;
+        }
+        assert!(steps < 60, "the rate took {} steps to come down", steps);
+        // fractions of a food add up over the steps
+        let slow = SimulationConfig { food_per_step: 1, food_control_gain: 0.5, food_control_integral_gain: 0.0, ..config };
+        let mut controller = FoodController::default();
+        let food: usize = (0..10).map(|_| controller.update(200.0, &slow)).sum();
+        assert_eq!(food, 5);
+    }
+
+    // random sizes and positions instead of a property testing crate, the seed keeps failures reproducible
+    #[test]
+    fn map_positions_are_checked_and_wrapped_on_any_size() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            let (width, height) = (rng.gen_range(1..40), rng.gen_range(1..40));
+            let mut map = Map2d::new(width, height, 0);
+            for (index, cell) in map.map.iter_mut().enumerate() {
+                *cell = index;
+            }
+            for (position, cell) in map.iter() {
+                assert_eq!(map.get(&position), cell);
+                assert_eq!(*cell, position.y as usize * width + position.x as usize);
+            }
+            for _ in 0..50 {
+                let position = Position { x: rng.gen_range(-100..100), y: rng.gen_range(-100..100) };
+                let inside = position.x >= 0 && position.x < width as i32 && position.y >= 0 && position.y < height as i32;
+                assert_eq!(map.get_checked(&position).is_some(), inside, "{:?} on a {}x{} map", position, width, height);
+                let wrapped = map.wrap(&position);
+                assert!(map.contains(&wrapped));
+                assert_eq!(map.get_wrapped(&position), map.get(&wrapped));
+                let shifted = Position { x: position.x + width as i32 * rng.gen_range(-3..3), y: position.y + height as i32 * rng.gen_range(-3..3) };
+                assert_eq!(map.get_wrapped(&shifted), map.get_wrapped(&position));
+                if inside {
+                    assert_eq!(wrapped.as_pair(), position.as_pair());
+                }
+            }
+        }
+        let mut segments: Map3d<u32> = Map3d::new(3, 2);
+        segments.add(&Position { x: 2, y: 1 }, 7);
+        assert_eq!(segments.get_wrapped(&Position { x: -1, y: -1 }), &vec![7]);
+        assert!(segments.get_checked(&Position { x: 3, y: 0 }).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the 3x2 map")]
+    fn reading_outside_of_the_map_panics_instead_of_reading_another_row() {
+        let map = Map2d::new(3, 2, 0);
+        map.get(&Position { x: -1, y: 1 });
+    }
+
+    #[test]
+    fn meat_rots_into_plants() {
+        let mut world = World::new();
+        let mut food_map = FoodMap { map: Map2d::new(2, 1, Food::default()) };
+        food_map.map.set(&Position { x: 0, y: 0 }, Food::from_meat(100.0));
+        food_map.map.set(&Position { x: 1, y: 0 }, Food::from_meat(1.5));
+        world.insert_resource(food_map);
+        world.insert_resource(Stats::default());
+        world.insert_resource(SimulationConfig { meat_decay_rate: 0.5, meat_compost_ratio: 0.5, ..SimulationConfig::default() });
+        world.run_system_once(decay_meat);
+        let food_map = world.resource::<FoodMap>();
+        assert_eq!(food_map.map.get(&Position { x: 0, y: 0 }).meat, 50.0);
+        assert_eq!(food_map.map.get(&Position { x: 0, y: 0 }).plant, 25.0);
+        assert!(!food_map.map.get(&Position { x: 1, y: 0 }).is_meat());
+        assert_eq!(world.resource::<Stats>().total_decayed_meat, 51.5);
+    }
+
+    #[test]
+    fn eaten_and_digested_food_is_counted_in_the_energy_flows() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(5);
+        let (position, meat, mut snake, age, just_born) = test_snake((0, 0), &mut rng);
+        snake.energy.energy = 0.0;
+        snake.metabolism = Metabolism { plant_processing_speed: 1.0, meat_processing_speed: 2.0, max_plants_in_stomach: 4.0, max_meat_in_stomach: 10.0, max_energy: 100.0, ..Metabolism::default() };
+        world.spawn((position, meat, snake, age, just_born));
+        let mut food_map = FoodMap { map: Map2d::new(1, 1, Food::default()) };
+        food_map.map.set(&Position { x: 0, y: 0 }, Food { plant: 6.0, meat: 3.0 });
+        world.insert_resource(food_map);
+        world.insert_resource(Stats::default());
+        world.insert_resource(SimulationConfig::default());
+        world.run_system_once(eat_food);
+        world.run_system_once(process_food);
+        let flows = world.resource::<Stats>().energy_flows;
+        assert_eq!((flows.plants_eaten, flows.meat_eaten), (4.0, 3.0));
+        assert_eq!((flows.plants_digested, flows.meat_digested), (1.0, 2.0));
+        assert_eq!(flows.carnivory(), 2.0 / 3.0);
+        assert_eq!(flows.since(&flows), EnergyFlows::default());
+    }
+
+    #[test]
+    fn controlled_snake_turns_once_then_goes_straight() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(5);
+        let (position, meat, mut snake, age, just_born) = test_snake((1, 1), &mut rng);
+        snake.energy.move_potential = 0.0;
+        let head = world.spawn((position, meat, snake, age, just_born, Controlled { decision: Decision::MoveLeft })).id();
+        world.run_system_once(steer_controlled);
+        assert_eq!(world.get::<Snake>(head).unwrap().decision, Decision::MoveLeft);
+        assert_eq!(world.get::<Controlled>(head).unwrap().decision, Decision::MoveLeft);
+        world.get_mut::<Snake>(head).unwrap().energy.move_potential = 1.0;
+        world.run_system_once(steer_controlled);
+        assert_eq!(world.get::<Snake>(head).unwrap().decision, Decision::MoveLeft);
+        assert_eq!(world.get::<Controlled>(head).unwrap().decision, Decision::MoveForward);
+    }
+
+    #[test]
+    fn action_temperature_lets_weaker_outputs_win_sometimes() {
+        let network = NeuralNetworkBuilder::for_snake(ActionSet::default().outputs())
+            .connect("Bias", "Move left", 2.0)
+            .connect("Bias", "Transfer energy", 5.0)
+            .build().unwrap();
+        let brain = RandomNeuralBrain::from_neural_network(network);
+        let mut inputs = vec![0.0; SENSOR_NAMES.len()];
+        inputs[0] = 1.0;
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut decide = |temperature: f32| (0..200).map(|_| brain.decide(inputs.clone(), ActionSet::default(), temperature, &mut rng).0).collect::<Vec<Decision>>();
+        assert!(decide(0.0).iter().all(|decision| *decision == Decision::MoveLeft));
+        let decisions = decide(0.5);
+        let count = |wanted: Decision| decisions.iter().filter(|decision| **decision == wanted).count();
+        assert!(count(Decision::MoveLeft) > count(Decision::MoveForward).max(count(Decision::MoveRight)).max(count(Decision::Wait)));
+        assert!(count(Decision::MoveForward) > 0 && count(Decision::Wait) > 0);
+        assert_eq!(count(Decision::TransferEnergy), 0);
+    }
+
+    #[test]
+    fn gene_difference_weighs_excess_disjoint_and_weights() {
+        let network = |genes: &[(usize, f32)]| {
+            let mut network = NeuralNetwork::new(vec![Activation::Relu; 4], vec![Activation::Sigmoid]);
+            for (innovation, weight) in genes {
+                network.add_connection(*innovation % 4, 4, *weight, true, *innovation);
+            }
+            network
+        };
+        let leader = network(&[(0, 1.0), (1, 1.0), (3, 1.0)]);
+        // 2 and 3 are disjoint, 5 is excess and the weight of 0 differs by 0.5
+        let other = network(&[(0, 0.5), (1, 1.0), (2, 1.0), (5, 1.0)]);
+        let config = SimulationConfig { excess_gene_coefficient: 1.0, disjoint_gene_coefficient: 0.0, weight_difference_coefficient: 0.0, ..SimulationConfig::default() };
+        assert_eq!(calculate_gene_difference(&leader, &other, &config), 0.25);
+        let config = SimulationConfig { excess_gene_coefficient: 0.0, disjoint_gene_coefficient: 1.0, weight_difference_coefficient: 0.0, ..SimulationConfig::default() };
+        assert_eq!(calculate_gene_difference(&leader, &other, &config), 0.5);
+        let config = SimulationConfig { excess_gene_coefficient: 0.0, disjoint_gene_coefficient: 0.0, weight_difference_coefficient: 1.0, ..SimulationConfig::default() };
+        assert_eq!(calculate_gene_difference(&leader, &other, &config), 0.25);
+        assert_eq!(calculate_gene_difference(&network(&[]), &network(&[]), &SimulationConfig::default()), 0.0);
+    }
+
+    #[test]
+    fn species_quotas_follow_food_eaten_per_member() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut spawn = |world: &mut World, specie: u32, food_eaten: f32| {
+            let head = spawn_test_snake(world, (0, 0), &mut rng);
+            let mut snake = world.get_mut::<Snake>(head).unwrap();
+            snake.species = Some(specie);
+            snake.energy.food_eaten = food_eaten;
+            head
+        };
+        spawn(&mut world, 1, 30.0);
+        let best = spawn(&mut world, 1, 90.0);
+        spawn(&mut world, 2, 15.0);
+        world.insert_resource(SpeciesQuotas::default());
+        world.insert_resource(SimulationConfig { species_quotas_enabled: true, quota_population: 10, ..SimulationConfig::default() });
+        world.run_system_once(update_species_quotas);
+        let mut quotas = world.resource_mut::<SpeciesQuotas>();
+        // adjusted fitness 60 against 15, so 8 and 2 of the 10 places
+        assert_eq!(quotas.remaining_splits[&1], 6);
+        assert_eq!(quotas.remaining_splits[&2], 1);
+        assert!(quotas.elites.contains(&best));
+        assert!(quotas.allows_split(Some(2)));
+        assert!(!quotas.allows_split(Some(2)));
+        assert!(quotas.allows_split(Some(3)));
+    }
+
+    #[test]
+    fn culling_removes_the_weakest_snakes_and_warns_once() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut spawn = |world: &mut World, energy: f32| {
+            let head = spawn_test_snake(world, (0, 0), &mut rng);
+            let mut snake = world.get_mut::<Snake>(head).unwrap();
+            snake.species = Some(1);
+            snake.energy.energy = energy;
+            head
+        };
+        let weakest = spawn(&mut world, 10.0);
+        let strong = spawn(&mut world, 90.0);
+        spawn(&mut world, 50.0);
+        let config = SimulationConfig { rows: 5, columns: 5, max_snakes: 2, max_food: 1, overpopulation_policy: OverpopulationPolicy::CullWeakest, ..SimulationConfig::default() };
+        let mut food_map = FoodMap { map: Map2d::new(config.columns, config.rows, Food::default()) };
+        food_map.map.set(&Position { x: 1, y: 1 }, Food::from_plant(10.0));
+        world.insert_resource(food_map);
+        world.insert_resource(SolidsMap { map: Map2d::new(config.columns, config.rows, false) });
+        world.insert_resource(Species::default());
+        world.insert_resource(SimulationEvents::default());
+        world.insert_resource(EntityCounts::default());
+        world.insert_resource(RegionCounters::default());
+        world.insert_resource(Stats::default());
+        world.insert_resource(config);
+        world.run_system_once(enforce_entity_limits);
+        assert!(world.get::<Snake>(weakest).is_none());
+        assert!(world.get::<Snake>(strong).is_some());
+        assert_eq!(world.resource::<EntityCounts>().snakes, 2);
+        world.run_system_once(enforce_entity_limits);
+        let events = world.resource_mut::<SimulationEvents>().drain();
+        let warnings: Vec<EntityLimit> = events.iter().filter_map(|event| match event.kind {
+            SimulationEventKind::EntityLimitReached { limit, .. } => Some(limit),
+            _ => None,
+        }).collect();
+        assert_eq!(warnings, vec![EntityLimit::Snakes, EntityLimit::Food]);
+        assert!(events.iter().any(|event| matches!(event.kind, SimulationEventKind::SnakeDied { cause: DeathCause::Culled, .. })));
+    }
+
+    #[test]
+    fn backward_moves_let_the_tail_lead_and_sprints_take_two_hexes() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(5);
+        let config = SimulationConfig { rows: 10, columns: 10, backward_move_enabled: true, sprint_enabled: true, ..SimulationConfig::default() };
+        let brain = RandomNeuralBrain::new(ActionSet::from_config(&config).outputs(), &mut InnovationTracker::new(), &mut rng);
+        assert_eq!(brain.get_neural_network().unwrap().get_nodes().iter().filter(|node| node.node_type == NodeType::Output).count(), 8);
+        let head = world.spawn(create_snake(100.0, (3, 0), East, Box::new(brain), Dna::random(4, &MutationConfig::default(), &mut rng))).id();
+        let body = [world.spawn(Position { x: 2, y: 0 }).id(), world.spawn(Position { x: 1, y: 0 }).id()];
+        let mut snake = world.get_mut::<Snake>(head).unwrap();
+        snake.segments = vec![head, body[0], body[1]];
+        snake.decision = Decision::MoveBackward;
+        world.insert_resource(SolidsMap { map: Map2d::new(config.columns, config.rows, false) });
+        world.insert_resource(WaterMap::new(&config));
+        world.insert_resource(BehaviorCounters::default());
+        world.insert_resource(config);
+        let positions = |world: &mut World| [head, body[0], body[1]].map(|segment| world.get::<Position>(segment).unwrap().as_pair());
+        world.run_system_once(update_positions);
+        assert_eq!(positions(&mut world), [(2, 0), (1, 0), (0, 0)]);
+        let mut snake = world.get_mut::<Snake>(head).unwrap();
+        snake.decision = Decision::Sprint;
+        snake.new_position = (3, 0);
+        world.run_system_once(update_positions);
+        assert_eq!(positions(&mut world), [(4, 0), (3, 0), (2, 0)]);
+        assert_eq!(world.get::<Snake>(head).unwrap().new_position, (4, 0));
+        assert!(world.get::<DiedFromCollision>(head).is_none());
+    }
+
+    #[test]
+    fn bouncing_snakes_stay_in_place_and_pay_instead_of_dying() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(5);
+        let config = SimulationConfig { rows: 10, columns: 10, collision_policy: CollisionPolicy::Bounce, bounce_cost: 7.0, ..SimulationConfig::default() };
+        let head = spawn_test_snake(&mut world, (3, 0), &mut rng);
+        let mut snake = world.get_mut::<Snake>(head).unwrap();
+        snake.segments = vec![head];
+        snake.decision = Decision::MoveForward;
+        snake.new_position = (4, 0);
+        let energy = snake.energy.energy;
+        let mut solids_map = SolidsMap { map: Map2d::new(config.columns, config.rows, false) };
+        solids_map.map.set(&Position { x: 4, y: 0 }, true);
+        world.insert_resource(solids_map);
+        world.insert_resource(WaterMap::new(&config));
+        world.insert_resource(BehaviorCounters::default());
+        world.insert_resource(config.clone());
+        world.run_system_once(update_positions);
+        let snake = world.get::<Snake>(head).unwrap();
+        assert_eq!(world.get::<Position>(head).unwrap().as_pair(), (3, 0));
+        assert_eq!(snake.new_position, (3, 0));
+        assert_eq!(snake.energy.energy, energy - 7.0);
+        assert!(snake.last_move_blocked);
+        assert!(world.get::<DiedFromCollision>(head).is_none());
+        assert_eq!(world.resource::<BehaviorCounters>().collisions, 1);
+        world.insert_resource(SimulationConfig { collision_policy: CollisionPolicy::Die, ..config });
+        world.get_mut::<Snake>(head).unwrap().new_position = (4, 0);
+        world.run_system_once(update_positions);
+        assert_eq!(world.get::<Position>(head).unwrap().as_pair(), (4, 0));
+        assert!(world.get::<DiedFromCollision>(head).is_some());
+    }
+
+    #[test]
+    fn starving_snakes_wait_out_the_grace_before_dying() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(6);
+        let config = SimulationConfig { rows: 10, columns: 10, starvation_grace: 2, ..SimulationConfig::default() };
+        let head = spawn_test_snake(&mut world, (3, 0), &mut rng);
+        let mut snake = world.get_mut::<Snake>(head).unwrap();
+        snake.segments = vec![head];
+        snake.species = Some(1);
+        snake.energy.energy = -1.0;
+        world.insert_resource(FoodMap { map: Map2d::new(config.columns, config.rows, Food::default()) });
+        world.insert_resource(SolidsMap { map: Map2d::new(config.columns, config.rows, false) });
+        world.insert_resource(WaterMap::new(&config));
+        world.insert_resource(TemperatureMap::default());
+        world.insert_resource(Species::default());
+        world.insert_resource(SpeciesQuotas::default());
+        world.insert_resource(SimulationEvents::default());
+        world.insert_resource(RegionCounters::default());
+        world.insert_resource(BehaviorCounters::default());
+        world.insert_resource(ActionRound::default());
+        world.insert_resource(Stats::default());
+        world.insert_resource(config);
+        world.run_system_once(starve);
+        assert_eq!(world.get::<Snake>(head).unwrap().starving, 1);
+        let mut snake = world.get_mut::<Snake>(head).unwrap();
+        snake.decision = Decision::MoveForward;
+        snake.energy.move_potential = 1.0;
+        world.run_system_once(movement);
+        let snake = world.get::<Snake>(head).unwrap();
+        assert_eq!(snake.decision, Decision::Wait);
+        assert_eq!(snake.new_position, (3, 0));
+        world.run_system_once(starve);
+        assert_eq!(world.get::<Snake>(head).unwrap().starving, 2);
+        world.run_system_once(starve);
+        assert!(world.get::<Snake>(head).is_none());
+        assert!(world.resource_mut::<SimulationEvents>().drain().iter().any(|event| matches!(event.kind, SimulationEventKind::SnakeDied { cause: DeathCause::Starvation, .. })));
+        // a protected elite doesn't starve, but it still owes the energy
+        let elite = spawn_test_snake(&mut world, (6, 0), &mut rng);
+        let mut snake = world.get_mut::<Snake>(elite).unwrap();
+        snake.segments = vec![elite];
+        snake.species = Some(1);
+        snake.energy.energy = -5.0;
+        world.resource_mut::<SpeciesQuotas>().elites.insert(elite);
+        world.resource_mut::<SimulationConfig>().protect_elites = true;
+        for _ in 0..5 {
+            world.run_system_once(starve);
+        }
+        let snake = world.get::<Snake>(elite).unwrap();
+        assert_eq!((snake.energy.energy, snake.starving), (-5.0, 0));
+    }
+
+    #[test]
+    fn old_snakes_die_by_their_efficiency_and_at_the_max_lifespan() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(6);
+        let config = SimulationConfig { rows: 10, columns: 10, old_age_mortality: 1.0, max_lifespan: 5_000, ..SimulationConfig::default() };
+        let heads: Vec<Entity> = [(0, 1.0), (3_000, MIN_EFFICIENCY), (5_000, 1.0)].iter().enumerate().map(|(x, (age, efficiency))| {
+            let head = spawn_test_snake(&mut world, (x as i32 * 2, 0), &mut rng);
+            let mut snake = world.get_mut::<Snake>(head).unwrap();
+            snake.segments = vec![head];
+            snake.species = Some(1);
+            let mut snake_age = world.get_mut::<Age>(head).unwrap();
+            snake_age.age = *age;
+            snake_age.efficiency_factor = *efficiency;
+            head
+        }).collect();
+        world.insert_resource(FoodMap { map: Map2d::new(config.columns, config.rows, Food::default()) });
+        world.insert_resource(SolidsMap { map: Map2d::new(config.columns, config.rows, false) });
+        world.insert_resource(Species::default());
+        world.insert_resource(SimulationEvents::default());
+        world.insert_resource(RegionCounters::default());
+        world.insert_resource(Stats::default());
+        world.insert_resource(RngResource::from_seed(1));
+        world.insert_resource(SimTime::default());
+        world.insert_resource(config);
+        world.run_system_once(die_of_old_age);
+        let alive: Vec<bool> = heads.iter().map(|head| world.get::<Snake>(*head).is_some()).collect();
+        assert_eq!(alive, [true, false, false]);
+        let events = world.resource_mut::<SimulationEvents>().drain();
+        assert_eq!(events.iter().filter(|event| matches!(event.kind, SimulationEventKind::SnakeDied { cause: DeathCause::OldAge, .. })).count(), 2);
+    }
+
+    #[test]
+    fn the_next_member_leads_a_specie_with_its_own_genome() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(7);
+        let config = SimulationConfig { rows: 10, columns: 10, old_age_mortality: 0.0, max_lifespan: 5_000, ..SimulationConfig::default() };
+        let heads: Vec<Entity> = [5_000, 0, 10].iter().enumerate().map(|(x, age)| {
+            let brain = RandomNeuralBrain::new(ActionSet::default().outputs(), &mut InnovationTracker::new(), &mut rng);
+            let head = world.spawn(create_snake(100.0, (x as i32 * 2, 0), East, Box::new(brain), Dna::random(4 + x, &MutationConfig::default(), &mut rng))).id();
+            let mut snake = world.get_mut::<Snake>(head).unwrap();
+            snake.segments = vec![head];
+            snake.species = Some(1);
+            world.get_mut::<Age>(head).unwrap().age = *age;
+            head
+        }).collect();
+        let genome = |world: &World, head: Entity| {
+            let snake = world.get::<Snake>(head).unwrap();
+            (snake.brain.get_neural_network().unwrap().connections().to_vec(), snake.dna.clone())
+        };
+        let leader = world.get::<Snake>(heads[0]).unwrap();
+        let mut species = Species::default();
+        species.species.push(Specie { id: 1, leader: heads[0], leader_network: leader.brain.get_neural_network().unwrap().clone(), leader_dna: leader.dna.clone(), members: heads.iter().copied().collect() });
+        world.insert_resource(species);
+        world.insert_resource(FoodMap { map: Map2d::new(config.columns, config.rows, Food::default()) });
+        world.insert_resource(SolidsMap { map: Map2d::new(config.columns, config.rows, false) });
+        world.insert_resource(SimulationEvents::default());
+        world.insert_resource(RegionCounters::default());
+        world.insert_resource(Stats::default());
+        world.insert_resource(RngResource::from_seed(1));
+        world.insert_resource(SimTime::default());
+        world.insert_resource(config);
+        let (network, dna) = genome(&world, heads[1]);
+        world.run_system_once(die_of_old_age);
+        let specie = &world.resource::<Species>().species[0];
+        assert_eq!(specie.leader, heads[1]);
+        assert_eq!(specie.leader_network.connections(), network);
+        assert_eq!(specie.leader_dna, dna);
+        assert_eq!(specie.members, [heads[1], heads[2]]);
+    }
+
+    #[test]
+    fn trails_keep_the_last_head_positions() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(5);
+        let head = spawn_test_snake(&mut world, (0, 0), &mut rng);
+        world.insert_resource(SimulationConfig { trail_length: 3, ..SimulationConfig::default() });
+        for x in 0..5 {
+            world.get_mut::<Position>(head).unwrap().x = x;
+            world.run_system_once(record_trails);
+            // standing still does not extend the trail
+            world.run_system_once(record_trails);
+        }
+        let trail: Vec<(i32, i32)> = world.get::<Trail>(head).unwrap().positions.iter().map(Position::as_pair).collect();
+        assert_eq!(trail, vec![(2, 0), (3, 0), (4, 0)]);
+    }
+
+    #[test]
+    fn transfers_move_energy_to_the_snake_in_front() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(6);
+        let config = SimulationConfig { rows: 10, columns: 10, energy_transfer_enabled: true, energy_transfer_amount: 30.0, ..SimulationConfig::default() };
+        let mut segment_map = SegmentMap { map: Map3d::new(config.columns, config.rows) };
+        let mut spawn = |world: &mut World, x: i32| {
+            let head = spawn_test_snake(world, (x, 0), &mut rng);
+            let mut snake = world.get_mut::<Snake>(head).unwrap();
+            snake.segments = vec![head];
+            snake.energy.energy = 100.0;
+            segment_map.map.add(&Position { x, y: 0 }, head);
+            head
+        };
+        let giver = spawn(&mut world, 0);
+        let receiver = spawn(&mut world, 1);
+        let lonely = spawn(&mut world, 5);
+        for snake in [giver, lonely] {
+            world.get_mut::<Snake>(snake).unwrap().decision = Decision::TransferEnergy;
+        }
+        world.insert_resource(segment_map);
+        world.insert_resource(config);
+        world.run_system_once(transfer_energy);
+        let energy = |world: &World, snake: Entity| world.get::<Snake>(snake).unwrap().energy.energy;
+        assert_eq!((energy(&world, giver), energy(&world, receiver), energy(&world, lonely)), (70.0, 130.0, 100.0));
+        world.resource_mut::<SimulationConfig>().energy_transfer_enabled = false;
+        world.run_system_once(transfer_energy);
+        assert_eq!(energy(&world, giver), 70.0);
+    }
+
+    #[test]
+    fn signals_are_seen_along_the_front_ray_until_a_wall() {
+        let mut world = World::new();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut config = SimulationConfig { rows: 10, columns: 10, ..SimulationConfig::default() };
+        config.mutation.signaling_enabled = true;
+        let signaling = spawn_test_snake(&mut world, (3, 0), &mut rng);
+        let mut snake = world.get_mut::<Snake>(signaling).unwrap();
+        snake.segments = vec![signaling];
+        snake.signal = 0.7;
+        world.insert_resource(SignalMap { map: Map2d::new(10, 10, 0.0) });
+        world.insert_resource(config.clone());
+        world.run_system_once(update_signal_map);
+        let mut solids_map = SolidsMap { map: Map2d::new(10, 10, false) };
+        solids_map.map.set(&Position { x: 3, y: 0 }, true);
+        let signal_map = world.resource::<SignalMap>();
+        let observer = Position { x: 0, y: 0 };
+        assert_eq!(see_signal(&East, &observer, 5, &solids_map, signal_map, &config), 0.7);
+        assert_eq!(see_signal(&East, &observer, 2, &solids_map, signal_map, &config), 0.0);
+        solids_map.map.set(&Position { x: 2, y: 0 }, true);
+        assert_eq!(see_signal(&East, &observer, 5, &solids_map, signal_map, &config), 0.0);
+        config.mutation.signaling_enabled = false;
+        solids_map.map.set(&Position { x: 2, y: 0 }, false);
+        assert_eq!(see_signal(&East, &observer, 5, &solids_map, signal_map, &config), 0.0);
+    }
+
+    #[test]
+    fn noses_smell_further_away_scents_weaker() {
+        let mut world = World::new();
+        let mut scent_map = ScentMap { map: Map2d::new(10, 10, 0.0) };
+        scent_map.map.set(&Position { x: 3, y: 0 }, 500.0);
+        world.insert_resource(scent_map);
+        world.insert_resource(SimulationConfig { rows: 10, columns: 10, ..SimulationConfig::default() });
+        let smelled = |world: &mut World, range: u32| world.run_system_once(move |scent_map: Res<ScentMap>, config: Res<SimulationConfig>| scent(&East, &Position { x: 0, y: 0 }, range, &scent_map, &config));
+        assert_eq!(smelled(&mut world, 1), 0.0);
+        assert_eq!(smelled(&mut world, 3), 1.0 / 3.0);
+        assert_eq!(smelled(&mut world, 2), smelled(&mut world, 1));
+        let mut rng = StdRng::seed_from_u64(3);
+        let mutation = MutationConfig { sense_segments_enabled: true, ..MutationConfig::default() };
+        let genes = Dna::random(200, &mutation, &mut rng).genes;
+        assert!(genes.iter().any(|gene| matches!(gene.segment_type, SegmentType::Eye(_))) && genes.iter().any(|gene| matches!(gene.segment_type, SegmentType::Nose(_))));
+        let genes = Dna::random(200, &MutationConfig::default(), &mut rng).genes;
+        assert!(!genes.iter().any(|gene| matches!(gene.segment_type, SegmentType::Eye(_) | SegmentType::Nose(_))));
+    }
+
+    #[test]
+    fn length_and_growth_inputs_show_how_close_the_next_segment_and_split_are() {
+        let config = SimulationConfig { rows: 10, columns: 10, size_to_split: 4, new_segment_cost: 50.0, ..SimulationConfig::default() };
+        let mut world = World::new();
+        world.insert_resource(FoodMap { map: Map2d::new(10, 10, Food::default()) });
+        world.insert_resource(SolidsMap { map: Map2d::new(10, 10, false) });
+        world.insert_resource(ScentMap { map: Map2d::new(10, 10, 0.0) });
+        world.insert_resource(SignalMap { map: Map2d::new(10, 10, 0.0) });
+        world.insert_resource(MineralMap { map: Map2d::new(10, 10, 0.0) });
+        world.insert_resource(WaterMap::new(&config));
+        world.insert_resource(config);
+        let mut rng = StdRng::seed_from_u64(4);
+        let (position, _, mut snake, age, _) = test_snake((5, 5), &mut rng);
+        snake.segments = vec![Entity::PLACEHOLDER; 2];
+        snake.energy.accumulated_meat_matter_for_growth = 20.0;
+        let inputs = world.run_system_once(move |food_map: Res<FoodMap>, solids_map: Res<SolidsMap>, scent_map: Res<ScentMap>, signal_map: Res<SignalMap>, mineral_map: Res<MineralMap>, water_map: Res<WaterMap>, config: Res<SimulationConfig>| {
+            sense(&position, &snake, &age, &food_map, &solids_map, &scent_map, &signal_map, &mineral_map, &water_map, &config, &mut rng)
+        });
+        let input = |name: &str| inputs[SENSOR_NAMES.iter().position(|sensor| *sensor == name).unwrap()];
+        assert_eq!(inputs.len(), SENSOR_NAMES.len());
+        assert_eq!((input("Length"), input("Growth")), (0.5, 0.4));
+    }
+
+    #[test]
+    fn behavior_stats_summarize_decisions_displacement_and_collisions() {
+        let mut world = World::new();
+        let moving = world.spawn_empty().id();
+        let newborn = world.spawn_empty().id();
+        let config = SimulationConfig { rows: 10, columns: 10, ..SimulationConfig::default() };
+        let mut counters = BehaviorCounters { decisions: [6, 1, 1, 2, 0, 0, 0, 0], collisions: 1, head_positions: HashMap::from([(moving, Position { x: 1, y: 2 })]) };
+        let stats = counters.take_stats([(moving, &Position { x: 4, y: 2 }), (newborn, &Position { x: 5, y: 5 })].into_iter(), &config);
+        assert_eq!(stats.decision_shares, [0.6, 0.1, 0.1, 0.2, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(stats.mean_displacement, 3.0);
+        assert_eq!(stats.collision_rate, 100.0);
+        assert_eq!(counters.decisions, [0; 8]);
+        assert_eq!(counters.head_positions.len(), 2);
+    }
+
+    #[test]
+    fn only_mature_snakes_before_menopause_can_split() {
+        let age = |age: u32| Age { age, efficiency_factor: 1.0 };
+        let config = SimulationConfig { maturity_age: 100, menopause_age: 500, ..SimulationConfig::default() };
+        assert!(!is_fertile(&age(0), &config));
+        assert!(is_fertile(&age(100), &config));
+        assert!(!is_fertile(&age(500), &config));
+        let without_menopause = SimulationConfig { menopause_age: 0, ..config };
+        assert!(is_fertile(&age(100_000), &without_menopause));
+        assert!(is_fertile(&age(0), &SimulationConfig::default()));
+    }
+
+    #[test]
+    fn region_stats_count_snakes_food_and_deaths_in_every_region() {
+        let regions = vec![Region::new("West", 0.0, 0.0, 0.5, 1.0), Region::new("Corner", 0.8, 0.8, 1.0, 1.0)];
+        let config = SimulationConfig { rows: 10, columns: 10, regions, ..SimulationConfig::default() };
+        let mut food_map = FoodMap { map: Map2d::new(10, 10, Food::default()) };
+        food_map.map.set(&Position { x: 1, y: 1 }, Food::from_plant(2.0));
+        food_map.map.set(&Position { x: 9, y: 9 }, Food::from_meat(3.0));
+        let deaths = [Position { x: 4, y: 0 }, Position { x: 5, y: 0 }, Position { x: 8, y: 8 }];
+        let heads = [Position { x: 0, y: 9 }, Position { x: 4, y: 4 }, Position { x: 9, y: 8 }];
+        let stats = region_stats(&heads, &deaths, &food_map, &config);
+        assert_eq!(stats.iter().map(|region| (region.name.as_str(), region.snakes, region.food, region.deaths)).collect::<Vec<_>>(), vec![("West", 2, 2.0, 1), ("Corner", 1, 3.0, 1)]);
+    }
+
+    #[test]
+    fn rng_streams_only_depend_on_the_seed_stream_and_tick() {
+        let draw = |rng: &RngResource, stream: RngStream, ticks: u64| rng.stream(stream, &SimTime { ticks }).gen::<u64>();
+        let rng = RngResource::from_seed(9);
+        // drawing from the shared generator doesn't move the streams
+        let mut used = RngResource::from_seed(9);
+        used.rng.gen::<u64>();
+        assert_eq!(draw(&rng, RngStream::Food, 5), draw(&used, RngStream::Food, 5));
+        assert_ne!(draw(&rng, RngStream::Food, 5), draw(&rng, RngStream::Food, 6));
+        assert_ne!(draw(&rng, RngStream::Food, 5), draw(&rng, RngStream::Split, 5));
+        assert_ne!(draw(&rng, RngStream::Think { round: 0 }, 5), draw(&rng, RngStream::Think { round: 1 }, 5));
+        assert_ne!(draw(&rng, RngStream::Food, 5), draw(&RngResource::from_seed(10), RngStream::Food, 5));
+    }
+
+    #[test]
+    fn temperature_follows_layout_and_world_size() {
+        let mut world = World::new();
+        world.insert_resource(TemperatureMap::default());
+        world.insert_resource(RngResource::from_seed(4));
+        world.insert_resource(SimTime::default());
+        world.insert_resource(SimulationConfig { rows: 11, columns: 23, temperature_layout: TemperatureLayout::Gradient, ..SimulationConfig::default() });
+        world.run_system_once(update_temperature);
+        let temperature_map = world.resource::<TemperatureMap>();
+        assert_eq!(temperature_map.temperature(&Position { x: 5, y: 0 }), 0.0);
+        assert_eq!(temperature_map.temperature(&Position { x: 5, y: 10 }), 1.0);
+        world.resource_mut::<SimulationConfig>().temperature_layout = TemperatureLayout::Noise;
+        world.run_system_once(update_temperature);
+        let temperature_map = world.resource::<TemperatureMap>();
+        assert_eq!(temperature_map.map.map.len(), 11 * 23);
+        assert!(temperature_map.map.map.iter().all(|temperature| (0.0..=1.0).contains(temperature)));
+        assert_eq!(temperature_map.temperature(&Position { x: 30, y: 30 }), NEUTRAL_TEMPERATURE);
+    }
+
+    #[test]
+    fn food_direction_is_relative_to_heading() {
+        let config = SimulationConfig { rows: 10, columns: 10, ..SimulationConfig::default() };
+        let mut food_map = FoodMap { map: Map2d::new(10, 10, Food::default()) };
+        let head = Position { x: 5, y: 4 };
+        food_map.map.set(&Position { x: 7, y: 4 }, Food::from_plant(1.0));
+        let (ahead, left) = food_direction(&East, &head, 3, &food_map, &config);
+        assert!((ahead - 1.0).abs() < 1e-5 && left.abs() < 1e-5);
+        let (ahead, left) = food_direction(&NorthEast, &head, 3, &food_map, &config);
+        assert!(ahead > 0.0 && left < 0.0);
+        let (ahead, left) = food_direction(&West, &head, 1, &food_map, &config);
+        assert_eq!((ahead, left), (0.0, 0.0));
+        // the closer food across the world edge wins
+        food_map.map.set(&Position { x: 7, y: 4 }, Food::default());
+        food_map.map.set(&Position { x: 5, y: 4 }, Food::from_plant(1.0));
+        food_map.map.set(&Position { x: 0, y: 4 }, Food::from_plant(1.0));
+        let (ahead, _) = food_direction(&East, &Position { x: 8, y: 4 }, 3, &food_map, &config);
+        assert!(ahead > 0.99);
+    }
+
+    #[test]
+    fn touch_reports_only_the_neighbouring_hex() {
+        let mut config = SimulationConfig { rows: 10, columns: 10, ..SimulationConfig::default() };
+        let mut food_map = FoodMap { map: Map2d::new(10, 10, Food::default()) };
+        let mut solids_map = SolidsMap { map: Map2d::new(10, 10, false) };
+        let head = Position { x: 5, y: 4 };
+        food_map.map.set(&Position { x: 6, y: 4 }, Food::from_meat(1.0));
+        solids_map.map.set(&Position { x: 4, y: 4 }, true);
+        solids_map.map.set(&Position { x: 7, y: 4 }, true);
+        assert_eq!(touch(&East, &head, &food_map, &solids_map, &config), (0.0, 1.0));
+        assert_eq!(touch(&West, &head, &food_map, &solids_map, &config), (1.0, 0.0));
+        config.mutation.touch_input_enabled = false;
+        assert_eq!(touch(&West, &head, &food_map, &solids_map, &config), (0.0, 0.0));
+    }
+
+    #[test]
+    fn gradient_puts_more_food_on_the_right() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let config = SimulationConfig { rows: 10, columns: 10, food_distribution: FoodDistribution::Gradient, ..SimulationConfig::default() };
+        let right = (0..1000).filter(|_| sample_food_position(&mut rng, &config, &[]).x >= 5).count();
+        assert!(right > 600);
+    }
+}
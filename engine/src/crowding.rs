@@ -0,0 +1,76 @@
+use bevy_ecs::prelude::*;
+use crate::core::{Position, SegmentMap, Snake};
+use crate::profile_system;
+use crate::simulation::{SimulationConfig, Stats};
+
+// Segments of other snakes in the square around the head, the world wraps so the square does too
+fn crowd_around(head: &Position, snake: &Snake, segment_map: &SegmentMap, radius: i32) -> usize {
+    let mut crowd = 0;
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            let position = segment_map.map.wrap(&Position { x: head.x + dx, y: head.y + dy });
+            crowd += segment_map.map.get(&position).iter().filter(|segment| !snake.segments.contains(segment)).count();
+        }
+    }
+    crowd
+}
+
+// Snakes with more foreign segments around their head than the capacity pay for every one of them, so clumps cost energy and spreading out pays
+pub fn crowd(mut snakes: Query<(&mut Snake, &Position)>, segment_map: Res<SegmentMap>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if config.crowding_penalty <= 0.0 {
+        return;
+    }
+    let mut crowded = 0;
+    for (mut snake, head) in &mut snakes {
+        let excess = crowd_around(head, &snake, &segment_map, config.crowding_radius as i32).saturating_sub(config.crowding_capacity);
+        if excess > 0 {
+            snake.energy.energy -= config.crowding_penalty * excess as f32;
+            crowded += 1;
+        }
+    }
+    stats.crowded_snakes = crowded;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::system::RunSystemOnce;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::{Map3d, spawn_test_snake};
+    use super::*;
+
+    #[test]
+    fn only_snakes_in_a_crowd_pay_for_it() {
+        let config = SimulationConfig { rows: 20, columns: 20, crowding_radius: 1, crowding_capacity: 2, crowding_penalty: 0.5, ..SimulationConfig::default() };
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(Stats::default());
+        let mut segment_map = SegmentMap { map: Map3d::new(20, 20) };
+        let mut rng = StdRng::seed_from_u64(1);
+        // every snake is a head with a tail to its west, heads on the first column have their tails across the edge
+        let mut spawn = |world: &mut World, segment_map: &mut SegmentMap, (x, y): (i32, i32)| {
+            let head = spawn_test_snake(world, (x, y), &mut rng);
+            let tail_position = segment_map.map.wrap(&Position { x: x - 1, y });
+            let tail = world.spawn(tail_position.clone()).id();
+            world.get_mut::<Snake>(head).unwrap().segments = vec![head, tail];
+            segment_map.map.add(&Position { x, y }, head);
+            segment_map.map.add(&tail_position, tail);
+            head
+        };
+        let first = spawn(&mut world, &mut segment_map, (0, 5));
+        let second = spawn(&mut world, &mut segment_map, (0, 6));
+        let third = spawn(&mut world, &mut segment_map, (19, 4));
+        let alone = spawn(&mut world, &mut segment_map, (10, 10));
+        world.insert_resource(segment_map);
+        world.run_system_once(crowd);
+        let energy = |world: &World, snake: Entity| world.get::<Snake>(snake).unwrap().energy.energy;
+        // the first snake sees both segments of the second and the head of the third across the edge, one more than the capacity
+        assert_eq!(energy(&world, first), 99.5);
+        // the others only see the two segments of the first, which the capacity allows
+        assert_eq!(energy(&world, second), 100.0);
+        assert_eq!(energy(&world, third), 100.0);
+        assert_eq!(energy(&world, alone), 100.0);
+        assert_eq!(world.resource::<Stats>().crowded_snakes, 1);
+    }
+}
@@ -0,0 +1,94 @@
+use std::mem::size_of;
+use bevy_ecs::prelude::*;
+use crate::core::{Food, FoodMap, Map2d, Map3d, Position, Scent, ScentMap, SegmentMap, SignalMap, Snake, Solid, SolidsMap, TemperatureMap, Trail};
+use crate::dna::SegmentType;
+use crate::minerals::MineralMap;
+use crate::nests::Nest;
+use crate::simulation::SimulationEvents;
+use crate::water::WaterMap;
+
+// What the world holds, a count that only ever grows points to a leak
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    pub entities: usize,
+    // entities with each kind of component and roughly how many bytes the components take
+    pub components: Vec<(&'static str, usize, usize)>,
+    // maps with a cell for every hex and roughly how many bytes they take
+    pub maps: Vec<(&'static str, usize)>,
+    // events waiting for the next repaint
+    pub buffered_events: usize,
+}
+
+impl Diagnostics {
+    // Heap memory behind the components, like the brains of the snakes, is not counted
+    pub fn capture(world: &mut World) -> Self {
+        let components = vec![
+            ("Positions", count::<Position>(world), size_of::<Position>()),
+            ("Snakes", count::<Snake>(world), size_of::<Snake>()),
+            ("Segments", count::<SegmentType>(world), size_of::<SegmentType>()),
+            ("Solids", count::<Solid>(world), size_of::<Solid>()),
+            ("Food", count::<Food>(world), size_of::<Food>()),
+            ("Scents", count::<Scent>(world), size_of::<Scent>()),
+            ("Nests", count::<Nest>(world), size_of::<Nest>()),
+            ("Trails", count::<Trail>(world), size_of::<Trail>()),
+        ];
+        let maps = vec![
+            ("Food", map_bytes(&world.resource::<FoodMap>().map)),
+            ("Solids", map_bytes(&world.resource::<SolidsMap>().map)),
+            ("Segments", map3d_bytes(&world.resource::<SegmentMap>().map)),
+            ("Scents", map_bytes(&world.resource::<ScentMap>().map)),
+            ("Signals", map_bytes(&world.resource::<SignalMap>().map)),
+            ("Minerals", map_bytes(&world.resource::<MineralMap>().map)),
+            ("Water", map_bytes(&world.resource::<WaterMap>().map)),
+            ("Temperature", map_bytes(&world.resource::<TemperatureMap>().map)),
+        ];
+        Diagnostics {
+            entities: world.entities().len() as usize,
+            components: components.into_iter().map(|(name, count, size)| (name, count, count * size)).collect(),
+            maps,
+            buffered_events: world.resource::<SimulationEvents>().events.len(),
+        }
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.components.iter().map(|(_, _, bytes)| bytes).sum::<usize>() + self.maps.iter().map(|(_, bytes)| bytes).sum::<usize>()
+    }
+}
+
+fn count<T: Component>(world: &mut World) -> usize {
+    world.query_filtered::<(), With<T>>().iter(world).count()
+}
+
+fn map_bytes<T>(map: &Map2d<T>) -> usize {
+    map.map.capacity() * size_of::<T>()
+}
+
+fn map3d_bytes<T>(map: &Map3d<T>) -> usize {
+    map.map.capacity() * size_of::<Vec<T>>() + map.map.iter().map(|cell| cell.capacity() * size_of::<T>()).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{event_channel, EventCapacity};
+    use crate::core::RngResource;
+    use crate::simulation::{EngineState, Simulation, SimulationConfig};
+    use super::*;
+
+    #[test]
+    fn counts_follow_the_world() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Diagnostics".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 30, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(3));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(5);
+        simulation.step();
+        let diagnostics = Diagnostics::capture(simulation.world_mut());
+        let component = |name: &str| diagnostics.components.iter().find(|(component, _, _)| *component == name).unwrap().1;
+        assert_eq!(component("Snakes"), simulation.world_mut().query::<&Snake>().iter(simulation.world()).count());
+        assert!(component("Snakes") > 0 && component("Positions") >= component("Snakes") + component("Food"));
+        assert!(diagnostics.entities >= component("Positions"));
+        // every map has a cell for each of the 600 hexes
+        assert!(diagnostics.maps.iter().all(|(_, bytes)| *bytes >= 600));
+        assert!(diagnostics.total_bytes() > 600 * diagnostics.maps.len());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use bevy_ecs::prelude::*;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::core::{Direction, Position, position_at_direction, RngResource, RngStream, Snake};
+use crate::profile_system;
+use crate::simulation::{SimulationConfig, Stats};
+use crate::time::SimTime;
+
+const NEIGHBOURS: [Direction; 6] = [Direction::NorthEast, Direction::East, Direction::SouthEast, Direction::SouthWest, Direction::West, Direction::NorthWest];
+
+// Carried by the head of a sick snake, every segment costs extra energy until it recovers or starves
+#[derive(Component, Debug, Clone)]
+pub struct Infection {
+    pub infected_at: u64,
+}
+
+// Recovered snakes can't catch the pathogen again for a while
+#[derive(Component, Debug, Clone)]
+pub struct Immunity {
+    pub until: u64,
+}
+
+// The hex of the head and the six hexes around it
+fn touched_hexes<'a>(head: &'a Position, config: &'a SimulationConfig) -> impl Iterator<Item = Position> + 'a {
+    std::iter::once(head.clone()).chain(NEIGHBOURS.iter().map(move |direction| position_at_direction(direction, head, config)))
+}
+
+// Snakes are in contact when the head of one touches the body of the other, every contact with a sick snake may pass the pathogen on
+pub fn spread_disease(mut commands: Commands, snakes: Query<(Entity, &Snake, Has<Infection>, Has<Immunity>)>, positions: Query<&Position>, time: Res<SimTime>, rng: Res<RngResource>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if !config.disease_enabled {
+        return;
+    }
+    let mut rng = rng.stream(RngStream::Disease, &time);
+    // immune snakes neither catch nor matter for the contacts of others
+    let mut bodies: HashMap<(i32, i32), (Entity, bool)> = HashMap::new();
+    for (head_id, snake, infected, immune) in &snakes {
+        if infected || !immune {
+            for position in snake.segments.iter().filter_map(|segment_id| positions.get(*segment_id).ok()) {
+                bodies.insert(position.as_pair(), (head_id, infected));
+            }
+        }
+    }
+    let mut exposed = vec![];
+    for (head_id, _, infected, immune) in &snakes {
+        let Ok(head) = positions.get(head_id) else {
+            continue;
+        };
+        for hex in touched_hexes(head, &config) {
+            match bodies.get(&hex.as_pair()) {
+                Some((owner, false)) if infected && *owner != head_id => exposed.push(*owner),
+                Some((owner, true)) if !infected && !immune && *owner != head_id => exposed.push(head_id),
+                _ => {}
+            }
+        }
+    }
+    // a snake touching several sick ones gets one chance to catch it per frame
+    exposed.sort();
+    exposed.dedup();
+    exposed.retain(|_| rng.gen_bool(config.disease_transmission as f64));
+    if config.disease_outbreak_chance > 0.0 && rng.gen_bool(config.disease_outbreak_chance as f64) {
+        let healthy: Vec<Entity> = snakes.iter().filter(|(_, _, infected, immune)| !infected && !immune).map(|(head_id, _, _, _)| head_id).collect();
+        if let Some(head_id) = healthy.choose(&mut rng) {
+            if !exposed.contains(head_id) {
+                exposed.push(*head_id);
+            }
+        }
+    }
+    for head_id in exposed {
+        commands.entity(head_id).insert(Infection { infected_at: time.ticks });
+        stats.infections += 1;
+    }
+}
+
+// Sick snakes pay for their illness until it is over, it keeps running when the disease gets disabled so the sick ones still recover
+pub fn progress_disease(mut commands: Commands, mut sick: Query<(Entity, &mut Snake, &Infection)>, immune: Query<(Entity, &Immunity)>, time: Res<SimTime>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    for (head_id, immunity) in &immune {
+        if time.ticks >= immunity.until {
+            commands.entity(head_id).remove::<Immunity>();
+        }
+    }
+    let mut infected = 0;
+    for (head_id, mut snake, infection) in &mut sick {
+        if time.ticks.saturating_sub(infection.infected_at) >= config.disease_duration as u64 {
+            commands.entity(head_id).remove::<Infection>().insert(Immunity { until: time.ticks + config.disease_immunity as u64 });
+            stats.recoveries += 1;
+        } else {
+            snake.energy.energy -= config.disease_virulence * snake.segments.len() as f32;
+            infected += 1;
+        }
+    }
+    stats.infected_snakes = infected;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::system::RunSystemOnce;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::spawn_test_snake;
+    use crate::events::{event_channel, EventCapacity};
+    use crate::simulation::{EngineState, Simulation};
+    use super::*;
+
+    #[test]
+    fn disease_spreads_by_contact_and_ends_in_immunity() {
+        let config = SimulationConfig { rows: 20, columns: 20, disease_enabled: true, disease_transmission: 1.0, disease_virulence: 2.0, disease_duration: 10, disease_immunity: 5, disease_outbreak_chance: 0.0, ..SimulationConfig::default() };
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(SimTime::default());
+        world.insert_resource(Stats::default());
+        world.insert_resource(RngResource::from_seed(1));
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut spawn = |world: &mut World, position: (i32, i32)| {
+            let head_id = spawn_test_snake(world, position, &mut rng);
+            world.get_mut::<Snake>(head_id).unwrap().segments.push(head_id);
+            head_id
+        };
+        let sick = spawn(&mut world, (5, 5));
+        let neighbour = spawn(&mut world, (6, 5));
+        let far_away = spawn(&mut world, (15, 15));
+        world.entity_mut(sick).insert(Infection { infected_at: 0 });
+        world.resource_mut::<SimTime>().ticks = 5;
+        world.run_system_once(spread_disease);
+        assert!(world.get::<Infection>(neighbour).is_some());
+        assert!(world.get::<Infection>(far_away).is_none());
+        assert_eq!(world.resource::<Stats>().infections, 1);
+        world.run_system_once(progress_disease);
+        assert_eq!(world.get::<Snake>(sick).unwrap().energy.energy, 98.0);
+        assert_eq!(world.get::<Snake>(far_away).unwrap().energy.energy, 100.0);
+        assert_eq!(world.resource::<Stats>().infected_snakes, 2);
+        world.resource_mut::<SimTime>().ticks = 10;
+        world.run_system_once(progress_disease);
+        assert!(world.get::<Infection>(sick).is_none());
+        assert_eq!(world.get::<Immunity>(sick).unwrap().until, 15);
+        // the neighbour is still sick but can't pass it back to the immune snake
+        world.run_system_once(spread_disease);
+        assert!(world.get::<Infection>(sick).is_none());
+        world.resource_mut::<SimTime>().ticks = 15;
+        world.run_system_once(progress_disease);
+        assert!(world.get::<Immunity>(sick).is_none());
+    }
+
+    #[test]
+    fn snakes_that_starve_neither_catch_nor_pass_on_the_disease() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let config = SimulationConfig { rows: 10, columns: 10, disease_enabled: true, disease_outbreak_chance: 1.0, protect_elites: false, ..SimulationConfig::default() };
+        let mut simulation = Simulation::new("Disease".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(1));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(1);
+        let world = simulation.world_mut();
+        let mut snakes = world.query::<&mut Snake>();
+        snakes.single_mut(world).energy.energy = -1.0;
+        // the outbreak can only pick the starving snake, which is gone by then
+        simulation.step();
+        let world = simulation.world_mut();
+        assert_eq!(world.query::<&Snake>().iter(world).count(), 0);
+        assert_eq!(world.resource::<Stats>().infections, 0);
+    }
+}
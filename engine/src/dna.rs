@@ -1,19 +1,27 @@
 use bevy_ecs::prelude::Component;
 use rand::prelude::SliceRandom;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::simulation::MutationConfig;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Segment {
     pub energy_cost_move: f32,
     pub energy_cost_always: f32,
     pub mobility: f32,
 }
-#[derive(Clone, Debug, Component)]
+#[derive(Clone, Debug, PartialEq, Component, Serialize, Deserialize)]
 pub enum SegmentType {
     Muscle(Segment),
     Solid(Segment),
     Solar(Segment),
     Stomach(Segment),
+    // lets the snake swim through water, which is cheaper to move through than land
+    Fin(Segment),
+    // every eye lets the snake see further
+    Eye(Segment),
+    // every nose lets the snake smell scents further away
+    Nose(Segment),
 }
 
 impl SegmentType {
@@ -47,12 +55,40 @@ impl SegmentType {
             mobility: 0.5,
         })
     }
+
+    pub fn fin() -> Self {
+        SegmentType::Fin(Segment {
+            energy_cost_move: 1.0,
+            energy_cost_always: 0.1,
+            mobility: 0.3,
+        })
+    }
+
+    pub fn eye() -> Self {
+        SegmentType::Eye(Segment {
+            energy_cost_move: 1.0,
+            energy_cost_always: 0.2,
+            mobility: 0.2,
+        })
+    }
+
+    pub fn nose() -> Self {
+        SegmentType::Nose(Segment {
+            energy_cost_move: 1.0,
+            energy_cost_always: 0.2,
+            mobility: 0.2,
+        })
+    }
+
     pub fn mobility(&self) -> f32 {
         match self {
             SegmentType::Muscle(segment) => segment.mobility,
             SegmentType::Solid(segment) => segment.mobility,
             SegmentType::Solar(segment) => segment.mobility,
             SegmentType::Stomach(segment) => segment.mobility,
+            SegmentType::Fin(segment) => segment.mobility,
+            SegmentType::Eye(segment) => segment.mobility,
+            SegmentType::Nose(segment) => segment.mobility,
         }
     }
 
@@ -62,6 +98,9 @@ impl SegmentType {
             SegmentType::Solid(segment) => segment.energy_cost_move,
             SegmentType::Solar(segment) => segment.energy_cost_move,
             SegmentType::Stomach(segment) => segment.energy_cost_move,
+            SegmentType::Fin(segment) => segment.energy_cost_move,
+            SegmentType::Eye(segment) => segment.energy_cost_move,
+            SegmentType::Nose(segment) => segment.energy_cost_move,
         }
     }
 
@@ -71,15 +110,26 @@ impl SegmentType {
             SegmentType::Solid(segment) => segment.energy_cost_always,
             SegmentType::Solar(segment) => segment.energy_cost_always,
             SegmentType::Stomach(segment) => segment.energy_cost_always,
+            SegmentType::Fin(segment) => segment.energy_cost_always,
+            SegmentType::Eye(segment) => segment.energy_cost_always,
+            SegmentType::Nose(segment) => segment.energy_cost_always,
         }
     }
 }
 
-fn all_segment_types() -> [SegmentType; 4] {
-    [SegmentType::muscle(), SegmentType::solid(), SegmentType::solar(), SegmentType::stomach()]
+// Fins, eyes and noses only evolve when they are enabled, without them the pool and the random draws stay the same as before
+fn all_segment_types(mutation: &MutationConfig) -> Vec<SegmentType> {
+    let mut segment_types = vec![SegmentType::muscle(), SegmentType::solid(), SegmentType::solar(), SegmentType::stomach()];
+    if mutation.fins_enabled {
+        segment_types.push(SegmentType::fin());
+    }
+    if mutation.sense_segments_enabled {
+        segment_types.extend([SegmentType::eye(), SegmentType::nose()]);
+    }
+    segment_types
 }
 
-
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MutationType {
     AddGene,
     RemoveGene,
@@ -87,26 +137,62 @@ pub enum MutationType {
     ChangeJump
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Gene {
     pub segment_type: SegmentType,
     pub id: usize,
     pub jump: usize,
 }
 
-#[derive(Clone, Debug)]
+// Multipliers of the metabolism that the body plan leaves alone, 1.0 keeps the value of the segments
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Traits {
+    pub max_energy: f32,
+    // how fast the stomach turns plants and meat into energy
+    pub digestion_speed: f32,
+    pub mobility: f32,
+}
+
+impl Default for Traits {
+    fn default() -> Self {
+        Traits { max_energy: 1.0, digestion_speed: 1.0, mobility: 1.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trait {
+    MaxEnergy,
+    DigestionSpeed,
+    Mobility,
+}
+
+impl Traits {
+    pub const ALL: [Trait; 3] = [Trait::MaxEnergy, Trait::DigestionSpeed, Trait::Mobility];
+
+    pub fn get_mut(&mut self, which: Trait) -> &mut f32 {
+        match which {
+            Trait::MaxEnergy => &mut self.max_energy,
+            Trait::DigestionSpeed => &mut self.digestion_speed,
+            Trait::Mobility => &mut self.mobility,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Dna {
     pub genes: Vec<Gene>,
     pub current_gene: usize,
+    // genomes saved before there were traits get the default ones
+    #[serde(default)]
+    pub traits: Traits,
 }
 
 impl Dna {
-    pub(crate) fn random(gene_pool_size: usize) -> Dna {
-        let mut rng = rand::thread_rng();
+    pub(crate) fn random(gene_pool_size: usize, mutation: &MutationConfig, rng: &mut impl Rng) -> Dna {
         let mut genes = Vec::new();
+        let segment_types = all_segment_types(mutation);
         for i in 0..gene_pool_size {
-            let segment_types = all_segment_types();
-            let random_segment_type = segment_types.choose(&mut rng).unwrap().clone();
+            let random_segment_type = segment_types.choose(rng).unwrap().clone();
             let random_jump = rng.gen_range(0..gene_pool_size);
             genes.push(Gene {
                 segment_type: random_segment_type,
@@ -117,13 +203,14 @@ impl Dna {
         Dna {
             genes,
             current_gene: 0,
+            traits: Traits::default(),
         }
     }
-    pub fn mutate(&mut self) {
-        let rng = &mut rand::thread_rng();
+    // Returns the operator that was applied, adding and removing genes don't change anything yet
+    pub fn mutate(&mut self, mutation: &MutationConfig, rng: &mut impl Rng) -> MutationType {
         let mutations = [MutationType::AddGene, MutationType::RemoveGene, MutationType::ChangeSegmentType, MutationType::ChangeJump];
         let random_mutation = mutations.choose(rng).unwrap();
-        let segment_types = all_segment_types();
+        let segment_types = all_segment_types(mutation);
         match random_mutation {
             MutationType::AddGene => {}
             MutationType::RemoveGene => {}
@@ -138,12 +225,33 @@ impl Dna {
                 self.genes[random_index].jump = random_jump;
             }
         }
+        *random_mutation
+    }
+
+    // Scales one random trait by up to the given fraction, the result stays within the range of that trait
+    pub fn mutate_traits(&mut self, mutation: &MutationConfig, rng: &mut impl Rng) -> Trait {
+        let which = *Traits::ALL.choose(rng).unwrap();
+        let (min, max) = mutation.trait_range(which);
+        let factor = 1.0 + rng.gen_range(-mutation.trait_mutation_range..=mutation.trait_mutation_range);
+        let value = self.traits.get_mut(which);
+        *value = (*value * factor).clamp(min, max);
+        which
     }
 
     pub fn get_current_gene(&self) -> &Gene {
         &self.genes[self.current_gene]
     }
 
+    // The segments a snake grows one after another when it starts at the first gene, the head is not part of it
+    pub fn body_plan(&self, segments: usize) -> Vec<SegmentType> {
+        let mut gene = 0;
+        (0..segments).map(|_| {
+            let segment = self.genes[gene].segment_type.clone();
+            gene = self.genes[gene].jump;
+            segment
+        }).collect()
+    }
+
     pub fn build_segment(&mut self) -> SegmentType {
         let segment = self.genes[self.current_gene].segment_type.clone();
         self.current_gene = self.genes[self.current_gene].jump;
@@ -0,0 +1,113 @@
+use crate::simulation::{Hex, HexType};
+
+// Everything drawn on every hex of the world, kept on both sides of the channel so only changed hexes are sent
+#[derive(Debug, Clone, Default)]
+pub struct HexCells {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<HexType>>,
+}
+
+// Hexes that changed since the last draw, a full delta replaces everything the receiver had before
+#[derive(Debug, Clone)]
+pub struct DrawDelta {
+    pub width: usize,
+    pub height: usize,
+    pub full: bool,
+    // index of the hex (row by row) and everything that is on it now, empty if it was cleared
+    pub cells: Vec<(usize, Vec<HexType>)>,
+}
+
+impl HexCells {
+    pub fn new(width: usize, height: usize) -> Self {
+        HexCells { width, height, cells: vec![vec![]; width * height] }
+    }
+
+    // Hexes outside the world are dropped, several hexes on one cell are drawn in the given order
+    pub fn from_hexes(width: usize, height: usize, hexes: impl IntoIterator<Item = Hex>) -> Self {
+        let mut cells = HexCells::new(width, height);
+        for hex in hexes {
+            if hex.x < width && hex.y < height {
+                cells.cells[hex.y * width + hex.x].push(hex.hex_type);
+            }
+        }
+        cells
+    }
+
+    // Replaces self with the newer cells and returns what changed, everything is sent after a resize
+    pub fn update(&mut self, newer: HexCells) -> DrawDelta {
+        let full = self.width != newer.width || self.height != newer.height;
+        let cells = newer.cells.iter().enumerate()
+            .filter(|(index, cell)| if full { !cell.is_empty() } else { self.cells[*index] != **cell })
+            .map(|(index, cell)| (index, cell.clone()))
+            .collect();
+        let delta = DrawDelta { width: newer.width, height: newer.height, full, cells };
+        *self = newer;
+        delta
+    }
+
+    pub fn apply(&mut self, delta: DrawDelta) {
+        if delta.full || self.width != delta.width || self.height != delta.height {
+            *self = HexCells::new(delta.width, delta.height);
+        }
+        for (index, cell) in delta.cells {
+            if let Some(current) = self.cells.get_mut(index) {
+                *current = cell;
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Everything on the hex in drawing order, hexes outside the world are empty
+    pub fn cell(&self, x: usize, y: usize) -> &[HexType] {
+        if x >= self.width || y >= self.height {
+            return &[];
+        }
+        &self.cells[y * self.width + x]
+    }
+
+    pub fn hexes(&self) -> impl Iterator<Item = Hex> + '_ {
+        self.cells.iter().enumerate().flat_map(move |(index, cell)| {
+            cell.iter().map(move |hex_type| Hex { x: index % self.width, y: index / self.width, hex_type: hex_type.clone() })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(x: usize, y: usize, hex_type: HexType) -> Hex {
+        Hex { x, y, hex_type }
+    }
+
+    #[test]
+    fn deltas_only_carry_changed_hexes_and_rebuild_the_receiver() {
+        let mut sender = HexCells::default();
+        let mut receiver = HexCells::default();
+        let first = sender.update(HexCells::from_hexes(4, 3, vec![hex(0, 0, HexType::Food), hex(2, 1, HexType::Meat), hex(2, 1, HexType::Scent { value: 0.5 })]));
+        assert!(first.full);
+        assert_eq!(first.cells.len(), 2);
+        receiver.apply(first);
+        let second = sender.update(HexCells::from_hexes(4, 3, vec![hex(2, 1, HexType::Meat), hex(2, 1, HexType::Scent { value: 0.5 }), hex(3, 2, HexType::SnakeTail)]));
+        assert!(!second.full);
+        // the food was eaten and a tail appeared, the meat did not change
+        assert_eq!(second.cells, vec![(0, vec![]), (11, vec![HexType::SnakeTail])]);
+        receiver.apply(second);
+        assert_eq!(receiver.cell(2, 1), &[HexType::Meat, HexType::Scent { value: 0.5 }]);
+        assert!(receiver.cell(4, 0).is_empty());
+        let received: Vec<(usize, usize)> = receiver.hexes().map(|hex| (hex.x, hex.y)).collect();
+        assert_eq!(received, vec![(2, 1), (2, 1), (3, 2)]);
+        let resized = sender.update(HexCells::from_hexes(2, 2, vec![hex(1, 1, HexType::Food)]));
+        assert!(resized.full);
+        receiver.apply(resized);
+        assert_eq!(receiver.hexes().count(), 1);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::core::{FoodMap, Position};
+use crate::simulation::SimulationConfig;
+
+// Energy in square blocks of hexes, food counts with its energy content and snakes with their energy at the head
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnergyGrid {
+    // hexes along one side of a block, the blocks at the right and bottom edges may be cut off
+    pub cell: usize,
+    pub columns: usize,
+    pub rows: usize,
+    // stored row by row
+    pub energies: Vec<f32>,
+}
+
+impl EnergyGrid {
+    pub fn new<'a>(cell: usize, food_map: &FoodMap, snakes: impl Iterator<Item = (&'a Position, f32)>, config: &SimulationConfig) -> Self {
+        let cell = cell.max(1);
+        let columns = food_map.map.width.div_ceil(cell);
+        let rows = food_map.map.height.div_ceil(cell);
+        let mut grid = EnergyGrid { cell, columns, rows, energies: vec![0.0; columns * rows] };
+        for (position, food) in food_map.map.iter() {
+            grid.add(&position, food.plant * config.plant_energy_content + food.meat * config.meat_energy_content);
+        }
+        for (position, energy) in snakes {
+            grid.add(position, energy.max(0.0));
+        }
+        grid
+    }
+
+    fn add(&mut self, position: &Position, energy: f32) {
+        if let Some(index) = self.index(position.x as usize / self.cell, position.y as usize / self.cell) {
+            self.energies[index] += energy;
+        }
+    }
+
+    fn index(&self, column: usize, row: usize) -> Option<usize> {
+        (column < self.columns && row < self.rows).then_some(row * self.columns + column)
+    }
+
+    pub fn energy(&self, column: usize, row: usize) -> f32 {
+        self.index(column, row).map_or(0.0, |index| self.energies[index])
+    }
+
+    pub fn max(&self) -> f32 {
+        self.energies.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{Food, Map2d};
+    use super::*;
+
+    #[test]
+    fn food_and_snakes_are_summed_per_block() {
+        let config = SimulationConfig { columns: 10, rows: 7, plant_energy_content: 2.0, meat_energy_content: 5.0, ..SimulationConfig::default() };
+        let mut food_map = FoodMap { map: Map2d::new(config.columns, config.rows, Food::default()) };
+        food_map.map.set(&Position { x: 1, y: 1 }, Food::from_plant(3.0));
+        food_map.map.set(&Position { x: 9, y: 6 }, Food::from_meat(1.0));
+        let heads = [Position { x: 3, y: 0 }, Position { x: 2, y: 2 }];
+        let grid = EnergyGrid::new(4, &food_map, heads.iter().zip([10.0, -4.0]), &config);
+        assert_eq!((grid.columns, grid.rows), (3, 2));
+        assert_eq!(grid.energy(0, 0), 16.0);
+        // the cut off block in the corner still counts its hexes
+        assert_eq!(grid.energy(2, 1), 5.0);
+        assert_eq!(grid.energy(1, 0), 0.0);
+        assert_eq!(grid.energy(5, 5), 0.0);
+        assert_eq!(grid.max(), 16.0);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use std::sync::mpsc::{sync_channel, Receiver, RecvError, SendError, SyncSender, TryRecvError, TrySendError};
+use serde::{Deserialize, Serialize};
+use crate::simulation::EngineEvent;
+
+// How many events may wait for the receiver before the engine slows down to its pace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventCapacity {
+    // events other than drawn frames, the engine waits when that many were not picked up yet
+    pub events: usize,
+    // drawn frames never make the engine wait, the oldest ones are merged into the next instead
+    pub frames: usize,
+}
+
+impl Default for EventCapacity {
+    fn default() -> Self {
+        EventCapacity { events: 1024, frames: 1 }
+    }
+}
+
+struct Frames {
+    pending: VecDeque<EngineEvent>,
+    capacity: usize,
+    receiver_dropped: bool,
+}
+
+// Sends events from the engine, frames go past the bounded queue so a stalled receiver only ever holds a few of them
+#[derive(Clone)]
+pub struct EventSender {
+    // None only wakes up a receiver waiting for the next frame
+    events: SyncSender<Option<EngineEvent>>,
+    frames: Arc<Mutex<Frames>>,
+    // nanoseconds all clones of the sender spent waiting for room in the queue
+    waited: Arc<AtomicU64>,
+}
+
+pub struct EventReceiver {
+    events: Receiver<Option<EngineEvent>>,
+    frames: Arc<Mutex<Frames>>,
+}
+
+// Capacities below 1 are raised to 1
+pub fn event_channel(capacity: EventCapacity) -> (EventSender, EventReceiver) {
+    let (events_sender, events) = sync_channel(capacity.events.max(1));
+    let frames = Arc::new(Mutex::new(Frames { pending: VecDeque::new(), capacity: capacity.frames.max(1), receiver_dropped: false }));
+    (EventSender { events: events_sender, frames: Arc::clone(&frames), waited: Arc::new(AtomicU64::new(0)) }, EventReceiver { events, frames })
+}
+
+impl EventSender {
+    // Waits while the queue is full, fails just like a channel once the receiver is gone
+    pub fn send(&self, event: EngineEvent) -> Result<(), SendError<EngineEvent>> {
+        if !matches!(event, EngineEvent::DrawData { .. }) {
+            let event = match self.events.try_send(Some(event)) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Disconnected(event)) => return Err(SendError(event.unwrap())),
+                Err(TrySendError::Full(event)) => event,
+            };
+            let waiting = Instant::now();
+            let result = self.events.send(event).map_err(|SendError(event)| SendError(event.unwrap()));
+            self.waited.fetch_add(waiting.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            return result;
+        }
+        let mut frames = self.frames.lock().unwrap();
+        if frames.receiver_dropped {
+            return Err(SendError(event));
+        }
+        let first = frames.pending.is_empty();
+        frames.pending.push_back(event);
+        if frames.pending.len() > frames.capacity {
+            let oldest = frames.pending.pop_front().unwrap();
+            merge_frames(oldest, frames.pending.front_mut().unwrap());
+        }
+        drop(frames);
+        // a full queue has events to read anyway and the frame is picked up along with them
+        if first {
+            if let Err(TrySendError::Disconnected(_)) = self.events.try_send(None) {
+                return Err(SendError(self.frames.lock().unwrap().pending.pop_back().unwrap()));
+            }
+        }
+        Ok(())
+    }
+
+    // Time spent waiting for a receiver that did not keep up
+    pub fn waited(&self) -> Duration {
+        Duration::from_nanos(self.waited.load(Ordering::Relaxed))
+    }
+}
+
+impl EventReceiver {
+    // Other events come before a waiting frame
+    pub fn try_recv(&self) -> Result<EngineEvent, TryRecvError> {
+        loop {
+            match self.events.try_recv() {
+                Ok(Some(event)) => return Ok(event),
+                Ok(None) => continue,
+                Err(error) => return self.next_frame().ok_or(error),
+            }
+        }
+    }
+
+    // Waits for the next event and fails once every sender is gone and nothing is left
+    pub fn recv(&self) -> Result<EngineEvent, RecvError> {
+        loop {
+            if let Ok(event) = self.try_recv() {
+                return Ok(event);
+            }
+            match self.events.recv() {
+                Ok(Some(event)) => return Ok(event),
+                Ok(None) => continue,
+                Err(error) => return self.next_frame().ok_or(error),
+            }
+        }
+    }
+
+    // Everything waiting right now
+    pub fn try_iter(&self) -> impl Iterator<Item = EngineEvent> + '_ {
+        std::iter::from_fn(|| self.try_recv().ok())
+    }
+
+    // Blocks until every sender is gone
+    pub fn iter(&self) -> impl Iterator<Item = EngineEvent> + '_ {
+        std::iter::from_fn(|| self.recv().ok())
+    }
+
+    fn next_frame(&self) -> Option<EngineEvent> {
+        self.frames.lock().unwrap().pending.pop_front()
+    }
+}
+
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        let mut frames = self.frames.lock().unwrap();
+        frames.receiver_dropped = true;
+        frames.pending.clear();
+    }
+}
+
+// The newer frame keeps its stats and simulation time, the hexes only the older frame changed are carried over
+fn merge_frames(older: EngineEvent, newer: &mut EngineEvent) {
+    let (EngineEvent::DrawData { delta: older, .. }, EngineEvent::DrawData { delta: newer, .. }) = (older, newer) else {
+        return;
+    };
+    if newer.full || newer.width != older.width || newer.height != older.height {
+        return;
+    }
+    // both deltas are sorted by index, so one pass merges them and the newer cell wins
+    let mut cells = Vec::with_capacity(older.cells.len() + newer.cells.len());
+    let mut older_cells = older.cells.into_iter().peekable();
+    let mut newer_cells = std::mem::take(&mut newer.cells).into_iter().peekable();
+    loop {
+        let cell = match (older_cells.peek(), newer_cells.peek()) {
+            (Some((older_index, _)), Some((newer_index, _))) if older_index < newer_index => older_cells.next(),
+            (Some((older_index, _)), Some((newer_index, _))) if older_index == newer_index => {
+                older_cells.next();
+                newer_cells.next()
+            }
+            (_, Some(_)) => newer_cells.next(),
+            (Some(_), None) => older_cells.next(),
+            (None, None) => break,
+        };
+        cells.extend(cell);
+    }
+    newer.cells = cells;
+    newer.full = older.full;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::draw::{DrawDelta, HexCells};
+    use crate::simulation::{Hex, HexType, RunClock, Stats};
+    use super::*;
+
+    fn draw_data(delta: DrawDelta, frame: u32) -> EngineEvent {
+        EngineEvent::DrawData { delta, stats: Box::new(Stats::default()), frame }
+    }
+
+    #[test]
+    fn frames_are_merged_and_other_events_wait_for_room() {
+        let (sender, receiver) = event_channel(EventCapacity { events: 2, frames: 1 });
+        let mut sent = HexCells::default();
+        let mut expected = HexCells::default();
+        let hexes = [vec![(0, 0, HexType::Food), (1, 1, HexType::Meat)], vec![(1, 1, HexType::SnakeTail)], vec![(0, 0, HexType::Meat), (3, 1, HexType::Food)]];
+        for (number, hexes) in hexes.into_iter().enumerate() {
+            let delta = sent.update(HexCells::from_hexes(4, 3, hexes.into_iter().map(|(x, y, hex_type)| Hex { x, y, hex_type })));
+            expected.apply(delta.clone());
+            sender.send(draw_data(delta, number as u32)).unwrap();
+        }
+        sender.send(EngineEvent::FrameDrawn { updates_left: 0.0, updates_done: 1, clock: RunClock::default() }).unwrap();
+        // the three frames add up to a single one that still rebuilds the receiver from scratch
+        let mut received = HexCells::default();
+        let events: Vec<EngineEvent> = receiver.try_iter().collect();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], EngineEvent::FrameDrawn { .. }));
+        let EngineEvent::DrawData { delta, frame, .. } = events[1].clone() else {
+            panic!("the frame was not received");
+        };
+        assert!(delta.full);
+        assert_eq!(frame, 2);
+        received.apply(delta);
+        let hexes = |cells: &HexCells| cells.hexes().map(|hex| (hex.x, hex.y, hex.hex_type)).collect::<Vec<_>>();
+        assert_eq!(hexes(&received), hexes(&expected));
+        assert_eq!(hexes(&received), vec![(0, 0, HexType::Meat), (3, 1, HexType::Food)]);
+        // the third event only fits once the receiver picked up the first one
+        let drawn = |updates_done| EngineEvent::FrameDrawn { updates_left: 0.0, updates_done, clock: RunClock::default() };
+        sender.send(drawn(0)).unwrap();
+        sender.send(drawn(1)).unwrap();
+        assert_eq!(sender.waited(), Duration::ZERO);
+        let waiting = sender.clone();
+        let blocked = thread::spawn(move || waiting.send(drawn(2)).unwrap());
+        thread::sleep(Duration::from_millis(10));
+        let updates: Vec<u32> = receiver.iter().take(3).map(|event| match event {
+            EngineEvent::FrameDrawn { updates_done, .. } => updates_done,
+            _ => panic!("unexpected event"),
+        }).collect();
+        assert_eq!(updates, vec![0, 1, 2]);
+        blocked.join().unwrap();
+        assert!(sender.waited() > Duration::ZERO);
+        drop(receiver);
+        assert!(sender.send(draw_data(DrawDelta { width: 4, height: 3, full: true, cells: vec![] }, 3)).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::events::{event_channel, EventCapacity};
+use bevy_ecs::prelude::World;
+use rayon::prelude::*;
+use crate::core::{RngResource, Snake, Species};
+use crate::simulation::{EngineState, Simulation, SimulationConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parameter {
+    FoodPerStep,
+    PlantMatterPerSegment,
+    WaitCost,
+    MoveCost,
+    NewSegmentCost,
+    SizeToSplit,
+    SpeciesThreshold,
+    SnakeMaxAge,
+    WeightPerturbationChance,
+    WeightPerturbationRange,
+    ConnectionFlipChance,
+    DnaMutationChance,
+    WeightResetChance,
+    WeightResetRange,
+    PruneChance,
+}
+
+impl Parameter {
+    pub const ALL: [Parameter; 15] = [
+        Parameter::FoodPerStep,
+        Parameter::PlantMatterPerSegment,
+        Parameter::WaitCost,
+        Parameter::MoveCost,
+        Parameter::NewSegmentCost,
+        Parameter::SizeToSplit,
+        Parameter::SpeciesThreshold,
+        Parameter::SnakeMaxAge,
+        Parameter::WeightPerturbationChance,
+        Parameter::WeightPerturbationRange,
+        Parameter::ConnectionFlipChance,
+        Parameter::DnaMutationChance,
+        Parameter::WeightResetChance,
+        Parameter::WeightResetRange,
+        Parameter::PruneChance,
+    ];
+
+    // names match the SimulationConfig/MutationConfig fields
+    pub fn name(&self) -> &'static str {
+        match self {
+            Parameter::FoodPerStep => "food_per_step",
+            Parameter::PlantMatterPerSegment => "plant_matter_per_segment",
+            Parameter::WaitCost => "wait_cost",
+            Parameter::MoveCost => "move_cost",
+            Parameter::NewSegmentCost => "new_segment_cost",
+            Parameter::SizeToSplit => "size_to_split",
+            Parameter::SpeciesThreshold => "species_threshold",
+            Parameter::SnakeMaxAge => "snake_max_age",
+            Parameter::WeightPerturbationChance => "weight_perturbation_chance",
+            Parameter::WeightPerturbationRange => "weight_perturbation_range",
+            Parameter::ConnectionFlipChance => "connection_flip_chance",
+            Parameter::DnaMutationChance => "dna_mutation_chance",
+            Parameter::WeightResetChance => "weight_reset_chance",
+            Parameter::WeightResetRange => "weight_reset_range",
+            Parameter::PruneChance => "prune_chance",
+        }
+    }
+
+    fn is_chance(&self) -> bool {
+        matches!(self, Parameter::WeightPerturbationChance | Parameter::ConnectionFlipChance | Parameter::DnaMutationChance | Parameter::WeightResetChance | Parameter::PruneChance)
+    }
+
+    // Every parameter is a count, an amount or a chance, none of them can be negative
+    fn check(&self, value: f64) -> Result<(), String> {
+        if self.is_chance() && !(0.0..=1.0).contains(&value) {
+            return Err(format!("{} is a chance and must be between 0 and 1, was {}", self.name(), value));
+        }
+        if value < 0.0 || value.is_nan() {
+            return Err(format!("{} can't be negative, was {}", self.name(), value));
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &mut SimulationConfig, value: f64) {
+        match self {
+            Parameter::FoodPerStep => config.food_per_step = value.round() as usize,
+            Parameter::PlantMatterPerSegment => config.plant_matter_per_segment = value as f32,
+            Parameter::WaitCost => config.wait_cost = value as f32,
+            Parameter::MoveCost => config.move_cost = value as f32,
+            Parameter::NewSegmentCost => config.new_segment_cost = value as f32,
+            Parameter::SizeToSplit => config.size_to_split = value.round() as usize,
+            Parameter::SpeciesThreshold => config.species_threshold = value as f32,
+            Parameter::SnakeMaxAge => config.snake_max_age = value.round() as u32,
+            Parameter::WeightPerturbationChance => config.mutation.weight_perturbation_chance = value,
+            Parameter::WeightPerturbationRange => config.mutation.weight_perturbation_range = value as f32,
+            Parameter::ConnectionFlipChance => config.mutation.connection_flip_chance = value,
+            Parameter::DnaMutationChance => config.mutation.dna_mutation_chance = value,
+            Parameter::WeightResetChance => config.mutation.weight_reset_chance = value,
+            Parameter::WeightResetRange => config.mutation.weight_reset_range = value as f32,
+            Parameter::PruneChance => config.mutation.prune_chance = value,
+        }
+    }
+}
+
+impl FromStr for Parameter {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Parameter::ALL.iter().find(|parameter| parameter.name() == name).copied().ok_or_else(|| {
+            let known: Vec<&str> = Parameter::ALL.iter().map(|parameter| parameter.name()).collect();
+            format!("Unknown parameter {}, expected one of: {}", name, known.join(", "))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterRange {
+    pub parameter: Parameter,
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+}
+
+impl ParameterRange {
+    // both ends are included, the end is matched with some tolerance for float steps
+    pub fn values(&self) -> Vec<f64> {
+        if self.step <= 0.0 || self.end <= self.start {
+            return vec![self.start];
+        }
+        let count = ((self.end - self.start) / self.step + 1e-9).floor() as usize + 1;
+        // rounding keeps values like 0.30000000000000004 out of the reports
+        (0..count).map(|index| ((self.start + index as f64 * self.step) * 1e9).round() / 1e9).collect()
+    }
+}
+
+// Parses `name=value` or `name=start..end:step`
+impl FromStr for ParameterRange {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, range) = text.split_once('=').ok_or_else(|| format!("Expected name=start..end:step, got {}", text))?;
+        let parameter = name.trim().parse()?;
+        let number = |value: &str| value.trim().parse::<f64>().map_err(|error| format!("Invalid number {} in {}: {}", value, text, error));
+        match range.split_once("..") {
+            Some((start, rest)) => {
+                let (end, step) = rest.split_once(':').ok_or_else(|| format!("Missing :step in {}", text))?;
+                let step = number(step)?;
+                if step <= 0.0 {
+                    return Err(format!("Step must be positive in {}", text));
+                }
+                let range = ParameterRange { parameter, start: number(start)?, end: number(end)?, step };
+                range.values().into_iter().try_for_each(|value| parameter.check(value))?;
+                Ok(range)
+            }
+            None => {
+                let value = number(range)?;
+                parameter.check(value)?;
+                Ok(ParameterRange { parameter, start: value, end: value, step: 0.0 })
+            }
+        }
+    }
+}
+
+pub struct Experiment {
+    pub base_config: SimulationConfig,
+    pub ranges: Vec<ParameterRange>,
+    pub steps: u32,
+    pub seeds: Vec<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RunOutcome {
+    pub parameters: Vec<(Parameter, f64)>,
+    pub seed: u64,
+    pub frames: u32,
+    pub population: usize,
+    pub species: usize,
+    pub max_generation: u32,
+    pub mean_energy: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct SummaryRow {
+    pub parameters: Vec<(Parameter, f64)>,
+    pub runs: usize,
+    pub extinct_runs: usize,
+    pub mean_population: f32,
+    pub mean_species: f32,
+    pub mean_max_generation: f32,
+    pub mean_energy: f32,
+}
+
+pub struct ExperimentSummary {
+    pub parameters: Vec<Parameter>,
+    pub rows: Vec<SummaryRow>,
+}
+
+impl Experiment {
+    // cartesian product of all ranges, the first range changes slowest
+    pub fn combinations(&self) -> Vec<Vec<(Parameter, f64)>> {
+        self.ranges.iter().fold(vec![vec![]], |combinations, range| {
+            combinations.iter().flat_map(|combination| {
+                range.values().into_iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push((range.parameter, value));
+                    combination
+                })
+            }).collect()
+        })
+    }
+
+    pub fn run(&self) -> ExperimentSummary {
+        let runs: Vec<(Vec<(Parameter, f64)>, u64)> = self.combinations().into_iter()
+            .flat_map(|combination| self.seeds.iter().map(move |seed| (combination.clone(), *seed)))
+            .collect();
+        let outcomes: Vec<RunOutcome> = runs.into_par_iter()
+            .map(|(parameters, seed)| run_single(self.base_config.clone(), parameters, seed, self.steps))
+            .collect();
+        summarize(self.ranges.iter().map(|range| range.parameter).collect(), outcomes, self.seeds.len())
+    }
+}
+
+pub fn run_single(base_config: SimulationConfig, parameters: Vec<(Parameter, f64)>, seed: u64, steps: u32) -> RunOutcome {
+    let mut config = base_config;
+    for (parameter, value) in &parameters {
+        parameter.apply(&mut config, *value);
+    }
+    // nothing is sent while stepping manually, so the receiver can be dropped right away
+    let (engine_events, _) = event_channel(EventCapacity::default());
+    let mut simulation = Simulation::new(format!("Experiment seed {}", seed), engine_events, None, config.clone());
+    simulation.insert_resource(RngResource::from_seed(seed));
+    simulation.insert_resource(EngineState::default());
+    simulation.create_snakes(config.starting_snakes);
+    let mut frames = 0;
+    while frames < steps {
+        simulation.step();
+        frames += 1;
+        // snakes are never respawned, so an empty world stays empty
+        if frames % 100 == 0 && count_snakes(simulation.world_mut()) == 0 {
+            break;
+        }
+    }
+    let world = simulation.world_mut();
+    let snakes: Vec<(u32, f32)> = world.query::<&Snake>().iter(world).map(|snake| (snake.generation, snake.energy.energy)).collect();
+    let population = snakes.len();
+    RunOutcome {
+        parameters,
+        seed,
+        frames,
+        population,
+        species: world.resource::<Species>().species.len(),
+        max_generation: snakes.iter().map(|(generation, _)| *generation).max().unwrap_or(0),
+        mean_energy: if population > 0 { snakes.iter().map(|(_, energy)| energy).sum::<f32>() / population as f32 } else { 0.0 },
+    }
+}
+
+fn count_snakes(world: &mut World) -> usize {
+    world.query::<&Snake>().iter(world).count()
+}
+
+fn summarize(parameters: Vec<Parameter>, outcomes: Vec<RunOutcome>, runs_per_row: usize) -> ExperimentSummary {
+    // outcomes keep the order of the runs, so every combination is a consecutive chunk
+    let rows = outcomes.chunks(runs_per_row.max(1)).map(|runs| {
+        let mean = |value: &dyn Fn(&RunOutcome) -> f32| runs.iter().map(value).sum::<f32>() / runs.len() as f32;
+        SummaryRow {
+            parameters: runs[0].parameters.clone(),
+            runs: runs.len(),
+            extinct_runs: runs.iter().filter(|run| run.population == 0).count(),
+            mean_population: mean(&|run| run.population as f32),
+            mean_species: mean(&|run| run.species as f32),
+            mean_max_generation: mean(&|run| run.max_generation as f32),
+            mean_energy: mean(&|run| run.mean_energy),
+        }
+    }).collect();
+    ExperimentSummary { parameters, rows }
+}
+
+impl ExperimentSummary {
+    const COLUMNS: [&'static str; 6] = ["runs", "extinct", "population", "species", "max_generation", "energy"];
+
+    fn values(row: &SummaryRow) -> Vec<String> {
+        let mut values: Vec<String> = row.parameters.iter().map(|(_, value)| format!("{}", value)).collect();
+        values.push(row.runs.to_string());
+        values.push(row.extinct_runs.to_string());
+        values.push(format!("{:.1}", row.mean_population));
+        values.push(format!("{:.1}", row.mean_species));
+        values.push(format!("{:.1}", row.mean_max_generation));
+        values.push(format!("{:.1}", row.mean_energy));
+        values
+    }
+
+    fn headers(&self) -> Vec<&'static str> {
+        self.parameters.iter().map(|parameter| parameter.name()).chain(Self::COLUMNS).collect()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.headers().join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            csv.push_str(&Self::values(row).join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+impl fmt::Display for ExperimentSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers = self.headers();
+        let rows: Vec<Vec<String>> = self.rows.iter().map(Self::values).collect();
+        let widths: Vec<usize> = headers.iter().enumerate()
+            .map(|(column, header)| rows.iter().map(|row| row[column].len()).chain([header.len()]).max().unwrap_or(0))
+            .collect();
+        let line = |cells: Vec<&str>| cells.iter().zip(&widths).map(|(cell, width)| format!("{:>width$}", cell, width = width)).collect::<Vec<String>>().join(" | ");
+        writeln!(f, "{}", line(headers.clone()))?;
+        writeln!(f, "{}", widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<String>>().join("-+-"))?;
+        for row in &rows {
+            writeln!(f, "{}", line(row.iter().map(|cell| cell.as_str()).collect()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameter_range_includes_both_ends() {
+        let range: ParameterRange = "connection_flip_chance=0.1..0.9:0.2".parse().unwrap();
+        assert_eq!(range.parameter, Parameter::ConnectionFlipChance);
+        let values = range.values();
+        assert_eq!(values.len(), 5);
+        assert!((values[4] - 0.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn single_value_and_invalid_ranges_are_parsed() {
+        let range: ParameterRange = "food_per_step=3".parse().unwrap();
+        assert_eq!(range.values(), vec![3.0]);
+        assert!("food_per_step=1..3".parse::<ParameterRange>().is_err());
+        assert!("unknown=1".parse::<ParameterRange>().is_err());
+        assert!("connection_flip_chance=1.5".parse::<ParameterRange>().unwrap_err().contains("between 0 and 1"));
+        assert!("prune_chance=0.5..1.5:0.5".parse::<ParameterRange>().is_err());
+        assert!("size_to_split=-2".parse::<ParameterRange>().unwrap_err().contains("negative"));
+    }
+
+    #[test]
+    fn runs_with_parameters_out_of_range_use_the_closest_valid_ones() {
+        let config = SimulationConfig { rows: 20, columns: 20, starting_snakes: 10, food_per_step: 50, ..SimulationConfig::default() };
+        let parameters = vec![(Parameter::ConnectionFlipChance, 1.5), (Parameter::SizeToSplit, 1.0), (Parameter::NewSegmentCost, 1.0)];
+        let outcome = run_single(config, parameters, 1, 300);
+        assert_eq!(outcome.frames, 300);
+    }
+
+    #[test]
+    fn experiment_summarizes_every_combination() {
+        let experiment = Experiment {
+            base_config: SimulationConfig { rows: 10, columns: 10, starting_snakes: 2, ..SimulationConfig::default() },
+            ranges: vec!["food_per_step=1..2:1".parse().unwrap(), "wait_cost=1".parse().unwrap()],
+            steps: 20,
+            seeds: vec![1, 2],
+        };
+        assert_eq!(experiment.combinations().len(), 2);
+        let summary = experiment.run();
+        assert_eq!(summary.rows.len(), 2);
+        assert!(summary.rows.iter().all(|row| row.runs == 2));
+        assert_eq!(summary.rows[1].parameters, vec![(Parameter::FoodPerStep, 2.0), (Parameter::WaitCost, 1.0)]);
+        assert_eq!(summary.to_csv().lines().count(), 3);
+    }
+}
@@ -0,0 +1,204 @@
+use bevy_ecs::prelude::*;
+use crate::core::{calculate_gene_difference, Snake, Species};
+use crate::neural::NeuralNetwork;
+use crate::simulation::SimulationConfig;
+
+// more genomes make the matrix too slow to cluster and too dense to draw
+pub const MAX_GENOMES: usize = 300;
+
+// A genome in the distance matrix, the leader of a specie or one of its members
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomeLabel {
+    pub specie: u32,
+    // None for the leader, its genes are kept even after it died
+    pub snake: Option<u64>,
+}
+
+// Compatibility distances between every pair of genomes, measured just like when species are assigned
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DistanceMatrix {
+    pub genomes: Vec<GenomeLabel>,
+    // row by row, symmetric with zeros on the diagonal
+    pub distances: Vec<f32>,
+    // the species threshold the distances are compared with
+    pub threshold: f32,
+}
+
+// Two clusters joined at their average distance, clusters below the number of genomes are single genomes and the merge at index i forms cluster genomes + i
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Merge {
+    pub left: usize,
+    pub right: usize,
+    pub distance: f32,
+    pub size: usize,
+}
+
+impl DistanceMatrix {
+    // The leader of every specie followed by up to the given number of its members, spread evenly over the members so old and young ones are both in
+    pub fn capture(world: &mut World, members_per_species: usize) -> Self {
+        let config = world.resource::<SimulationConfig>().clone();
+        let mut genomes = vec![];
+        let mut networks: Vec<NeuralNetwork> = vec![];
+        for specie in &world.resource::<Species>().species {
+            if genomes.len() >= MAX_GENOMES {
+                break;
+            }
+            genomes.push(GenomeLabel { specie: specie.id, snake: None });
+            networks.push(specie.leader_network.clone());
+            let members: Vec<Entity> = specie.members.iter().copied().filter(|member| *member != specie.leader).collect();
+            let sampled = members_per_species.min(members.len()).min(MAX_GENOMES - genomes.len());
+            for index in 0..sampled {
+                let member = members[index * members.len() / sampled];
+                if let Some(network) = world.get::<Snake>(member).and_then(|snake| snake.brain.get_neural_network().map(|network| (snake.id, network.clone()))) {
+                    genomes.push(GenomeLabel { specie: specie.id, snake: Some(network.0) });
+                    networks.push(network.1);
+                }
+            }
+        }
+        let size = genomes.len();
+        let mut distances = vec![0.0; size * size];
+        for row in 0..size {
+            for column in row + 1..size {
+                let distance = calculate_gene_difference(&networks[row], &networks[column], &config);
+                distances[row * size + column] = distance;
+                distances[column * size + row] = distance;
+            }
+        }
+        DistanceMatrix { genomes, distances, threshold: config.species_threshold }
+    }
+
+    pub fn len(&self) -> usize {
+        self.genomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genomes.is_empty()
+    }
+
+    pub fn distance(&self, row: usize, column: usize) -> f32 {
+        self.distances[row * self.len() + column]
+    }
+
+    // Average linkage, the closest two clusters are joined until one is left
+    pub fn cluster(&self) -> Vec<Merge> {
+        let size = self.len();
+        let mut distances = self.distances.clone();
+        // index of the cluster every row stands for and how many genomes it holds, None once it was joined
+        let mut clusters: Vec<Option<(usize, usize)>> = (0..size).map(|genome| Some((genome, 1))).collect();
+        let mut merges = vec![];
+        for _ in 1..size {
+            let mut closest = None;
+            for row in 0..size {
+                for column in row + 1..size {
+                    if clusters[row].is_none() || clusters[column].is_none() {
+                        continue;
+                    }
+                    let distance = distances[row * size + column];
+                    if closest.is_none_or(|(_, _, closest)| distance < closest) {
+                        closest = Some((row, column, distance));
+                    }
+                }
+            }
+            let (row, column, distance) = closest.unwrap();
+            let ((left, left_size), (right, right_size)) = (clusters[row].unwrap(), clusters[column].unwrap());
+            // the joined cluster takes the row of the left one
+            for other in 0..size {
+                if other == row || other == column || clusters[other].is_none() {
+                    continue;
+                }
+                let joined = (distances[row * size + other] * left_size as f32 + distances[column * size + other] * right_size as f32) / (left_size + right_size) as f32;
+                distances[row * size + other] = joined;
+                distances[other * size + row] = joined;
+            }
+            clusters[row] = Some((size + merges.len(), left_size + right_size));
+            clusters[column] = None;
+            merges.push(Merge { left, right, distance, size: left_size + right_size });
+        }
+        merges
+    }
+
+    // Genomes in the order of the leaves of the dendrogram, so every cluster is a block in the heatmap
+    pub fn leaf_order(&self, merges: &[Merge]) -> Vec<usize> {
+        let size = self.len();
+        let Some(root) = merges.len().checked_sub(1) else {
+            return (0..size).collect();
+        };
+        let mut order = vec![];
+        let mut pending = vec![size + root];
+        while let Some(cluster) = pending.pop() {
+            if cluster < size {
+                order.push(cluster);
+            } else {
+                let merge = merges[cluster - size];
+                pending.push(merge.right);
+                pending.push(merge.left);
+            }
+        }
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::arena::Genome;
+    use crate::core::{ActionSet, RngResource, SENSOR_NAMES};
+    use crate::dna::Dna;
+    use crate::events::{event_channel, EventCapacity};
+    use crate::neural::InnovationTracker;
+    use crate::simulation::{EngineState, Simulation};
+    use super::*;
+
+    #[test]
+    fn members_are_closer_to_their_leader_than_the_threshold() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Distances".to_string(), engine_events, None, SimulationConfig { rows: 30, columns: 30, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(5));
+        simulation.insert_resource(EngineState::default());
+        let config = SimulationConfig::default();
+        let mut rng = StdRng::seed_from_u64(5);
+        let network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), ActionSet::from_config(&config).outputs(), 0.1, &mut InnovationTracker::new(), &mut rng);
+        let genome = Genome { network, dna: Dna::random(4, &config.mutation, &mut rng) };
+        // four copies of the same genome make up one specie
+        for x in 0..4 {
+            simulation.create_snake_at((3 + 4 * x, 10), Some(genome.clone())).unwrap();
+        }
+        simulation.create_snakes(10);
+        simulation.step();
+        let matrix = DistanceMatrix::capture(simulation.world_mut(), 2);
+        let species = simulation.world().resource::<Species>().species.len();
+        assert!(matrix.len() >= species + 2 && matrix.len() <= species * 3);
+        for row in 0..matrix.len() {
+            assert_eq!(matrix.distance(row, row), 0.0);
+            for column in 0..matrix.len() {
+                assert_eq!(matrix.distance(row, column), matrix.distance(column, row));
+            }
+        }
+        let leader = |specie: u32| matrix.genomes.iter().position(|genome| genome.specie == specie && genome.snake.is_none()).unwrap();
+        for (index, genome) in matrix.genomes.iter().enumerate().filter(|(_, genome)| genome.snake.is_some()) {
+            assert!(matrix.distance(index, leader(genome.specie)) < matrix.threshold);
+        }
+        assert_eq!(matrix.cluster().len(), matrix.len() - 1);
+    }
+
+    #[test]
+    fn close_genomes_are_joined_first() {
+        // two pairs of close genomes far away from each other
+        let distances = vec![
+            0.0, 0.1, 0.9, 0.8,
+            0.1, 0.0, 0.7, 0.9,
+            0.9, 0.7, 0.0, 0.2,
+            0.8, 0.9, 0.2, 0.0,
+        ];
+        let genomes = (0..4).map(|specie| GenomeLabel { specie, snake: None }).collect();
+        let matrix = DistanceMatrix { genomes, distances, threshold: 0.2 };
+        let merges = matrix.cluster();
+        assert_eq!(merges[0], Merge { left: 0, right: 1, distance: 0.1, size: 2 });
+        assert_eq!(merges[1], Merge { left: 2, right: 3, distance: 0.2, size: 2 });
+        // the average of all four distances between the pairs
+        assert_eq!(merges[2].size, 4);
+        assert!((merges[2].distance - 0.825).abs() < 1e-6);
+        assert_eq!(matrix.leaf_order(&merges), vec![0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use crate::core::RngResource;
+use crate::events::{event_channel, EventCapacity, EventReceiver};
+use crate::simulation::{EngineCommand, EngineEvent, EngineState, Simulation, SimulationConfig};
+
+// A simulation running on its own thread, driven through commands and watched through events without touching the ecs world
+pub struct SimulationHandle {
+    commands: Sender<EngineCommand>,
+    subscribers: Arc<Mutex<Vec<Sender<EngineEvent>>>>,
+    simulation: Option<JoinHandle<EngineEvent>>,
+    speed_limit: Option<f32>,
+    paused: bool,
+}
+
+impl SimulationHandle {
+    // Starts running right away as fast as possible, the same seed and config always give the same run
+    pub fn start(name: &str, config: SimulationConfig, seed: u64) -> Self {
+        let (commands, commands_receiver) = channel();
+        let (events_sender, events) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new(name.to_string(), events_sender, Some(Arc::new(Mutex::new(commands_receiver))), config);
+        simulation.insert_resource(RngResource::from_seed(seed));
+        simulation.insert_resource(EngineState::default());
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        let forwarded_to = Arc::clone(&subscribers);
+        // ends once the simulation is gone and with it the last events sender
+        thread::spawn(move || forward_events(events, forwarded_to));
+        let simulation = thread::spawn(move || simulation.run());
+        SimulationHandle { commands, subscribers, simulation: Some(simulation), speed_limit: None, paused: false }
+    }
+
+    // Every event sent from now on, events sent before subscribing are not replayed
+    pub fn subscribe(&self) -> Receiver<EngineEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.send(EngineCommand::SetSpeedLimit(Some(0.0)));
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.send(EngineCommand::SetSpeedLimit(self.speed_limit));
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Simulates exactly one more frame and pauses
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.send(EngineCommand::AdvanceOneFrame);
+    }
+
+    // Frames per second divided by 60, None runs as fast as possible, a paused simulation keeps the limit for resuming
+    pub fn set_speed_limit(&mut self, speed_limit: Option<f32>) {
+        self.speed_limit = speed_limit;
+        if !self.paused {
+            self.send(EngineCommand::SetSpeedLimit(speed_limit));
+        }
+    }
+
+    pub fn create_snakes(&self, amount: usize) {
+        self.send(EngineCommand::CreateSnakes(amount));
+    }
+
+    // The world size stays the one the simulation was started with
+    pub fn update_config(&self, config: SimulationConfig) {
+        self.send(EngineCommand::UpdateSimulationConfig(Box::new(config)));
+    }
+
+    // Answers come as events to the subscribers
+    pub fn send(&self, command: EngineCommand) {
+        // the simulation only stops through stop, so it is always there to receive
+        let _ = self.commands.send(command);
+    }
+
+    // Waits for the simulation to finish the current frame, the result is the SimulationFinished event
+    pub fn stop(mut self) -> Result<EngineEvent, String> {
+        self.send(EngineCommand::StopSimulation);
+        let simulation = self.simulation.take().ok_or("The simulation was already stopped")?;
+        simulation.join().map_err(|_| "The simulation thread panicked".to_string())
+    }
+}
+
+impl Drop for SimulationHandle {
+    fn drop(&mut self) {
+        if self.simulation.is_some() {
+            self.send(EngineCommand::StopSimulation);
+        }
+    }
+}
+
+// Subscribers that dropped their receiver are forgotten
+fn forward_events(events: EventReceiver, subscribers: Arc<Mutex<Vec<Sender<EngineEvent>>>>) {
+    for event in events.iter() {
+        subscribers.lock().unwrap().retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn next_snapshot_frame(events: &Receiver<EngineEvent>) -> u32 {
+        loop {
+            if let EngineEvent::Snapshot { snapshot } = events.recv_timeout(Duration::from_secs(10)).unwrap() {
+                return snapshot.frame;
+            }
+        }
+    }
+
+    #[test]
+    fn paused_simulation_moves_one_frame_per_step() {
+        let mut handle = SimulationHandle::start("Handle", SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() }, 4);
+        let events = handle.subscribe();
+        let other_events = handle.subscribe();
+        handle.create_snakes(5);
+        handle.pause();
+        handle.send(EngineCommand::TakeSnapshot);
+        let paused_at = next_snapshot_frame(&events);
+        handle.step();
+        // a repaint answers with FrameDrawn once the step was simulated
+        handle.send(EngineCommand::RepaintRequested);
+        while !matches!(events.recv_timeout(Duration::from_secs(10)).unwrap(), EngineEvent::FrameDrawn { .. }) {}
+        handle.send(EngineCommand::TakeSnapshot);
+        assert_eq!(next_snapshot_frame(&events), paused_at + 1);
+        assert!(handle.is_paused());
+        let Ok(EngineEvent::SimulationFinished { steps, .. }) = handle.stop() else {
+            panic!("the simulation did not finish");
+        };
+        assert_eq!(steps, paused_at + 1);
+        assert_eq!(next_snapshot_frame(&other_events), paused_at);
+        assert!(other_events.iter().any(|event| matches!(event, EngineEvent::SimulationFinished { .. })));
+    }
+
+    #[test]
+    fn paused_time_is_kept_apart_from_simulated_time() {
+        let mut handle = SimulationHandle::start("Clock", SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() }, 4);
+        handle.pause();
+        thread::sleep(Duration::from_millis(200));
+        let Ok(EngineEvent::SimulationFinished { paused, .. }) = handle.stop() else {
+            panic!("the simulation did not finish");
+        };
+        assert!(paused >= 100);
+    }
+}
@@ -0,0 +1,326 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use rusqlite::{Connection, OpenFlags, params, params_from_iter};
+pub use rusqlite::types::Value;
+use crate::simulation::{Simulation, SimulationEvent, SimulationEventKind, Stats};
+
+// frames written in one transaction, committing every frame makes long runs crawl
+const COMMIT_INTERVAL: u32 = 100;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS frames (
+        frame INTEGER PRIMARY KEY,
+        snakes INTEGER NOT NULL,
+        segments INTEGER NOT NULL,
+        food INTEGER NOT NULL,
+        species INTEGER NOT NULL,
+        oldest_snake INTEGER NOT NULL,
+        max_generation INTEGER NOT NULL,
+        infected INTEGER NOT NULL,
+        snake_energy REAL NOT NULL,
+        total_energy REAL NOT NULL,
+        plants REAL NOT NULL,
+        meat REAL NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        frame INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        entity INTEGER,
+        specie INTEGER,
+        detail TEXT,
+        age INTEGER,
+        generation INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS events_by_frame ON events (frame);
+    CREATE INDEX IF NOT EXISTS events_by_kind ON events (kind, frame)
+";
+
+// Column names and the rows of a query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rows {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(value) => Some(*value),
+        Value::Real(value) => Some(*value as i64),
+        _ => None,
+    }
+}
+
+pub fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(value) => Some(*value as f64),
+        Value::Real(value) => Some(*value),
+        _ => None,
+    }
+}
+
+// How the cli prints a value
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Integer(value) => value.to_string(),
+        Value::Real(value) => value.to_string(),
+        Value::Text(value) => value.clone(),
+        Value::Blob(value) => format!("<{} bytes>", value.len()),
+    }
+}
+
+fn sql_error(error: rusqlite::Error) -> String {
+    error.to_string()
+}
+
+// A row of the frames table, written whenever the stats were calculated
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameRecord {
+    pub frame: u32,
+    pub snakes: usize,
+    pub segments: usize,
+    pub food: usize,
+    pub species: usize,
+    pub oldest_snake: u32,
+    pub max_generation: u32,
+    pub infected: usize,
+    pub snake_energy: f32,
+    pub total_energy: f32,
+    pub plants: f32,
+    pub meat: f32,
+}
+
+// A row of the events table, kind is the name of the event and only the columns that event has are set
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventRecord {
+    pub frame: u32,
+    pub kind: String,
+    // bits of the entity, entities of dead snakes get reused with a new generation so the bits stay unique
+    pub entity: Option<u64>,
+    pub specie: Option<u32>,
+    // the cause of a death or the entity limit that was reached
+    pub detail: Option<String>,
+    pub age: Option<u32>,
+    pub generation: Option<u32>,
+}
+
+// Stats and lifecycle events of a run in a sqlite database, so any slice of a long run can be looked at afterwards with plain sql
+pub struct HistoryDb {
+    connection: Connection,
+    // frames written in the open transaction, None without one
+    uncommitted: Option<u32>,
+}
+
+impl HistoryDb {
+    // Opens or creates the database to write to, a run continued from a snapshot replaces the frames it repeats
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(sql_error)?;
+        connection.execute_batch(SCHEMA).map_err(sql_error)?;
+        Ok(HistoryDb { connection, uncommitted: None })
+    }
+
+    // Opens an existing database to query it, while a run may still be writing to it
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX).map_err(sql_error)?;
+        Ok(HistoryDb { connection, uncommitted: None })
+    }
+
+    // Writes the stats and events of every step of the simulation until it is dropped, the returned database may be flushed or queried in between
+    pub fn attach(self, simulation: &mut Simulation) -> Arc<Mutex<HistoryDb>> {
+        let history = Arc::new(Mutex::new(self));
+        let events = Arc::clone(&history);
+        simulation.hooks().on_event(move |_, event| {
+            if let Err(error) = events.lock().unwrap().record_event(event) {
+                warn!("Could not write the event to the history: {}", error);
+            }
+        });
+        let frames = Arc::clone(&history);
+        // stats are only calculated every few frames
+        let mut recorded = None;
+        simulation.hooks().on_step(move |world| {
+            let stats = world.resource::<Stats>();
+            if recorded == Some(stats.frame) {
+                return;
+            }
+            recorded = Some(stats.frame);
+            if let Err(error) = frames.lock().unwrap().record_stats(stats) {
+                warn!("Could not write frame {} to the history: {}", stats.frame, error);
+            }
+        });
+        history
+    }
+
+    pub fn record_stats(&mut self, stats: &Stats) -> Result<(), String> {
+        self.begin()?;
+        self.connection.prepare_cached("INSERT OR REPLACE INTO frames VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)").and_then(|mut statement| statement.execute(params![
+            stats.frame,
+            stats.total_snakes as i64,
+            stats.total_segments as i64,
+            stats.total_food as i64,
+            stats.species.species.len() as i64,
+            stats.oldest_snake,
+            stats.max_generation,
+            stats.infected_snakes as i64,
+            stats.total_snake_energy,
+            stats.total_energy,
+            stats.total_plants,
+            stats.total_meat,
+        ])).map_err(sql_error)?;
+        let uncommitted = self.uncommitted.unwrap_or(0) + 1;
+        self.uncommitted = Some(uncommitted);
+        if uncommitted >= COMMIT_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn record_event(&mut self, event: &SimulationEvent) -> Result<(), String> {
+        self.begin()?;
+        let entity = |entity: bevy_ecs::entity::Entity| Value::Integer(entity.to_bits() as i64);
+        let number = |number: u32| Value::Integer(number as i64);
+        let (kind, entity, specie, detail, age, generation) = match event.kind {
+            SimulationEventKind::SnakeBorn { snake, generation, .. } => ("SnakeBorn", entity(snake), Value::Null, Value::Null, Value::Null, number(generation)),
+            SimulationEventKind::SnakeDied { snake, cause, age, generation } => ("SnakeDied", entity(snake), Value::Null, Value::Text(format!("{:?}", cause)), number(age), number(generation)),
+            SimulationEventKind::SpecieCreated { specie } => ("SpecieCreated", Value::Null, number(specie), Value::Null, Value::Null, Value::Null),
+            SimulationEventKind::SpecieExtinct { specie } => ("SpecieExtinct", Value::Null, number(specie), Value::Null, Value::Null, Value::Null),
+            SimulationEventKind::LifespanRecord { snake, age } => ("LifespanRecord", entity(snake), Value::Null, Value::Null, number(age), Value::Null),
+            SimulationEventKind::EntityLimitReached { limit, .. } => ("EntityLimitReached", Value::Null, Value::Null, Value::Text(format!("{:?}", limit)), Value::Null, Value::Null),
+        };
+        let values = [number(event.frame), Value::Text(kind.to_string()), entity, specie, detail, age, generation];
+        self.connection.prepare_cached("INSERT INTO events VALUES (?, ?, ?, ?, ?, ?, ?)").and_then(|mut statement| statement.execute(params_from_iter(values))).map_err(sql_error)?;
+        Ok(())
+    }
+
+    // Commits what was written so far, readers only see committed frames
+    pub fn flush(&mut self) -> Result<(), String> {
+        if self.uncommitted.take().is_some() {
+            self.connection.execute_batch("COMMIT").map_err(sql_error)?;
+        }
+        Ok(())
+    }
+
+    // Frames from the first up to and including the last
+    pub fn frames(&self, first: u32, last: u32) -> Result<Vec<FrameRecord>, String> {
+        let rows = self.query("SELECT * FROM frames WHERE frame BETWEEN ? AND ? ORDER BY frame", &[Value::Integer(first as i64), Value::Integer(last as i64)])?;
+        Ok(rows.rows.iter().map(|row| {
+            let integer = |column: usize| as_i64(&row[column]).unwrap_or(0);
+            let real = |column: usize| as_f64(&row[column]).unwrap_or(0.0) as f32;
+            FrameRecord {
+                frame: integer(0) as u32,
+                snakes: integer(1) as usize,
+                segments: integer(2) as usize,
+                food: integer(3) as usize,
+                species: integer(4) as usize,
+                oldest_snake: integer(5) as u32,
+                max_generation: integer(6) as u32,
+                infected: integer(7) as usize,
+                snake_energy: real(8),
+                total_energy: real(9),
+                plants: real(10),
+                meat: real(11),
+            }
+        }).collect())
+    }
+
+    // Events from the first up to and including the last frame, only those of the given kind like "SnakeDied" when there is one
+    pub fn events(&self, first: u32, last: u32, kind: Option<&str>) -> Result<Vec<EventRecord>, String> {
+        let rows = self.query("SELECT * FROM events WHERE frame BETWEEN ? AND ? AND (? IS NULL OR kind = ?) ORDER BY frame, rowid", &[
+            Value::Integer(first as i64),
+            Value::Integer(last as i64),
+            kind.map_or(Value::Null, |kind| Value::Text(kind.to_string())),
+            kind.map_or(Value::Null, |kind| Value::Text(kind.to_string())),
+        ])?;
+        Ok(rows.rows.iter().map(|row| {
+            let integer = |column: usize| as_i64(&row[column]);
+            let text = |column: usize| match &row[column] {
+                Value::Text(text) => Some(text.clone()),
+                _ => None,
+            };
+            EventRecord {
+                frame: integer(0).unwrap_or(0) as u32,
+                kind: text(1).unwrap_or_default(),
+                entity: integer(2).map(|entity| entity as u64),
+                specie: integer(3).map(|specie| specie as u32),
+                detail: text(4),
+                age: integer(5).map(|age| age as u32),
+                generation: integer(6).map(|generation| generation as u32),
+            }
+        }).collect())
+    }
+
+    // Number of deaths of every cause in the frames, the most common first
+    pub fn deaths_by_cause(&self, first: u32, last: u32) -> Result<Vec<(String, usize)>, String> {
+        let rows = self.query("SELECT detail, count(*) AS deaths FROM events WHERE kind = 'SnakeDied' AND frame BETWEEN ? AND ? GROUP BY detail ORDER BY deaths DESC, detail", &[Value::Integer(first as i64), Value::Integer(last as i64)])?;
+        Ok(rows.rows.iter().map(|row| (to_text(&row[0]), as_i64(&row[1]).unwrap_or(0) as usize)).collect())
+    }
+
+    // Any sql, the ? in it are bound to the params in order
+    pub fn query(&self, sql: &str, params: &[Value]) -> Result<Rows, String> {
+        let mut statement = self.connection.prepare(sql).map_err(sql_error)?;
+        let columns: Vec<String> = statement.column_names().into_iter().map(String::from).collect();
+        let rows = statement.query_map(params_from_iter(params), |row| (0..columns.len()).map(|column| row.get(column)).collect())
+            .and_then(|rows| rows.collect::<Result<Vec<Vec<Value>>, _>>())
+            .map_err(sql_error)?;
+        Ok(Rows { columns, rows })
+    }
+
+    fn begin(&mut self) -> Result<(), String> {
+        if self.uncommitted.is_none() {
+            self.connection.execute_batch("BEGIN").map_err(sql_error)?;
+            self.uncommitted = Some(0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HistoryDb {
+    fn drop(&mut self) {
+        if let Err(error) = self.flush() {
+            warn!("Could not commit the history: {}", error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::RngResource;
+    use crate::events::{event_channel, EventCapacity};
+    use crate::simulation::{EngineState, SimulationConfig};
+    use super::*;
+
+    #[test]
+    fn runs_are_written_and_can_be_sliced_afterwards() {
+        let path = std::env::temp_dir().join(format!("hex_brains_history_{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = SimulationConfig { rows: 30, columns: 30, size_to_split: 2, starting_food: 300, food_per_step: 10, stats_interval: 10, ..SimulationConfig::default() };
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("History".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(7));
+        simulation.insert_resource(EngineState::default());
+        HistoryDb::create(&path).unwrap().attach(&mut simulation);
+        simulation.create_snakes(30);
+        for _ in 0..300 {
+            simulation.step();
+        }
+        let events = simulation.world_mut().resource_mut::<crate::simulation::SimulationEvents>().drain();
+        // dropping the simulation drops the hooks and commits the rest
+        drop(simulation);
+        let history = HistoryDb::open(&path).unwrap();
+        let frames = history.frames(0, u32::MAX).unwrap();
+        assert!(frames.len() >= 20);
+        assert!(frames.windows(2).all(|pair| pair[0].frame < pair[1].frame));
+        assert!(frames.iter().any(|frame| frame.snakes > 0));
+        let slice = history.frames(100, 199).unwrap();
+        assert!(!slice.is_empty() && slice.iter().all(|frame| (100..=199).contains(&frame.frame)));
+        let deaths = events.iter().filter(|event| matches!(event.kind, SimulationEventKind::SnakeDied { .. })).count();
+        assert!(deaths > 0);
+        assert_eq!(history.events(0, u32::MAX, Some("SnakeDied")).unwrap().len(), deaths);
+        assert_eq!(history.deaths_by_cause(0, u32::MAX).unwrap().iter().map(|(_, count)| count).sum::<usize>(), deaths);
+        assert_eq!(history.events(0, u32::MAX, None).unwrap().len(), events.len());
+        let born = history.query("SELECT count(*) FROM events WHERE kind = ?", &[Value::Text("SnakeBorn".to_string())]).unwrap();
+        assert_eq!(as_i64(&born.rows[0][0]).unwrap() as usize, events.iter().filter(|event| matches!(event.kind, SimulationEventKind::SnakeBorn { .. })).count());
+        drop(history);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
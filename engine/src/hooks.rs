@@ -0,0 +1,111 @@
+use bevy_ecs::prelude::*;
+use crate::simulation::{DeathCause, SimulationEvent, SimulationEventKind, SimulationEvents};
+
+type Hook = Box<dyn FnMut(&mut World, &SimulationEvent) + Send>;
+type StepHook = Box<dyn FnMut(&mut World) + Send>;
+
+// Callbacks of an embedder, called with the world after every step for the events of that step in the order they happened
+#[derive(Default)]
+pub struct Hooks {
+    hooks: Vec<Hook>,
+    step_hooks: Vec<StepHook>,
+}
+
+impl Hooks {
+    // Every event, the others are shortcuts for the common ones
+    pub fn on_event(&mut self, hook: impl FnMut(&mut World, &SimulationEvent) + Send + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    // Only snakes born from a split, snakes created from nothing are not born
+    pub fn on_snake_born(&mut self, mut hook: impl FnMut(&mut World, Entity) + Send + 'static) {
+        self.on_event(move |world, event| {
+            if let SimulationEventKind::SnakeBorn { snake, .. } = event.kind {
+                hook(world, snake);
+            }
+        });
+    }
+
+    // The snake is already gone from the world
+    pub fn on_snake_died(&mut self, mut hook: impl FnMut(&mut World, Entity, DeathCause) + Send + 'static) {
+        self.on_event(move |world, event| {
+            if let SimulationEventKind::SnakeDied { snake, cause, .. } = event.kind {
+                hook(world, snake, cause);
+            }
+        });
+    }
+
+    pub fn on_species_created(&mut self, mut hook: impl FnMut(&mut World, u32) + Send + 'static) {
+        self.on_event(move |world, event| {
+            if let SimulationEventKind::SpecieCreated { specie } = event.kind {
+                hook(world, specie);
+            }
+        });
+    }
+
+    // After every step, once the event hooks of that step ran
+    pub fn on_step(&mut self, hook: impl FnMut(&mut World) + Send + 'static) {
+        self.step_hooks.push(Box::new(hook));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty() && self.step_hooks.is_empty()
+    }
+
+    // Events that happened between two steps, like snakes killed by a command, are passed on after the next step
+    pub(crate) fn dispatch(&mut self, world: &mut World) {
+        let events = world.resource_mut::<SimulationEvents>().take_unhooked();
+        for event in &events {
+            for hook in &mut self.hooks {
+                hook(world, event);
+            }
+        }
+        for hook in &mut self.step_hooks {
+            hook(world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use crate::core::{RngResource, Snake};
+    use crate::events::{event_channel, EventCapacity};
+    use crate::simulation::{EngineState, Simulation, SimulationConfig};
+    use super::*;
+
+    #[test]
+    fn hooks_hear_about_births_and_deaths_and_may_change_the_world() {
+        let config = SimulationConfig { rows: 30, columns: 30, size_to_split: 2, starting_food: 300, food_per_step: 10, ..SimulationConfig::default() };
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Hooks".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(7));
+        simulation.insert_resource(EngineState::default());
+        let heard = Arc::new(Mutex::new((0, 0, 0)));
+        let born = Arc::clone(&heard);
+        // a newborn may already have died again in the step it was born in
+        simulation.hooks().on_snake_born(move |world, snake| {
+            born.lock().unwrap().0 += 1;
+            if let Some(mut snake) = world.get_mut::<Snake>(snake) {
+                snake.energy.energy += 100.0;
+            }
+        });
+        let died = Arc::clone(&heard);
+        simulation.hooks().on_snake_died(move |world, snake, _| {
+            died.lock().unwrap().1 += 1;
+            assert!(world.get::<Snake>(snake).is_none());
+        });
+        let created = Arc::clone(&heard);
+        simulation.hooks().on_species_created(move |_, _| created.lock().unwrap().2 += 1);
+        simulation.create_snakes(30);
+        for _ in 0..300 {
+            simulation.step();
+        }
+        let (born, died, created) = *heard.lock().unwrap();
+        assert!(born > 0 && died > 0 && created > 0);
+        // the hooks see the same events the gui gets
+        let events = simulation.world_mut().resource_mut::<SimulationEvents>().drain();
+        assert_eq!(events.iter().filter(|event| matches!(event.kind, SimulationEventKind::SnakeBorn { .. })).count(), born);
+        assert_eq!(events.iter().filter(|event| matches!(event.kind, SimulationEventKind::SnakeDied { .. })).count(), died);
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+use bevy_ecs::prelude::*;
+use crate::core::{Position, SegmentMap, Snake, Solid, SolidsMap};
+use crate::dna::SegmentType;
+use crate::simulation::SimulationConfig;
+use crate::time::SimTime;
+
+// Checks after every step that the maps still match the entities, so a desync fails where it happens instead of frames later
+// The checks always panic, stepping only calls them in debug builds with the invariants feature and tests call them directly
+pub fn check_invariants(world: &mut World) {
+    let (columns, rows) = {
+        let config = world.resource::<SimulationConfig>();
+        (config.columns as i32, config.rows as i32)
+    };
+    let frame = world.resource::<SimTime>().ticks;
+    for (entity, position) in world.query_filtered::<(Entity, &Position), Or<(With<SegmentType>, With<Snake>, With<Solid>)>>().iter(world) {
+        assert!((0..columns).contains(&position.x) && (0..rows).contains(&position.y), "{:?} at {:?} is outside of the {}x{} world (frame {})", entity, position, columns, rows, frame);
+    }
+    let segments: HashSet<(Entity, (i32, i32))> = world.query_filtered::<(Entity, &Position), With<SegmentType>>().iter(world).map(|(entity, position)| (entity, position.as_pair())).collect();
+    let segment_map = &world.resource::<SegmentMap>().map;
+    let mapped: HashSet<(Entity, (i32, i32))> = segment_map.map.iter().enumerate().flat_map(|(index, entities)| {
+        let position = ((index % segment_map.width) as i32, (index / segment_map.width) as i32);
+        entities.iter().map(move |entity| (*entity, position))
+    }).collect();
+    let missing: Vec<_> = segments.difference(&mapped).collect();
+    assert!(missing.is_empty(), "segments {:?} are missing from the segment map (frame {})", missing, frame);
+    let stale: Vec<_> = mapped.difference(&segments).collect();
+    assert!(stale.is_empty(), "the segment map holds {:?} where there are no such segments (frame {})", stale, frame);
+    let solids: HashSet<(i32, i32)> = world.query_filtered::<&Position, With<Solid>>().iter(world).map(Position::as_pair).collect();
+    for (position, solid) in world.resource::<SolidsMap>().map.iter() {
+        assert_eq!(*solid, solids.contains(&position.as_pair()), "the solids map disagrees with the solid entities at {:?} (frame {})", position, frame);
+    }
+    for (head, snake) in world.query::<(Entity, &Snake)>().iter(world) {
+        for segment in &snake.segments {
+            assert!(world.get::<Position>(*segment).is_some(), "segment {:?} of snake {:?} does not exist (frame {})", segment, head, frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{event_channel, EventCapacity};
+    use crate::core::RngResource;
+    use crate::simulation::{EngineState, Simulation, Stats};
+    use super::*;
+
+    fn crowded_world() -> Simulation {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Invariants".to_string(), engine_events, None, SimulationConfig { rows: 12, columns: 12, stats_interval: 1, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(5));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(30);
+        simulation
+    }
+
+    #[test]
+    fn collisions_in_a_crowded_world_keep_the_maps_in_sync() {
+        let mut simulation = crowded_world();
+        let mut collided = false;
+        for _ in 0..150 {
+            simulation.step();
+            check_invariants(simulation.world_mut());
+            collided |= simulation.world().resource::<Stats>().behavior.collision_rate > 0.0;
+        }
+        assert!(collided, "the world was not crowded enough for collisions");
+    }
+
+    #[test]
+    #[should_panic(expected = "the solids map disagrees")]
+    fn solids_without_an_entity_are_caught() {
+        let mut simulation = crowded_world();
+        simulation.step();
+        let world = simulation.world_mut();
+        let free = world.resource::<SolidsMap>().map.iter().find(|(_, solid)| !**solid).map(|(position, _)| position).unwrap();
+        world.resource_mut::<SolidsMap>().map.set(&free, true);
+        check_invariants(world);
+    }
+}
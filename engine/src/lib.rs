@@ -3,6 +3,39 @@ pub mod simulation_manager;
 pub mod core;
 pub mod neural;
 pub mod dna;
+pub mod experiments;
+pub mod genome_distances;
+pub mod arena;
+pub mod crowding;
+pub mod diagnostics;
+pub mod draw;
+pub mod events;
+pub mod energy_grid;
+pub mod lineage;
+pub mod snapshot;
+mod compact;
+pub mod walls;
+pub mod navigation;
+pub mod nests;
+pub mod obstacles;
+pub mod disease;
+pub mod minerals;
+pub mod migration;
+#[cfg(test)]
+mod mutation_properties;
+pub mod water;
+pub mod view;
+pub mod handle;
+#[cfg(feature = "history_db")]
+pub mod history_db;
+pub mod hooks;
+pub mod invariants;
+pub mod prelude;
+pub mod profiling;
+pub mod stats_worker;
+pub mod logging;
+pub mod regression;
+pub mod time;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
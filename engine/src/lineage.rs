@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use bevy_ecs::prelude::*;
+use crate::core::{JustBorn, Snake};
+use crate::dna::{MutationType, Trait};
+use crate::profile_system;
+use crate::simulation::SimulationEvents;
+
+// below this many records the lineage is never pruned
+const MIN_RECORDS_BEFORE_PRUNING: usize = 1_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MutationKind {
+    ConnectionFlip,
+    WeightPerturbation,
+    WeightReset,
+    Prune,
+    Dna(MutationType),
+    Trait(Trait),
+}
+
+#[derive(Debug, Clone)]
+pub struct AncestryRecord {
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub generation: u32,
+    pub birth_frame: u32,
+    pub specie: Option<u32>,
+    // mutations that happened when this snake split off its parent
+    pub mutations: Vec<MutationKind>,
+}
+
+// Birth records of every snake that is alive or has living descendants
+#[derive(Resource)]
+pub struct Lineage {
+    next_id: u64,
+    records: HashMap<u64, AncestryRecord>,
+    size_after_pruning: usize,
+}
+
+impl Default for Lineage {
+    fn default() -> Self {
+        // 0 marks snakes that were not registered yet
+        Lineage { next_id: 1, records: HashMap::new(), size_after_pruning: 0 }
+    }
+}
+
+impl Lineage {
+    fn register(&mut self, snake: &Snake, birth_frame: u32) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.records.insert(id, AncestryRecord { id, parent: snake.parent_id, generation: snake.generation, birth_frame, specie: snake.species, mutations: snake.birth_mutations.clone() });
+        id
+    }
+
+    // The snake itself first, followed by its parent and so on up to the oldest known ancestor
+    pub fn ancestors(&self, id: u64) -> Vec<AncestryRecord> {
+        let mut ancestors = vec![];
+        let mut next = Some(id);
+        while let Some(record) = next.and_then(|id| self.records.get(&id)) {
+            ancestors.push(record.clone());
+            next = record.parent;
+        }
+        ancestors
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    // Forgets every snake without living descendants
+    fn prune(&mut self, living: impl Iterator<Item = u64>) {
+        let mut kept = HashSet::new();
+        for id in living {
+            let mut next = Some(id);
+            while let Some(id) = next.filter(|id| kept.insert(*id)) {
+                next = self.records.get(&id).and_then(|record| record.parent);
+            }
+        }
+        self.records.retain(|id, _| kept.contains(id));
+        self.size_after_pruning = self.records.len();
+    }
+}
+
+pub fn record_lineage(new_borns: Query<Entity, Added<JustBorn>>, mut snakes: Query<&mut Snake>, mut lineage: ResMut<Lineage>, simulation_events: Res<SimulationEvents>) {
+    profile_system!();
+    for snake_id in &new_borns {
+        if let Ok(mut snake) = snakes.get_mut(snake_id) {
+            snake.id = lineage.register(&snake, simulation_events.frame);
+        }
+    }
+    if lineage.len() > MIN_RECORDS_BEFORE_PRUNING.max(2 * lineage.size_after_pruning) {
+        lineage.prune(snakes.iter().map(|snake| snake.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::test_snake;
+    use crate::simulation::MutationStats;
+    use super::*;
+
+    fn snake(parent_id: Option<u64>, generation: u32, mutations: Vec<MutationKind>) -> Snake {
+        let mut rng = StdRng::seed_from_u64(generation as u64);
+        let (_, _, mut snake, _, _) = test_snake((0, 0), &mut rng);
+        snake.parent_id = parent_id;
+        snake.generation = generation;
+        snake.birth_mutations = mutations;
+        snake
+    }
+
+    #[test]
+    fn mutation_stats_follow_offspring_from_birth_to_death() {
+        let mut stats = MutationStats::default();
+        let mutations = vec![MutationKind::WeightPerturbation, MutationKind::Dna(MutationType::AddGene)];
+        stats.record_birth(&mutations);
+        stats.record_birth(&[]);
+        let mut offspring = snake(Some(1), 1, mutations);
+        offspring.energy.food_eaten = 30.0;
+        stats.record_death(&offspring, 200);
+        stats.record_death(&snake(Some(1), 1, vec![]), 100);
+        stats.record_death(&snake(None, 0, vec![]), 1_000);
+        let perturbed = stats.operators[&MutationKind::WeightPerturbation];
+        assert_eq!((perturbed.fired, perturbed.deaths, perturbed.mean_lifespan(), perturbed.mean_food_eaten()), (1, 1, 200.0, 30.0));
+        assert_eq!(stats.operators[&MutationKind::Dna(MutationType::AddGene)], perturbed);
+        assert_eq!((stats.unmutated.fired, stats.unmutated.deaths, stats.unmutated.mean_lifespan()), (1, 1, 100.0));
+        assert!(!stats.operators.contains_key(&MutationKind::Prune));
+    }
+
+    #[test]
+    fn ancestors_follow_the_parents_and_survive_pruning() {
+        let mut lineage = Lineage::default();
+        let root = lineage.register(&snake(None, 0, vec![]), 0);
+        let child = lineage.register(&snake(Some(root), 1, vec![MutationKind::Dna(MutationType::ChangeJump)]), 10);
+        let dead_end = lineage.register(&snake(Some(root), 1, vec![]), 12);
+        let grandchild = lineage.register(&snake(Some(child), 2, vec![MutationKind::WeightReset, MutationKind::Prune]), 20);
+        let ancestors = lineage.ancestors(grandchild);
+        assert_eq!(ancestors.iter().map(|record| record.id).collect::<Vec<_>>(), vec![grandchild, child, root]);
+        assert_eq!(ancestors[1].mutations, vec![MutationKind::Dna(MutationType::ChangeJump)]);
+        lineage.prune([grandchild].into_iter());
+        assert_eq!(lineage.len(), 3);
+        assert!(lineage.ancestors(dead_end).is_empty());
+        assert_eq!(lineage.ancestors(grandchild).len(), 3);
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+// Levels the log can be switched to while a simulation runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug, LogLevel::Trace];
+
+    pub fn as_tracing(self) -> Level {
+        match self {
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+            LogLevel::Trace => Level::TRACE,
+        }
+    }
+}
+
+type LevelSetter = Box<dyn Fn(LogLevel) + Send + Sync>;
+
+// The engine only uses the tracing macros, the application owns the subscriber and knows how to change its level
+static LEVEL_SETTER: OnceLock<LevelSetter> = OnceLock::new();
+
+// Only the first setter is kept, returns false when there already was one
+pub fn install_level_setter(setter: impl Fn(LogLevel) + Send + Sync + 'static) -> bool {
+    LEVEL_SETTER.set(Box::new(setter)).is_ok()
+}
+
+pub fn set_log_level(level: LogLevel) -> Result<(), String> {
+    let setter = LEVEL_SETTER.get().ok_or_else(|| format!("Unable to switch the log to {:?}, the application can't change its log level", level))?;
+    setter(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use super::*;
+
+    #[test]
+    fn levels_are_changed_by_the_installed_setter() {
+        static CHANGES: Mutex<Vec<LogLevel>> = Mutex::new(Vec::new());
+        assert!(set_log_level(LogLevel::Debug).is_err());
+        assert!(install_level_setter(|level| CHANGES.lock().unwrap().push(level)));
+        assert!(!install_level_setter(|_| {}));
+        set_log_level(LogLevel::Trace).unwrap();
+        assert_eq!(*CHANGES.lock().unwrap(), vec![LogLevel::Trace]);
+        assert_eq!(LogLevel::Warn.as_tracing(), Level::WARN);
+    }
+}
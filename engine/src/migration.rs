@@ -0,0 +1,77 @@
+use toml::{Table, Value};
+use crate::simulation::SimulationConfig;
+
+// Version of the saved settings, bump it when a saved setting is renamed or changes its meaning and add a migration for the old version
+pub const CONFIG_VERSION: u32 = 1;
+
+type Migration = fn(&mut Table) -> Result<(), String>;
+
+// The migration at index n turns settings of version n into version n + 1, settings saved before they had a version are version 0
+const MIGRATIONS: [Migration; CONFIG_VERSION as usize] = [from_unversioned];
+
+// Nothing was renamed before the settings had a version, settings added since then get their defaults
+fn from_unversioned(_: &mut Table) -> Result<(), String> {
+    Ok(())
+}
+
+// Brings saved settings up to the current version, settings that are not known even after migrating are an error instead of being dropped silently
+pub fn migrate_config(table: &mut Table) -> Result<(), String> {
+    let version = match table.get("version") {
+        None => 0,
+        Some(Value::Integer(version)) if (0..=CONFIG_VERSION as i64).contains(version) => *version as u32,
+        Some(Value::Integer(version)) if *version > CONFIG_VERSION as i64 => return Err(format!("The settings were saved by a newer version of hex brains (settings version {}, this one reads up to {})", version, CONFIG_VERSION)),
+        Some(version) => return Err(format!("Invalid settings version {}", version)),
+    };
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(table)?;
+    }
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION as i64));
+    let defaults = Table::try_from(SimulationConfig::default()).map_err(|error| error.to_string())?;
+    let unknown = unknown_settings(table, &defaults, "");
+    if !unknown.is_empty() {
+        return Err(format!("Unknown settings: {}", unknown.join(", ")));
+    }
+    Ok(())
+}
+
+// Nested settings like the mutation ones are checked too, lists like the regions are not
+fn unknown_settings(table: &Table, known: &Table, prefix: &str) -> Vec<String> {
+    table.iter().flat_map(|(key, value)| match (value, known.get(key)) {
+        (_, None) => vec![format!("{}{}", prefix, key)],
+        (Value::Table(table), Some(Value::Table(known))) => unknown_settings(table, known, &format!("{}{}.", prefix, key)),
+        _ => vec![],
+    }).collect()
+}
+
+impl SimulationConfig {
+    // Settings saved by any earlier version load with defaults for everything added since
+    pub fn from_toml(content: &str) -> Result<SimulationConfig, String> {
+        let mut table: Table = content.parse().map_err(|error: toml::de::Error| error.to_string())?;
+        migrate_config(&mut table)?;
+        Value::Table(table).try_into().map_err(|error: toml::de::Error| error.to_string())
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|error| error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulation::CollisionPolicy;
+    use super::*;
+
+    #[test]
+    fn old_settings_are_migrated_and_mistakes_are_reported() {
+        let config = SimulationConfig { rows: 42, collision_policy: CollisionPolicy::Bounce, ..SimulationConfig::default() };
+        assert_eq!(SimulationConfig::from_toml(&config.to_toml().unwrap()).unwrap(), config);
+        // saved before the settings had a version and before most settings existed
+        let unversioned = SimulationConfig::from_toml("rows = 42\ncolumns = 50\n[mutation]\nweight_perturbation_range = 0.5\n").unwrap();
+        assert_eq!(unversioned.version, CONFIG_VERSION);
+        assert_eq!((unversioned.rows, unversioned.columns, unversioned.starting_snakes), (42, 50, SimulationConfig::default().starting_snakes));
+        assert_eq!(unversioned.mutation.weight_perturbation_range, 0.5);
+        assert_eq!(SimulationConfig::from_toml("rowz = 42\n[mutation]\nmistake = 1\n"), Err("Unknown settings: mutation.mistake, rowz".to_string()));
+        assert!(SimulationConfig::from_toml(&format!("version = {}\n", CONFIG_VERSION + 1)).unwrap_err().contains("newer version"));
+        assert!(SimulationConfig::from_toml("rows = \"many\"\n").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use bevy_ecs::prelude::*;
+use crate::core::{Direction, find_free_position, Map2d, position_at_direction, Position, random_position, RngResource, RngStream, SegmentMap, Snake, SolidsMap};
+use crate::profile_system;
+use crate::simulation::{SimulationConfig, Stats};
+use crate::time::SimTime;
+
+// Minerals lying on every hex, a second resource next to food that is needed to grow solid segments
+#[derive(Resource)]
+pub struct MineralMap {
+    pub map: Map2d<f32>,
+}
+
+// Deposits show up on free hexes like plants do, a new deposit tops the hex up instead of piling on it
+pub fn create_minerals(mut mineral_map: ResMut<MineralMap>, solids_map: Res<SolidsMap>, segment_map: Res<SegmentMap>, rng: Res<RngResource>, time: Res<SimTime>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if !config.minerals_enabled {
+        return;
+    }
+    let rng = &mut rng.stream(RngStream::Minerals, &time);
+    for _ in 0..config.minerals_per_step {
+        let Some(position) = find_free_position(rng, &solids_map, &segment_map, |rng| random_position(rng, &config)) else {
+            continue;
+        };
+        let deposit = mineral_map.map.get_mut(&position);
+        stats.minerals_deposited += (config.mineral_deposit - *deposit).max(0.0);
+        *deposit = deposit.max(config.mineral_deposit);
+    }
+}
+
+// Heads pick up the minerals on their hex, as much as they can still carry
+pub fn collect_minerals(mut snakes: Query<(&mut Snake, &Position)>, mut mineral_map: ResMut<MineralMap>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if !config.minerals_enabled {
+        return;
+    }
+    for (mut snake, position) in &mut snakes {
+        let deposit = mineral_map.map.get_mut(position);
+        let taken = deposit.min(config.max_minerals_carried - snake.energy.minerals);
+        if taken > 0.0 {
+            *deposit -= taken;
+            snake.energy.minerals += taken;
+            stats.minerals_collected += taken;
+        }
+    }
+    stats.total_minerals = mineral_map.map.map.iter().sum();
+}
+
+// Solid segments are only grown from carried minerals, growth waits until the snake has enough
+pub fn can_build_solid(snake: &Snake, config: &SimulationConfig) -> bool {
+    !config.minerals_enabled || snake.energy.minerals >= config.solid_segment_minerals
+}
+
+// Closer deposits give a stronger signal, walls don't block the view just like for plants
+pub fn see_minerals(direction: &Direction, position: &Position, mineral_map: &MineralMap, config: &SimulationConfig) -> f32 {
+    if !config.minerals_enabled {
+        return 0.0;
+    }
+    let range = config.mineral_vision_range;
+    let mut current_vision_position = position.clone();
+    for current_range in 0..range {
+        current_vision_position = position_at_direction(direction, &current_vision_position, config);
+        if *mineral_map.map.get(&current_vision_position) > 0.0 {
+            return (range - current_range) as f32 / range as f32;
+        }
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::system::RunSystemOnce;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::{Map3d, spawn_test_snake};
+    use super::*;
+
+    #[test]
+    fn minerals_are_collected_up_to_the_limit_and_pay_for_solids() {
+        let config = SimulationConfig { rows: 10, columns: 10, minerals_enabled: true, minerals_per_step: 3, mineral_deposit: 4.0, max_minerals_carried: 6.0, solid_segment_minerals: 5.0, mineral_vision_range: 4, ..SimulationConfig::default() };
+        let mut world = World::new();
+        world.insert_resource(config.clone());
+        world.insert_resource(SimTime::default());
+        world.insert_resource(Stats::default());
+        world.insert_resource(RngResource::from_seed(1));
+        world.insert_resource(SolidsMap { map: Map2d::new(10, 10, false) });
+        world.insert_resource(SegmentMap { map: Map3d::new(10, 10) });
+        world.insert_resource(MineralMap { map: Map2d::new(10, 10, 0.0) });
+        world.run_system_once(create_minerals);
+        assert!(world.resource::<MineralMap>().map.map.iter().all(|deposit| *deposit == 0.0 || *deposit == 4.0));
+        assert!(world.resource::<Stats>().minerals_deposited > 0.0);
+        let mut rng = StdRng::seed_from_u64(1);
+        let miner = spawn_test_snake(&mut world, (2, 2), &mut rng);
+        world.resource_mut::<MineralMap>().map.map.fill(0.0);
+        world.resource_mut::<MineralMap>().map.set(&Position { x: 2, y: 2 }, 4.0);
+        world.resource_mut::<MineralMap>().map.set(&Position { x: 4, y: 2 }, 4.0);
+        assert_eq!(see_minerals(&Direction::East, &Position { x: 2, y: 2 }, world.resource::<MineralMap>(), &config), 0.75);
+        world.run_system_once(collect_minerals);
+        assert_eq!(world.get::<Snake>(miner).unwrap().energy.minerals, 4.0);
+        assert!(!can_build_solid(world.get::<Snake>(miner).unwrap(), &config));
+        world.get_mut::<Position>(miner).unwrap().x = 4;
+        world.run_system_once(collect_minerals);
+        assert_eq!(world.get::<Snake>(miner).unwrap().energy.minerals, 6.0);
+        assert_eq!(*world.resource::<MineralMap>().map.get(&Position { x: 4, y: 2 }), 2.0);
+        assert!(can_build_solid(world.get::<Snake>(miner).unwrap(), &config));
+        assert!(can_build_solid(world.get::<Snake>(miner).unwrap(), &SimulationConfig::default()));
+    }
+}
@@ -0,0 +1,202 @@
+// Property tests for the mutation operators: random genomes go through random sequences of mutations and have to stay valid after every single one
+// proptest shrinks a failing case to the smallest genome and the shortest sequence of mutations that still breaks it
+use std::collections::HashSet;
+use proptest::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::arena::Genome;
+use crate::core::{ActionSet, SENSOR_NAMES};
+use crate::dna::{Dna, Traits};
+use crate::neural::{Activation, InnovationTracker, NeuralNetwork, SensorInput};
+use crate::simulation::{MutationConfig, SimulationConfig};
+
+const CASES: u32 = 100;
+const MAX_MUTATIONS: usize = 40;
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Flip,
+    Perturb,
+    Reset,
+    Prune,
+    // the activation of the new node
+    Split(usize),
+    Dna,
+    Trait,
+    // everything a newborn gets at once, with the chances of the settings
+    Birth,
+}
+
+// Also the extremes of always and never
+fn chance() -> impl Strategy<Value = f64> {
+    prop_oneof![Just(0.0), Just(1.0), 0.0..1.0]
+}
+
+fn mutation_config() -> impl Strategy<Value = MutationConfig> {
+    (
+        (chance(), chance(), chance(), chance(), chance(), chance()),
+        (0.0f32..5.0, 0.0f32..5.0, 0.0f32..0.9),
+        (any::<bool>(), any::<bool>(), any::<bool>(), any::<bool>()),
+    ).prop_map(|((flip, perturb, reset, prune, dna, traits), (perturbation_range, reset_range, trait_range), (perturb_disabled, perturb_reset, fins, sense_segments))| MutationConfig {
+        connection_flip_chance: flip,
+        weight_perturbation_chance: perturb,
+        weight_reset_chance: reset,
+        prune_chance: prune,
+        dna_mutation_chance: dna,
+        trait_mutation_chance: traits,
+        weight_perturbation_range: perturbation_range,
+        weight_reset_range: reset_range,
+        trait_mutation_range: trait_range,
+        perturb_disabled_connections: perturb_disabled,
+        perturb_reset_connections: perturb_reset,
+        fins_enabled: fins,
+        sense_segments_enabled: sense_segments,
+        ..MutationConfig::default()
+    })
+}
+
+// The shape of a genome, the seed only picks its weights and genes
+#[derive(Debug, Clone)]
+struct GenomeShape {
+    connection_probability: f32,
+    // connections split into hidden nodes, by index and activation
+    splits: Vec<(usize, usize)>,
+    genes: usize,
+    seed: u64,
+}
+
+fn genome_shape() -> impl Strategy<Value = GenomeShape> {
+    (0.0f32..1.0, prop::collection::vec((any::<usize>(), 0..3usize), 0..5), 1..10usize, any::<u64>())
+        .prop_map(|(connection_probability, splits, genes, seed)| GenomeShape { connection_probability, splits, genes, seed })
+}
+
+fn operator() -> impl Strategy<Value = Operator> {
+    prop_oneof![
+        Just(Operator::Flip),
+        Just(Operator::Perturb),
+        Just(Operator::Reset),
+        Just(Operator::Prune),
+        (0..3usize).prop_map(Operator::Split),
+        Just(Operator::Dna),
+        Just(Operator::Trait),
+        Just(Operator::Birth),
+    ]
+}
+
+// Every mutation gets its own seed, so dropping one while shrinking leaves the others as they were
+fn mutations() -> impl Strategy<Value = Vec<(Operator, u64)>> {
+    prop::collection::vec((operator(), any::<u64>()), 0..=MAX_MUTATIONS)
+}
+
+// A brain of the size snakes get, the innovation tracker is shared with the splits that follow
+fn build_genome(shape: &GenomeShape, mutation: &MutationConfig, innovation_tracker: &mut InnovationTracker) -> Genome {
+    let mut rng = StdRng::seed_from_u64(shape.seed);
+    let outputs = ActionSet::from_config(&SimulationConfig::default()).outputs();
+    let mut network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), outputs, shape.connection_probability, innovation_tracker, &mut rng);
+    for (connection, activation) in &shape.splits {
+        split_connection(&mut network, *connection, *activation, innovation_tracker);
+    }
+    Genome { network, dna: Dna::random(shape.genes, mutation, &mut rng) }
+}
+
+fn split_connection(network: &mut NeuralNetwork, connection: usize, activation: usize, innovation_tracker: &mut InnovationTracker) {
+    if network.connections().is_empty() {
+        return;
+    }
+    // None is no activation a node can be run with
+    let activation = match activation {
+        0 => Activation::Sigmoid,
+        1 => Activation::Relu,
+        _ => Activation::Tanh,
+    };
+    network.split_connection(connection % network.connections().len(), activation, innovation_tracker);
+}
+
+fn apply(operator: Operator, seed: u64, genome: &mut Genome, mutation: &MutationConfig, innovation_tracker: &mut InnovationTracker) {
+    let rng = &mut StdRng::seed_from_u64(seed);
+    match operator {
+        Operator::Flip => genome.network.flip_random_connection(rng),
+        Operator::Perturb => genome.network.mutate_perturb_random_connection_weight(mutation.weight_perturbation_range, mutation.perturb_disabled_connections, rng),
+        Operator::Reset => genome.network.mutate_reset_random_connection_weight(mutation.weight_reset_range, mutation.perturb_reset_connections, rng),
+        Operator::Prune => genome.network.prune(),
+        Operator::Split(activation) => split_connection(&mut genome.network, seed as usize, activation, innovation_tracker),
+        Operator::Dna => {
+            genome.dna.mutate(mutation, rng);
+        }
+        Operator::Trait => {
+            genome.dna.mutate_traits(mutation, rng);
+        }
+        Operator::Birth => *genome = genome.mutate(mutation, rng).0,
+    }
+}
+
+// Everything a genome needs to be run by a snake
+fn check(genome: &Genome, mutation: &MutationConfig, inputs: &[SensorInput]) -> Result<(), String> {
+    let nodes = genome.network.get_nodes().len();
+    if let Some(connection) = genome.network.connections().iter().find(|connection| connection.in_node >= nodes || connection.out_node >= nodes) {
+        return Err(format!("connection {:?} points past the {} nodes", connection, nodes));
+    }
+    let mut innovations = HashSet::new();
+    if let Some(connection) = genome.network.connections().iter().find(|connection| !innovations.insert(connection.innovation_number)) {
+        return Err(format!("innovation number {} is used twice", connection.innovation_number));
+    }
+    if genome.network.connections().iter().any(|connection| !connection.weight.is_finite()) {
+        return Err("a weight is not finite".to_string());
+    }
+    let dna = &genome.dna;
+    if dna.genes.is_empty() || dna.current_gene >= dna.genes.len() || dna.genes.iter().any(|gene| gene.jump >= dna.genes.len()) {
+        return Err(format!("dna is empty or jumps outside of its {} genes", dna.genes.len()));
+    }
+    for which in Traits::ALL {
+        let (min, max) = mutation.trait_range(which);
+        let value = *genome.dna.traits.clone().get_mut(which);
+        // traits start at 1 no matter the range and only get clamped once they mutate
+        if !(value == 1.0 || (min..=max).contains(&value)) {
+            return Err(format!("{:?} is {} outside of {}..={}", which, value, min, max));
+        }
+    }
+    let outputs = genome.network.run(inputs.to_vec());
+    let expected = ActionSet::from_config(&SimulationConfig::default()).outputs();
+    if outputs.len() != expected || outputs.iter().any(|output| !output.is_finite()) {
+        return Err(format!("run gave {:?} for {} outputs", outputs, expected));
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn genomes_stay_valid_after_any_sequence_of_mutations(
+        mutation in mutation_config(),
+        shape in genome_shape(),
+        mutations in mutations(),
+        inputs in prop::collection::vec(-10.0f32..10.0, SENSOR_NAMES.len()),
+    ) {
+        let inputs: Vec<SensorInput> = inputs.into_iter().enumerate().map(|(index, value)| SensorInput { value, index }).collect();
+        let mut innovation_tracker = InnovationTracker::new();
+        let mut genome = build_genome(&shape, &mutation, &mut innovation_tracker);
+        if let Err(error) = check(&genome, &mutation, &inputs) {
+            return Err(TestCaseError::fail(format!("the random genome is invalid: {}", error)));
+        }
+        for (step, (operator, seed)) in mutations.iter().enumerate() {
+            apply(*operator, *seed, &mut genome, &mutation, &mut innovation_tracker);
+            if let Err(error) = check(&genome, &mutation, &inputs) {
+                return Err(TestCaseError::fail(format!("{} after mutation {} of {:?}", error, step, mutations)));
+            }
+        }
+        // saving is slow, so only the end result has to load again
+        if let Err(error) = Genome::from_toml(&genome.to_toml()) {
+            return Err(TestCaseError::fail(format!("the mutated genome does not load again: {}", error)));
+        }
+    }
+
+    #[test]
+    fn mutated_children_never_change_their_parent(mutation in mutation_config(), shape in genome_shape(), seed in any::<u64>()) {
+        let parent = build_genome(&shape, &mutation, &mut InnovationTracker::new());
+        let before = parent.to_toml();
+        let (child, _) = parent.mutate(&mutation, &mut StdRng::seed_from_u64(seed));
+        prop_assert_eq!(parent.to_toml(), before);
+        prop_assert!(child.network.get_nodes().len() <= parent.network.get_nodes().len(), "mutations at birth never add nodes");
+    }
+}
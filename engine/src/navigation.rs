@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::fmt;
+use crate::events::{event_channel, EventCapacity};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use crate::arena::Genome;
+use crate::core::{ActionSet, Direction, Map2d, Position, position_at_direction, RngResource, Snake, SENSOR_NAMES};
+use crate::dna::Dna;
+use crate::neural::{InnovationTracker, NeuralNetwork};
+use crate::simulation::{EngineState, FoodKind, Simulation, SimulationConfig, WallLayout};
+use crate::walls::{perimeter_positions, wall_positions};
+
+// A single snake has to find one piece of food at the far end of a maze, every generation the best of a few mutated children becomes the next parent
+// Counting the generations until the food is found compares mutation settings on a task that always has the same answer
+pub struct NavigationBenchmark {
+    // the size of the world and the mutation settings, walls and food are laid out by the benchmark
+    pub config: SimulationConfig,
+    pub seed: u64,
+    pub generations: u32,
+    // children tried in every generation, the parent is tried again along with them
+    pub candidates: usize,
+    // steps every candidate gets to reach the food
+    pub steps: u32,
+}
+
+pub struct NavigationReport {
+    // hexes on the shortest way from the start to the food
+    pub shortest_path: u32,
+    pub solved_at: Option<u32>,
+    // hexes the best candidate of every generation was still away from the food
+    pub best_distances: Vec<u32>,
+}
+
+// How close a candidate got, a candidate that reached the food is 0 hexes away and faster ones are better
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Attempt {
+    distance: u32,
+    steps: u32,
+}
+
+impl NavigationBenchmark {
+    fn world_config(&self) -> SimulationConfig {
+        SimulationConfig {
+            wall_layout: WallLayout::Maze,
+            wall_seed: self.seed,
+            perimeter_walls: true,
+            starting_snakes: 0,
+            starting_food: 0,
+            food_per_step: 0,
+            ..self.config.clone()
+        }
+    }
+
+    // Starts in the first cell of the maze, the food lies on the free hex farthest away from there
+    fn layout(&self) -> Result<(Position, Position, Map2d<Option<u32>>), String> {
+        let config = self.world_config();
+        let mut solid = Map2d::new(config.columns, config.rows, false);
+        for wall in wall_positions(config.wall_layout, config.columns, config.rows, config.wall_seed).iter().chain(perimeter_positions(config.columns, config.rows).iter()) {
+            solid.set(wall, true);
+        }
+        let start = solid.iter().find(|(position, solid)| !**solid && position.x > 1 && position.y > 1).map(|(position, _)| position).ok_or("The maze has no free hexes")?;
+        let from_start = path_lengths(&solid, &start, &config);
+        let goal = from_start.iter().filter_map(|(position, length)| length.map(|length| (length, position))).max_by_key(|(length, position)| (*length, -position.x, -position.y)).map(|(_, position)| position).filter(|goal| goal.as_pair() != start.as_pair()).ok_or("The start is walled in")?;
+        let to_goal = path_lengths(&solid, &goal, &config);
+        Ok((start, goal, to_goal))
+    }
+
+    pub fn run(&self) -> Result<NavigationReport, String> {
+        let (start, goal, to_goal) = self.layout()?;
+        let shortest_path = (*to_goal.get(&start)).ok_or("The food can't be reached")?;
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let outputs = ActionSet::from_config(&self.config).outputs();
+        let network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), outputs, 0.1, &mut InnovationTracker::new(), &mut rng);
+        let mut parent = Genome { network, dna: Dna::random(8, &self.config.mutation, &mut rng) };
+        let mut report = NavigationReport { shortest_path, solved_at: None, best_distances: vec![] };
+        for generation in 0..self.generations {
+            let children = (0..self.candidates).map(|_| parent.mutate(&self.config.mutation, &mut rng).0);
+            let candidates: Vec<Genome> = std::iter::once(parent.clone()).chain(children).collect();
+            // every candidate runs with the same seed, so the parent does exactly as well as in the generation before
+            let attempts: Vec<Attempt> = candidates.par_iter()
+                .map(|genome| self.attempt(genome, &start, &goal, &to_goal))
+                .collect();
+            // the parent comes first, so it is only replaced by a child that does better
+            let (best, attempt) = attempts.iter().enumerate().min_by_key(|(_, attempt)| **attempt).unwrap();
+            report.best_distances.push(attempt.distance);
+            parent = candidates[best].clone();
+            if attempt.distance == 0 {
+                report.solved_at = Some(generation + 1);
+                break;
+            }
+        }
+        Ok(report)
+    }
+
+    fn attempt(&self, genome: &Genome, start: &Position, goal: &Position, to_goal: &Map2d<Option<u32>>) -> Attempt {
+        // nothing is sent while stepping manually, so the receiver can be dropped right away
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Navigation".to_string(), engine_events, None, self.world_config());
+        simulation.insert_resource(RngResource::from_seed(self.seed));
+        simulation.insert_resource(EngineState::default());
+        simulation.add_food_at(goal.x as usize, goal.y as usize, FoodKind::Plant);
+        let mut best = Attempt { distance: to_goal.get(start).unwrap_or(u32::MAX), steps: self.steps };
+        let Some(snake) = simulation.create_snake_at((start.x as usize, start.y as usize), Some(genome.clone())) else {
+            return best;
+        };
+        for step in 1..=self.steps {
+            simulation.step();
+            let world = simulation.world();
+            let (Some(head), Some(state)) = (world.get::<Position>(snake), world.get::<Snake>(snake)) else {
+                break;
+            };
+            if state.energy.food_eaten > 0.0 {
+                return Attempt { distance: 0, steps: step };
+            }
+            best.distance = best.distance.min(to_goal.get(head).unwrap_or(u32::MAX));
+        }
+        best
+    }
+}
+
+// Length of the shortest way from the given hex to every other one, None where walls are in the way
+fn path_lengths(solid: &Map2d<bool>, from: &Position, config: &SimulationConfig) -> Map2d<Option<u32>> {
+    let mut lengths = Map2d::new(solid.width, solid.height, None);
+    lengths.set(from, Some(0));
+    let mut queue = VecDeque::from([(from.clone(), 0)]);
+    while let Some((position, length)) = queue.pop_front() {
+        for direction in [Direction::NorthEast, Direction::East, Direction::SouthEast, Direction::SouthWest, Direction::West, Direction::NorthWest] {
+            let next = position_at_direction(&direction, &position, config);
+            if !*solid.get(&next) && lengths.get(&next).is_none() {
+                lengths.set(&next, Some(length + 1));
+                queue.push_back((next, length + 1));
+            }
+        }
+    }
+    lengths
+}
+
+impl fmt::Display for NavigationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "shortest path: {} hexes", self.shortest_path)?;
+        for (generation, distance) in self.best_distances.iter().enumerate() {
+            writeln!(f, "generation {:>4}: {:>4} hexes away", generation + 1, distance)?;
+        }
+        match self.solved_at {
+            Some(generation) => writeln!(f, "solved after {} generations", generation),
+            None => writeln!(f, "not solved after {} generations", self.best_distances.len()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_maze_is_solvable_and_runs_are_reproducible() {
+        let benchmark = NavigationBenchmark { config: SimulationConfig { rows: 18, columns: 18, ..SimulationConfig::default() }, seed: 3, generations: 3, candidates: 3, steps: 40 };
+        let (start, goal, to_goal) = benchmark.layout().unwrap();
+        assert_eq!(to_goal.get(&goal), &Some(0));
+        // the farthest hex of a maze is never just around the corner
+        assert!(to_goal.get(&start).unwrap() > 10);
+        let report = benchmark.run().unwrap();
+        assert_eq!(Some(report.shortest_path), *to_goal.get(&start));
+        assert!(!report.best_distances.is_empty() && report.best_distances.len() <= 3);
+        // the parent is tried again, so the best candidate never gets worse
+        assert!(report.best_distances.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(benchmark.run().unwrap().best_distances, report.best_distances);
+    }
+}
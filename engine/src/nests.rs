@@ -0,0 +1,116 @@
+use bevy_ecs::prelude::*;
+use crate::core::{Decision, Position, Snake};
+use crate::profile_system;
+use crate::simulation::{SimulationConfig, Stats};
+use crate::time::SimTime;
+
+// Built by a snake where its head is, feeds the snakes of the builder's specie that stay close to it
+#[derive(Component, Debug, Clone)]
+pub struct Nest {
+    pub specie: Option<u32>,
+    pub built_at: u64,
+}
+
+// Hexes in a square around the nest, the world wraps so the square does too
+fn within_radius(a: &Position, b: &Position, radius: u32, config: &SimulationConfig) -> bool {
+    let wrapped = |delta: i32, size: usize| {
+        let delta = delta.rem_euclid(size as i32);
+        delta.min(size as i32 - delta)
+    };
+    wrapped(a.x - b.x, config.columns) <= radius as i32 && wrapped(a.y - b.y, config.rows) <= radius as i32
+}
+
+// Nests can't be built within the radius of another one, the snake keeps its energy when it tries
+pub fn build_nests(mut commands: Commands, mut snakes: Query<(&mut Snake, &Position)>, nests: Query<&Position, With<Nest>>, time: Res<SimTime>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    if !config.nests_enabled {
+        return;
+    }
+    let mut nest_positions: Vec<Position> = nests.iter().cloned().collect();
+    for (mut snake, position) in &mut snakes {
+        if snake.decision != Decision::BuildNest || snake.energy.energy < config.nest_cost {
+            continue;
+        }
+        if nest_positions.iter().any(|nest| within_radius(nest, position, config.nest_radius, &config)) {
+            continue;
+        }
+        snake.energy.energy -= config.nest_cost;
+        commands.spawn((position.clone(), Nest { specie: snake.species, built_at: time.ticks }));
+        nest_positions.push(position.clone());
+        stats.nests_built += 1;
+    }
+}
+
+// Every snake near a nest of its specie gets fed once per frame, no matter how many nests are around
+pub fn feed_from_nests(mut commands: Commands, nests: Query<(Entity, &Nest, &Position)>, mut snakes: Query<(&mut Snake, &Position)>, time: Res<SimTime>, mut stats: ResMut<Stats>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let mut alive = vec![];
+    for (nest_id, nest, position) in &nests {
+        if time.ticks.saturating_sub(nest.built_at) >= config.nest_lifetime as u64 {
+            commands.entity(nest_id).despawn();
+        } else {
+            alive.push((nest.specie, position));
+        }
+    }
+    stats.total_nests = alive.len();
+    if !config.nests_enabled || alive.is_empty() {
+        return;
+    }
+    for (mut snake, position) in &mut snakes {
+        let Some(specie) = snake.species else {
+            continue;
+        };
+        let missing = snake.metabolism.max_energy - snake.energy.energy;
+        if missing <= 0.0 || !alive.iter().any(|(nest_specie, nest)| *nest_specie == Some(specie) && within_radius(nest, position, config.nest_radius, &config)) {
+            continue;
+        }
+        let fed = config.nest_feeding.min(missing);
+        snake.energy.energy += fed;
+        stats.nest_energy_given += fed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs::system::RunSystemOnce;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::spawn_test_snake;
+    use super::*;
+
+    #[test]
+    fn nests_are_built_apart_and_feed_their_specie() {
+        let config = SimulationConfig { rows: 20, columns: 20, nests_enabled: true, nest_cost: 30.0, nest_radius: 2, nest_feeding: 5.0, nest_lifetime: 100, ..SimulationConfig::default() };
+        let mut world = World::new();
+        world.insert_resource(config);
+        world.insert_resource(SimTime::default());
+        world.insert_resource(Stats::default());
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut spawn = |world: &mut World, position: (i32, i32), specie: u32| {
+            let head = spawn_test_snake(world, position, &mut rng);
+            let mut snake = world.get_mut::<Snake>(head).unwrap();
+            snake.species = Some(specie);
+            snake.decision = Decision::BuildNest;
+            snake.energy.energy = 100.0;
+            head
+        };
+        let builder = spawn(&mut world, (5, 5), 1);
+        world.run_system_once(build_nests);
+        // too close to the first nest
+        let neighbour = spawn(&mut world, (6, 4), 1);
+        let far_away = spawn(&mut world, (15, 5), 1);
+        world.run_system_once(build_nests);
+        assert_eq!(world.resource::<Stats>().nests_built, 2);
+        assert_eq!(world.get::<Snake>(far_away).unwrap().energy.energy, 70.0);
+        assert_eq!(world.get::<Snake>(builder).unwrap().energy.energy, 70.0);
+        assert_eq!(world.get::<Snake>(neighbour).unwrap().energy.energy, 100.0);
+        let stranger = spawn(&mut world, (6, 6), 2);
+        world.get_mut::<Snake>(stranger).unwrap().energy.energy = 50.0;
+        world.run_system_once(feed_from_nests);
+        assert_eq!(world.get::<Snake>(builder).unwrap().energy.energy, 75.0);
+        assert_eq!(world.get::<Snake>(stranger).unwrap().energy.energy, 50.0);
+        world.resource_mut::<SimTime>().ticks = 100;
+        world.run_system_once(feed_from_nests);
+        assert_eq!(world.query::<&Nest>().iter(&world).count(), 0);
+    }
+}
@@ -1,8 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::OnceLock;
 use bevy_ecs::prelude::Resource;
 use rand::Rng;
-use rayon::join;
-use tracing::{debug, info};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use crate::core::{OUTPUT_NAMES, SENSOR_NAMES};
 
 // Define a trait that all sensor inputs will implement.
 #[derive(Debug, Clone)]
@@ -34,9 +39,19 @@ impl InnovationTracker {
             new_innovation
         })
     }
+
+    // Pruning renumbers hidden nodes, so a pair of indices may already stand for a different connection of the network
+    fn get_unused_innovation_number(&mut self, in_node: usize, out_node: usize, connections: &[ConnectionGene]) -> usize {
+        let innovation = self.get_innovation_number(in_node, out_node);
+        if !connections.iter().any(|connection| connection.innovation_number == innovation) {
+            return innovation;
+        }
+        self.current_innovation += 1;
+        self.current_innovation - 1
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ConnectionGene {
     pub in_node: usize,
     pub out_node: usize,
@@ -45,14 +60,14 @@ pub struct ConnectionGene {
     pub innovation_number: InnovationNumber,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum NodeType {
     Input,
     Hidden,
     Output,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Activation {
     Sigmoid,
     Relu,
@@ -71,7 +86,7 @@ impl Activation {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeGene {
     pub node_type: NodeType,
     activation: Activation,
@@ -87,11 +102,81 @@ impl NodeGene {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NetworkSize {
+    pub nodes: usize,
+    pub connections: usize,
+}
+
+// Order the nodes get their values in and the enabled connections feeding each of them, so a run doesn't sort the network again
+#[derive(Clone, Debug, Default)]
+struct EvaluationPlan {
+    // hidden and output nodes with the range of their sources, sources come before the nodes they feed and nodes in a loop follow in index order
+    steps: Vec<(usize, Range<usize>)>,
+    // source node and weight of every enabled connection, grouped by the node they feed in the order of the connections
+    sources: Vec<usize>,
+    weights: Vec<f32>,
+    // hash of the nodes and steps, networks that only differ in their weights share it
+    topology: u64,
+}
+
+impl EvaluationPlan {
+    fn new(nodes: &[NodeGene], connections: &[ConnectionGene]) -> Self {
+        let mut incoming = vec![vec![]; nodes.len()];
+        let mut outgoing = vec![vec![]; nodes.len()];
+        let mut missing_sources = vec![0; nodes.len()];
+        for connection in connections.iter().filter(|connection| connection.enabled) {
+            incoming[connection.out_node].push((connection.in_node, connection.weight));
+            if nodes[connection.in_node].node_type != NodeType::Input {
+                outgoing[connection.in_node].push(connection.out_node);
+                missing_sources[connection.out_node] += 1;
+            }
+        }
+        let mut ready: VecDeque<usize> = (0..nodes.len()).filter(|index| nodes[*index].node_type != NodeType::Input && missing_sources[*index] == 0).collect();
+        let mut planned = vec![false; nodes.len()];
+        let mut order = vec![];
+        while let Some(index) = ready.pop_front() {
+            planned[index] = true;
+            order.push(index);
+            for target in &outgoing[index] {
+                missing_sources[*target] -= 1;
+                if missing_sources[*target] == 0 {
+                    ready.push_back(*target);
+                }
+            }
+        }
+        // a node in a loop sees the nodes of the loop that come after it as 0
+        order.extend((0..nodes.len()).filter(|index| nodes[*index].node_type != NodeType::Input && !planned[*index]));
+        let mut plan = EvaluationPlan::default();
+        for index in order {
+            let start = plan.sources.len();
+            for (source, weight) in &incoming[index] {
+                plan.sources.push(*source);
+                plan.weights.push(*weight);
+            }
+            plan.steps.push((index, start..plan.sources.len()));
+        }
+        let mut hasher = DefaultHasher::new();
+        nodes.iter().for_each(|node| (&node.node_type, &node.activation).hash(&mut hasher));
+        (&plan.steps, &plan.sources).hash(&mut hasher);
+        plan.topology = hasher.finish();
+        plan
+    }
+
+    // Comparing the whole plans would take as long as running them, the hash covers them and the sizes keep a collision from mixing up networks of different sizes
+    fn same_topology(&self, other: &EvaluationPlan) -> bool {
+        self.topology == other.topology && self.steps.len() == other.steps.len() && self.sources.len() == other.sources.len()
+    }
+}
+
 // Your neural network with a generic vector for input values.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NeuralNetwork {
     nodes: Vec<NodeGene>,
-    pub connections: Vec<ConnectionGene>,
+    connections: Vec<ConnectionGene>,
+    // built on the first run after the network changed, every mutation throws it away
+    #[serde(skip)]
+    plan: OnceLock<EvaluationPlan>,
 }
 
 impl NeuralNetwork {
@@ -102,6 +187,7 @@ impl NeuralNetwork {
         let mut network = NeuralNetwork {
             nodes: Vec::new(),
             connections: Vec::new(),
+            plan: OnceLock::new(),
         };
 
         // Initialize input nodes with their respective activation functions
@@ -122,15 +208,15 @@ impl NeuralNetwork {
         network
     }
 
-    pub fn random_brain(total_inputs: usize, connection_active_probability: f32, innovation_tracker: &mut InnovationTracker) -> NeuralNetwork {
+    pub fn random_brain(total_inputs: usize, total_outputs: usize, connection_active_probability: f32, innovation_tracker: &mut InnovationTracker, rng: &mut impl Rng) -> NeuralNetwork {
         // Define input activations: one for bias (using ReLU to keep it at 1) and one for the actual input.
         let input_activations = vec![Activation::Relu; total_inputs];
 
         // For outputs, we initially choose Sigmoid, as we want to simulate probabilities. Later, we'll apply softmax.
-        let output_activations = vec![Activation::Sigmoid; 4];
+        // forward, left, right, wait, transfer energy and the signal followed by the optional moves
+        let output_activations = vec![Activation::Sigmoid; total_outputs];
 
         let mut network = NeuralNetwork::new(input_activations.clone(), output_activations.clone());
-        let rng = &mut rand::thread_rng();
         for (i,_) in input_activations.iter().enumerate(){
             for (j,_) in output_activations.iter().enumerate() {
                network.add_connection(i, j + input_activations.len(), rng.gen_range(0.0..1.0)-0.5, rng.gen_range(0.0..1.0) < connection_active_probability, innovation_tracker.get_innovation_number(i, j))
@@ -150,40 +236,65 @@ impl NeuralNetwork {
         assert!(in_node < self.nodes.len(), "The input node index is out of bounds");
         assert!(out_node < self.nodes.len(), "The output node index is out of bounds");
         self.connections.push(connection);
+        self.plan.take();
+    }
+
+    // Puts a new hidden node in the middle of an enabled connection, which gets disabled, the path through the node keeps the weight on its second half
+    pub fn split_connection(&mut self, index: usize, activation: Activation, innovation_tracker: &mut InnovationTracker) -> Option<usize> {
+        let connection = self.connections.get(index).filter(|connection| connection.enabled)?.clone();
+        self.nodes.push(NodeGene::new(NodeType::Hidden, activation));
+        let hidden = self.nodes.len() - 1;
+        self.connections[index].enabled = false;
+        let innovation = innovation_tracker.get_unused_innovation_number(connection.in_node, hidden, &self.connections);
+        self.add_connection(connection.in_node, hidden, 1.0, true, innovation);
+        let innovation = innovation_tracker.get_unused_innovation_number(hidden, connection.out_node, &self.connections);
+        self.add_connection(hidden, connection.out_node, connection.weight, true, innovation);
+        Some(hidden)
+    }
+
+    pub fn connections(&self) -> &[ConnectionGene] {
+        &self.connections
     }
 
-    pub fn flip_random_connection(&mut self){
-        let mut rng = rand::thread_rng();
+    pub fn flip_random_connection(&mut self, rng: &mut impl Rng) {
+        if self.connections.is_empty() {
+            return;
+        }
         let index = rng.gen_range(0..self.connections.len());
         debug!("Flipping connection {}", index);
         self.connections[index].enabled = !self.connections[index].enabled;
+        self.plan.take();
     }
 
-    pub(crate) fn mutate_perturb_random_connection_weight(&mut self, mutation_strength: f32, perturb_disabled_connections: bool) {
-        let mut rng = rand::thread_rng();
-        let mut index = rng.gen_range(0..self.connections.len());
+    pub(crate) fn mutate_perturb_random_connection_weight(&mut self, mutation_strength: f32, perturb_disabled_connections: bool, rng: &mut impl Rng) {
+        if self.connections.is_empty() {
+            return;
+        }
         let active_connections = self.get_active_connections();
-        if perturb_disabled_connections || active_connections.is_empty() {
-            index = rng.gen_range(0..self.connections.len());
+        let index = if perturb_disabled_connections || active_connections.is_empty() {
+            rng.gen_range(0..self.connections.len())
         } else {
-            index = rng.gen_range(0..self.get_active_connections().len());
-            index = self.connections.iter().position(|c| active_connections.get(index).unwrap() == &c).unwrap();
-        }
+            let active_index = rng.gen_range(0..active_connections.len());
+            self.connections.iter().position(|c| active_connections.get(active_index).unwrap() == &c).unwrap()
+        };
         self.connections[index].weight += rng.gen_range(-mutation_strength..mutation_strength);
+        self.plan.take();
         debug!("Mutating connection {} to value {}", index, self.connections[index].weight);
     }
 
-    pub(crate) fn mutate_reset_random_connection_weight(&mut self, mutation_strength: f32, perturb_disabled_connections: bool) {
-        let mut rng = rand::thread_rng();
-        let mut index = rng.gen_range(0..self.connections.len());
+    pub(crate) fn mutate_reset_random_connection_weight(&mut self, mutation_strength: f32, perturb_disabled_connections: bool, rng: &mut impl Rng) {
+        if self.connections.is_empty() {
+            return;
+        }
         let active_connections = self.get_active_connections();
-        if perturb_disabled_connections || active_connections.is_empty() {
-            index = rng.gen_range(0..self.connections.len());
+        let index = if perturb_disabled_connections || active_connections.is_empty() {
+            rng.gen_range(0..self.connections.len())
         } else {
-            index = rng.gen_range(0..self.get_active_connections().len());
-            index = self.connections.iter().position(|c| active_connections.get(index).unwrap() == &c).unwrap();
-        }
+            let active_index = rng.gen_range(0..active_connections.len());
+            self.connections.iter().position(|c| active_connections.get(active_index).unwrap() == &c).unwrap()
+        };
         self.connections[index].weight = rng.gen_range(-mutation_strength..mutation_strength);
+        self.plan.take();
         debug!("Mutating connection {} to value {}", index, self.connections[index].weight);
     }
 
@@ -191,17 +302,129 @@ impl NeuralNetwork {
         self.connections.iter().filter(|connection| connection.enabled).collect()
     }
 
+    // Nodes with an enabled path from an input and to an output, inputs and outputs always count
+    fn get_effective_nodes(&self) -> Vec<bool> {
+        let enabled: Vec<&ConnectionGene> = self.get_active_connections();
+        let spread = |mut reached: Vec<bool>, forward: bool| {
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for connection in &enabled {
+                    let (from, to) = if forward { (connection.in_node, connection.out_node) } else { (connection.out_node, connection.in_node) };
+                    if reached[from] && !reached[to] {
+                        reached[to] = true;
+                        changed = true;
+                    }
+                }
+            }
+            reached
+        };
+        let from_inputs = spread(self.nodes.iter().map(|node| node.node_type == NodeType::Input).collect(), true);
+        let to_outputs = spread(self.nodes.iter().map(|node| node.node_type == NodeType::Output).collect(), false);
+        self.nodes.iter().enumerate()
+            .map(|(index, node)| node.node_type != NodeType::Hidden || (from_inputs[index] && to_outputs[index]))
+            .collect()
+    }
+
+    // Enabled connections that can change an output, dangling ones are free
+    pub fn get_effective_connections(&self) -> Vec<&ConnectionGene> {
+        let effective_nodes = self.get_effective_nodes();
+        self.get_active_connections().into_iter()
+            .filter(|connection| effective_nodes[connection.in_node] && effective_nodes[connection.out_node])
+            .collect()
+    }
+
+    // Column of every node when drawing the network, hidden nodes come right after their deepest enabled source and outputs after all of them
+    pub fn node_layers(&self) -> Vec<usize> {
+        let mut layers = vec![0; self.nodes.len()];
+        let feeds_hidden = |connection: &&ConnectionGene| {
+            self.nodes[connection.out_node].node_type == NodeType::Hidden && self.nodes[connection.in_node].node_type != NodeType::Output
+        };
+        // connections closing a loop would push the nodes deeper forever, so nothing gets deeper than the number of nodes
+        for _ in 0..self.nodes.len() {
+            let mut changed = false;
+            for connection in self.get_active_connections().into_iter().filter(feeds_hidden) {
+                let depth = layers[connection.in_node] + 1;
+                if depth > layers[connection.out_node] && depth < self.nodes.len() {
+                    layers[connection.out_node] = depth;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let hidden = |index: &usize| self.nodes[*index].node_type == NodeType::Hidden;
+        let output_layer = (0..self.nodes.len()).filter(hidden).map(|index| layers[index].max(1)).max().unwrap_or(0) + 1;
+        for (index, node) in self.nodes.iter().enumerate() {
+            match node.node_type {
+                NodeType::Output => layers[index] = output_layer,
+                // unconnected hidden nodes still get their own column
+                NodeType::Hidden => layers[index] = layers[index].max(1),
+                NodeType::Input => {}
+            }
+        }
+        layers
+    }
+
+    pub fn effective_size(&self) -> NetworkSize {
+        NetworkSize {
+            nodes: self.get_effective_nodes().iter().filter(|effective| **effective).count(),
+            connections: self.get_effective_connections().len(),
+        }
+    }
+
+    // Removes disabled connections and hidden nodes that can't influence an output, inputs and outputs keep their indices
+    pub fn prune(&mut self) {
+        let effective_nodes = self.get_effective_nodes();
+        let mut new_indices = vec![None; self.nodes.len()];
+        let mut next_index = 0;
+        for (index, effective) in effective_nodes.iter().enumerate() {
+            if *effective {
+                new_indices[index] = Some(next_index);
+                next_index += 1;
+            }
+        }
+        self.connections = self.connections.iter()
+            .filter(|connection| connection.enabled)
+            .filter_map(|connection| Some(ConnectionGene {
+                in_node: new_indices[connection.in_node]?,
+                out_node: new_indices[connection.out_node]?,
+                ..connection.clone()
+            }))
+            .collect();
+        let mut effective = effective_nodes.into_iter();
+        self.nodes.retain(|_| effective.next().unwrap());
+        self.plan.take();
+    }
+
     pub fn get_nodes(&self) -> Vec<&NodeGene> {
         self.nodes.iter().collect()
     }
 
     pub fn run_cost(&self) -> f32 {
-        let active_connections = self.get_active_connections();
+        let active_connections = self.get_effective_connections();
         let think_cost = active_connections.len() as f32 * 0.15 + active_connections.iter().map(|c| c.weight.abs()).sum::<f32>() * 0.1;
         think_cost + 0.01
     }
 
     pub fn run(&self, inputs: Vec<SensorInput>) -> Vec<f32> {
+        let node_values = self.evaluate(inputs);
+        // Extract the output values and return them
+        self.nodes.iter()
+            .enumerate()
+            .filter_map(|(i, node)| {
+                if matches!(node.node_type, NodeType::Output) {
+                    Some(node_values[i])
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Values of all nodes after a run in the order of get_nodes, used to inspect what a snake was thinking
+    pub fn evaluate(&self, inputs: Vec<SensorInput>) -> Vec<f32> {
         debug!("Running network with inputs: {:?}", inputs);
         debug!("Nodes len: {}", self.nodes.len());
         let mut node_values = vec![0.0; self.nodes.len()];
@@ -215,41 +438,233 @@ impl NeuralNetwork {
             }
         }
 
-        // Propagate values through the network
-        for connection in &self.connections {
-            if connection.enabled {
-                let input_value = node_values[connection.in_node];
-                node_values[connection.out_node] += input_value * connection.weight;
-                debug!("Propagating value {} from node {} to node {}", input_value, connection.in_node, connection.out_node)
+        // Every node sums up its sources and applies its activation function (skipping input nodes)
+        let plan = self.plan();
+        for (index, range) in &plan.steps {
+            let mut sum = 0.0;
+            for (source, weight) in plan.sources[range.clone()].iter().zip(&plan.weights[range.clone()]) {
+                sum += node_values[*source] * weight;
             }
+            node_values[*index] = self.nodes[*index].activation.apply(sum);
+            debug!("Node {} got value {}", index, node_values[*index]);
         }
+        node_values
+    }
+
+    fn plan(&self) -> &EvaluationPlan {
+        self.plan.get_or_init(|| EvaluationPlan::new(&self.nodes, &self.connections))
+    }
 
-        // Apply activation functions to all nodes (skipping input nodes)
-        for (i, node) in self.nodes.iter().enumerate() {
-            if matches!(node.node_type, NodeType::Hidden | NodeType::Output) {
-                node_values[i] = node.activation.apply(node_values[i]);
-                debug!("Applying activation function to node {} with value {}", i, node_values[i]);
+    // Same for networks that only differ in their weights, those can be run together by run_batch
+    pub fn topology(&self) -> u64 {
+        self.plan().topology
+    }
+
+    // Runs networks of one topology at once, every node value and weight is a row over all networks so the loops vectorize
+    // Sums are added up in the same order as in run so the outputs are exactly the same, networks of another topology are run on their own
+    pub fn run_batch(networks: &[&NeuralNetwork], inputs: &[&[f32]]) -> Vec<Vec<f32>> {
+        let Some(first) = networks.first() else {
+            return vec![];
+        };
+        let plan = first.plan();
+        let (batch, others): (Vec<usize>, Vec<usize>) = (0..networks.len()).partition(|index| networks[*index].plan().same_topology(plan));
+        let lanes = batch.len();
+        let mut node_values = vec![vec![0.0; lanes]; first.nodes.len()];
+        for (lane, network) in batch.iter().enumerate() {
+            for (index, value) in inputs[*network].iter().enumerate() {
+                if index < first.nodes.len() && first.nodes[index].node_type == NodeType::Input {
+                    node_values[index][lane] = *value;
+                }
             }
         }
-
-        // Extract the output values and return them
-        self.nodes.iter()
-            .enumerate()
-            .filter_map(|(i, node)| {
-                if matches!(node.node_type, NodeType::Output) {
-                    Some(node_values[i])
-                } else {
-                    None
+        // one row of weights for every connection
+        let plans: Vec<&EvaluationPlan> = batch.iter().map(|network| networks[*network].plan()).collect();
+        let mut weights = Vec::with_capacity(plan.weights.len() * lanes);
+        for connection in 0..plan.weights.len() {
+            weights.extend(plans.iter().map(|plan| plan.weights[connection]));
+        }
+        let mut sums = vec![0.0; lanes];
+        for (index, range) in &plan.steps {
+            sums.fill(0.0);
+            for connection in range.clone() {
+                let source = &node_values[plan.sources[connection]];
+                for ((sum, value), weight) in sums.iter_mut().zip(source).zip(&weights[connection * lanes..(connection + 1) * lanes]) {
+                    *sum += value * weight;
                 }
-            })
-            .collect()
+            }
+            let activation = &first.nodes[*index].activation;
+            for (value, sum) in node_values[*index].iter_mut().zip(&sums) {
+                *value = activation.apply(*sum);
+            }
+        }
+        let outputs: Vec<usize> = (0..first.nodes.len()).filter(|index| first.nodes[*index].node_type == NodeType::Output).collect();
+        let mut results = vec![vec![]; networks.len()];
+        for (lane, network) in batch.iter().enumerate() {
+            results[*network] = outputs.iter().map(|index| node_values[*index][lane]).collect();
+        }
+        for network in others {
+            results[network] = networks[network].run(inputs[network].iter().enumerate().map(|(index, value)| SensorInput { index, value: *value }).collect());
+        }
+        results
     }
 }
 
 
+// Builds a network from named nodes, so tests and hand made brains don't depend on node indices
+// Inputs come first and outputs second like in every network, hidden nodes follow in the order they were added
+#[derive(Default, Clone)]
+pub struct NeuralNetworkBuilder {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    hidden: Vec<(String, Activation)>,
+    // innovation numbers follow the order of the connections
+    connections: Vec<(String, String, f32, bool)>,
+}
+
+impl NeuralNetworkBuilder {
+    pub fn new() -> Self {
+        NeuralNetworkBuilder::default()
+    }
+
+    // Every sensor of a snake and the first outputs, the same nodes a random snake brain has
+    pub fn for_snake(outputs: usize) -> Self {
+        let builder = SENSOR_NAMES.iter().fold(NeuralNetworkBuilder::new(), |builder, name| builder.input(name));
+        OUTPUT_NAMES.iter().take(outputs).fold(builder, |builder, name| builder.output(name))
+    }
+
+    pub fn input(mut self, name: &str) -> Self {
+        self.inputs.push(name.to_string());
+        self
+    }
+
+    pub fn output(mut self, name: &str) -> Self {
+        self.outputs.push(name.to_string());
+        self
+    }
+
+    pub fn hidden(mut self, name: &str, activation: Activation) -> Self {
+        self.hidden.push((name.to_string(), activation));
+        self
+    }
+
+    pub fn connect(mut self, from: &str, to: &str, weight: f32) -> Self {
+        self.connections.push((from.to_string(), to.to_string(), weight, true));
+        self
+    }
+
+    pub fn connect_disabled(mut self, from: &str, to: &str, weight: f32) -> Self {
+        self.connections.push((from.to_string(), to.to_string(), weight, false));
+        self
+    }
+
+    pub fn build(self) -> Result<NeuralNetwork, String> {
+        let names: Vec<&String> = self.inputs.iter().chain(&self.outputs).chain(self.hidden.iter().map(|(name, _)| name)).collect();
+        if let Some(duplicate) = names.iter().enumerate().find(|(index, name)| names[..*index].contains(name)).map(|(_, name)| name) {
+            return Err(format!("Node '{}' is defined more than once", duplicate));
+        }
+        let index_of = |name: &str| names.iter().position(|node| *node == name).ok_or(format!("Unknown node '{}'", name));
+        let mut network = NeuralNetwork::new(vec![Activation::Relu; self.inputs.len()], vec![Activation::Sigmoid; self.outputs.len()]);
+        for (_, activation) in &self.hidden {
+            network.nodes.push(NodeGene::new(NodeType::Hidden, activation.clone()));
+        }
+        for (innovation_number, (from, to, weight, enabled)) in self.connections.iter().enumerate() {
+            let (in_node, out_node) = (index_of(from)?, index_of(to)?);
+            if network.nodes[out_node].node_type == NodeType::Input {
+                return Err(format!("Input '{}' can't receive a connection", to));
+            }
+            network.add_connection(in_node, out_node, *weight, *enabled, innovation_number);
+        }
+        Ok(network)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn layers_follow_the_longest_path_and_survive_loops() {
+        // a chain of hidden nodes with a shortcut from the second input, one hidden node is left unconnected
+        let chain = NeuralNetworkBuilder::new()
+            .input("first").input("second").output("out")
+            .hidden("a", Activation::Relu).hidden("b", Activation::Relu).hidden("c", Activation::Relu).hidden("unused", Activation::Relu)
+            .connect("first", "a", 1.0).connect("a", "b", 1.0).connect("b", "c", 1.0).connect("second", "c", 1.0).connect("c", "out", 1.0);
+        let network = chain.clone().build().unwrap();
+        assert_eq!(network.node_layers(), vec![0, 0, 4, 1, 2, 3, 1]);
+        // a loop from the end of the chain back to its start
+        let network = chain.connect("c", "a", 1.0).build().unwrap();
+        let layers = network.node_layers();
+        assert!(layers.iter().all(|layer| *layer <= network.nodes.len()));
+        assert_eq!(layers[2], layers.iter().take(2).chain(layers.iter().skip(3)).max().unwrap() + 1);
+    }
+
+    #[test]
+    fn pruning_keeps_only_connections_reaching_an_output() {
+        // only hidden node "used" is on a path to the output
+        let mut network = NeuralNetworkBuilder::new()
+            .input("first").input("second").output("out")
+            .hidden("used", Activation::Relu).hidden("dead end", Activation::Relu).hidden("no input", Activation::Relu)
+            .connect("first", "used", 1.0).connect("used", "out", 1.0).connect("second", "dead end", 1.0).connect("no input", "out", 1.0).connect_disabled("second", "out", 1.0)
+            .build().unwrap();
+        assert_eq!(network.effective_size(), NetworkSize { nodes: 4, connections: 2 });
+        let outputs_before = network.run(vec![SensorInput { value: 1.0, index: 0 }, SensorInput { value: 0.5, index: 1 }]);
+        network.prune();
+        assert_eq!(network.nodes.len(), 4);
+        assert_eq!(network.connections.len(), 2);
+        assert_eq!((network.connections[0].in_node, network.connections[0].out_node), (0, 3));
+        assert_eq!(network.effective_size(), NetworkSize { nodes: 4, connections: 2 });
+        assert_eq!(network.run(vec![SensorInput { value: 1.0, index: 0 }, SensorInput { value: 0.5, index: 1 }]), outputs_before);
+    }
+
+    #[test]
+    fn nodes_are_evaluated_after_their_sources_even_after_mutations() {
+        // the connection leaving the hidden node is stored before the one reaching it
+        let mut network = NeuralNetworkBuilder::new()
+            .input("in").output("out").hidden("middle", Activation::Relu)
+            .connect("middle", "out", 1.0).connect("in", "middle", 2.0)
+            .build().unwrap();
+        let inputs = || vec![SensorInput { value: 1.5, index: 0 }];
+        assert_eq!(network.evaluate(inputs()), vec![1.5, Activation::Sigmoid.apply(3.0), 3.0]);
+        // the new node only gets a value when the cached order is built again
+        assert_eq!(network.split_connection(1, Activation::Relu, &mut InnovationTracker::new()), Some(3));
+        assert_eq!(network.evaluate(inputs()), vec![1.5, Activation::Sigmoid.apply(3.0), 3.0, 1.5]);
+        assert_eq!(network.split_connection(1, Activation::Relu, &mut InnovationTracker::new()), None);
+    }
+
+    #[test]
+    fn batches_give_the_outputs_of_single_runs() {
+        let network = |first: f32, second: f32| NeuralNetworkBuilder::new()
+            .input("a").input("b").output("out").output("other").hidden("middle", Activation::Tanh)
+            .connect("middle", "out", first).connect("a", "middle", second).connect("b", "other", 0.3).connect("b", "middle", -0.7)
+            .build().unwrap();
+        // the last one is missing a connection and has to be run on its own
+        let different = NeuralNetworkBuilder::new().input("a").input("b").output("out").output("other").connect("a", "out", 1.0).build().unwrap();
+        let networks = [network(1.0, 2.0), network(-0.5, 0.25), network(3.0, -1.0), different];
+        let inputs: Vec<Vec<f32>> = (0..networks.len()).map(|index| vec![index as f32 * 0.4, 1.0 - index as f32 * 0.3]).collect();
+        assert_eq!(networks[0].topology(), networks[1].topology());
+        assert_ne!(networks[0].topology(), networks[3].topology());
+        let batch: Vec<&NeuralNetwork> = networks.iter().collect();
+        let slices: Vec<&[f32]> = inputs.iter().map(|input| input.as_slice()).collect();
+        let single: Vec<Vec<f32>> = networks.iter().zip(&inputs).map(|(network, input)| network.run(input.iter().enumerate().map(|(index, value)| SensorInput { index, value: *value }).collect())).collect();
+        assert_eq!(NeuralNetwork::run_batch(&batch, &slices), single);
+    }
+
+    #[test]
+    fn builder_finds_nodes_by_name_and_rejects_mistakes() {
+        let network = NeuralNetworkBuilder::for_snake(OUTPUT_NAMES.len())
+            .hidden("hungry", Activation::Tanh)
+            .connect("Energy", "hungry", -1.0)
+            .connect("hungry", "Move left", 2.0)
+            .build().unwrap();
+        let hungry = SENSOR_NAMES.len() + OUTPUT_NAMES.len();
+        assert_eq!(network.get_nodes().len(), hungry + 1);
+        assert_eq!((network.connections[0].in_node, network.connections[0].out_node), (16, hungry));
+        assert_eq!((network.connections[1].in_node, network.connections[1].out_node, network.connections[1].innovation_number), (hungry, SENSOR_NAMES.len() + 1, 1));
+        assert!(NeuralNetworkBuilder::for_snake(4).connect("Bias", "Sprint", 1.0).build().is_err());
+        assert!(NeuralNetworkBuilder::new().input("a").output("out").connect("out", "a", 1.0).build().is_err());
+        assert!(NeuralNetworkBuilder::new().input("a").hidden("a", Activation::Relu).build().is_err());
+    }
+
     //
     // struct FloatInput {
     //     value: f32,
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::core::{Direction, Food, FoodMap, is_free, Position, position_at_direction, SegmentMap, Solid, SolidsMap};
+use crate::profile_system;
+use crate::simulation::SimulationConfig;
+use crate::time::SimTime;
+
+// A line of hexes running east from its first hex that only blocks the way some of the time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    // where the first hex is, as shares of the world width and height, so it stays in the same part of the world after a resize
+    pub x: f32,
+    pub y: f32,
+    // hexes past the east edge of the world are left out
+    pub length: u32,
+    pub pattern: MovementPattern,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MovementPattern {
+    // closed for period frames, then open for period frames
+    Gate { period: u32 },
+    // moves distance hexes into the direction and the same way back, one hex every period frames
+    Slide { direction: Direction, distance: u32, period: u32 },
+}
+
+impl MovementPattern {
+    pub fn period(&self) -> u32 {
+        match self {
+            MovementPattern::Gate { period } | MovementPattern::Slide { period, .. } => *period,
+        }
+    }
+}
+
+// Every hex of an obstacle has one, the hexes of an obstacle always move together
+#[derive(Component, Debug, Clone)]
+pub struct Movement {
+    // index of the obstacle in the config
+    pub obstacle: usize,
+    pub pattern: MovementPattern,
+    // hexes slid so far, a slide is back where it started after twice its distance
+    pub progress: u32,
+}
+
+impl Obstacle {
+    pub fn gate(x: f32, y: f32, length: u32, period: u32) -> Self {
+        Obstacle { x, y, length, pattern: MovementPattern::Gate { period } }
+    }
+
+    pub fn slide(x: f32, y: f32, length: u32, direction: Direction, distance: u32, period: u32) -> Self {
+        Obstacle { x, y, length, pattern: MovementPattern::Slide { direction, distance, period } }
+    }
+
+    pub fn positions(&self, config: &SimulationConfig) -> Vec<Position> {
+        let x = ((self.x * config.columns as f32) as usize).min(config.columns - 1);
+        let y = ((self.y * config.rows as f32) as usize).min(config.rows - 1);
+        (x..(x + self.length as usize).min(config.columns)).map(|x| Position { x: x as i32, y: y as i32 }).collect()
+    }
+}
+
+// Obstacles start closed, hexes that are already solid are left out just like walls sharing a hex
+pub fn create_obstacles(world: &mut World, config: &SimulationConfig) {
+    for (index, obstacle) in config.obstacles.iter().enumerate() {
+        for position in obstacle.positions(config) {
+            if *world.resource::<SolidsMap>().map.get(&position) {
+                continue;
+            }
+            world.resource_mut::<SolidsMap>().map.set(&position, true);
+            world.spawn((Solid, position, Movement { obstacle: index, pattern: obstacle.pattern.clone(), progress: 0 }));
+        }
+    }
+}
+
+// An obstacle that would close on or slide into a snake waits for its next turn, food it closes on or slides into is gone
+pub fn move_obstacles(mut commands: Commands, mut hexes: Query<(Entity, &mut Position, &mut Movement, Has<Solid>)>, mut solids_map: ResMut<SolidsMap>, segment_map: Res<SegmentMap>, mut food_map: ResMut<FoodMap>, time: Res<SimTime>, config: Res<SimulationConfig>) {
+    profile_system!();
+    let mut obstacles: BTreeMap<usize, Vec<Entity>> = BTreeMap::new();
+    for (hex_id, _, movement, _) in &hexes {
+        obstacles.entry(movement.obstacle).or_default().push(hex_id);
+    }
+    for members in obstacles.values() {
+        let (_, _, movement, closed) = hexes.get(members[0]).unwrap();
+        if time.ticks == 0 || !time.is_multiple_of(movement.pattern.period()) {
+            continue;
+        }
+        let positions: Vec<Position> = members.iter().map(|hex_id| hexes.get(*hex_id).unwrap().1.clone()).collect();
+        match movement.pattern.clone() {
+            MovementPattern::Gate { .. } if closed => {
+                for (hex_id, position) in members.iter().zip(&positions) {
+                    commands.entity(*hex_id).remove::<Solid>();
+                    solids_map.map.set(position, false);
+                }
+            }
+            MovementPattern::Gate { .. } => {
+                if !positions.iter().all(|position| is_free(position, &solids_map, &segment_map)) {
+                    continue;
+                }
+                for (hex_id, position) in members.iter().zip(&positions) {
+                    commands.entity(*hex_id).insert(Solid);
+                    solids_map.map.set(position, true);
+                    food_map.map.set(position, Food::default());
+                }
+            }
+            MovementPattern::Slide { direction, distance, .. } => {
+                let progress = movement.progress;
+                let direction = if progress < distance { direction } else { direction.opposite() };
+                let targets: Vec<Position> = positions.iter().map(|position| position_at_direction(&direction, position, &config)).collect();
+                // hexes of the obstacle itself make way as it moves
+                let own = |target: &Position| positions.iter().any(|position| position.as_pair() == target.as_pair());
+                if !targets.iter().all(|target| is_free(target, &solids_map, &segment_map) || (own(target) && segment_map.map.get(target).is_empty())) {
+                    continue;
+                }
+                for position in &positions {
+                    solids_map.map.set(position, false);
+                }
+                for (hex_id, target) in members.iter().zip(targets) {
+                    solids_map.map.set(&target, true);
+                    food_map.map.set(&target, Food::default());
+                    let (_, mut position, mut movement, _) = hexes.get_mut(*hex_id).unwrap();
+                    *position = target;
+                    movement.progress = (progress + 1) % (2 * distance);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{event_channel, EventCapacity};
+    use crate::core::RngResource;
+    use crate::invariants::check_invariants;
+    use crate::simulation::{EngineState, Simulation};
+    use super::*;
+
+    fn hexes(simulation: &mut Simulation) -> Vec<((i32, i32), bool)> {
+        let mut hexes: Vec<((i32, i32), bool)> = simulation.world_mut().query::<(&Position, &Movement, Has<Solid>)>().iter(simulation.world()).map(|(position, _, solid)| (position.as_pair(), solid)).collect();
+        hexes.sort();
+        hexes
+    }
+
+    #[test]
+    fn gates_toggle_and_slides_go_back_and_forth() {
+        let config = SimulationConfig {
+            rows: 20,
+            columns: 20,
+            starting_snakes: 0,
+            obstacles: vec![Obstacle::gate(0.1, 0.1, 3, 4), Obstacle::slide(0.5, 0.5, 2, Direction::East, 2, 2)],
+            ..SimulationConfig::default()
+        };
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Obstacles".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(2));
+        simulation.insert_resource(EngineState::default());
+        let start = hexes(&mut simulation);
+        assert_eq!(start, vec![((2, 2), true), ((3, 2), true), ((4, 2), true), ((10, 10), true), ((11, 10), true)]);
+        let mut gate_open = vec![];
+        let mut slide_x = vec![];
+        for _ in 0..16 {
+            simulation.step();
+            check_invariants(simulation.world_mut());
+            let now = hexes(&mut simulation);
+            gate_open.push(!now[0].1);
+            slide_x.push(now[3].0.0);
+        }
+        assert_eq!(gate_open, [false, false, false, false, true, true, true, true, false, false, false, false, true, true, true, true]);
+        assert_eq!(slide_x, [10, 10, 11, 11, 12, 12, 11, 11, 10, 10, 11, 11, 12, 12, 11, 11]);
+    }
+
+    #[test]
+    fn gates_wait_for_snakes_to_pass() {
+        let config = SimulationConfig { rows: 12, columns: 12, starting_snakes: 0, obstacles: vec![Obstacle::gate(0.0, 0.5, 12, 1)], ..SimulationConfig::default() };
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Obstacles".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(2));
+        simulation.insert_resource(EngineState::default());
+        // nothing moves in the very first frame
+        simulation.step();
+        simulation.step();
+        assert!(hexes(&mut simulation).iter().all(|(_, solid)| !solid));
+        // the opened gate frees the whole row, a snake standing in it keeps it from closing
+        let snake = simulation.create_snake_at((5, 6), None).unwrap();
+        // snakes placed between frames only show up in the segment map during the next frame
+        simulation.world_mut().resource_mut::<SegmentMap>().map.add(&Position { x: 5, y: 6 }, snake);
+        simulation.step();
+        assert!(hexes(&mut simulation).iter().all(|(_, solid)| !solid));
+    }
+}
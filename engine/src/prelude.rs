@@ -0,0 +1,12 @@
+// What embedding tools need to drive and watch a simulation, names here keep working across releases while the modules behind them change
+pub use crate::arena::Genome;
+pub use crate::core::{Decision, Direction, Energy, Metabolism, Position};
+pub use crate::dna::{Dna, SegmentType};
+pub use crate::events::{event_channel, EventCapacity, EventReceiver, EventSender};
+pub use crate::handle::SimulationHandle;
+pub use crate::hooks::Hooks;
+pub use crate::logging::LogLevel;
+pub use crate::neural::NeuralNetwork;
+pub use crate::simulation::{DeathCause, EngineCommand, EngineEvent, FoodKind, MutationConfig, RunMetadata, Simulation, SimulationConfig, SimulationEvent, SimulationEventKind, Stats, WallLayout, WaterLayout};
+pub use crate::snapshot::WorldSnapshot;
+pub use crate::view::SnakeView;
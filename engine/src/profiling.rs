@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+use bevy_ecs::prelude::Resource;
+
+pub use puffin;
+pub use tracing;
+
+#[derive(Debug, Clone)]
+pub struct SystemTiming {
+    pub name: &'static str,
+    pub calls_per_second: f32,
+    pub ms_per_second: f32,
+    pub average_ms: f32,
+}
+
+type Timings = Vec<(&'static str, Duration, u32)>;
+
+thread_local! {
+    // Systems run on the thread that steps their world, which moves the timings into it after every step
+    static THREAD_TIMINGS: RefCell<Timings> = const { RefCell::new(Vec::new()) };
+}
+
+fn add_timing(timings: &mut Timings, name: &'static str, total: Duration, calls: u32) {
+    match timings.iter_mut().find(|(existing, _, _)| *existing == name) {
+        Some((_, existing_total, existing_calls)) => {
+            *existing_total += total;
+            *existing_calls += calls;
+        }
+        None => timings.push((name, total, calls)),
+    }
+}
+
+pub struct SystemTimer {
+    name: &'static str,
+    start: Instant,
+}
+
+impl SystemTimer {
+    pub fn start(name: &'static str) -> Self {
+        SystemTimer { name, start: Instant::now() }
+    }
+}
+
+impl Drop for SystemTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        THREAD_TIMINGS.with_borrow_mut(|timings| add_timing(timings, self.name, elapsed, 1));
+    }
+}
+
+// The timings of the systems of one world
+#[derive(Resource, Default)]
+pub struct SystemTimings {
+    timings: Timings,
+}
+
+impl SystemTimings {
+    // Takes over everything timed on this thread since the last call
+    pub fn collect(&mut self) {
+        THREAD_TIMINGS.with_borrow_mut(|timings| {
+            for (name, total, calls) in timings.drain(..) {
+                add_timing(&mut self.timings, name, total, calls);
+            }
+        });
+    }
+
+    // Returns timings collected since the last call, normalized to the given window
+    pub fn drain(&mut self, window: Duration) -> Vec<SystemTiming> {
+        let window = window.as_secs_f32().max(f32::EPSILON);
+        let mut timings: Vec<SystemTiming> = self.timings.drain(..).map(|(name, total, calls)| {
+            let total_ms = total.as_secs_f32() * 1000.0;
+            SystemTiming {
+                name,
+                calls_per_second: calls as f32 / window,
+                ms_per_second: total_ms / window,
+                average_ms: total_ms / calls as f32,
+            }
+        }).collect();
+        timings.sort_by(|a, b| b.ms_per_second.total_cmp(&a.ms_per_second));
+        timings
+    }
+}
+
+// Opens a puffin scope named after the enclosing function and records its wall time, everything logged by the system is inside its span
+#[macro_export]
+macro_rules! profile_system {
+    () => {
+        $crate::profiling::puffin::profile_function!();
+        let _system_timer = $crate::profiling::SystemTimer::start($crate::profiling::puffin::current_function_name!());
+        let _system_span = $crate::profiling::tracing::debug_span!("system", name = $crate::profiling::puffin::current_function_name!()).entered();
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiled_system() {
+        profile_system!();
+    }
+
+    #[test]
+    fn timings_are_named_after_the_system() {
+        profiled_system();
+        profiled_system();
+        let mut system_timings = SystemTimings::default();
+        system_timings.collect();
+        let timings = system_timings.drain(Duration::from_secs(1));
+        let timing = timings.iter().find(|timing| timing.name.ends_with("profiled_system")).unwrap();
+        assert_eq!(timing.calls_per_second, 2.0);
+        system_timings.collect();
+        assert!(system_timings.drain(Duration::from_secs(1)).is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::events::{event_channel, EventCapacity};
+use bevy_ecs::prelude::World;
+use crate::core::{FoodMap, Position, RngResource, Snake};
+use crate::simulation::{EngineState, Simulation, SimulationConfig};
+
+// FNV-1a, unlike the std hashers its output is guaranteed to stay the same between rust versions
+struct StateHasher {
+    hash: u64,
+}
+
+impl StateHasher {
+    fn new() -> Self {
+        StateHasher { hash: 0xcbf2_9ce4_8422_2325 }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+}
+
+// Hash of the snakes (positions, energy and genome) and the food on the map, equal states always give equal hashes
+pub fn state_hash(world: &mut World) -> u64 {
+    let mut hasher = StateHasher::new();
+    let mut positions = world.query::<&Position>();
+    let mut snakes: Vec<&Snake> = world.query::<&Snake>().iter(world).collect();
+    snakes.sort_by_key(|snake| snake.id);
+    for snake in snakes {
+        hasher.write_u64(snake.id);
+        hasher.write_u64(snake.generation as u64);
+        hasher.write_f32(snake.energy.energy);
+        hasher.write_f32(snake.energy.plant_in_stomach);
+        hasher.write_f32(snake.energy.meat_in_stomach);
+        for segment in &snake.segments {
+            let position = positions.get(world, *segment).map_or((-1, -1), Position::as_pair);
+            hasher.write_u64(position.0 as u64);
+            hasher.write_u64(position.1 as u64);
+        }
+        if let Some(network) = snake.brain.get_neural_network() {
+            for connection in network.connections() {
+                hasher.write_u64(connection.innovation_number as u64);
+                hasher.write_f32(connection.weight);
+                hasher.write(&[connection.enabled as u8]);
+            }
+        }
+        hasher.write(format!("{:?}", snake.dna.genes).as_bytes());
+    }
+    for (position, food) in world.resource::<FoodMap>().map.iter() {
+        if food.contains_food() {
+            hasher.write_u64(position.x as u64);
+            hasher.write_u64(position.y as u64);
+            hasher.write_f32(food.plant);
+            hasher.write_f32(food.meat);
+        }
+    }
+    hasher.hash
+}
+
+// Hash of the serialized settings, short enough to note next to a result and compare with the settings of another run
+pub fn config_hash(config: &SimulationConfig) -> u64 {
+    let mut hasher = StateHasher::new();
+    hasher.write(toml::to_string(config).unwrap_or_default().as_bytes());
+    hasher.hash
+}
+
+// Runs a seeded simulation without any gui and returns the state hash after every step
+pub fn seeded_run_hashes(config: SimulationConfig, seed: u64, snakes: usize, steps: usize) -> Vec<u64> {
+    let (engine_events, _) = event_channel(EventCapacity::default());
+    let mut simulation = Simulation::new("Regression".to_string(), engine_events, None, config);
+    simulation.insert_resource(RngResource::from_seed(seed));
+    simulation.insert_resource(EngineState::default());
+    simulation.create_snakes(snakes);
+    (0..steps).map(|_| {
+        simulation.step();
+        state_hash(simulation.world_mut())
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use crate::simulation::ENGINE_VERSION;
+    use super::*;
+
+    // Update only when a change is meant to alter the simulation, refactors have to keep these
+    const GOLDEN_HASHES: [(usize, u64); 3] = [(10, 17753409785364147936), (50, 7316109434735772318), (200, 18181505581384149985)];
+
+    #[test]
+    fn seeded_runs_match_the_golden_hashes() {
+        let hashes = seeded_run_hashes(SimulationConfig { rows: 30, columns: 30, ..SimulationConfig::default() }, 42, 20, 200);
+        for (step, golden) in GOLDEN_HASHES {
+            assert_eq!(hashes[step - 1], golden, "state after step {} changed", step);
+        }
+    }
+
+    #[test]
+    fn batched_thinking_makes_the_same_decisions() {
+        let config = SimulationConfig { rows: 30, columns: 30, ..SimulationConfig::default() };
+        let batched = seeded_run_hashes(config.clone(), 7, 20, 100);
+        assert_eq!(seeded_run_hashes(SimulationConfig { batched_thinking: false, ..config }, 7, 20, 100), batched);
+    }
+
+    #[test]
+    fn config_hash_changes_only_with_the_settings() {
+        let config = SimulationConfig::default();
+        assert_eq!(config_hash(&config), config_hash(&config.clone()));
+        assert_ne!(config_hash(&config), config_hash(&SimulationConfig { speed_factor: 2.0, ..config.clone() }));
+    }
+
+    #[test]
+    fn runs_started_from_entropy_can_be_repeated_with_their_seed() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Metadata".to_string(), engine_events, None, SimulationConfig::default());
+        let metadata = simulation.run_metadata();
+        let mut repeated = RngResource::from_seed(metadata.seed);
+        assert_eq!(simulation.world_mut().resource_mut::<RngResource>().rng.gen::<u64>(), repeated.rng.gen::<u64>());
+        assert_eq!(metadata.config_hash, format!("{:016x}", config_hash(&SimulationConfig::default())));
+        assert_eq!(metadata.engine_version, ENGINE_VERSION);
+        assert_eq!(metadata.frame, 0);
+    }
+}
@@ -1,29 +1,64 @@
-use crate::core::{assign_segment_positions, Brain, despawn_food, Food, incease_move_potential, Map2d, Map3d, process_food, ScentMap, SegmentMap};
+use crate::core::{assign_segment_positions, Brain, is_free, despawn_food, spawn_food_entity, Food, incease_move_potential, Map2d, Map3d, process_food, ScentMap, SegmentMap};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
+use std::str::FromStr;
 use std::sync::Arc;
-use std::f32::consts::PI;
 use crate::core::{add_scents, assign_solid_positions, destroy_old_food, diffuse_scents, disperse_scents, Solid};
-use crate::core::{die_from_collisions};
+use crate::core::{die_from_collisions, die_of_old_age, wrap_position};
 use crate::core::SolidsMap;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::Receiver;
 use std::sync::Mutex;
-use std::time::Instant;
-use bevy_ecs::prelude::{IntoSystemConfigs, Res, ResMut, Resource, Schedule, World};
-use rand::{Rng, thread_rng};
-use crate::core::{create_food, create_snake, Decision, Direction, eat_food, FoodMap, grow, Snake, movement, Position, RandomBrain, reproduce, split, starve, think, update_positions, assign_missing_segments, increase_age, calculate_stats, RandomNeuralBrain, assign_species, Species};
-use crate::dna::{Dna, SegmentType};
-use crate::neural::InnovationTracker;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use bevy_ecs::prelude::{apply_deferred, Commands, Entity, IntoSystemConfigs, Mut, Or, Res, ResMut, Resource, Schedule, With, Without, World};
+use bevy_ecs::schedule::ExecutorKind;
+use bevy_ecs::system::CommandQueue;
+use bevy_tasks::{ComputeTaskPool, TaskPool};
+use serde::{Deserialize, Serialize};
+use crate::core::{create_food, create_snake, eat_food, FoodMap, grow, movement, Position, split, starve, think, update_positions, assign_missing_segments, increase_age, calculate_stats, RandomNeuralBrain, assign_species, Species, Direction, RngResource, Scent, Snake, FoodSources, FoodController, decay_meat, TemperatureMap, update_temperature, find_free_position, random_position, Controlled, Decision, steer_controlled, SpeciesQuotas, update_species_quotas, EntityCounts, enforce_entity_limits, record_trails, BehaviorCounters, RegionCounters, ActionRound, transfer_energy, SignalMap, update_signal_map, ThoughtTrace, ActionSet};
+use crate::dna::{Dna, SegmentType, Trait};
+use crate::draw::DrawDelta;
+use crate::diagnostics::Diagnostics;
+use crate::events::EventSender;
+use crate::genome_distances::DistanceMatrix;
+use crate::hooks::Hooks;
+use crate::energy_grid::EnergyGrid;
+use crate::lineage::{AncestryRecord, Lineage, MutationKind, record_lineage};
+use crate::neural::{InnovationTracker, NeuralNetwork};
+use crate::profile_system;
+use crate::profiling::{SystemTiming, SystemTimings};
+use crate::snapshot::WorldSnapshot;
+use crate::arena::Genome;
+use crate::walls::{perimeter_positions, wall_positions};
+use crate::nests::{build_nests, feed_from_nests};
+use crate::obstacles::{create_obstacles, Movement, MovementPattern, move_obstacles, Obstacle};
+use crate::crowding::crowd;
+use crate::disease::{progress_disease, spread_disease};
+use crate::migration::CONFIG_VERSION;
+use crate::minerals::{collect_minerals, create_minerals, MineralMap};
+use crate::stats_worker::{receive_stats, StatsWorker};
+use crate::water::WaterMap;
+use crate::regression::config_hash;
+use crate::time::{AGING_INTERVAL, SimTime};
+use crate::logging::{LogLevel, set_log_level};
+use tracing::warn;
+
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub struct Simulation {
     first_schedule: Schedule,
     core_schedule: Schedule,
+    // extra rounds of thinking and moving for snakes that can act more than once per frame
+    action_schedule: Schedule,
     secondary_schedule: Schedule,
     gui_schedule: Schedule,
     world: World,
     pub name: String,
-    engine_events: Sender<EngineEvent>,
+    engine_events: EventSender,
     // only the main simulation may receive commands
     engine_commands: Option<Arc<Mutex<Receiver<EngineCommand>>>>,
     has_gui: bool,
+    hooks: Hooks,
 }
 
 #[derive(Debug, Clone)]
@@ -33,12 +68,13 @@ pub struct Hex {
     pub hex_type: HexType,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum HexType {
     Food,
     SnakeHead {
         specie: u32,
     },
+    ControlledHead,
     SnakeTail,
     Scent {
         value: f32,
@@ -47,42 +83,438 @@ pub enum HexType {
         segment_type: SegmentType
     },
     Meat,
+    // fade goes from 1 on the newest position to almost 0 on the oldest one
+    Trail {
+        specie: u32,
+        fade: f32,
+    },
+    // drawn over the snake, a stronger signal glows brighter
+    Signal {
+        value: f32,
+    },
+    Nest {
+        specie: u32,
+    },
+    Mineral,
+    Water,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct SpecieStats {
+    pub id: u32,
+    pub population: usize,
+    pub mean_energy: f32,
+    // mean network outputs of the members at their last think in the order of OUTPUT_NAMES, shows how the specie tends to act
+    pub mean_outputs: Vec<f32>,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Histogram {
+    pub min: f32,
+    pub bucket_width: f32,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn from_values(values: &[f32], buckets: usize) -> Self {
+        if values.is_empty() || buckets == 0 {
+            return Histogram::default();
+        }
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        // all values equal still need a non zero bucket to land in
+        let bucket_width = ((max - min) / buckets as f32).max(f32::EPSILON);
+        let mut counts = vec![0; buckets];
+        for value in values {
+            let bucket = (((value - min) / bucket_width) as usize).min(buckets - 1);
+            counts[bucket] += 1;
+        }
+        Histogram { min, bucket_width, counts }
+    }
+
+    pub fn bucket_start(&self, bucket: usize) -> f32 {
+        self.min + bucket as f32 * self.bucket_width
+    }
 }
 
 #[derive(Resource, Default, Debug, Clone)]
 pub struct Stats {
+    pub frame: u32,
+    pub time: SimTime,
     pub total_snakes: usize,
     pub total_food: usize,
     pub oldest_snake: u32,
     pub food: usize,
+    // food spawned per step right now, the food controller changes it over time
+    pub food_per_step: f32,
     pub total_segments: usize,
     pub max_generation: u32,
     pub max_mutations: u32,
     pub species: Species,
     pub total_entities: usize,
     pub total_scents: usize,
+    pub total_nests: usize,
+    // cumulative since the start of the simulation
+    pub nests_built: usize,
+    pub nest_energy_given: f32,
+    pub infected_snakes: usize,
+    // snakes that paid for crowding in the last frame
+    pub crowded_snakes: usize,
+    // cumulative since the start of the simulation
+    pub infections: usize,
+    pub recoveries: usize,
+    pub disease_deaths: usize,
+    pub total_minerals: f32,
+    // cumulative since the start of the simulation
+    pub minerals_deposited: f32,
+    pub minerals_collected: f32,
     pub total_snake_energy: f32,
     pub total_plants_in_stomachs: f32,
     pub total_meat_in_stomachs: f32,
     pub total_plants: f32,
     pub total_meat: f32,
     pub total_energy: f32,
+    // cumulative since the start of the simulation
+    pub total_decayed_meat: f32,
+    pub total_composted_plants: f32,
+    pub energy_flows: EnergyFlows,
+    pub mutations: MutationStats,
+    pub species_stats: Vec<SpecieStats>,
+    pub age_distribution: Histogram,
+    pub energy_distribution: Histogram,
+    pub segment_distribution: Histogram,
+    pub connection_distribution: Histogram,
+    pub behavior: BehaviorStats,
+    pub regions: Vec<RegionStats>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RegionStats {
+    pub name: String,
+    pub snakes: usize,
+    // plants and meat on the hexes of the region
+    pub food: f32,
+    // since the previous statistics update
+    pub deaths: usize,
+}
+
+// Movement behavior of all snakes since the previous statistics update
+#[derive(Default, Debug, Clone, Copy)]
+pub struct BehaviorStats {
+    // share of the forward, left, right, wait, transfer energy, backward, sprint and build nest decisions
+    pub decision_shares: [f32; 8],
+    // mean distance in hexes the snake heads got away from where they were at the previous update
+    pub mean_displacement: f32,
+    // collisions with something solid per 1000 decisions
+    pub collision_rate: f32,
+}
+
+// Offspring that got a mutation operator at birth, cumulative since the start of the simulation
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct OperatorStats {
+    pub fired: u32,
+    // only the offspring that already died have a known lifespan
+    pub deaths: u32,
+    pub total_lifespan: u64,
+    pub total_food_eaten: f32,
+}
+
+impl OperatorStats {
+    pub fn mean_lifespan(&self) -> f32 {
+        if self.deaths > 0 { self.total_lifespan as f32 / self.deaths as f32 } else { 0.0 }
+    }
+
+    pub fn mean_food_eaten(&self) -> f32 {
+        if self.deaths > 0 { self.total_food_eaten / self.deaths as f32 } else { 0.0 }
+    }
+}
+
+// How often every mutation operator fires and how the offspring carrying it fare compared to offspring born without mutations
+#[derive(Default, Debug, Clone)]
+pub struct MutationStats {
+    pub operators: HashMap<MutationKind, OperatorStats>,
+    pub unmutated: OperatorStats,
+}
+
+impl MutationStats {
+    pub fn record_birth(&mut self, mutations: &[MutationKind]) {
+        self.record(mutations, |stats| stats.fired += 1);
+    }
+
+    // Snakes of the first generation were never mutated, they are not counted
+    pub fn record_death(&mut self, snake: &Snake, age: u32) {
+        if snake.parent_id.is_none() {
+            return;
+        }
+        self.record(&snake.birth_mutations, |stats| {
+            stats.deaths += 1;
+            stats.total_lifespan += age as u64;
+            stats.total_food_eaten += snake.energy.food_eaten;
+        });
+    }
+
+    fn record(&mut self, mutations: &[MutationKind], update: impl Fn(&mut OperatorStats)) {
+        if mutations.is_empty() {
+            update(&mut self.unmutated);
+        }
+        for mutation in mutations {
+            update(self.operators.entry(*mutation).or_default());
+        }
+    }
+}
+
+// Parts of the world food matter moves between, scavengers are the snakes eating meat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compartment {
+    Soil,
+    Plants,
+    Meat,
+    Stomachs,
+    Snakes,
+}
+
+// Food matter moved between the compartments, cumulative since the start of the simulation
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct EnergyFlows {
+    pub plants_grown: f32,
+    pub plants_eaten: f32,
+    pub meat_eaten: f32,
+    pub plants_digested: f32,
+    pub meat_digested: f32,
+    // segments of dead snakes left on the map
+    pub corpse_meat: f32,
+    // all the meat that rotted, the composted part of it became plants
+    pub meat_decayed: f32,
+    pub meat_composted: f32,
+}
+
+impl EnergyFlows {
+    // Flows during the window between an earlier state and this one
+    pub fn since(&self, earlier: &EnergyFlows) -> EnergyFlows {
+        EnergyFlows {
+            plants_grown: self.plants_grown - earlier.plants_grown,
+            plants_eaten: self.plants_eaten - earlier.plants_eaten,
+            meat_eaten: self.meat_eaten - earlier.meat_eaten,
+            plants_digested: self.plants_digested - earlier.plants_digested,
+            meat_digested: self.meat_digested - earlier.meat_digested,
+            corpse_meat: self.corpse_meat - earlier.corpse_meat,
+            meat_decayed: self.meat_decayed - earlier.meat_decayed,
+            meat_composted: self.meat_composted - earlier.meat_composted,
+        }
+    }
+
+    pub fn links(&self) -> [(Compartment, Compartment, f32); 7] {
+        [
+            (Compartment::Soil, Compartment::Plants, self.plants_grown),
+            (Compartment::Plants, Compartment::Stomachs, self.plants_eaten),
+            (Compartment::Meat, Compartment::Stomachs, self.meat_eaten),
+            (Compartment::Stomachs, Compartment::Snakes, self.plants_digested + self.meat_digested),
+            (Compartment::Snakes, Compartment::Meat, self.corpse_meat),
+            (Compartment::Meat, Compartment::Plants, self.meat_composted),
+            (Compartment::Meat, Compartment::Soil, self.meat_decayed - self.meat_composted),
+        ]
+    }
+
+    // Share of the digested food that was meat, 0 for pure herbivory and 1 for pure carnivory
+    pub fn carnivory(&self) -> f32 {
+        let digested = self.plants_digested + self.meat_digested;
+        if digested > 0.0 { self.meat_digested / digested } else { 0.0 }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum EngineEvent {
-    SimulationFinished { steps: u32, name: String, duration: u128 },
-    DrawData { hexes: Vec<Hex>, stats: Stats },
-    FrameDrawn { updates_left: f32, updates_done: u32 },
+    // duration is the time spent simulating in milliseconds, time spent paused or waiting for the receiver is in paused
+    SimulationFinished { steps: u32, name: String, duration: u128, paused: u128 },
+    // frame is the simulation time of the drawn state
+    DrawData { delta: DrawDelta, stats: Box<Stats>, frame: u32 },
+    FrameDrawn { updates_left: f32, updates_done: u32, clock: RunClock },
+    SimulationEvents { events: Vec<SimulationEvent> },
+    // sent only when the temperatures change, values are stored row by row
+    TemperatureMap { columns: usize, rows: usize, temperatures: Vec<f32> },
+    // sent every few frames for the energy overlay
+    EnergyGrid { grid: Box<EnergyGrid> },
+    SystemTimings { timings: Vec<SystemTiming> },
+    // sent along with the timings
+    Diagnostics { diagnostics: Box<Diagnostics> },
+    // answer to InspectSnakeAt, the inspected snake comes first followed by its ancestors
+    Ancestry { ancestors: Vec<AncestryRecord> },
+    // answer to TakeSnapshot
+    Snapshot { snapshot: Box<WorldSnapshot> },
+    // what the inspected snake sensed and decided during the last simulated frame
+    Thoughts { snake: u64, frame: u32, trace: Box<ThoughtTrace> },
+    // sent once before the first frame of the interactive simulation
+    RunStarted { metadata: RunMetadata },
+    // answer to MeasureGenomeDistances
+    GenomeDistances { matrix: Box<DistanceMatrix> },
+}
+
+// Wall clock time when the world was created, in seconds since the unix epoch
+#[derive(Resource, Debug)]
+pub struct RunStart {
+    pub started_at: u64,
+}
+
+// Wall clock time the run spent simulating and paused, waiting for a receiver that does not keep up counts as paused
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunClock {
+    pub active: Duration,
+    pub paused: Duration,
+}
+
+impl RunClock {
+    pub fn record(&mut self, elapsed: Duration, stalled: Duration, paused: bool) {
+        let simulated = elapsed.saturating_sub(stalled);
+        if paused {
+            self.paused += simulated;
+        } else {
+            self.active += simulated;
+        }
+        self.paused += stalled.min(elapsed);
+    }
+}
+
+// Everything needed to trace a result back to the exact run and settings that produced it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub seed: u64,
+    // hex, toml integers can't hold every u64
+    pub config_hash: String,
+    pub engine_version: String,
+    pub started_at: u64,
+    pub frame: u32,
+    // wall clock milliseconds spent simulating and paused so far, missing in metadata saved before they were tracked
+    #[serde(default)]
+    pub active_millis: u64,
+    #[serde(default)]
+    pub paused_millis: u64,
+}
+
+impl RunMetadata {
+    pub fn capture(world: &World) -> RunMetadata {
+        let mut metadata = RunMetadata {
+            seed: world.resource::<RngResource>().seed,
+            config_hash: String::new(),
+            engine_version: ENGINE_VERSION.to_string(),
+            started_at: world.resource::<RunStart>().started_at,
+            frame: 0,
+            active_millis: 0,
+            paused_millis: 0,
+        };
+        metadata.update(world.resource::<SimulationConfig>(), world.resource::<SimulationEvents>().frame, world.resource::<RunClock>());
+        metadata
+    }
+
+    // The settings may change while the world runs, the seed and start stay
+    pub fn update(&mut self, config: &SimulationConfig, frame: u32, clock: &RunClock) {
+        self.config_hash = format!("{:016x}", config_hash(config));
+        self.frame = frame;
+        self.active_millis = clock.active.as_millis() as u64;
+        self.paused_millis = clock.paused.as_millis() as u64;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeathCause {
+    Starvation,
+    Collision,
+    // removed to keep the population under its limit
+    Culled,
+    // ran out of energy while infected
+    Disease,
+    OldAge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityLimit {
+    Snakes,
+    Segments,
+    Food,
+    Scents,
+}
+
+#[derive(Debug, Clone)]
+pub enum SimulationEventKind {
+    SnakeBorn { snake: Entity, generation: u32, mutations: u32 },
+    SnakeDied { snake: Entity, cause: DeathCause, age: u32, generation: u32 },
+    SpecieCreated { specie: u32 },
+    SpecieExtinct { specie: u32 },
+    LifespanRecord { snake: Entity, age: u32 },
+    EntityLimitReached { limit: EntityLimit, count: usize, max: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationEvent {
+    pub frame: u32,
+    pub kind: SimulationEventKind,
+}
+
+// events are kept until the next repaint, simulations without gui only keep the newest ones
+const MAX_BUFFERED_EVENTS: usize = 10_000;
+const TIMINGS_INTERVAL: Duration = Duration::from_secs(1);
+// speed limits are updates per reference frame, independent of how often the gui actually repaints
+const REFERENCE_FPS: f32 = 60.0;
+// walls need a few rows to fit in
+const MIN_WORLD_SIZE: usize = 4;
+// a snake gains at most the speed factor in move potential per frame, so it never needs more rounds than these
+const MAX_SPEED_FACTOR: f32 = 5.0;
+// beyond that the food per step jumps between nothing and the maximum
+const MAX_FOOD_CONTROL_GAIN: f32 = 100.0;
+const MAX_ACTION_ROUNDS: u32 = 7;
+// outputs only range from 0 to 1, far above that the choice is as good as uniform
+pub(crate) const MAX_ACTION_TEMPERATURE: f32 = 10.0;
+
+#[derive(Resource, Default, Debug)]
+pub struct SimulationEvents {
+    pub frame: u32,
+    pub longest_lifespan: u32,
+    pub events: VecDeque<SimulationEvent>,
+    // events the hooks did not get yet, kept apart so the gui draining the events doesn't take them away
+    unhooked: Vec<SimulationEvent>,
+}
+
+impl SimulationEvents {
+    pub fn push(&mut self, kind: SimulationEventKind) {
+        if self.events.len() >= MAX_BUFFERED_EVENTS {
+            self.events.pop_front();
+        }
+        let event = SimulationEvent { frame: self.frame, kind };
+        self.unhooked.push(event.clone());
+        self.events.push_back(event);
+    }
+
+    pub(crate) fn take_unhooked(&mut self) -> Vec<SimulationEvent> {
+        std::mem::take(&mut self.unhooked)
+    }
+
+    pub fn drain(&mut self) -> Vec<SimulationEvent> {
+        self.events.drain(..).collect()
+    }
 }
 
-#[derive(Debug, Resource, Clone, Copy)]
+#[derive(Debug, Resource, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MutationConfig {
     pub scent_sensing_enabled: bool,
     pub plant_vision_enabled: bool,
     pub meat_vision_enabled: bool,
     pub obstacle_vision_enabled: bool,
     pub chaos_input_enabled: bool,
+    pub memory_input_enabled: bool,
+    pub compass_input_enabled: bool,
+    pub food_direction_enabled: bool,
+    pub food_direction_range: u32,
+    pub touch_input_enabled: bool,
+    // an extra output sets a signal that other snakes see through their front obstacle vision
+    pub signaling_enabled: bool,
+    // fins can show up in the dna, a single one lets the snake swim
+    pub fins_enabled: bool,
+    // eyes and noses can show up in the dna, they cost energy all the time but let the snake sense further
+    pub sense_segments_enabled: bool,
+    // hexes every eye adds to all vision ranges
+    pub eye_vision_bonus: u32,
+    // hexes every nose adds to how far scents are smelled, without noses only the neighbouring hexes are smelled
+    pub nose_smell_bonus: u32,
     pub plant_vision_front_range: u32,
     pub plant_vision_left_range: u32,
     pub plant_vision_right_range: u32,
@@ -99,7 +531,28 @@ pub struct MutationConfig {
     pub dna_mutation_chance: f64,
     pub weight_reset_chance: f64,
     pub weight_reset_range: f32,
-    pub perturb_reset_connections: bool
+    pub perturb_reset_connections: bool,
+    // removes disabled connections and dead hidden nodes from a newborn network, they can't be flipped back on afterwards
+    pub prune_chance: f64,
+    // offspring get the action temperature of their parent shifted by up to this much, 0 keeps the one of the settings for every snake
+    pub action_temperature_mutation_range: f32,
+    // chance that one metabolism trait of the dna gets scaled by up to trait_mutation_range
+    pub trait_mutation_chance: f64,
+    pub trait_mutation_range: f32,
+    // smallest and largest multiplier of each trait
+    pub max_energy_range: (f32, f32),
+    pub digestion_speed_range: (f32, f32),
+    pub mobility_range: (f32, f32),
+}
+
+impl MutationConfig {
+    pub fn trait_range(&self, which: Trait) -> (f32, f32) {
+        match which {
+            Trait::MaxEnergy => self.max_energy_range,
+            Trait::DigestionSpeed => self.digestion_speed_range,
+            Trait::Mobility => self.mobility_range,
+        }
+    }
 }
 
 impl Default for MutationConfig {
@@ -109,6 +562,16 @@ impl Default for MutationConfig {
             plant_vision_enabled: true,
             obstacle_vision_enabled: true,
             chaos_input_enabled: true,
+            memory_input_enabled: true,
+            compass_input_enabled: true,
+            food_direction_enabled: true,
+            food_direction_range: 5,
+            touch_input_enabled: true,
+            signaling_enabled: false,
+            fins_enabled: false,
+            sense_segments_enabled: false,
+            eye_vision_bonus: 2,
+            nose_smell_bonus: 2,
             plant_vision_front_range: 5,
             plant_vision_left_range: 3,
             plant_vision_right_range: 3,
@@ -123,6 +586,13 @@ impl Default for MutationConfig {
             weight_reset_chance: 0.1,
             weight_reset_range: 1.0,
             perturb_reset_connections: true,
+            prune_chance: 0.0,
+            action_temperature_mutation_range: 0.0,
+            trait_mutation_chance: 0.0,
+            trait_mutation_range: 0.1,
+            max_energy_range: (0.5, 2.0),
+            digestion_speed_range: (0.5, 2.0),
+            mobility_range: (0.5, 2.0),
             meat_vision_front_range: 5,
             meat_vision_left_range: 3,
             meat_vision_right_range: 3,
@@ -131,29 +601,607 @@ impl Default for MutationConfig {
     }
 }
 
-type EnergyValue = f32;
-
-#[derive(Debug, Resource, Clone, Copy)]
+#[derive(Debug, Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SimulationConfig {
+    // version of the saved settings, old files are brought up to date by migrate_config
+    pub version: u32,
     pub rows: usize,
     pub columns: usize,
     pub starting_snakes: usize,
     pub starting_food: usize,
     pub food_per_step: usize,
+    // adjusts the food per step to hold the population or the snake energy near the target, off spawns exactly food_per_step
+    pub food_control: FoodControl,
+    // snakes or total snake energy the controller aims for
+    pub food_control_target: f32,
+    // how strongly the food per step follows the relative distance to the target right now and summed up over the steps
+    pub food_control_gain: f32,
+    pub food_control_integral_gain: f32,
+    // the controller never spawns more than this per step
+    pub food_control_max_rate: usize,
     pub plant_matter_per_segment: f32,
     pub wait_cost: f32,
     pub move_cost: f32,
+    pub collision_policy: CollisionPolicy,
+    // frames a snake survives below zero energy, too weak to act, before it starves, 0 starves it right away
+    pub starvation_grace: u32,
+    // multiplies the efficiency of starving snakes, so they produce less and their upkeep costs more
+    pub starving_efficiency: f32,
+    // energy lost on top of the move when a snake bounces off something solid
+    pub bounce_cost: f32,
     pub new_segment_cost: f32,
     pub size_to_split: usize,
+    // snakes younger than the maturity age can't split, neither can snakes at the menopause age or older, 0 turns either off
+    pub maturity_age: u32,
+    pub menopause_age: u32,
     pub species_threshold: f32,
+    // weights of the excess genes, disjoint genes and matching gene weight difference in the species distance
+    pub excess_gene_coefficient: f32,
+    pub disjoint_gene_coefficient: f32,
+    pub weight_difference_coefficient: f32,
+    // limits the splits of every specie to its share of quota_population, based on the food its members ate
+    pub species_quotas_enabled: bool,
+    pub quota_population: usize,
+    // the snake of every specie that ate the most can't starve before it gets old, its energy can still go below zero, needs species quotas
+    pub protect_elites: bool,
     pub mutation: MutationConfig,
-    pub add_walls: bool,
+    pub wall_layout: WallLayout,
+    // the same seed always lays out the same walls
+    pub wall_seed: u64,
+    // walls along the edges of the world, without them snakes crossing an edge come out on the other side
+    pub perimeter_walls: bool,
+    pub water_layout: WaterLayout,
+    // the same seed always lays out the same water
+    pub water_seed: u64,
+    // multiplier of the move cost for every hex swum through
+    pub water_move_cost: f32,
     pub scent_diffusion_rate: f32,
     pub scent_dispersion_per_step: f32,
     pub create_scents: bool,
     pub snake_max_age: u32,
+    // age gained every tick
+    pub aging_rate: u32,
+    pub aging_curve: AgingCurve,
+    // chance per frame that a snake at the lowest efficiency dies of old age, snakes with more efficiency left die less often
+    pub old_age_mortality: f32,
+    // snakes die once they are this old, 0 lets them live as long as their energy lasts
+    pub max_lifespan: u32,
+    // only changes how the time is shown, snakes age in ticks
+    pub ticks_per_day: u32,
+    pub days_per_year: u32,
     pub meat_energy_content: f32,
-    pub plant_energy_content: f32
+    pub plant_energy_content: f32,
+    // how much plant digestion a body made only of stomachs loses, 0 lets snakes digest both at full speed
+    pub diet_tradeoff_strength: f32,
+    // shape of the loss over the stomach share of the body, above 1 the first stomachs cost little, below 1 they cost the most
+    pub diet_tradeoff_exponent: f32,
+    pub food_distribution: FoodDistribution,
+    pub food_patches: usize,
+    pub food_patch_radius: u32,
+    pub food_patch_drift: f32,
+    // spawn rate multipliers of the north west, north east, south west and south east quadrants
+    pub food_region_rates: [f32; 4],
+    // part of the meat on a hex that rots away every step
+    pub meat_decay_rate: f32,
+    // part of the rotten meat that turns into plants on the same hex
+    pub meat_compost_ratio: f32,
+    pub temperature_layout: TemperatureLayout,
+    // how much hot hexes raise and cold ones lower move costs and energy production, 0 turns it off
+    pub temperature_strength: f32,
+    // hard caps that keep runaway populations from grinding the simulation to a halt
+    pub max_snakes: usize,
+    pub max_segments: usize,
+    pub max_food: usize,
+    pub max_scents: usize,
+    pub overpopulation_policy: OverpopulationPolicy,
+    // lets snakes give energy to the snake in front of them through an extra brain output
+    pub energy_transfer_enabled: bool,
+    pub energy_transfer_amount: f32,
+    // scales how fast every snake gains move potential, above 1 the most mobile snakes act several times per frame
+    pub speed_factor: f32,
+    // extra brain outputs for crawling backward and for sprinting two hexes at twice the cost
+    pub backward_move_enabled: bool,
+    pub sprint_enabled: bool,
+    // an extra brain output builds a nest for energy, snakes of the builder's specie close to it get fed until it crumbles
+    pub nests_enabled: bool,
+    pub nest_cost: f32,
+    // nests feed within this many hexes and keep other nests that far away
+    pub nest_radius: u32,
+    // energy every close snake gets each frame
+    pub nest_feeding: f32,
+    // frames until a nest crumbles
+    pub nest_lifetime: u32,
+    // a pathogen passed on when a head touches another snake, sick snakes pay extra energy for every segment until they recover
+    pub disease_enabled: bool,
+    // chance per frame that a contact with a sick snake passes the pathogen on
+    pub disease_transmission: f32,
+    // extra energy every segment of a sick snake costs each frame
+    pub disease_virulence: f32,
+    // frames until a sick snake recovers
+    pub disease_duration: u32,
+    // frames a recovered snake can't catch it again
+    pub disease_immunity: u32,
+    // chance per frame that a random healthy snake falls sick on its own
+    pub disease_outbreak_chance: f32,
+    // snakes pay for every segment in the square around their head beyond the capacity, a penalty of 0 turns crowding off
+    pub crowding_radius: u32,
+    pub crowding_capacity: usize,
+    // extra energy every segment over the capacity costs each frame
+    pub crowding_penalty: f32,
+    // a second resource lying around next to food, solid segments can only be grown from carried minerals
+    pub minerals_enabled: bool,
+    pub minerals_per_step: usize,
+    // minerals in a new deposit
+    pub mineral_deposit: f32,
+    pub max_minerals_carried: f32,
+    // minerals spent on every solid segment
+    pub solid_segment_minerals: f32,
+    // hexes the minerals front sensor looks ahead
+    pub mineral_vision_range: u32,
+    // softmax temperature of the action choice, 0 always takes the strongest output
+    pub action_temperature: f32,
+    // positions remembered by every snake head to draw its trail, 0 turns trails off
+    pub trail_length: usize,
+    // snakes with networks that only differ in their weights are run together, the decisions stay exactly the same
+    pub batched_thinking: bool,
+    // frames between two calculations of the statistics, every one of them becomes a sample in the plots
+    pub stats_interval: u32,
+    // the statistics are summed up on another thread and show up a few frames later, the simulation doesn't wait for them but copies the food map for every sample
+    // off by default because headless runs read the statistics of a frame right after stepping it, the gui turns it on
+    pub background_stats: bool,
+    // statistics are also collected separately for every region
+    pub regions: Vec<Region>,
+    // gates and sliding barriers, laid out on top of the walls
+    pub obstacles: Vec<Obstacle>,
+}
+
+// A named rectangle given as shares of the world width and height, so it covers the same part of the world after a resize
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    pub name: String,
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Region {
+    pub fn new(name: &str, left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Region { name: name.to_string(), left, top, right, bottom }
+    }
+
+    // in the same order as the food region rates
+    pub fn quadrants() -> Vec<Region> {
+        vec![
+            Region::new("North west", 0.0, 0.0, 0.5, 0.5),
+            Region::new("North east", 0.5, 0.0, 1.0, 0.5),
+            Region::new("South west", 0.0, 0.5, 0.5, 1.0),
+            Region::new("South east", 0.5, 0.5, 1.0, 1.0),
+        ]
+    }
+
+    // A hex belongs to the region if its center does
+    pub fn contains(&self, position: &Position, config: &SimulationConfig) -> bool {
+        let x = (position.x as f32 + 0.5) / config.columns as f32;
+        let y = (position.y as f32 + 0.5) / config.rows as f32;
+        x >= self.left && x < self.right && y >= self.top && y < self.bottom
+    }
+}
+
+// What happens when there are too many snakes or segments, food and scents just stop spawning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverpopulationPolicy {
+    // snakes can't split while there are too many snakes or grow while there are too many segments
+    BlockSplits,
+    // the snakes with the least energy die
+    CullWeakest,
+}
+
+// What happens to a snake that moves into something solid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollisionPolicy {
+    Die,
+    // the move is cancelled and the snake stays where it was, paying the bounce cost
+    Bounce,
+}
+
+impl CollisionPolicy {
+    pub const ALL: [CollisionPolicy; 2] = [CollisionPolicy::Die, CollisionPolicy::Bounce];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WallLayout {
+    None,
+    // three horizontal bars with a gap in the middle
+    Bars,
+    // corridors between a grid of cells
+    Maze,
+    // the world split into rooms of random sizes that are connected by doors
+    Rooms,
+    // a square spiral winding out of the middle
+    Spiral,
+}
+
+impl WallLayout {
+    pub const ALL: [WallLayout; 5] = [WallLayout::None, WallLayout::Bars, WallLayout::Maze, WallLayout::Rooms, WallLayout::Spiral];
+}
+
+impl FromStr for WallLayout {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        WallLayout::ALL.iter().find(|layout| format!("{:?}", layout).eq_ignore_ascii_case(name)).copied().ok_or_else(|| {
+            let known: Vec<String> = WallLayout::ALL.iter().map(|layout| format!("{:?}", layout).to_lowercase()).collect();
+            format!("Unknown wall layout {}, expected one of: {}", name, known.join(", "))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WaterLayout {
+    None,
+    // round lakes scattered over the world
+    Lakes,
+    // two rivers from the top to the bottom that cut the world in half
+    Rivers,
+}
+
+impl WaterLayout {
+    pub const ALL: [WaterLayout; 3] = [WaterLayout::None, WaterLayout::Lakes, WaterLayout::Rivers];
+}
+
+impl FromStr for WaterLayout {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        WaterLayout::ALL.iter().find(|layout| format!("{:?}", layout).eq_ignore_ascii_case(name)).copied().ok_or_else(|| {
+            let known: Vec<String> = WaterLayout::ALL.iter().map(|layout| format!("{:?}", layout).to_lowercase()).collect();
+            format!("Unknown water layout {}, expected one of: {}", name, known.join(", "))
+        })
+    }
+}
+
+// How the efficiency of a snake falls once it is older than snake_max_age
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgingCurve {
+    // falls with one over the age, never reaches zero
+    Inverse,
+    // falls in a straight line until twice the max age
+    Linear,
+    // stays high a while longer and then drops quickly around one and a half times the max age
+    Sigmoid,
+    // drops all at once to the lowest efficiency
+    Step,
+}
+
+pub(crate) const MAX_AGING_RATE: u32 = 1_000;
+const MAX_NEST_RADIUS: u32 = 50;
+// every head looks at the whole square each frame
+const MAX_CROWDING_RADIUS: u32 = 10;
+const MAX_MINERAL_VISION_RANGE: u32 = 50;
+// every hex of vision or smell is looked at by the sensors of every snake in every frame
+const MAX_SENSE_BONUS: u32 = 10;
+const MIN_DIET_TRADEOFF_EXPONENT: f32 = 0.1;
+const MAX_DIET_TRADEOFF_EXPONENT: f32 = 10.0;
+// the upkeep is divided by the efficiency
+const MIN_STARVING_EFFICIENCY: f32 = 0.01;
+// trait multipliers stay within these whatever the ranges are set to
+const MIN_TRAIT: f32 = 0.01;
+const MAX_TRAIT: f32 = 100.0;
+// costs are divided by the efficiency, so it can't reach zero
+pub(crate) const MIN_EFFICIENCY: f32 = 0.05;
+const SIGMOID_STEEPNESS: f32 = 10.0;
+
+impl AgingCurve {
+    pub const ALL: [AgingCurve; 4] = [AgingCurve::Inverse, AgingCurve::Linear, AgingCurve::Sigmoid, AgingCurve::Step];
+
+    // Full efficiency until the max age for every curve
+    pub fn efficiency(self, age: u32, max_age: u32) -> f32 {
+        let relative_age = age as f32 / max_age as f32;
+        if relative_age <= 1.0 {
+            return 1.0;
+        }
+        match self {
+            AgingCurve::Inverse => 1.0 / relative_age,
+            AgingCurve::Linear => (2.0 - relative_age).max(MIN_EFFICIENCY),
+            AgingCurve::Sigmoid => (1.0 / (1.0 + (SIGMOID_STEEPNESS * (relative_age - 1.5)).exp())).max(MIN_EFFICIENCY),
+            AgingCurve::Step => MIN_EFFICIENCY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TemperatureLayout {
+    Uniform,
+    // cold in the north, hot in the south
+    Gradient,
+    // smooth random hot and cold regions
+    Noise,
+}
+
+// What the food controller holds near its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FoodControl {
+    Off,
+    // number of snakes
+    Population,
+    // energy of all snakes together
+    Energy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FoodDistribution {
+    // any free cell
+    Uniform,
+    // gaussian clouds around a few centers that drift over time
+    Patches,
+    // gaussian clouds around a few centers that never move
+    Oases,
+    // a band around the middle of the world
+    Ring,
+    // sparse on the left edge, rich on the right one
+    Gradient,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            version: CONFIG_VERSION,
+            rows: 100,
+            columns: 100,
+            wall_layout: WallLayout::None,
+            wall_seed: 0,
+            perimeter_walls: false,
+            water_layout: WaterLayout::None,
+            water_seed: 0,
+            water_move_cost: 0.5,
+            create_scents: false,
+            scent_diffusion_rate: 0.25,
+            scent_dispersion_per_step: 150.0,
+            starting_snakes: 10,
+            starting_food: 100,
+            food_per_step: 2,
+            food_control: FoodControl::Off,
+            food_control_target: 50.0,
+            food_control_gain: 2.0,
+            food_control_integral_gain: 0.01,
+            food_control_max_rate: 50,
+            plant_matter_per_segment: 100.0,
+            wait_cost: 1.0,
+            move_cost: 10.0,
+            collision_policy: CollisionPolicy::Die,
+            starvation_grace: 0,
+            starving_efficiency: 0.5,
+            bounce_cost: 10.0,
+            new_segment_cost: 100.0,
+            size_to_split: 10,
+            maturity_age: 0,
+            menopause_age: 0,
+            species_threshold: 0.2,
+            excess_gene_coefficient: 0.6,
+            disjoint_gene_coefficient: 0.6,
+            weight_difference_coefficient: 0.4,
+            species_quotas_enabled: false,
+            quota_population: 200,
+            protect_elites: false,
+            mutation: MutationConfig::default(),
+            snake_max_age: 2_000,
+            aging_rate: 1,
+            aging_curve: AgingCurve::Inverse,
+            old_age_mortality: 0.0,
+            max_lifespan: 0,
+            ticks_per_day: 100,
+            days_per_year: 40,
+            meat_energy_content: 5.0,
+            plant_energy_content: 1.0,
+            diet_tradeoff_strength: 0.0,
+            diet_tradeoff_exponent: 1.0,
+            food_distribution: FoodDistribution::Uniform,
+            food_patches: 5,
+            food_patch_radius: 5,
+            food_patch_drift: 0.1,
+            food_region_rates: [1.0; 4],
+            meat_decay_rate: 0.001,
+            meat_compost_ratio: 0.5,
+            temperature_layout: TemperatureLayout::Uniform,
+            temperature_strength: 0.5,
+            max_snakes: 5_000,
+            max_segments: 100_000,
+            max_food: 100_000,
+            max_scents: 100_000,
+            overpopulation_policy: OverpopulationPolicy::BlockSplits,
+            energy_transfer_enabled: false,
+            energy_transfer_amount: 20.0,
+            speed_factor: 1.0,
+            backward_move_enabled: false,
+            sprint_enabled: false,
+            nests_enabled: false,
+            nest_cost: 100.0,
+            nest_radius: 3,
+            nest_feeding: 0.5,
+            nest_lifetime: 2_000,
+            disease_enabled: false,
+            disease_transmission: 0.05,
+            disease_virulence: 0.2,
+            disease_duration: 1_000,
+            disease_immunity: 2_000,
+            disease_outbreak_chance: 0.001,
+            crowding_radius: 2,
+            crowding_capacity: 12,
+            crowding_penalty: 0.0,
+            minerals_enabled: false,
+            minerals_per_step: 1,
+            mineral_deposit: 5.0,
+            max_minerals_carried: 20.0,
+            solid_segment_minerals: 5.0,
+            mineral_vision_range: 10,
+            action_temperature: 0.0,
+            trail_length: 0,
+            batched_thinking: true,
+            stats_interval: 100,
+            background_stats: false,
+            regions: Region::quadrants(),
+            obstacles: vec![],
+        }
+    }
+}
+
+impl SimulationConfig {
+    // Forces every value into its valid range, returns a description of each value that had to be changed
+    pub fn clamp(&mut self) -> Vec<String> {
+        let mut problems = vec![];
+        clamp_field(&mut problems, "Rows", &mut self.rows, MIN_WORLD_SIZE, usize::MAX);
+        clamp_field(&mut problems, "Columns", &mut self.columns, MIN_WORLD_SIZE, usize::MAX);
+        clamp_field(&mut problems, "Plant matter per segment", &mut self.plant_matter_per_segment, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Wait cost", &mut self.wait_cost, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Move cost", &mut self.move_cost, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Bounce cost", &mut self.bounce_cost, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Starving efficiency", &mut self.starving_efficiency, MIN_STARVING_EFFICIENCY, 1.0);
+        clamp_field(&mut problems, "New segment cost", &mut self.new_segment_cost, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Size to split", &mut self.size_to_split, 2, usize::MAX);
+        clamp_field(&mut problems, "Species threshold", &mut self.species_threshold, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Excess gene coefficient", &mut self.excess_gene_coefficient, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Disjoint gene coefficient", &mut self.disjoint_gene_coefficient, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Weight difference coefficient", &mut self.weight_difference_coefficient, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Quota population", &mut self.quota_population, 1, usize::MAX);
+        clamp_field(&mut problems, "Scent diffusion rate", &mut self.scent_diffusion_rate, 0.0, 1.0);
+        clamp_field(&mut problems, "Scent dispersion per step", &mut self.scent_dispersion_per_step, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Snake max age", &mut self.snake_max_age, 1, u32::MAX);
+        clamp_field(&mut problems, "Aging rate", &mut self.aging_rate, 0, MAX_AGING_RATE);
+        clamp_field(&mut problems, "Old age mortality", &mut self.old_age_mortality, 0.0, 1.0);
+        clamp_field(&mut problems, "Max lifespan", &mut self.max_lifespan, 0, u32::MAX);
+        clamp_field(&mut problems, "Ticks per day", &mut self.ticks_per_day, 1, u32::MAX);
+        clamp_field(&mut problems, "Days per year", &mut self.days_per_year, 1, u32::MAX);
+        clamp_field(&mut problems, "Meat energy content", &mut self.meat_energy_content, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Plant energy content", &mut self.plant_energy_content, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Diet tradeoff strength", &mut self.diet_tradeoff_strength, 0.0, 1.0);
+        clamp_field(&mut problems, "Diet tradeoff exponent", &mut self.diet_tradeoff_exponent, MIN_DIET_TRADEOFF_EXPONENT, MAX_DIET_TRADEOFF_EXPONENT);
+        clamp_field(&mut problems, "Food control target", &mut self.food_control_target, 1.0, f32::MAX);
+        clamp_field(&mut problems, "Food control gain", &mut self.food_control_gain, 0.0, MAX_FOOD_CONTROL_GAIN);
+        clamp_field(&mut problems, "Food control integral gain", &mut self.food_control_integral_gain, 0.0, MAX_FOOD_CONTROL_GAIN);
+        clamp_field(&mut problems, "Food patches", &mut self.food_patches, 1, usize::MAX);
+        clamp_field(&mut problems, "Food patch drift", &mut self.food_patch_drift, 0.0, f32::MAX);
+        for rate in self.food_region_rates.iter_mut() {
+            clamp_field(&mut problems, "Food region rate", rate, 0.0, f32::MAX);
+        }
+        clamp_field(&mut problems, "Meat decay rate", &mut self.meat_decay_rate, 0.0, 1.0);
+        clamp_field(&mut problems, "Meat compost ratio", &mut self.meat_compost_ratio, 0.0, 1.0);
+        clamp_field(&mut problems, "Temperature strength", &mut self.temperature_strength, 0.0, 1.0);
+        clamp_field(&mut problems, "Max snakes", &mut self.max_snakes, 1, usize::MAX);
+        clamp_field(&mut problems, "Max segments", &mut self.max_segments, 1, usize::MAX);
+        clamp_field(&mut problems, "Max food", &mut self.max_food, 1, usize::MAX);
+        clamp_field(&mut problems, "Max scents", &mut self.max_scents, 1, usize::MAX);
+        clamp_field(&mut problems, "Energy transfer amount", &mut self.energy_transfer_amount, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Speed factor", &mut self.speed_factor, 0.0, MAX_SPEED_FACTOR);
+        clamp_field(&mut problems, "Action temperature", &mut self.action_temperature, 0.0, MAX_ACTION_TEMPERATURE);
+        clamp_field(&mut problems, "Trail length", &mut self.trail_length, 0, 1000);
+        clamp_field(&mut problems, "Stats interval", &mut self.stats_interval, 1, u32::MAX);
+        clamp_field(&mut problems, "Nest cost", &mut self.nest_cost, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Nest radius", &mut self.nest_radius, 0, MAX_NEST_RADIUS);
+        clamp_field(&mut problems, "Nest feeding", &mut self.nest_feeding, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Nest lifetime", &mut self.nest_lifetime, 1, u32::MAX);
+        clamp_field(&mut problems, "Disease transmission", &mut self.disease_transmission, 0.0, 1.0);
+        clamp_field(&mut problems, "Disease virulence", &mut self.disease_virulence, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Disease duration", &mut self.disease_duration, 1, u32::MAX);
+        clamp_field(&mut problems, "Disease immunity", &mut self.disease_immunity, 0, u32::MAX);
+        clamp_field(&mut problems, "Disease outbreak chance", &mut self.disease_outbreak_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Crowding radius", &mut self.crowding_radius, 0, MAX_CROWDING_RADIUS);
+        clamp_field(&mut problems, "Crowding penalty", &mut self.crowding_penalty, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Mineral deposit", &mut self.mineral_deposit, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Max minerals carried", &mut self.max_minerals_carried, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Solid segment minerals", &mut self.solid_segment_minerals, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Mineral vision range", &mut self.mineral_vision_range, 1, MAX_MINERAL_VISION_RANGE);
+        clamp_field(&mut problems, "Water move cost", &mut self.water_move_cost, 0.0, f32::MAX);
+        for region in self.regions.iter_mut() {
+            clamp_field(&mut problems, "Region left", &mut region.left, 0.0, 1.0);
+            clamp_field(&mut problems, "Region top", &mut region.top, 0.0, 1.0);
+            clamp_field(&mut problems, "Region right", &mut region.right, region.left, 1.0);
+            clamp_field(&mut problems, "Region bottom", &mut region.bottom, region.top, 1.0);
+        }
+        for obstacle in self.obstacles.iter_mut() {
+            clamp_field(&mut problems, "Obstacle x", &mut obstacle.x, 0.0, 1.0);
+            clamp_field(&mut problems, "Obstacle y", &mut obstacle.y, 0.0, 1.0);
+            clamp_field(&mut problems, "Obstacle length", &mut obstacle.length, 1, u32::MAX);
+            match &mut obstacle.pattern {
+                MovementPattern::Gate { period } => clamp_field(&mut problems, "Gate period", period, 1, u32::MAX),
+                MovementPattern::Slide { distance, period, .. } => {
+                    clamp_field(&mut problems, "Slide distance", distance, 1, u32::MAX);
+                    clamp_field(&mut problems, "Slide period", period, 1, u32::MAX);
+                }
+            }
+        }
+        let mutation = &mut self.mutation;
+        clamp_field(&mut problems, "Food direction range", &mut mutation.food_direction_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Plant vision front range", &mut mutation.plant_vision_front_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Plant vision left range", &mut mutation.plant_vision_left_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Plant vision right range", &mut mutation.plant_vision_right_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Meat vision front range", &mut mutation.meat_vision_front_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Meat vision left range", &mut mutation.meat_vision_left_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Meat vision right range", &mut mutation.meat_vision_right_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Obstacle vision front range", &mut mutation.obstacle_vision_front_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Obstacle vision left range", &mut mutation.obstacle_vision_left_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Obstacle vision right range", &mut mutation.obstacle_vision_right_range, 1, u32::MAX);
+        clamp_field(&mut problems, "Eye vision bonus", &mut mutation.eye_vision_bonus, 0, MAX_SENSE_BONUS);
+        clamp_field(&mut problems, "Nose smell bonus", &mut mutation.nose_smell_bonus, 0, MAX_SENSE_BONUS);
+        clamp_field(&mut problems, "Weight perturbation range", &mut mutation.weight_perturbation_range, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Weight perturbation chance", &mut mutation.weight_perturbation_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Connection flip chance", &mut mutation.connection_flip_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Dna mutation chance", &mut mutation.dna_mutation_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Weight reset chance", &mut mutation.weight_reset_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Weight reset range", &mut mutation.weight_reset_range, 0.0, f32::MAX);
+        clamp_field(&mut problems, "Prune chance", &mut mutation.prune_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Action temperature mutation range", &mut mutation.action_temperature_mutation_range, 0.0, MAX_ACTION_TEMPERATURE);
+        clamp_field(&mut problems, "Trait mutation chance", &mut mutation.trait_mutation_chance, 0.0, 1.0);
+        clamp_field(&mut problems, "Trait mutation range", &mut mutation.trait_mutation_range, 0.0, 1.0);
+        for (name, range) in [("Max energy", &mut mutation.max_energy_range), ("Digestion speed", &mut mutation.digestion_speed_range), ("Mobility", &mut mutation.mobility_range)] {
+            clamp_field(&mut problems, &format!("{} range minimum", name), &mut range.0, MIN_TRAIT, MAX_TRAIT);
+            clamp_field(&mut problems, &format!("{} range maximum", name), &mut range.1, range.0, MAX_TRAIT);
+        }
+        problems
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut config = self.clone();
+        config.clamp()
+    }
+}
+
+// NaN counts as below the minimum
+fn clamp_field<T: PartialOrd + Copy + Display>(problems: &mut Vec<String>, name: &str, value: &mut T, min: T, max: T) {
+    if matches!((*value).partial_cmp(&min), None | Some(Ordering::Less)) {
+        problems.push(format!("{} must be at least {}, was {}", name, min, value));
+        *value = min;
+    } else if *value > max {
+        problems.push(format!("{} must be at most {}, was {}", name, max, value));
+        *value = max;
+    }
+}
+
+// Ancestry of the snake with its head or any segment on the given hex
+fn snake_at(world: &mut World, x: usize, y: usize) -> Option<Entity> {
+    let target = (x as i32, y as i32);
+    let mut snakes = world.query::<(Entity, &Snake)>();
+    let mut positions = world.query::<&Position>();
+    snakes.iter(world).find(|(_, snake)| snake.segments.iter().any(|segment| positions.get(world, *segment).is_ok_and(|position| position.as_pair() == target))).map(|(head, _)| head)
+}
+
+fn ancestry_at(world: &mut World, x: usize, y: usize) -> Option<Vec<AncestryRecord>> {
+    let head = snake_at(world, x, y)?;
+    let id = world.get::<Snake>(head)?.id;
+    Some(world.resource::<Lineage>().ancestors(id))
+}
+
+// Only one snake records its thoughts at a time, think would slow down if every snake did
+fn trace_snake_at(world: &mut World, x: usize, y: usize) {
+    let Some(head) = snake_at(world, x, y) else {
+        return;
+    };
+    let traced: Vec<Entity> = world.query_filtered::<Entity, With<ThoughtTrace>>().iter(world).collect();
+    for entity in traced {
+        world.entity_mut(entity).remove::<ThoughtTrace>();
+    }
+    world.entity_mut(head).insert(ThoughtTrace::default());
 }
 
 #[derive(Debug, Clone)]
@@ -165,8 +1213,36 @@ pub enum EngineCommand {
     FlipRunningState,
     CreateSnakes(usize),
     StopSimulation,
-    UpdateSimulationConfig(SimulationConfig),
+    UpdateSimulationConfig(Box<SimulationConfig>),
     AdvanceOneFrame,
+    // at high speeds only every n-th repaint gets new draw data
+    SetDrawInterval(u32),
+    // None runs as fast as possible, 0 pauses until the next AdvanceOneFrame
+    SetSpeedLimit(Option<f32>),
+    ResizeWorld(usize, usize),
+    // spawns a new snake steered by the player, the previously controlled one goes back to its brain
+    SpawnControlledSnake,
+    SetControlledDecision(Decision),
+    // looks up the ancestry of the snake on the given column and row
+    InspectSnakeAt(usize, usize),
+    TakeSnapshot,
+    // spawns the given number of snakes with a copy of the genome
+    SpawnGenome(Box<Genome>, usize),
+    // paints or erases a wall on the given column and row
+    SetSolid { x: usize, y: usize, solid: bool },
+    AddFoodAt { x: usize, y: usize, kind: FoodKind },
+    // a snake on the given column and row, random genes without a genome
+    CreateSnakeAt { position: (usize, usize), genome: Option<Box<Genome>> },
+    // the level is process wide, every running simulation logs with it
+    SetLogLevel(LogLevel),
+    // distances between the species leaders and up to the given number of members of each specie
+    MeasureGenomeDistances(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoodKind {
+    Plant,
+    Meat,
 }
 
 #[derive(Debug, Resource)]
@@ -179,39 +1255,259 @@ pub struct EngineState {
     pub updates_done: u32,
     pub finished: bool,
     pub ignore_speed_limit: bool,
+    pub draw_interval: u32,
+    pub repaints_since_draw: u32,
+}
+
+// Running without a speed limit and drawing every frame, what headless runs that step by hand need
+impl Default for EngineState {
+    fn default() -> Self {
+        EngineState { repaint_needed: false, speed_limit: None, running: true, frames_left: 0.0, frames: 0, updates_done: 0, finished: false, ignore_speed_limit: false, draw_interval: 1, repaints_since_draw: 0 }
+    }
+}
+
+impl EngineState {
+    // Paused by the running switch or by a speed limit of 0, stepping frame by frame counts as paused too
+    pub fn is_paused(&self) -> bool {
+        !self.running || (!self.ignore_speed_limit && self.speed_limit == Some(0.0))
+    }
+
+    // Drawing is skipped only when more than one update happens per reference frame, slower runs are always drawn
+    pub fn draw_due(&self) -> bool {
+        let high_speed = self.ignore_speed_limit || self.speed_limit.is_none_or(|limit| limit > 1.0);
+        !self.running || !high_speed || self.repaints_since_draw + 1 >= self.draw_interval
+    }
 }
 
 #[derive(Resource)]
 pub struct EngineEvents {
-    pub events: Mutex<Sender<EngineEvent>>,
+    pub events: Mutex<EventSender>,
 }
 
-fn turn_counter(mut engine_state: ResMut<EngineState>) {
-    puffin::profile_function!();
+fn turn_counter(mut engine_state: ResMut<EngineState>, mut simulation_events: ResMut<SimulationEvents>, mut time: ResMut<SimTime>) {
+    profile_system!();
     if engine_state.speed_limit.is_some() && !engine_state.ignore_speed_limit {
         engine_state.frames_left -= 1.0;
     }
     engine_state.updates_done += 1;
     engine_state.frames += 1;
+    time.advance();
+    simulation_events.frame = engine_state.frames;
 }
 
 fn should_simulate_frame(engine_state: Res<EngineState>) -> bool {
     engine_state.ignore_speed_limit || engine_state.speed_limit.is_none() || (engine_state.running && engine_state.frames_left > 0.0)
 }
 
-fn should_calculate_stats(engine_state: Res<EngineState>) -> bool {
-    engine_state.frames % 100 == 0
+fn should_calculate_stats(engine_state: Res<EngineState>, config: Res<SimulationConfig>) -> bool {
+    engine_state.frames.is_multiple_of(config.stats_interval)
 }
 fn should_despawn_food(engine_state: Res<EngineState>) -> bool {
-    engine_state.frames % 10 == 0
+    engine_state.frames.is_multiple_of(10)
+}
+
+fn should_increase_age(time: Res<SimTime>) -> bool {
+    time.is_multiple_of(AGING_INTERVAL)
+}
+
+fn create_walls(world: &mut World, config: &SimulationConfig) {
+    let mut walls = wall_positions(config.wall_layout, config.columns, config.rows, config.wall_seed);
+    if config.perimeter_walls {
+        walls.extend(perimeter_positions(config.columns, config.rows));
+    }
+    for position in walls {
+        // the layout and the perimeter can share hexes
+        if *world.resource::<SolidsMap>().map.get(&position) {
+            continue;
+        }
+        world.resource_mut::<SolidsMap>().map.set(&position, true);
+        world.spawn((Solid, position));
+    }
+}
+
+// Snakes are never painted over or erased, only walls are, food under a new wall is gone
+fn set_solid(world: &mut World, x: usize, y: usize, solid: bool) {
+    let config = world.resource::<SimulationConfig>();
+    if x >= config.columns || y >= config.rows {
+        return;
+    }
+    let position = Position { x: x as i32, y: y as i32 };
+    let wall = world.query_filtered::<(Entity, &Position), (With<Solid>, Without<SegmentType>, Without<Movement>)>().iter(world)
+        .find(|(_, wall_position)| wall_position.as_pair() == position.as_pair())
+        .map(|(wall_id, _)| wall_id);
+    match (wall, solid) {
+        (None, true) => {
+            if !is_free(&position, world.resource::<SolidsMap>(), world.resource::<SegmentMap>()) {
+                return;
+            }
+            world.resource_mut::<FoodMap>().map.set(&position, Food::default());
+            world.resource_mut::<SolidsMap>().map.set(&position, true);
+            world.spawn((Solid, position));
+        }
+        (Some(wall_id), false) => {
+            world.despawn(wall_id);
+            world.resource_mut::<SolidsMap>().map.set(&position, false);
+        }
+        _ => {}
+    }
+}
+
+// Tops the hex up to what grows there or what a segment leaves behind, so painting over it again adds nothing
+fn add_food_at(world: &mut World, x: usize, y: usize, kind: FoodKind) {
+    let config = world.resource::<SimulationConfig>().clone();
+    if x >= config.columns || y >= config.rows {
+        return;
+    }
+    let position = Position { x: x as i32, y: y as i32 };
+    if !is_free(&position, world.resource::<SolidsMap>(), world.resource::<SegmentMap>()) {
+        return;
+    }
+    let mut food_map = world.resource_mut::<FoodMap>();
+    let food = food_map.map.get_mut(&position);
+    let had_food = food.contains_food();
+    match kind {
+        FoodKind::Plant => food.plant = food.plant.max(config.plant_matter_per_segment),
+        FoodKind::Meat => food.meat = food.meat.max(config.new_segment_cost),
+    }
+    if !had_food {
+        let mut queue = CommandQueue::default();
+        spawn_food_entity(&mut Commands::new(&mut queue, world), position);
+        queue.apply(world);
+    }
+}
+
+// Everything keeps living, positions outside of the new bounds wrap around just like movement does
+fn resize_world(world: &mut World, rows: usize, columns: usize) {
+    let rows = rows.max(MIN_WORLD_SIZE);
+    let columns = columns.max(MIN_WORLD_SIZE);
+    let old_config = world.resource::<SimulationConfig>().clone();
+    if old_config.rows == rows && old_config.columns == columns {
+        return;
+    }
+    let config = SimulationConfig { rows, columns, ..old_config };
+    world.insert_resource(config.clone());
+    let wrap = |position: &Position| wrap_position(columns, rows, position);
+    // walls and obstacles are laid out for the world size and scents fade quickly, all of them are simply dropped
+    let walls: Vec<Entity> = world.query_filtered::<Entity, Or<((With<Solid>, Without<SegmentType>), With<Movement>)>>().iter(world).collect();
+    let scents: Vec<Entity> = world.query_filtered::<Entity, With<Scent>>().iter(world).collect();
+    for entity in walls.into_iter().chain(scents) {
+        world.despawn(entity);
+    }
+    // the food map is the source of truth, food entities only follow it
+    let old_food_map = world.remove_resource::<FoodMap>().unwrap();
+    let mut food_map = FoodMap { map: Map2d::new(columns, rows, Food::default()) };
+    for (position, old_food) in old_food_map.map.iter().filter(|(_, food)| food.contains_food()) {
+        let cell = food_map.map.get_wrapped_mut(&position);
+        cell.plant += old_food.plant;
+        cell.meat += old_food.meat;
+    }
+    // cells that were folded onto each other only keep one food entity
+    let food: Vec<(Entity, Position)> = world.query_filtered::<(Entity, &Position), With<Food>>().iter(world).map(|(food_id, position)| (food_id, position.clone())).collect();
+    let mut occupied_cells = HashSet::new();
+    for (food_id, position) in food {
+        if !occupied_cells.insert(wrap(&position).as_pair()) {
+            world.despawn(food_id);
+        }
+    }
+    world.insert_resource(food_map);
+    for mut position in world.query::<&mut Position>().iter_mut(world) {
+        *position = wrap(&position);
+    }
+    for mut snake in world.query::<&mut Snake>().iter_mut(world) {
+        let new_position = wrap(&Position { x: snake.new_position.0, y: snake.new_position.1 });
+        let last_position = wrap(&Position { x: snake.last_position.0, y: snake.last_position.1 });
+        snake.new_position = new_position.as_pair();
+        snake.last_position = last_position.as_pair();
+    }
+    let mut solids = SolidsMap { map: Map2d::new(columns, rows, false) };
+    for (position, _) in world.query::<(&Position, &Solid)>().iter(world) {
+        solids.map.set(position, true);
+    }
+    let mut segments = SegmentMap { map: Map3d::new(columns, rows) };
+    for (segment_id, position, _) in world.query::<(Entity, &Position, &SegmentType)>().iter(world) {
+        segments.map.add(position, segment_id);
+    }
+    world.insert_resource(solids);
+    world.insert_resource(segments);
+    world.insert_resource(ScentMap { map: Map2d::new(columns, rows, 0.0) });
+    world.insert_resource(SignalMap { map: Map2d::new(columns, rows, 0.0) });
+    let old_mineral_map = world.remove_resource::<MineralMap>().unwrap();
+    let mut mineral_map = MineralMap { map: Map2d::new(columns, rows, 0.0) };
+    for (position, deposit) in old_mineral_map.map.iter().filter(|(_, deposit)| **deposit > 0.0) {
+        *mineral_map.map.get_wrapped_mut(&position) += deposit;
+    }
+    world.insert_resource(mineral_map);
+    world.insert_resource(WaterMap::new(&config));
+    create_walls(world, &config);
+    create_obstacles(world, &config);
 }
 
-fn should_increase_age(engine_state: Res<EngineState>) -> bool {
-    engine_state.frames % 10 == 0
+fn create_snakes(world: &mut World, amount: usize) -> Vec<Entity> {
+    (0..amount).filter_map(|_| {
+        let (brain, dna) = random_genes(world);
+        spawn_snake(world, brain, dna)
+    }).collect()
+}
+
+fn random_genes(world: &mut World) -> (RandomNeuralBrain, Dna) {
+    world.resource_scope(|world, mut rng: Mut<RngResource>| {
+        let outputs = ActionSet::from_config(world.resource::<SimulationConfig>()).outputs();
+        let brain = RandomNeuralBrain::new(outputs, &mut world.resource_mut::<InnovationTracker>(), &mut rng.rng);
+        let mutation = world.resource::<SimulationConfig>().mutation;
+        (brain, Dna::random(8, &mutation, &mut rng.rng))
+    })
+}
+
+// None when no free place was found
+fn spawn_snake(world: &mut World, brain: RandomNeuralBrain, dna: Dna) -> Option<Entity> {
+    let position = world.resource_scope(|world, mut rng: Mut<RngResource>| {
+        let config = world.resource::<SimulationConfig>();
+        find_free_position(&mut rng.rng, world.resource::<SolidsMap>(), world.resource::<SegmentMap>(), |rng| random_position(rng, config))
+    })?;
+    Some(place_snake(world, brain, dna, position))
+}
+
+// None when the hex is outside of the world or taken, without a genome the snake gets random genes like the starting snakes
+fn create_snake_at(world: &mut World, (x, y): (usize, usize), genome: Option<Genome>) -> Option<Entity> {
+    let config = world.resource::<SimulationConfig>();
+    if x >= config.columns || y >= config.rows {
+        return None;
+    }
+    let position = Position { x: x as i32, y: y as i32 };
+    if !is_free(&position, world.resource::<SolidsMap>(), world.resource::<SegmentMap>()) {
+        return None;
+    }
+    let (brain, dna) = match genome {
+        Some(genome) => (RandomNeuralBrain::from_neural_network(genome.network), genome.dna),
+        None => random_genes(world),
+    };
+    Some(place_snake(world, brain, dna, position))
+}
+
+fn place_snake(world: &mut World, brain: RandomNeuralBrain, dna: Dna, position: Position) -> Entity {
+    let direction = Direction::random(&mut world.resource_mut::<RngResource>().rng);
+    let run_cost = brain.get_neural_network().unwrap().run_cost();
+    let (position, meat, mut snake, age, just_born) = create_snake(100.0, position.as_pair(), direction, Box::new(brain), dna);
+    snake.metabolism.segment_basic_cost = run_cost;
+    world.spawn((position, meat, snake, age, just_born)).id()
+}
+
+fn spawn_controlled_snake(world: &mut World) {
+    let controlled: Vec<Entity> = world.query_filtered::<Entity, With<Controlled>>().iter(world).collect();
+    for entity in controlled {
+        world.entity_mut(entity).remove::<Controlled>();
+    }
+    for entity in create_snakes(world, 1) {
+        world.entity_mut(entity).insert(Controlled { decision: Decision::MoveForward });
+    }
 }
 
 impl Simulation {
-    pub fn new(name: String, engine_events: Sender<EngineEvent>, engine_commands: Option<Arc<Mutex<Receiver<EngineCommand>>>>, config: SimulationConfig) -> Self {
+    // Settings that come from files or sweeps never went through the gui, out of range values are replaced with the closest valid ones
+    pub fn new(name: String, engine_events: EventSender, engine_commands: Option<Arc<Mutex<Receiver<EngineCommand>>>>, mut config: SimulationConfig) -> Self {
+        for problem in config.clamp() {
+            warn!("{}, the simulation uses the closest valid value", problem);
+        }
         let mut world = World::new();
         let innovation_tracker = InnovationTracker::new();
         // for _ in 0..config.starting_snakes {
@@ -220,47 +1516,121 @@ impl Simulation {
         // for _ in 0..config.starting_food {
         //     world.spawn(
         // }
-        let mut solids = SolidsMap { map: Map2d::new(config.columns, config.rows, false) };
-        if config.add_walls {
-            for x in 0..config.columns {
-                let middle = config.rows / 2;
-                if x != middle && x != middle + 1 && x != middle - 1 {
-                    let position = Position { x: x as i32, y: (config.rows / 4) as i32 };
-                    solids.map.set(&position, true);
-                    world.spawn((Solid, position));
-                    let position = Position { x: x as i32, y: (2 * config.rows / 4) as i32 };
-                    solids.map.set(&position, true);
-                    world.spawn((Solid, position));
-                    let position = Position { x: x as i32, y: (3 * config.rows / 4) as i32 };
-                    solids.map.set(&position, true);
-                    world.spawn((Solid, position));
-                }
-            }
-        }
-        world.insert_resource(config);
+        world.insert_resource(config.clone());
         world.insert_resource(Stats::default());
+        world.insert_resource(StatsWorker::default());
         world.insert_resource(FoodMap { map: Map2d::new(config.columns, config.rows, Food::default()) });
-        world.insert_resource(solids);
+        world.insert_resource(SolidsMap { map: Map2d::new(config.columns, config.rows, false) });
         world.insert_resource(ScentMap { map: Map2d::new(config.columns, config.rows, 0.0) });
+        world.insert_resource(SignalMap { map: Map2d::new(config.columns, config.rows, 0.0) });
+        world.insert_resource(MineralMap { map: Map2d::new(config.columns, config.rows, 0.0) });
+        world.insert_resource(WaterMap::new(&config));
         world.insert_resource(SegmentMap { map: Map3d::new(config.columns, config.rows) });
         world.insert_resource(EngineEvents { events: Mutex::new(engine_events.clone()) });
         world.insert_resource(innovation_tracker);
         world.insert_resource(Species::default());
+        world.insert_resource(SimulationEvents::default());
+        world.insert_resource(SystemTimings::default());
+        world.insert_resource(FoodSources::default());
+        world.insert_resource(FoodController::default());
+        world.insert_resource(SpeciesQuotas::default());
+        world.insert_resource(EntityCounts::default());
+        world.insert_resource(BehaviorCounters::default());
+        world.insert_resource(ActionRound::default());
+        world.insert_resource(RegionCounters::default());
+        world.insert_resource(Lineage::default());
+        world.insert_resource(TemperatureMap::default());
+        world.insert_resource(SimTime::default());
+        world.insert_resource(RngResource::from_entropy());
+        world.insert_resource(RunStart { started_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()) });
+        world.insert_resource(RunClock::default());
+        create_walls(&mut world, &config);
+        create_obstacles(&mut world, &config);
         let mut first_schedule = Schedule::default();
         let mut core_schedule = Schedule::default();
         let mut secondary_schedule = Schedule::default();
-        first_schedule.add_systems((assign_species, starve, (assign_missing_segments, create_food, incease_move_potential, process_food), die_from_collisions, grow, add_scents).chain().run_if(should_simulate_frame));
-        core_schedule.add_systems(((think, increase_age.run_if(should_increase_age), calculate_stats.run_if(should_calculate_stats), diffuse_scents, ), (movement, update_positions, split).chain(), eat_food, destroy_old_food).chain().run_if(should_simulate_frame));
+        let mut action_schedule = Schedule::default();
+        // the systems run on the thread stepping the world, so their timings end up in this world only
+        // the multi-threaded executor would have started the pool the systems iterate in parallel with
+        ComputeTaskPool::get_or_init(TaskPool::default);
+        for schedule in [&mut first_schedule, &mut core_schedule, &mut secondary_schedule, &mut action_schedule] {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        }
+        // snakes that died earlier in the frame are despawned before the disease spreads, so they neither catch nor pass it on
+        first_schedule.add_systems((move_obstacles, assign_species, record_lineage, update_species_quotas, enforce_entity_limits, update_temperature, progress_disease, crowd, starve, die_of_old_age, (assign_missing_segments, create_food, create_minerals, incease_move_potential, process_food), decay_meat, die_from_collisions, grow, add_scents, update_signal_map, apply_deferred, spread_disease).chain().run_if(should_simulate_frame));
+        core_schedule.add_systems(((think, increase_age.run_if(should_increase_age), calculate_stats.run_if(should_calculate_stats), receive_stats, diffuse_scents, ), (steer_controlled, movement, transfer_energy, build_nests, update_positions, record_trails, split).chain(), eat_food, collect_minerals, feed_from_nests, destroy_old_food).chain().run_if(should_simulate_frame));
+        action_schedule.add_systems(((assign_solid_positions, assign_segment_positions), (think, steer_controlled, movement, transfer_energy, build_nests, update_positions, record_trails).chain(), eat_food, collect_minerals).chain().run_if(should_simulate_frame));
         secondary_schedule.add_systems(((assign_solid_positions, assign_segment_positions), (turn_counter, disperse_scents, despawn_food.run_if(should_despawn_food))).chain().run_if(should_simulate_frame));
         let gui_schedule = Schedule::default();
-        Simulation { first_schedule, core_schedule, secondary_schedule, gui_schedule, world, name, engine_events, engine_commands, has_gui: false }
+        Simulation { first_schedule, core_schedule, action_schedule, secondary_schedule, gui_schedule, world, name, engine_events, engine_commands, has_gui: false, hooks: Hooks::default() }
     }
 
     pub fn step(&mut self) {
-        puffin::profile_function!();
+        profile_system!();
+        self.world.resource_mut::<ActionRound>().round = 0;
         self.first_schedule.run(&mut self.world);
         self.core_schedule.run(&mut self.world);
+        // snakes with move potential left act again within the same frame, in the order of the rounds
+        while self.world.resource::<ActionRound>().round + 1 < MAX_ACTION_ROUNDS && self.has_pending_actions() {
+            self.world.resource_mut::<ActionRound>().round += 1;
+            self.action_schedule.run(&mut self.world);
+        }
         self.secondary_schedule.run(&mut self.world);
+        #[cfg(all(feature = "invariants", debug_assertions))]
+        crate::invariants::check_invariants(&mut self.world);
+        self.hooks.dispatch(&mut self.world);
+        self.world.resource_mut::<SystemTimings>().collect();
+    }
+
+    // Callbacks for what happens in the world, registered ones stay for the whole run
+    pub fn hooks(&mut self) -> &mut Hooks {
+        &mut self.hooks
+    }
+
+    // Steps until the predicate holds, returns the number of steps taken
+    // Without an EngineState every step simulates a frame, a speed limit set by the caller still applies
+    pub fn run_until(&mut self, mut predicate: impl FnMut(&Simulation) -> bool) -> u32 {
+        self.ensure_engine_state();
+        let mut steps = 0;
+        while !predicate(self) {
+            self.step();
+            steps += 1;
+        }
+        steps
+    }
+
+    // Calls back after every interval steps until the callback returns false, returns the number of steps taken
+    pub fn run_with_callback(&mut self, interval: u32, mut callback: impl FnMut(&mut Simulation) -> bool) -> u32 {
+        self.ensure_engine_state();
+        let mut steps = 0;
+        loop {
+            for _ in 0..interval.max(1) {
+                self.step();
+                steps += 1;
+            }
+            if !callback(self) {
+                return steps;
+            }
+        }
+    }
+
+    // Frames simulated so far
+    pub fn frame(&self) -> u32 {
+        self.world.get_resource::<EngineState>().map_or(0, |engine_state| engine_state.frames)
+    }
+
+    fn ensure_engine_state(&mut self) {
+        if !self.world.contains_resource::<EngineState>() {
+            self.world.insert_resource(EngineState::default());
+        }
+    }
+
+    // Up to a speed factor of 1 no snake gains enough potential to act twice, so the snakes don't even need to be checked
+    fn has_pending_actions(&mut self) -> bool {
+        if self.world.resource::<SimulationConfig>().speed_factor <= 1.0 {
+            return false;
+        }
+        self.world.query::<&Snake>().iter(&self.world).any(|snake| snake.energy.move_potential >= 1.0)
     }
 
     pub fn is_done(&mut self) -> bool {
@@ -269,13 +1639,24 @@ impl Simulation {
     }
 
     pub fn run(&mut self) -> EngineEvent {
-        let start_time = Instant::now();
+        let mut timings_sent = Instant::now();
+        let mut last_update = Instant::now();
+        let mut last_tick = Instant::now();
+        let mut waited = self.engine_events.waited();
+        // batch runs share the channel of the interactive one, their metadata would replace its own
+        if self.engine_commands.is_some() {
+            let _ = self.engine_events.send(EngineEvent::RunStarted { metadata: self.run_metadata() });
+        }
         while !self.is_done() {
             if let Some(commands) = match &self.engine_commands {
                 Some(arc_mutex) => arc_mutex.lock().ok(),
                 None => None
             } {
-                commands.try_iter().for_each(|command| {
+                // commands sent after a stop are left for the next simulation reading the same channel
+                while !self.world.resource::<EngineState>().finished {
+                    let Ok(command) = commands.try_recv() else {
+                        break;
+                    };
                     let mut engine_state = self.world.get_resource_mut::<EngineState>().unwrap();
                     match command {
                         EngineCommand::RepaintRequested => {
@@ -294,30 +1675,66 @@ impl Simulation {
                             engine_state.running = !engine_state.running;
                         }
                         EngineCommand::CreateSnakes(amount) => {
-                            // let config = self.world.get_resource::<SimulationConfig>().unwrap();
-                            let mut brains = vec![];
-                            for _ in 0..amount {
-                                let mut innovation_tracker = self.world.get_resource_mut::<InnovationTracker>().unwrap();
-                                brains.push(RandomNeuralBrain::new(&mut innovation_tracker));
-                            }
-                            for brain in brains {
-                                let mut rng = thread_rng();
-                                let config = self.world.get_resource::<SimulationConfig>().unwrap();
-                                let x = rng.gen_range(0..config.columns) as i32;
-                                let y = rng.gen_range(0..config.rows) as i32;
-                                {
-                                    let (a,b,mut c,d,e) = create_snake(100.0, (x, y), Box::new(brain.clone()), Dna::random(8));
-                                    c.metabolism.segment_basic_cost = brain.get_neural_network().unwrap().run_cost();
-                                    self.world.spawn((a,b,c,d,e));
-                                }
-                            }
+                            create_snakes(&mut self.world, amount);
                         }
                         EngineCommand::StopSimulation => {
                             engine_state.finished = true;
                         }
                         EngineCommand::UpdateSimulationConfig(new_config) => {
-                            self.world.remove_resource::<SimulationConfig>();
-                            self.world.insert_resource(new_config);
+                            // the maps depend on the world size, so it can only change through ResizeWorld, the water stays the one the world was created with
+                            let old_config = self.world.remove_resource::<SimulationConfig>().unwrap();
+                            let mut config = SimulationConfig { rows: old_config.rows, columns: old_config.columns, water_layout: old_config.water_layout, water_seed: old_config.water_seed, ..*new_config };
+                            config.clamp();
+                            self.world.insert_resource(config);
+                        }
+                        EngineCommand::ResizeWorld(rows, columns) => {
+                            resize_world(&mut self.world, rows, columns);
+                        }
+                        EngineCommand::SpawnControlledSnake => {
+                            spawn_controlled_snake(&mut self.world);
+                        }
+                        EngineCommand::SetControlledDecision(decision) => {
+                            for mut controlled in self.world.query::<&mut Controlled>().iter_mut(&mut self.world) {
+                                controlled.decision = decision;
+                            }
+                        }
+                        EngineCommand::SetSpeedLimit(limit) => {
+                            engine_state.speed_limit = limit;
+                            engine_state.ignore_speed_limit = false;
+                            engine_state.frames_left = 0.0;
+                        }
+                        EngineCommand::SetDrawInterval(interval) => {
+                            engine_state.draw_interval = interval.max(1);
+                            engine_state.repaints_since_draw = 0;
+                        }
+                        EngineCommand::InspectSnakeAt(x, y) => {
+                            if let Some(ancestors) = ancestry_at(&mut self.world, x, y) {
+                                self.engine_events.send(EngineEvent::Ancestry { ancestors }).unwrap();
+                            }
+                            trace_snake_at(&mut self.world, x, y);
+                        }
+                        EngineCommand::TakeSnapshot => {
+                            let snapshot = WorldSnapshot::capture(&mut self.world);
+                            self.engine_events.send(EngineEvent::Snapshot { snapshot: Box::new(snapshot) }).unwrap();
+                        }
+                        EngineCommand::MeasureGenomeDistances(members_per_species) => {
+                            let matrix = DistanceMatrix::capture(&mut self.world, members_per_species);
+                            self.engine_events.send(EngineEvent::GenomeDistances { matrix: Box::new(matrix) }).unwrap();
+                        }
+                        EngineCommand::SetLogLevel(level) => {
+                            if let Err(error) = set_log_level(level) {
+                                warn!("{}", error);
+                            }
+                        }
+                        EngineCommand::SetSolid { x, y, solid } => set_solid(&mut self.world, x, y, solid),
+                        EngineCommand::AddFoodAt { x, y, kind } => add_food_at(&mut self.world, x, y, kind),
+                        EngineCommand::CreateSnakeAt { position, genome } => {
+                            create_snake_at(&mut self.world, position, genome.map(|genome| *genome));
+                        }
+                        EngineCommand::SpawnGenome(genome, amount) => {
+                            for _ in 0..amount {
+                                spawn_snake(&mut self.world, RandomNeuralBrain::from_neural_network(genome.network.clone()), genome.dna.clone());
+                            }
                         }
                         EngineCommand::AdvanceOneFrame => {
                             engine_state.ignore_speed_limit = false;
@@ -325,25 +1742,98 @@ impl Simulation {
                             engine_state.frames_left += 1.0;
                         }
                     }
-                });
+                }
+            }
+            let elapsed = last_update.elapsed().as_secs_f32();
+            last_update = Instant::now();
+            let mut engine_state = self.world.get_resource_mut::<EngineState>().unwrap();
+            if let Some(limit) = engine_state.speed_limit.filter(|limit| engine_state.running && *limit > 0.0) {
+                // at most a second of updates is caught up after a hiccup
+                engine_state.frames_left = (engine_state.frames_left + limit * elapsed * REFERENCE_FPS).min(limit * REFERENCE_FPS);
             }
             self.step();
+            let engine_state = self.world.resource::<EngineState>();
+            let frame_drawn = engine_state.repaint_needed && engine_state.running;
+            if frame_drawn && engine_state.updates_done > 0 {
+                let frame = engine_state.frames;
+                if let Some((snake, trace)) = self.world.query::<(&Snake, &ThoughtTrace)>().iter(&self.world).next() {
+                    self.engine_events.send(EngineEvent::Thoughts { snake: snake.id, frame, trace: Box::new(trace.clone()) }).unwrap();
+                }
+            }
+            let clock = *self.world.resource::<RunClock>();
             let mut engine_state = self.world.get_resource_mut::<EngineState>().unwrap();
-            if engine_state.repaint_needed && engine_state.running {
-                engine_state.frames_left += engine_state.speed_limit.unwrap_or(0.00);
-                self.engine_events.send(EngineEvent::FrameDrawn { updates_left: engine_state.frames_left, updates_done: engine_state.updates_done }).unwrap();
+            if frame_drawn {
+                engine_state.repaints_since_draw = if engine_state.draw_due() { 0 } else { engine_state.repaints_since_draw + 1 };
+                self.engine_events.send(EngineEvent::FrameDrawn { updates_left: engine_state.frames_left, updates_done: engine_state.updates_done, clock }).unwrap();
                 engine_state.updates_done = 0;
             }
             engine_state.repaint_needed = false;
+            if frame_drawn {
+                let events = self.world.get_resource_mut::<SimulationEvents>().unwrap().drain();
+                if !events.is_empty() {
+                    self.engine_events.send(EngineEvent::SimulationEvents { events }).unwrap();
+                }
+            }
+            // only the interactive simulation reports timings, batch runs would just add noise
+            if self.engine_commands.is_some() && timings_sent.elapsed() >= TIMINGS_INTERVAL {
+                let timings = self.world.resource_mut::<SystemTimings>().drain(timings_sent.elapsed());
+                timings_sent = Instant::now();
+                self.engine_events.send(EngineEvent::SystemTimings { timings }).unwrap();
+                let diagnostics = Box::new(Diagnostics::capture(&mut self.world));
+                self.engine_events.send(EngineEvent::Diagnostics { diagnostics }).unwrap();
+            }
+            let stalled = self.engine_events.waited() - waited;
+            waited += stalled;
+            let paused = self.world.resource::<EngineState>().is_paused();
+            self.world.resource_mut::<RunClock>().record(last_tick.elapsed(), stalled, paused);
+            last_tick = Instant::now();
         }
-        let duration = start_time.elapsed().as_millis();
+        let clock = self.world.resource::<RunClock>();
+        let (duration, paused) = (clock.active.as_millis(), clock.paused.as_millis());
 
         let engine_state = self.world.get_resource::<EngineState>().unwrap();
-        let result = EngineEvent::SimulationFinished { steps: engine_state.frames, name: self.name.clone(), duration };
-        self.engine_events.send(result.clone());
+        let result = EngineEvent::SimulationFinished { steps: engine_state.frames, name: self.name.clone(), duration, paused };
+        let _ = self.engine_events.send(result.clone());
         result
     }
 
+    pub fn create_snakes(&mut self, amount: usize) {
+        create_snakes(&mut self.world, amount);
+    }
+
+    pub fn spawn_snake(&mut self, network: NeuralNetwork, dna: Dna) -> Option<Entity> {
+        spawn_snake(&mut self.world, RandomNeuralBrain::from_neural_network(network), dna)
+    }
+
+    pub fn create_snake_at(&mut self, position: (usize, usize), genome: Option<Genome>) -> Option<Entity> {
+        create_snake_at(&mut self.world, position, genome)
+    }
+
+    // Only on free hexes, food already there grows to a full portion
+    pub fn add_food_at(&mut self, x: usize, y: usize, kind: FoodKind) {
+        add_food_at(&mut self.world, x, y, kind);
+    }
+
+    pub fn spawn_controlled_snake(&mut self) {
+        spawn_controlled_snake(&mut self.world);
+    }
+
+    pub fn ancestry_at(&mut self, x: usize, y: usize) -> Option<Vec<AncestryRecord>> {
+        ancestry_at(&mut self.world, x, y)
+    }
+
+    pub fn trace_snake_at(&mut self, x: usize, y: usize) {
+        trace_snake_at(&mut self.world, x, y)
+    }
+
+    pub fn run_metadata(&self) -> RunMetadata {
+        RunMetadata::capture(&self.world)
+    }
+
+    pub fn snapshot(&mut self) -> WorldSnapshot {
+        WorldSnapshot::capture(&mut self.world)
+    }
+
     pub fn add_system<M>(&mut self, system: impl IntoSystemConfigs<M>) {
         self.core_schedule.add_systems(system);
     }
@@ -356,4 +1846,264 @@ impl Simulation {
     pub fn insert_resource<T: Resource>(&mut self, resource: T) {
         self.world.insert_resource(resource);
     }
-}
\ No newline at end of file
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+}
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::events::{event_channel, EventCapacity};
+    use crate::core::{position_at_direction, OUTPUT_NAMES, SENSOR_NAMES};
+    use super::*;
+
+    #[test]
+    fn clamping_fixes_out_of_range_values() {
+        let mut config = SimulationConfig { move_cost: -1.0, meat_decay_rate: f32::NAN, ..SimulationConfig::default() };
+        config.mutation.dna_mutation_chance = 1.5;
+        config.mutation.plant_vision_front_range = 0;
+        assert_eq!(config.validate().len(), 4);
+        assert_eq!(config.clamp().len(), 4);
+        assert_eq!((config.move_cost, config.meat_decay_rate), (0.0, 0.0));
+        assert_eq!((config.mutation.dna_mutation_chance, config.mutation.plant_vision_front_range), (1.0, 1));
+        assert!(config.validate().is_empty());
+        assert!(SimulationConfig::default().validate().is_empty());
+    }
+
+    #[test]
+    fn histogram_puts_every_value_in_a_bucket() {
+        let histogram = Histogram::from_values(&[0.0, 1.0, 2.0, 3.0, 10.0], 5);
+        assert_eq!(histogram.counts, vec![2, 2, 0, 0, 1]);
+        assert_eq!(histogram.bucket_start(1), 2.0);
+    }
+
+    #[test]
+    fn histogram_of_equal_values_has_single_filled_bucket() {
+        let histogram = Histogram::from_values(&[4.0, 4.0, 4.0], 3);
+        assert_eq!(histogram.counts, vec![3, 0, 0]);
+    }
+
+    #[test]
+    fn histogram_of_no_values_is_empty() {
+        assert!(Histogram::from_values(&[], 10).counts.is_empty());
+    }
+
+    #[test]
+    fn drawing_is_only_skipped_at_high_speed() {
+        let mut engine_state = EngineState { repaint_needed: true, speed_limit: Some(0.5), draw_interval: 3, ..EngineState::default() };
+        assert!(engine_state.draw_due());
+        engine_state.speed_limit = Some(4.0);
+        assert!(!engine_state.draw_due());
+        engine_state.repaints_since_draw = 2;
+        assert!(engine_state.draw_due());
+        engine_state.repaints_since_draw = 0;
+        engine_state.running = false;
+        assert!(engine_state.draw_due());
+    }
+
+    #[test]
+    fn runs_stop_at_the_predicate_and_call_back_every_interval() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Steps".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(5));
+        simulation.create_snakes(3);
+        assert_eq!(simulation.run_until(|simulation| simulation.frame() >= 7), 7);
+        assert_eq!(simulation.frame(), 7);
+        let mut seen = vec![];
+        let steps = simulation.run_with_callback(5, |simulation| {
+            seen.push((simulation.frame(), simulation.snakes().count()));
+            simulation.frame() < 20
+        });
+        assert_eq!(steps, 15);
+        assert_eq!(seen.iter().map(|(frame, _)| *frame).collect::<Vec<_>>(), vec![12, 17, 22]);
+        assert!(seen.iter().all(|(_, snakes)| *snakes > 0));
+    }
+
+    #[test]
+    fn traced_snake_records_its_inputs_network_and_decision() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Trace".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(2));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(3);
+        simulation.step();
+        let world = simulation.world_mut();
+        let heads: Vec<(i32, i32)> = world.query_filtered::<&Position, With<Snake>>().iter(world).map(Position::as_pair).collect();
+        for (x, y) in &heads {
+            simulation.trace_snake_at(*x as usize, *y as usize);
+        }
+        simulation.step();
+        let world = simulation.world_mut();
+        let traces: Vec<&ThoughtTrace> = world.query::<&ThoughtTrace>().iter(world).collect();
+        assert_eq!(traces.len(), 1);
+        let trace = traces[0];
+        assert_eq!(trace.inputs.len(), crate::core::SENSOR_NAMES.len());
+        assert_eq!(trace.node_values.len(), trace.node_types.len());
+        // compared bit by bit as a stomach that can't hold anything gives NaN levels
+        let bits = |values: &[f32]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&trace.node_values[..trace.inputs.len()]), bits(&trace.inputs));
+        assert!(trace.decision.is_some());
+    }
+
+    #[test]
+    fn stopping_leaves_later_commands_for_the_next_simulation() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let (commands, receiver) = std::sync::mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut simulation = Simulation::new("Stop".to_string(), engine_events, Some(Arc::clone(&receiver)), SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() });
+        simulation.insert_resource(EngineState::default());
+        commands.send(EngineCommand::StopSimulation).unwrap();
+        commands.send(EngineCommand::CreateSnakes(3)).unwrap();
+        assert!(matches!(simulation.run(), EngineEvent::SimulationFinished { .. }));
+        assert!(matches!(receiver.lock().unwrap().try_recv(), Ok(EngineCommand::CreateSnakes(3))));
+    }
+
+    #[test]
+    fn mobile_snakes_act_several_times_per_frame_and_sluggish_ones_wait() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Speed".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 20, speed_factor: 3.0, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(3));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(2);
+        let world = simulation.world_mut();
+        let heads: Vec<Entity> = world.query_filtered::<Entity, With<Snake>>().iter(world).collect();
+        for (head, (mobility, row)) in heads.iter().zip([(1.0, 2), (0.1, 8)]) {
+            let mut entity = world.entity_mut(*head);
+            entity.insert((Position { x: 0, y: row }, Controlled { decision: Decision::MoveForward }));
+            let mut snake = entity.get_mut::<Snake>().unwrap();
+            snake.direction = Direction::East;
+            snake.metabolism.mobility = mobility;
+            snake.energy.move_potential = 0.0;
+            snake.new_position = (0, row);
+        }
+        let columns = |simulation: &mut Simulation| heads.iter().map(|head| simulation.world_mut().get::<Position>(*head).unwrap().x).collect::<Vec<i32>>();
+        simulation.step();
+        assert_eq!(columns(&mut simulation), vec![3, 0]);
+        simulation.step();
+        simulation.step();
+        assert_eq!(columns(&mut simulation), vec![9, 0]);
+        simulation.step();
+        assert_eq!(columns(&mut simulation), vec![12, 1]);
+    }
+
+    #[test]
+    fn resizing_keeps_snakes_alive_and_in_bounds() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Resize".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 20, wall_layout: WallLayout::Bars, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(1));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(5);
+        for _ in 0..10 {
+            simulation.step();
+        }
+        let world = simulation.world_mut();
+        let snakes = world.query::<&Snake>().iter(world).count();
+        assert!(world.query::<&Snake>().iter(world).all(|snake| snake.id > 0));
+        let total_food = |world: &World| world.resource::<FoodMap>().map.map.iter().map(|food| food.plant + food.meat).sum::<f32>();
+        let food_before = total_food(world);
+        resize_world(world, 12, 8);
+        assert!((total_food(world) - food_before).abs() < 0.01);
+        assert_eq!(world.query::<&Snake>().iter(world).count(), snakes);
+        assert!(world.query::<&Position>().iter(world).all(|position| position.x >= 0 && position.x < 8 && position.y >= 0 && position.y < 12));
+        assert_eq!(world.resource::<FoodMap>().map.width, 8);
+        assert_eq!(world.resource::<FoodMap>().map.height, 12);
+        for _ in 0..10 {
+            simulation.step();
+        }
+    }
+
+    #[test]
+    fn wide_worlds_wrap_and_keep_everything_in_bounds() {
+        let config = SimulationConfig { rows: 10, columns: 40, wall_layout: WallLayout::Maze, create_scents: true, ..SimulationConfig::default() };
+        assert_eq!(position_at_direction(&Direction::East, &Position { x: 39, y: 4 }, &config).as_pair(), (0, 4));
+        assert_eq!(position_at_direction(&Direction::NorthWest, &Position { x: 5, y: 0 }, &config).as_pair(), (5, 9));
+        assert_eq!(position_at_direction(&Direction::SouthEast, &Position { x: 39, y: 8 }, &config).as_pair(), (0, 9));
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Wide".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(3));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(10);
+        for _ in 0..100 {
+            simulation.step();
+        }
+        let world = simulation.world_mut();
+        assert!(world.query::<&Position>().iter(world).all(|position| position.x >= 0 && position.x < 40 && position.y >= 0 && position.y < 10));
+        assert_eq!(world.resource::<SegmentMap>().map.width, 40);
+        assert_eq!(world.resource::<SegmentMap>().map.height, 10);
+    }
+
+    #[test]
+    fn walls_are_painted_and_erased_one_hex_at_a_time() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Walls".to_string(), engine_events, None, SimulationConfig { rows: 10, columns: 10, starting_snakes: 0, perimeter_walls: true, ..SimulationConfig::default() });
+        let world = simulation.world_mut();
+        let walls = |world: &mut World| world.query_filtered::<&Position, (With<Solid>, Without<SegmentType>)>().iter(world).count();
+        assert_eq!(walls(world), 36);
+        let center = Position { x: 5, y: 5 };
+        world.resource_mut::<FoodMap>().map.set(&center, Food::from_plant(1.0));
+        set_solid(world, 5, 5, true);
+        set_solid(world, 5, 5, true);
+        assert_eq!(walls(world), 37);
+        assert!(*world.resource::<SolidsMap>().map.get(&center));
+        assert!(!world.resource::<FoodMap>().map.get(&center).contains_food());
+        set_solid(world, 0, 0, false);
+        set_solid(world, 5, 5, false);
+        set_solid(world, 20, 5, true);
+        assert_eq!(walls(world), 35);
+        assert!(!*world.resource::<SolidsMap>().map.get(&center));
+    }
+
+    #[test]
+    fn food_is_placed_only_on_free_hexes() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Food".to_string(), engine_events, None, SimulationConfig { rows: 10, columns: 10, starting_snakes: 0, perimeter_walls: true, ..SimulationConfig::default() });
+        let world = simulation.world_mut();
+        let config = world.resource::<SimulationConfig>().clone();
+        add_food_at(world, 5, 5, FoodKind::Plant);
+        add_food_at(world, 5, 5, FoodKind::Plant);
+        add_food_at(world, 5, 5, FoodKind::Meat);
+        add_food_at(world, 0, 0, FoodKind::Meat);
+        let food = world.resource::<FoodMap>().map.get(&Position { x: 5, y: 5 }).clone();
+        assert_eq!((food.plant, food.meat), (config.plant_matter_per_segment, config.new_segment_cost));
+        assert!(!world.resource::<FoodMap>().map.get(&Position { x: 0, y: 0 }).contains_food());
+        #[cfg(not(feature = "map_food"))]
+        assert_eq!(world.query_filtered::<&Position, With<Food>>().iter(world).count(), 1);
+    }
+
+    #[test]
+    fn snakes_are_created_on_the_chosen_free_hex() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Placement".to_string(), engine_events, None, SimulationConfig { rows: 10, columns: 10, starting_snakes: 0, perimeter_walls: true, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(3));
+        let mut rng = StdRng::seed_from_u64(3);
+        let network = NeuralNetwork::random_brain(SENSOR_NAMES.len(), OUTPUT_NAMES.len(), 0.5, &mut InnovationTracker::new(), &mut rng);
+        let genome = Genome { network, dna: Dna::random(8, &MutationConfig::default(), &mut rng) };
+        let placed = simulation.create_snake_at((4, 6), Some(genome.clone())).unwrap();
+        let random = simulation.create_snake_at((2, 2), None).unwrap();
+        assert!(simulation.create_snake_at((0, 3), None).is_none());
+        assert!(simulation.create_snake_at((10, 3), None).is_none());
+        let world = simulation.world_mut();
+        assert_eq!(world.get::<Position>(placed).unwrap().as_pair(), (4, 6));
+        assert_eq!(world.get::<Position>(random).unwrap().as_pair(), (2, 2));
+        assert_eq!(world.get::<Snake>(placed).unwrap().dna, genome.dna);
+    }
+
+    #[test]
+    fn aging_curves_keep_young_snakes_at_full_efficiency() {
+        for curve in AgingCurve::ALL {
+            assert_eq!(curve.efficiency(1_000, 1_000), 1.0);
+            let old = curve.efficiency(1_800, 1_000);
+            assert!((MIN_EFFICIENCY..1.0).contains(&old), "{:?} gives {}", curve, old);
+            assert!(curve.efficiency(5_000, 1_000) <= old);
+        }
+        assert_eq!(AgingCurve::Inverse.efficiency(4_000, 1_000), 0.25);
+        assert_eq!(AgingCurve::Linear.efficiency(1_500, 1_000), 0.5);
+        assert_eq!(AgingCurve::Step.efficiency(1_001, 1_000), MIN_EFFICIENCY);
+    }
+}
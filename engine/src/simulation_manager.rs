@@ -1,5 +1,4 @@
 use rayon::prelude::*;
-use std::sync::mpsc::{channel, Sender};
 use crate::simulation::{Simulation, EngineEvent};
 
 pub fn simulate_batch(simulations: Vec<Simulation>) -> Vec<EngineEvent> {
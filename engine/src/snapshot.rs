@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fmt;
+use bevy_ecs::prelude::World;
+use serde::{Deserialize, Serialize};
+use crate::arena::Genome;
+use crate::compact;
+use crate::core::{calculate_gene_difference, FoodMap, Snake, Species};
+use crate::migration::migrate_config;
+use crate::simulation::{RunMetadata, SimulationConfig, SimulationEvents};
+
+const SNAPSHOT_TAG: &[u8; 4] = b"HXSN";
+
+// Summary of a world that is small enough to be saved during a long run and compared later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub frame: u32,
+    pub total_plants: f32,
+    pub total_meat: f32,
+    pub config: SimulationConfig,
+    pub snakes: Vec<SnakeSnapshot>,
+    pub species: Vec<SpecieSnapshot>,
+    // missing in snapshots saved before runs were traced
+    #[serde(default)]
+    pub run: Option<RunMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnakeSnapshot {
+    pub id: u64,
+    pub specie: Option<u32>,
+    pub generation: u32,
+    pub energy: f32,
+    pub segments: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecieSnapshot {
+    pub id: u32,
+    pub members: usize,
+    pub leader: Genome,
+}
+
+impl WorldSnapshot {
+    pub fn capture(world: &mut World) -> WorldSnapshot {
+        let snakes = world.query::<&Snake>().iter(world).map(|snake| SnakeSnapshot {
+            id: snake.id,
+            specie: snake.species,
+            generation: snake.generation,
+            energy: snake.energy.energy,
+            segments: snake.segments.len(),
+        }).collect();
+        let species = world.resource::<Species>().species.iter().map(|specie| SpecieSnapshot {
+            id: specie.id,
+            members: specie.members.len(),
+            leader: Genome { network: specie.leader_network.clone(), dna: specie.leader_dna.clone() },
+        }).collect();
+        let food_map = world.resource::<FoodMap>();
+        WorldSnapshot {
+            frame: world.resource::<SimulationEvents>().frame,
+            total_plants: food_map.map.map.iter().map(|food| food.plant).sum(),
+            total_meat: food_map.map.map.iter().map(|food| food.meat).sum(),
+            config: world.resource::<SimulationConfig>().clone(),
+            snakes,
+            species,
+            run: Some(RunMetadata::capture(world)),
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|error| error.to_string())
+    }
+
+    // The settings in the snapshot are migrated like saved settings
+    pub fn from_toml(content: &str) -> Result<WorldSnapshot, String> {
+        let mut table: toml::Table = content.parse().map_err(|error: toml::de::Error| error.to_string())?;
+        if let Some(toml::Value::Table(config)) = table.get_mut("config") {
+            migrate_config(config)?;
+        }
+        toml::Value::Table(table).try_into().map_err(|error: toml::de::Error| error.to_string())
+    }
+
+    // Several times smaller and faster than toml for worlds with many snakes, the settings stay toml inside so they are migrated as well
+    pub fn to_binary(&self) -> Result<Vec<u8>, String> {
+        let config = self.config.to_toml()?;
+        compact::encode(SNAPSHOT_TAG, &(self.frame, self.total_plants, self.total_meat, config, &self.snakes, &self.species, &self.run))
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<WorldSnapshot, String> {
+        let (frame, total_plants, total_meat, config, snakes, species, run): (u32, f32, f32, String, _, _, _) = compact::decode(SNAPSHOT_TAG, bytes)?;
+        let config = SimulationConfig::from_toml(&config)?;
+        Ok(WorldSnapshot { frame, total_plants, total_meat, config, snakes, species, run })
+    }
+
+    // Reads a snapshot file of either format
+    pub fn from_bytes(bytes: &[u8]) -> Result<WorldSnapshot, String> {
+        if compact::is_compact(bytes, SNAPSHOT_TAG) {
+            return WorldSnapshot::from_binary(bytes);
+        }
+        WorldSnapshot::from_toml(std::str::from_utf8(bytes).map_err(|error| error.to_string())?)
+    }
+}
+
+// Values of the older snapshot come first
+#[derive(Debug, Clone)]
+pub struct SnapshotDiff {
+    pub frames: (u32, u32),
+    pub snakes: (usize, usize),
+    pub segments: (usize, usize),
+    pub max_generation: (u32, u32),
+    pub plants: (f32, f32),
+    pub meat: (f32, f32),
+    pub extinct_species: Vec<u32>,
+    pub new_species: Vec<u32>,
+    // species in both snapshots with the compatibility distance between their leaders
+    pub leader_distances: Vec<(u32, f32)>,
+}
+
+// The leader distance uses the species coefficients of the newer snapshot
+pub fn diff_snapshots(before: &WorldSnapshot, after: &WorldSnapshot) -> SnapshotDiff {
+    let both = |value: &dyn Fn(&WorldSnapshot) -> usize| (value(before), value(after));
+    let segments = |snapshot: &WorldSnapshot| snapshot.snakes.iter().map(|snake| snake.segments).sum();
+    let max_generation = |snapshot: &WorldSnapshot| snapshot.snakes.iter().map(|snake| snake.generation).max().unwrap_or(0);
+    let leaders: HashMap<u32, &Genome> = before.species.iter().map(|specie| (specie.id, &specie.leader)).collect();
+    let newer: HashMap<u32, &Genome> = after.species.iter().map(|specie| (specie.id, &specie.leader)).collect();
+    SnapshotDiff {
+        frames: (before.frame, after.frame),
+        snakes: both(&|snapshot| snapshot.snakes.len()),
+        segments: both(&segments),
+        max_generation: (max_generation(before), max_generation(after)),
+        plants: (before.total_plants, after.total_plants),
+        meat: (before.total_meat, after.total_meat),
+        extinct_species: before.species.iter().map(|specie| specie.id).filter(|id| !newer.contains_key(id)).collect(),
+        new_species: after.species.iter().map(|specie| specie.id).filter(|id| !leaders.contains_key(id)).collect(),
+        leader_distances: after.species.iter().filter_map(|specie| {
+            leaders.get(&specie.id).map(|leader| (specie.id, calculate_gene_difference(&leader.network, &specie.leader.network, &after.config)))
+        }).collect(),
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = |species: &[u32]| if species.is_empty() { "none".to_string() } else { species.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ") };
+        writeln!(f, "{:<15} | {:>10} | {:>10} | {:>10}", "", "before", "after", "change")?;
+        writeln!(f, "{:<15} | {:>10} | {:>10} | {:>+10}", "frame", self.frames.0, self.frames.1, self.frames.1 as i64 - self.frames.0 as i64)?;
+        writeln!(f, "{:<15} | {:>10} | {:>10} | {:>+10}", "snakes", self.snakes.0, self.snakes.1, self.snakes.1 as i64 - self.snakes.0 as i64)?;
+        writeln!(f, "{:<15} | {:>10} | {:>10} | {:>+10}", "segments", self.segments.0, self.segments.1, self.segments.1 as i64 - self.segments.0 as i64)?;
+        writeln!(f, "{:<15} | {:>10} | {:>10} | {:>+10}", "max generation", self.max_generation.0, self.max_generation.1, self.max_generation.1 as i64 - self.max_generation.0 as i64)?;
+        writeln!(f, "{:<15} | {:>10.1} | {:>10.1} | {:>+10.1}", "plants", self.plants.0, self.plants.1, self.plants.1 - self.plants.0)?;
+        writeln!(f, "{:<15} | {:>10.1} | {:>10.1} | {:>+10.1}", "meat", self.meat.0, self.meat.1, self.meat.1 - self.meat.0)?;
+        writeln!(f, "extinct species: {}", ids(&self.extinct_species))?;
+        writeln!(f, "new species: {}", ids(&self.new_species))?;
+        for (specie, distance) in &self.leader_distances {
+            writeln!(f, "specie {} leader moved by {:.3}", specie, distance)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{event_channel, EventCapacity};
+    use crate::core::RngResource;
+    use crate::simulation::{EngineState, Simulation};
+    use super::*;
+
+    #[test]
+    fn snapshots_survive_toml_and_diff_a_run() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("Snapshot".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(5));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(6);
+        simulation.step();
+        let before = WorldSnapshot::from_toml(&simulation.snapshot().to_toml().unwrap()).unwrap();
+        assert_eq!(before.run.as_ref().map(|run| run.seed), Some(5));
+        let binary = WorldSnapshot::from_bytes(&simulation.snapshot().to_binary().unwrap()).unwrap();
+        assert_eq!(binary.run.as_ref().map(|run| run.seed), Some(5));
+        assert!(diff_snapshots(&before, &binary).leader_distances.iter().all(|(_, distance)| *distance == 0.0));
+        assert_eq!(binary.snakes.len(), before.snakes.len());
+        for _ in 0..30 {
+            simulation.step();
+        }
+        let after = simulation.snapshot();
+        let diff = diff_snapshots(&before, &after);
+        assert_eq!(diff.snakes, (before.snakes.len(), after.snakes.len()));
+        assert!(diff.frames.1 > diff.frames.0);
+        let identical = diff_snapshots(&after, &after);
+        assert!(identical.extinct_species.is_empty() && identical.new_species.is_empty());
+        assert_eq!(identical.leader_distances.len(), after.species.len());
+        assert!(identical.leader_distances.iter().all(|(_, distance)| *distance == 0.0));
+        assert!(diff.to_string().contains("snakes"));
+    }
+}
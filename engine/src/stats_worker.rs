@@ -0,0 +1,240 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use bevy_ecs::prelude::*;
+use crate::core::{FoodMap, Position, region_stats, Species, HISTOGRAM_BUCKETS};
+use crate::profile_system;
+use crate::simulation::{BehaviorStats, Histogram, SimulationConfig, SpecieStats, Stats};
+use crate::time::SimTime;
+
+// Everything the statistics are calculated from, the maps and settings are only borrowed when it is summed up right away and copied out of the world for another thread
+pub struct StatsSample<'a> {
+    pub(crate) frame: u32,
+    pub(crate) time: SimTime,
+    pub(crate) snakes: Vec<SnakeSample>,
+    pub(crate) species: Species,
+    pub(crate) food_map: Cow<'a, FoodMap>,
+    pub(crate) heads: Vec<Position>,
+    pub(crate) deaths: Vec<Position>,
+    pub(crate) total_segments: usize,
+    pub(crate) total_scents: usize,
+    pub(crate) total_entities: usize,
+    // the counters are reset when they are read, so they are summarized right away
+    pub(crate) behavior: BehaviorStats,
+    pub(crate) config: Cow<'a, SimulationConfig>,
+}
+
+pub(crate) struct SnakeSample {
+    pub(crate) age: u32,
+    pub(crate) generation: u32,
+    pub(crate) mutations: u32,
+    pub(crate) species: Option<u32>,
+    pub(crate) energy: f32,
+    pub(crate) plants_in_stomach: f32,
+    pub(crate) meat_in_stomach: f32,
+    pub(crate) segments: usize,
+    pub(crate) connections: usize,
+    pub(crate) outputs: Vec<f32>,
+}
+
+impl StatsSample<'_> {
+    pub(crate) fn into_owned(self) -> StatsSample<'static> {
+        StatsSample { food_map: Cow::Owned(self.food_map.into_owned()), config: Cow::Owned(self.config.into_owned()), ..self }
+    }
+
+    // Fills in every statistic taken from the sample, the ones counted while simulating are left alone
+    pub fn summarize(self, stats: &mut Stats) {
+        let snakes = &self.snakes;
+        stats.frame = self.frame;
+        stats.time = self.time;
+        stats.oldest_snake = snakes.iter().map(|snake| snake.age).max().unwrap_or(0);
+        stats.total_snakes = snakes.len();
+        stats.total_food = self.food_map.map.map.iter().filter(|food| food.contains_food()).count();
+        stats.total_segments = self.total_segments;
+        stats.total_scents = self.total_scents;
+        stats.max_generation = snakes.iter().map(|snake| snake.generation).max().unwrap_or(0);
+        stats.max_mutations = snakes.iter().map(|snake| snake.mutations).max().unwrap_or(0);
+        stats.total_entities = self.total_entities;
+        stats.total_snake_energy = snakes.iter().map(|snake| snake.energy).sum();
+        stats.total_plants_in_stomachs = snakes.iter().map(|snake| snake.plants_in_stomach).sum();
+        stats.total_meat_in_stomachs = snakes.iter().map(|snake| snake.meat_in_stomach).sum();
+        stats.total_plants = self.food_map.map.map.iter().map(|food| food.plant).sum();
+        stats.total_meat = self.food_map.map.map.iter().map(|food| food.meat).sum();
+        stats.total_energy = stats.total_snake_energy + stats.total_plants * self.config.plant_energy_content + stats.total_meat * self.config.meat_energy_content;
+        let mut by_specie: HashMap<u32, (usize, f32, Vec<f32>)> = HashMap::new();
+        for snake in snakes {
+            if let Some(specie) = snake.species {
+                let (population, energy, outputs) = by_specie.entry(specie).or_default();
+                *population += 1;
+                *energy += snake.energy;
+                // members that didn't think yet count as all zeros
+                outputs.resize(outputs.len().max(snake.outputs.len()), 0.0);
+                for (sum, output) in outputs.iter_mut().zip(&snake.outputs) {
+                    // an output that is not a number is never chosen, e.g. after an input divided by zero
+                    if output.is_finite() {
+                        *sum += output;
+                    }
+                }
+            }
+        }
+        stats.species_stats = self.species.species.iter().map(|specie| {
+            let (population, energy, outputs) = by_specie.remove(&specie.id).unwrap_or_default();
+            let mean = |sum: f32| if population > 0 { sum / population as f32 } else { 0.0 };
+            SpecieStats { id: specie.id, population, mean_energy: mean(energy), mean_outputs: outputs.into_iter().map(mean).collect() }
+        }).collect();
+        let distribution = |value: fn(&SnakeSample) -> f32| Histogram::from_values(&snakes.iter().map(value).collect::<Vec<f32>>(), HISTOGRAM_BUCKETS);
+        stats.age_distribution = distribution(|snake| snake.age as f32);
+        stats.energy_distribution = distribution(|snake| snake.energy);
+        stats.segment_distribution = distribution(|snake| snake.segments as f32);
+        stats.connection_distribution = distribution(|snake| snake.connections as f32);
+        stats.behavior = self.behavior;
+        stats.regions = region_stats(&self.heads, &self.deaths, &self.food_map, &self.config);
+        stats.species = self.species;
+    }
+}
+
+// Sums up samples on its own thread, started with the first sample
+#[derive(Resource, Default)]
+pub struct StatsWorker {
+    channels: Mutex<Option<(Sender<StatsSample<'static>>, Receiver<Stats>)>>,
+}
+
+impl StatsWorker {
+    pub(crate) fn send(&mut self, sample: StatsSample<'static>) {
+        let (samples, _) = self.channels.get_mut().unwrap().get_or_insert_with(|| {
+            let (samples, samples_receiver) = channel::<StatsSample<'static>>();
+            let (summaries_sender, summaries) = channel();
+            // ends when the world and with it the worker is dropped
+            thread::spawn(move || {
+                for sample in samples_receiver {
+                    let mut summary = Stats::default();
+                    sample.summarize(&mut summary);
+                    if summaries_sender.send(summary).is_err() {
+                        break;
+                    }
+                }
+            });
+            (samples, summaries)
+        });
+        let _ = samples.send(sample);
+    }
+
+    // Only the newest summary matters when several finished since the last frame
+    fn latest(&mut self) -> Option<Stats> {
+        self.channels.get_mut().unwrap().as_ref().and_then(|(_, summaries)| summaries.try_iter().last())
+    }
+}
+
+pub fn receive_stats(mut stats: ResMut<Stats>, mut worker: ResMut<StatsWorker>) {
+    profile_system!();
+    if let Some(summary) = worker.latest() {
+        copy_summary(&mut stats, summary);
+    }
+}
+
+// Everything StatsSample::summarize fills in
+fn copy_summary(stats: &mut Stats, summary: Stats) {
+    stats.frame = summary.frame;
+    stats.time = summary.time;
+    stats.oldest_snake = summary.oldest_snake;
+    stats.total_snakes = summary.total_snakes;
+    stats.total_food = summary.total_food;
+    stats.total_segments = summary.total_segments;
+    stats.total_scents = summary.total_scents;
+    stats.max_generation = summary.max_generation;
+    stats.max_mutations = summary.max_mutations;
+    stats.species = summary.species;
+    stats.total_entities = summary.total_entities;
+    stats.total_snake_energy = summary.total_snake_energy;
+    stats.total_plants_in_stomachs = summary.total_plants_in_stomachs;
+    stats.total_meat_in_stomachs = summary.total_meat_in_stomachs;
+    stats.total_plants = summary.total_plants;
+    stats.total_meat = summary.total_meat;
+    stats.total_energy = summary.total_energy;
+    stats.species_stats = summary.species_stats;
+    stats.age_distribution = summary.age_distribution;
+    stats.energy_distribution = summary.energy_distribution;
+    stats.segment_distribution = summary.segment_distribution;
+    stats.connection_distribution = summary.connection_distribution;
+    stats.behavior = summary.behavior;
+    stats.regions = summary.regions;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use bevy_ecs::system::RunSystemOnce;
+    use crate::events::{event_channel, EventCapacity};
+    use crate::simulation::{EngineState, Simulation};
+    use crate::core::{RngResource, OUTPUT_NAMES};
+    use super::*;
+
+    fn run(background_stats: bool) -> Simulation {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let config = SimulationConfig { rows: 20, columns: 20, stats_interval: 5, background_stats, ..SimulationConfig::default() };
+        let mut simulation = Simulation::new("Stats".to_string(), engine_events, None, config);
+        simulation.insert_resource(RngResource::from_seed(8));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(4);
+        for _ in 0..11 {
+            simulation.step();
+        }
+        simulation
+    }
+
+    #[test]
+    fn background_stats_match_the_ones_summed_on_the_simulation_thread() {
+        let inline = run(false);
+        let mut background = run(true);
+        let inline_stats = inline.world().resource::<Stats>();
+        assert_eq!(inline_stats.frame, 10);
+        // the last sample is summed up while the simulation goes on
+        let started = Instant::now();
+        while background.world().resource::<Stats>().frame != 10 && started.elapsed() < Duration::from_secs(10) {
+            background.world_mut().run_system_once(receive_stats);
+        }
+        // only the summarized part, the mutation stats are kept in a hash map that is printed in any order
+        let summarized = |stats: &Stats| {
+            let mut summary = Stats::default();
+            copy_summary(&mut summary, stats.clone());
+            format!("{:?}", summary)
+        };
+        assert_eq!(background.world().resource::<Stats>().frame, 10);
+        assert_eq!(summarized(background.world().resource::<Stats>()), summarized(inline_stats));
+        assert_ne!(summarized(inline_stats), summarized(&Stats::default()));
+    }
+
+    #[test]
+    fn species_average_the_outputs_of_their_members() {
+        let simulation = run(false);
+        let species_stats = &simulation.world().resource::<Stats>().species_stats;
+        assert!(!species_stats.is_empty());
+        for specie in species_stats.iter().filter(|specie| specie.population > 0) {
+            // outputs of disabled actions are only added to the networks once the action is turned on
+            assert!((1..=OUTPUT_NAMES.len()).contains(&specie.mean_outputs.len()));
+            assert!(specie.mean_outputs.iter().all(|output| output.is_finite()));
+        }
+        let sample = |specie, outputs| SnakeSample { age: 0, generation: 0, mutations: 0, species: Some(specie), energy: 0.0, plants_in_stomach: 0.0, meat_in_stomach: 0.0, segments: 1, connections: 0, outputs };
+        let mut species = Species::default();
+        species.species = simulation.world().resource::<Stats>().species.species.iter().take(1).cloned().collect();
+        let specie = species.species[0].id;
+        let mut stats = Stats::default();
+        StatsSample {
+            frame: 0,
+            time: SimTime::default(),
+            snakes: vec![sample(specie, vec![1.0, 0.0]), sample(specie, vec![f32::NAN, 0.5]), sample(specie, vec![])],
+            species,
+            food_map: Cow::Borrowed(simulation.world().resource::<FoodMap>()),
+            heads: vec![],
+            deaths: vec![],
+            total_segments: 0,
+            total_scents: 0,
+            total_entities: 0,
+            behavior: BehaviorStats::default(),
+            config: Cow::Owned(SimulationConfig::default()),
+        }.summarize(&mut stats);
+        assert_eq!(stats.species_stats[0].mean_outputs, vec![1.0 / 3.0, 0.5 / 3.0]);
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+use bevy_ecs::prelude::Resource;
+use crate::simulation::SimulationConfig;
+
+// ages only grow every few ticks, so every age is a multiple of this
+pub const AGING_INTERVAL: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+// Clock of the simulation, every simulated frame is one tick, how long a day and a year is comes from the config
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SimTime {
+    pub ticks: u64,
+}
+
+impl SimTime {
+    pub fn advance(&mut self) {
+        self.ticks += 1;
+    }
+
+    pub fn is_multiple_of(&self, ticks: u32) -> bool {
+        self.ticks.is_multiple_of(ticks as u64)
+    }
+
+    pub fn days(&self, config: &SimulationConfig) -> f64 {
+        self.ticks as f64 / config.ticks_per_day as f64
+    }
+
+    pub fn years(&self, config: &SimulationConfig) -> f64 {
+        self.days(config) / config.days_per_year as f64
+    }
+
+    // How much of the current year has passed, from 0 up to but not including 1
+    pub fn year_progress(&self, config: &SimulationConfig) -> f64 {
+        self.years(config).fract()
+    }
+
+    // Every year starts with spring and the seasons are equally long
+    pub fn season(&self, config: &SimulationConfig) -> Season {
+        match (self.year_progress(config) * 4.0) as usize {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    pub fn calendar(&self, config: &SimulationConfig) -> Calendar {
+        let day = self.ticks / config.ticks_per_day as u64;
+        Calendar { year: day / config.days_per_year as u64 + 1, day: day % config.days_per_year as u64 + 1, season: self.season(config) }
+    }
+}
+
+// The date of a tick, years and days are counted from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calendar {
+    pub year: u64,
+    pub day: u64,
+    pub season: Season,
+}
+
+impl fmt::Display for Calendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Year {}, day {} ({:?})", self.year, self.day, self.season)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_turned_into_days_years_and_seasons() {
+        let config = SimulationConfig { ticks_per_day: 10, days_per_year: 8, ..SimulationConfig::default() };
+        let time = SimTime { ticks: 125 };
+        assert_eq!(time.days(&config), 12.5);
+        assert_eq!(time.calendar(&config), Calendar { year: 2, day: 5, season: Season::Autumn });
+        assert_eq!(SimTime::default().calendar(&config).to_string(), "Year 1, day 1 (Spring)");
+        assert_eq!(SimTime { ticks: 79 }.season(&config), Season::Winter);
+    }
+}
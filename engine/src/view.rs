@@ -0,0 +1,107 @@
+use bevy_ecs::prelude::*;
+use crate::arena::Genome;
+use crate::core::{Age, Decision, Direction, Energy, Metabolism, Position, Snake};
+use crate::dna::Dna;
+use crate::neural::NeuralNetwork;
+use crate::simulation::Simulation;
+
+// What a snake looks like from outside the engine, it borrows the world so it can't outlive the next step
+pub struct SnakeView<'w> {
+    pub entity: Entity,
+    // 0 until the snake was alive for one step
+    pub id: u64,
+    pub parent_id: Option<u64>,
+    pub species: Option<u32>,
+    pub generation: u32,
+    pub position: &'w Position,
+    pub direction: &'w Direction,
+    pub decision: Decision,
+    pub age: u32,
+    pub energy: &'w Energy,
+    pub metabolism: &'w Metabolism,
+    pub dna: &'w Dna,
+    pub network: Option<&'w NeuralNetwork>,
+    segments: &'w [Entity],
+    world: &'w World,
+}
+
+impl<'w> SnakeView<'w> {
+    fn new(entity: Entity, snake: &'w Snake, position: &'w Position, age: &'w Age, world: &'w World) -> Self {
+        SnakeView {
+            entity,
+            id: snake.id,
+            parent_id: snake.parent_id,
+            species: snake.species,
+            generation: snake.generation,
+            position,
+            direction: &snake.direction,
+            decision: snake.decision,
+            age: age.age,
+            energy: &snake.energy,
+            metabolism: &snake.metabolism,
+            dna: &snake.dna,
+            network: snake.brain.get_neural_network(),
+            segments: &snake.segments,
+            world,
+        }
+    }
+
+    // The head included
+    pub fn length(&self) -> usize {
+        self.segments.len()
+    }
+
+    // From the head to the tail
+    pub fn segment_positions(&self) -> impl Iterator<Item = &'w Position> + '_ {
+        self.segments.iter().filter_map(|segment| self.world.get::<Position>(*segment))
+    }
+
+    // A copy that can be saved or spawned into another simulation
+    pub fn genome(&self) -> Option<Genome> {
+        self.network.map(|network| Genome { network: network.clone(), dna: self.dna.clone() })
+    }
+}
+
+impl Simulation {
+    // Every living snake, in no particular order
+    pub fn snakes(&self) -> impl Iterator<Item = SnakeView<'_>> {
+        let world = self.world();
+        world.iter_entities().filter_map(move |entity| {
+            let snake = entity.get::<Snake>()?;
+            Some(SnakeView::new(entity.id(), snake, entity.get::<Position>()?, entity.get::<Age>()?, world))
+        })
+    }
+
+    pub fn snake(&self, id: u64) -> Option<SnakeView<'_>> {
+        self.snakes().find(|snake| snake.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::events::{event_channel, EventCapacity};
+    use crate::core::RngResource;
+    use crate::simulation::{EngineState, SimulationConfig};
+    use super::*;
+
+    #[test]
+    fn snakes_are_seen_without_queries() {
+        let (engine_events, _) = event_channel(EventCapacity::default());
+        let mut simulation = Simulation::new("View".to_string(), engine_events, None, SimulationConfig { rows: 20, columns: 20, ..SimulationConfig::default() });
+        simulation.insert_resource(RngResource::from_seed(3));
+        simulation.insert_resource(EngineState::default());
+        simulation.create_snakes(3);
+        simulation.step();
+        let snakes: Vec<SnakeView> = simulation.snakes().collect();
+        assert_eq!(snakes.len(), 3);
+        for snake in &snakes {
+            assert_ne!(snake.id, 0);
+            assert!(snake.position.x < 20 && snake.position.y < 20);
+            assert_eq!(snake.segment_positions().next().map(Position::as_pair), Some(snake.position.as_pair()));
+            assert_eq!(snake.segment_positions().count(), snake.length());
+            assert_eq!(snake.genome().unwrap().dna, *snake.dna);
+        }
+        assert_eq!(simulation.snake(snakes[1].id).map(|snake| snake.entity), Some(snakes[1].entity));
+        assert!(simulation.snake(u64::MAX).is_none());
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeSet;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::core::Position;
+use crate::simulation::WallLayout;
+
+// distance between the walls of neighbouring maze cells
+const MAZE_CELL_SIZE: usize = 6;
+// rooms are only divided further while both halves keep at least this size
+const MIN_ROOM_SIZE: usize = 8;
+const DOOR_WIDTH: usize = 3;
+// free hexes between the turns of the spiral
+const SPIRAL_SPACING: usize = 5;
+
+// Every hex covered by a wall, the same seed always gives the same walls
+pub fn wall_positions(layout: WallLayout, columns: usize, rows: usize, seed: u64) -> Vec<Position> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut walls = BTreeSet::new();
+    match layout {
+        WallLayout::None => {}
+        WallLayout::Bars => bars(&mut walls, columns, rows),
+        WallLayout::Maze => maze(&mut walls, columns, rows, &mut rng),
+        WallLayout::Rooms => divide(&mut walls, (0, 0), (columns, rows), &mut rng),
+        WallLayout::Spiral => spiral(&mut walls, columns, rows, &mut rng),
+    }
+    walls.into_iter().filter(|(x, y)| *x < columns && *y < rows).map(|(x, y)| Position { x: x as i32, y: y as i32 }).collect()
+}
+
+// The first and last column and row, they stop the world from wrapping around
+pub fn perimeter_positions(columns: usize, rows: usize) -> Vec<Position> {
+    let mut walls = BTreeSet::new();
+    for x in 0..columns {
+        walls.insert((x, 0));
+        walls.insert((x, rows.saturating_sub(1)));
+    }
+    for y in 0..rows {
+        walls.insert((0, y));
+        walls.insert((columns.saturating_sub(1), y));
+    }
+    walls.into_iter().map(|(x, y)| Position { x: x as i32, y: y as i32 }).collect()
+}
+
+// Three horizontal bars with a gap in the middle
+fn bars(walls: &mut BTreeSet<(usize, usize)>, columns: usize, rows: usize) {
+    let middle = columns / 2;
+    for x in (0..columns).filter(|x| x.abs_diff(middle) > 1) {
+        for y in [rows / 4, 2 * rows / 4, 3 * rows / 4] {
+            walls.insert((x, y));
+        }
+    }
+}
+
+// A grid of cells with walls on every side, a random depth first walk knocks out the walls between visited cells
+fn maze(walls: &mut BTreeSet<(usize, usize)>, columns: usize, rows: usize, rng: &mut StdRng) {
+    let (width, height) = ((columns / MAZE_CELL_SIZE).max(1), (rows / MAZE_CELL_SIZE).max(1));
+    // the last cell in a row or column takes the hexes left over
+    let start = |cell: usize| cell * MAZE_CELL_SIZE;
+    let end = |cell: usize, cells: usize, size: usize| if cell + 1 == cells { size } else { start(cell + 1) };
+    let mut open = BTreeSet::new();
+    let mut visited = vec![false; width * height];
+    let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+    visited[0] = true;
+    while let Some(&(x, y)) = stack.last() {
+        let mut neighbours: Vec<(usize, usize)> = [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)].into_iter()
+            .filter(|(nx, ny)| *nx < width && *ny < height && !visited[ny * width + nx])
+            .collect();
+        neighbours.shuffle(rng);
+        match neighbours.first() {
+            Some(&(nx, ny)) => {
+                visited[ny * width + nx] = true;
+                open.insert(((x, y).min((nx, ny)), (x, y).max((nx, ny))));
+                stack.push((nx, ny));
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    for cy in 0..height {
+        for cx in 0..width {
+            let (x0, x1) = (start(cx), end(cx, width, columns));
+            let (y0, y1) = (start(cy), end(cy, height, rows));
+            // only the west and north sides, the world wraps so they are also the east and south sides of the neighbours
+            walls.insert((x0, y0));
+            if cx == 0 || !open.contains(&((cx - 1, cy), (cx, cy))) {
+                walls.extend((y0 + 1..y1).map(|y| (x0, y)));
+            }
+            if cy == 0 || !open.contains(&((cx, cy - 1), (cx, cy))) {
+                walls.extend((x0 + 1..x1).map(|x| (x, y0)));
+            }
+        }
+    }
+}
+
+// Splits the area with a wall that has a door in it and keeps splitting both halves until the rooms get too small
+fn divide(walls: &mut BTreeSet<(usize, usize)>, (x0, y0): (usize, usize), (width, height): (usize, usize), rng: &mut StdRng) {
+    let vertical = width > height || (width == height && rng.gen_bool(0.5));
+    let (length, across) = if vertical { (height, width) } else { (width, height) };
+    if across < 2 * MIN_ROOM_SIZE + 1 || length <= DOOR_WIDTH {
+        return;
+    }
+    let wall = rng.gen_range(MIN_ROOM_SIZE..across - MIN_ROOM_SIZE);
+    let door = rng.gen_range(0..=length - DOOR_WIDTH);
+    for along in (0..length).filter(|along| !(door..door + DOOR_WIDTH).contains(along)) {
+        walls.insert(if vertical { (x0 + wall, y0 + along) } else { (x0 + along, y0 + wall) });
+    }
+    if vertical {
+        divide(walls, (x0, y0), (wall, height), rng);
+        divide(walls, (x0 + wall + 1, y0), (width - wall - 1, height), rng);
+    } else {
+        divide(walls, (x0, y0), (width, wall), rng);
+        divide(walls, (x0, y0 + wall + 1), (width, height - wall - 1), rng);
+    }
+}
+
+// A square spiral winding out of the middle until it leaves the world, the seed picks the starting direction and the turning side
+fn spiral(walls: &mut BTreeSet<(usize, usize)>, columns: usize, rows: usize, rng: &mut StdRng) {
+    let mut directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    if rng.gen_bool(0.5) {
+        directions.reverse();
+    }
+    directions.rotate_left(rng.gen_range(0..4));
+    let (mut x, mut y) = (columns as i64 / 2, rows as i64 / 2);
+    let inside = |x: i64, y: i64| x >= 0 && y >= 0 && x < columns as i64 && y < rows as i64;
+    // every two turns the arms get longer by the spacing plus the wall itself
+    for turn in 0.. {
+        let (dx, dy) = directions[turn % 4];
+        let length = (turn / 2 + 1) * (SPIRAL_SPACING + 1);
+        for _ in 0..length {
+            if !inside(x, y) {
+                return;
+            }
+            walls.insert((x as usize, y as usize));
+            x += dx;
+            y += dy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts_are_reproducible_and_stay_inside_the_world() {
+        for layout in [WallLayout::Bars, WallLayout::Maze, WallLayout::Rooms, WallLayout::Spiral] {
+            let walls = wall_positions(layout, 60, 40, 3);
+            assert!(!walls.is_empty(), "{:?} has no walls", layout);
+            assert!(walls.len() < 60 * 40 / 2, "{:?} covers most of the world", layout);
+            assert!(walls.iter().all(|position| position.x >= 0 && position.y >= 0 && position.x < 60 && position.y < 40));
+            assert_eq!(wall_positions(layout, 60, 40, 3).iter().map(Position::as_pair).collect::<Vec<_>>(), walls.iter().map(Position::as_pair).collect::<Vec<_>>());
+        }
+        assert!(wall_positions(WallLayout::None, 60, 40, 3).is_empty());
+        let other_seed: Vec<(i32, i32)> = wall_positions(WallLayout::Maze, 60, 40, 4).iter().map(Position::as_pair).collect();
+        assert_ne!(other_seed, wall_positions(WallLayout::Maze, 60, 40, 3).iter().map(Position::as_pair).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn perimeter_covers_every_edge_hex_once() {
+        let walls = perimeter_positions(10, 6);
+        assert_eq!(walls.len(), 2 * 10 + 2 * 6 - 4);
+        assert!(walls.iter().all(|position| position.x == 0 || position.y == 0 || position.x == 9 || position.y == 5));
+    }
+}
@@ -0,0 +1,133 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use bevy_ecs::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::core::{Direction, Map2d, position_at_direction, Position, Snake};
+use crate::simulation::{SimulationConfig, WaterLayout};
+
+// lakes are spread so there is about one for every this many hexes
+const HEXES_PER_LAKE: usize = 1_000;
+const RIVER_WIDTH: usize = 3;
+
+// Hexes covered by water, only snakes with a fin can enter them
+#[derive(Resource)]
+pub struct WaterMap {
+    pub map: Map2d<bool>,
+}
+
+impl WaterMap {
+    pub fn new(config: &SimulationConfig) -> Self {
+        let mut map = Map2d::new(config.columns, config.rows, false);
+        for position in water_positions(config.water_layout, config.columns, config.rows, config.water_seed) {
+            map.set(&position, true);
+        }
+        WaterMap { map }
+    }
+}
+
+// Every hex covered by water, the same seed always gives the same water
+pub fn water_positions(layout: WaterLayout, columns: usize, rows: usize, seed: u64) -> Vec<Position> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut water = BTreeSet::new();
+    match layout {
+        WaterLayout::None => {}
+        WaterLayout::Lakes => lakes(&mut water, columns, rows, &mut rng),
+        WaterLayout::Rivers => rivers(&mut water, columns, rows, &mut rng),
+    }
+    water.into_iter().filter(|(x, y)| *x < columns && *y < rows).map(|(x, y)| Position { x: x as i32, y: y as i32 }).collect()
+}
+
+// Round lakes of random sizes scattered over the world
+fn lakes(water: &mut BTreeSet<(usize, usize)>, columns: usize, rows: usize, rng: &mut StdRng) {
+    let max_radius = (columns.min(rows) / 8).max(2) as i32;
+    for _ in 0..(columns * rows / HEXES_PER_LAKE).max(1) {
+        let (center_x, center_y) = (rng.gen_range(0..columns) as i32, rng.gen_range(0..rows) as i32);
+        let radius = rng.gen_range(1..=max_radius);
+        for y in (center_y - radius).max(0)..=(center_y + radius) {
+            for x in (center_x - radius).max(0)..=(center_x + radius) {
+                if (x - center_x).pow(2) + (y - center_y).pow(2) <= radius.pow(2) {
+                    water.insert((x as usize, y as usize));
+                }
+            }
+        }
+    }
+}
+
+// Two rivers winding from the top to the bottom split the world into two halves that only swimmers can cross
+fn rivers(water: &mut BTreeSet<(usize, usize)>, columns: usize, rows: usize, rng: &mut StdRng) {
+    for start in [columns / 4, 3 * columns / 4] {
+        // each river stays in its half of the world
+        let (min_x, max_x) = (start.saturating_sub(columns / 8), start + columns / 8);
+        let mut x = start;
+        for y in 0..rows {
+            for width in 0..RIVER_WIDTH {
+                water.insert((x + width, y));
+            }
+            // the world wraps, so the river winds back to where it started before the last row
+            let rows_left = rows - y;
+            x = if x.abs_diff(start) + 1 >= rows_left {
+                match x.cmp(&start) {
+                    Ordering::Greater => x - 1,
+                    Ordering::Less => x + 1,
+                    Ordering::Equal => x,
+                }
+            } else {
+                (x as i32 + rng.gen_range(-1..=1)).clamp(min_x as i32, max_x as i32) as usize
+            };
+        }
+    }
+}
+
+pub fn can_enter(snake: &Snake, position: &Position, water_map: &WaterMap) -> bool {
+    snake.metabolism.swims || !*water_map.map.get(position)
+}
+
+// Closer water gives a stronger signal, walls don't block the view
+pub fn see_water(direction: &Direction, position: &Position, range: u32, water_map: &WaterMap, config: &SimulationConfig) -> f32 {
+    if config.water_layout == WaterLayout::None {
+        return 0.0;
+    }
+    let mut current_vision_position = position.clone();
+    for current_range in 0..range {
+        current_vision_position = position_at_direction(direction, &current_vision_position, config);
+        if *water_map.map.get(&current_vision_position) {
+            return (range - current_range) as f32 / range as f32;
+        }
+    }
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use crate::core::test_snake;
+    use super::*;
+
+    #[test]
+    fn water_blocks_snakes_without_fins() {
+        for layout in [WaterLayout::Lakes, WaterLayout::Rivers] {
+            let water = water_positions(layout, 60, 40, 3);
+            assert!(!water.is_empty(), "{:?} has no water", layout);
+            assert!(water.iter().all(|position| position.x >= 0 && position.y >= 0 && position.x < 60 && position.y < 40));
+            assert_eq!(water_positions(layout, 60, 40, 3).iter().map(Position::as_pair).collect::<Vec<_>>(), water.iter().map(Position::as_pair).collect::<Vec<_>>());
+        }
+        assert!(water_positions(WaterLayout::None, 60, 40, 3).is_empty());
+        // every row is crossed by both rivers, so no snake walks around them
+        let rivers = water_positions(WaterLayout::Rivers, 60, 40, 3);
+        assert!((0..40).all(|y| rivers.iter().filter(|position| position.y == y).count() == 2 * RIVER_WIDTH));
+        let config = SimulationConfig { rows: 10, columns: 10, water_layout: WaterLayout::Rivers, ..SimulationConfig::default() };
+        let water_map = WaterMap::new(&config);
+        let mut rng = StdRng::seed_from_u64(1);
+        let (_, _, mut snake, _, _) = test_snake((0, 0), &mut rng);
+        let river = Position { x: 2, y: 0 };
+        assert!(*water_map.map.get(&river));
+        assert!(!can_enter(&snake, &river, &water_map));
+        assert!(can_enter(&snake, &Position { x: 0, y: 0 }, &water_map));
+        assert_eq!(see_water(&Direction::East, &Position { x: 0, y: 0 }, 4, &water_map, &config), 0.75);
+        assert_eq!(see_water(&Direction::East, &Position { x: 0, y: 0 }, 4, &water_map, &SimulationConfig { rows: 10, columns: 10, ..SimulationConfig::default() }), 0.0);
+        snake.metabolism.swims = true;
+        assert!(can_enter(&snake, &river, &water_map));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use egui::Ui;
+use hex_brains_engine::simulation::{SimulationEvent, SimulationEventKind};
+
+// rate (events per second) at which a sound reaches full volume
+const FULL_VOLUME_RATE: f32 = 20.0;
+const MIN_RELATIVE_VOLUME: f32 = 0.1;
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+// the same sound is never played more often than this, bursts only get louder
+const MIN_REPEAT_DELAY: Duration = Duration::from_millis(150);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Sound {
+    Birth,
+    Death,
+    Extinction,
+}
+
+impl Sound {
+    const ALL: [Sound; 3] = [Sound::Birth, Sound::Death, Sound::Extinction];
+
+    fn from_event(kind: &SimulationEventKind) -> Option<Sound> {
+        match kind {
+            SimulationEventKind::SnakeBorn { .. } => Some(Sound::Birth),
+            SimulationEventKind::SnakeDied { .. } => Some(Sound::Death),
+            SimulationEventKind::SpecieExtinct { .. } => Some(Sound::Extinction),
+            _ => None,
+        }
+    }
+
+    fn frequency(&self) -> f32 {
+        match self {
+            Sound::Birth => 880.0,
+            Sound::Death => 220.0,
+            Sound::Extinction => 110.0,
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        match self {
+            Sound::Birth => Duration::from_millis(40),
+            Sound::Death => Duration::from_millis(60),
+            Sound::Extinction => Duration::from_millis(400),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AudioSettings {
+    pub enabled: bool,
+    pub volume: f32,
+    pub births: bool,
+    pub deaths: bool,
+    pub extinctions: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            enabled: false,
+            volume: 0.3,
+            births: false,
+            deaths: true,
+            extinctions: true,
+        }
+    }
+}
+
+impl AudioSettings {
+    fn is_enabled(&self, sound: Sound) -> bool {
+        match sound {
+            Sound::Birth => self.births,
+            Sound::Death => self.deaths,
+            Sound::Extinction => self.extinctions,
+        }
+    }
+}
+
+struct SoundChannel {
+    sound: Sound,
+    recent: VecDeque<Instant>,
+    last_played: Option<Instant>,
+}
+
+impl SoundChannel {
+    fn rate(&self) -> f32 {
+        self.recent.len() as f32 / RATE_WINDOW.as_secs_f32()
+    }
+}
+
+pub struct AudioFeedback {
+    pub settings: AudioSettings,
+    channels: Vec<SoundChannel>,
+    // output device is opened lazily when sound gets enabled for the first time
+    backend: Option<backend::Backend>,
+    backend_error: Option<String>,
+}
+
+impl AudioFeedback {
+    pub fn new() -> Self {
+        AudioFeedback {
+            settings: AudioSettings::default(),
+            channels: Sound::ALL.iter().map(|sound| SoundChannel { sound: *sound, recent: VecDeque::new(), last_played: None }).collect(),
+            backend: None,
+            backend_error: None,
+        }
+    }
+
+    pub fn on_events(&mut self, events: &[SimulationEvent]) {
+        if !self.settings.enabled {
+            return;
+        }
+        let now = Instant::now();
+        for event in events {
+            if let Some(sound) = Sound::from_event(&event.kind) {
+                self.channels[sound as usize].recent.push_back(now);
+            }
+        }
+        self.play_pending(now);
+    }
+
+    fn play_pending(&mut self, now: Instant) {
+        if self.backend.is_none() && self.backend_error.is_none() {
+            match backend::Backend::new() {
+                Ok(backend) => self.backend = Some(backend),
+                Err(error) => self.backend_error = Some(error),
+            }
+        }
+        for channel in self.channels.iter_mut() {
+            while channel.recent.front().is_some_and(|time| now.duration_since(*time) > RATE_WINDOW) {
+                channel.recent.pop_front();
+            }
+            if channel.recent.is_empty() || !self.settings.is_enabled(channel.sound) {
+                continue;
+            }
+            if channel.last_played.is_some_and(|time| now.duration_since(time) < MIN_REPEAT_DELAY) {
+                continue;
+            }
+            let relative_volume = (channel.rate() / FULL_VOLUME_RATE).clamp(MIN_RELATIVE_VOLUME, 1.0);
+            if let Some(backend) = &self.backend {
+                backend.play(channel.sound.frequency(), channel.sound.duration(), self.settings.volume * relative_volume);
+            }
+            channel.last_played = Some(now);
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.settings.enabled, "Enable sounds");
+        ui.add_enabled_ui(self.settings.enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Volume");
+                ui.add(egui::Slider::new(&mut self.settings.volume, 0.0..=1.0));
+            });
+            ui.checkbox(&mut self.settings.births, "Births");
+            ui.checkbox(&mut self.settings.deaths, "Deaths");
+            ui.checkbox(&mut self.settings.extinctions, "Extinctions");
+        });
+        ui.label("Sounds get louder the more often their events happen, so population crashes are easy to notice.");
+        if let Some(error) = &self.backend_error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+mod backend {
+    use std::time::Duration;
+    use rodio::{OutputStream, OutputStreamHandle, Source};
+    use rodio::source::SineWave;
+
+    pub struct Backend {
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+    }
+
+    impl Backend {
+        pub fn new() -> Result<Self, String> {
+            let (stream, handle) = OutputStream::try_default().map_err(|error| format!("Unable to open audio output: {}", error))?;
+            Ok(Backend { _stream: stream, handle })
+        }
+
+        pub fn play(&self, frequency: f32, duration: Duration, volume: f32) {
+            let source = SineWave::new(frequency).take_duration(duration).amplify(volume);
+            let _ = self.handle.play_raw(source);
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod backend {
+    use std::time::Duration;
+
+    pub struct Backend;
+
+    impl Backend {
+        pub fn new() -> Result<Self, String> {
+            Err("Built without audio support, rebuild with `--features audio` to hear sounds".to_string())
+        }
+
+        pub fn play(&self, _frequency: f32, _duration: Duration, _volume: f32) {}
+    }
+}
@@ -0,0 +1,36 @@
+use std::iter::once;
+use egui::{Sense, Ui};
+use hex_brains_engine::dna::{Dna, SegmentType};
+use hex_brains_engine::simulation::HexType;
+use crate::{hex_color, Config};
+use crate::palette::SpeciesColors;
+
+const RADIUS: f32 = 7.0;
+
+// The head in the color of its specie followed by the segments it grows until it is long enough to split
+pub fn body_plan_ui(ui: &mut Ui, specie: u32, dna: &Dna, size_to_split: usize, config: &Config, species_colors: &SpeciesColors) {
+    let segments = dna.body_plan(size_to_split.saturating_sub(1));
+    let names: Vec<&str> = segments.iter().map(segment_name).collect();
+    let hexes: Vec<HexType> = once(HexType::SnakeHead { specie }).chain(segments.into_iter().map(|segment_type| HexType::Segment { segment_type })).collect();
+    ui.horizontal(|ui| {
+        ui.label("Body plan");
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(2.0 * RADIUS * hexes.len() as f32, 2.0 * RADIUS), Sense::hover());
+        for (index, hex_type) in hexes.iter().enumerate() {
+            let center = rect.left_center() + egui::vec2(RADIUS * (2 * index + 1) as f32, 0.0);
+            ui.painter().circle_filled(center, RADIUS - 1.0, hex_color(hex_type, config, species_colors));
+        }
+        response.on_hover_text(format!("Head, {}", names.join(", ")));
+    });
+}
+
+fn segment_name(segment_type: &SegmentType) -> &'static str {
+    match segment_type {
+        SegmentType::Muscle(_) => "muscle",
+        SegmentType::Solid(_) => "solid",
+        SegmentType::Solar(_) => "solar",
+        SegmentType::Stomach(_) => "stomach",
+        SegmentType::Fin(_) => "fin",
+        SegmentType::Eye(_) => "eye",
+        SegmentType::Nose(_) => "nose",
+    }
+}
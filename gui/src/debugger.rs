@@ -0,0 +1,75 @@
+use egui::{ScrollArea, Ui};
+use hex_brains_engine::core::{ThoughtTrace, OUTPUT_NAMES, SENSOR_NAMES};
+use hex_brains_engine::neural::NodeType;
+
+pub struct Debugger {
+    snake: u64,
+    frame: u32,
+    trace: Option<ThoughtTrace>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger { snake: 0, frame: 0, trace: None }
+    }
+
+    pub fn record(&mut self, snake: u64, frame: u32, trace: ThoughtTrace) {
+        self.snake = snake;
+        self.frame = frame;
+        self.trace = Some(trace);
+    }
+
+    // Returns true when the next frame was requested
+    pub fn ui(&mut self, ui: &mut Ui) -> bool {
+        let step = ui.horizontal(|ui| {
+            let step = ui.button("Step").on_hover_text("Advance the simulation by one frame, same as pressing 'a'").clicked();
+            ui.label("Click on a snake to follow its thoughts");
+            step
+        }).inner;
+        let Some(trace) = &self.trace else {
+            return step;
+        };
+        ui.label(format!("Snake {} at frame {} decided to {} and signals {:.2}", self.snake, self.frame, trace.decision.map_or("-".to_string(), |decision| format!("{:?}", decision)), trace.signal));
+        ui.label(format!("Age {} with {:.0}% efficiency", trace.age, trace.efficiency * 100.0));
+        ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                egui::Grid::new("debugger_inputs").striped(true).num_columns(2).show(ui, |ui| {
+                    ui.strong("Input");
+                    ui.strong("Value");
+                    ui.end_row();
+                    for (index, value) in trace.inputs.iter().enumerate() {
+                        ui.label(SENSOR_NAMES.get(index).copied().unwrap_or("Unknown"));
+                        ui.label(format!("{:.3}", value));
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+                egui::Grid::new("debugger_nodes").striped(true).num_columns(2).show(ui, |ui| {
+                    ui.strong("Node");
+                    ui.strong("Value");
+                    ui.end_row();
+                    if trace.node_values.is_empty() {
+                        ui.label("This brain has no network");
+                        ui.end_row();
+                    }
+                    // inputs are already listed, outputs come before the hidden nodes
+                    let nodes = trace.node_types.iter().zip(&trace.node_values).filter(|(node_type, _)| **node_type != NodeType::Input);
+                    let (mut outputs, mut hidden) = (0, 0);
+                    for (node_type, value) in nodes {
+                        let name = if *node_type == NodeType::Output {
+                            outputs += 1;
+                            OUTPUT_NAMES.get(outputs - 1).map_or(format!("Output {}", outputs), |name| name.to_string())
+                        } else {
+                            hidden += 1;
+                            format!("Hidden {}", hidden)
+                        };
+                        ui.label(name);
+                        ui.label(format!("{:.3}", value));
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+        step
+    }
+}
@@ -0,0 +1,79 @@
+use eframe::epaint::Color32;
+use egui::Ui;
+use hex_brains_engine::diagnostics::Diagnostics;
+
+// Entity counts and memory of the world, compared with the report before so growing counts stand out
+pub struct DiagnosticsPanel {
+    latest: Option<Diagnostics>,
+    previous: Option<Diagnostics>,
+    // most events that were waiting in the channel at a single repaint, since the latest report and up to it
+    backlog: usize,
+    reported_backlog: usize,
+}
+
+impl DiagnosticsPanel {
+    pub fn new() -> Self {
+        DiagnosticsPanel { latest: None, previous: None, backlog: 0, reported_backlog: 0 }
+    }
+
+    pub fn record(&mut self, diagnostics: Diagnostics) {
+        self.previous = self.latest.replace(diagnostics);
+        self.reported_backlog = self.backlog;
+        self.backlog = 0;
+    }
+
+    // called on every repaint with the number of events that were waiting
+    pub fn on_events_received(&mut self, events: usize) {
+        self.backlog = self.backlog.max(events);
+    }
+
+    pub fn ui(&self, ui: &mut Ui) {
+        let Some(latest) = &self.latest else {
+            ui.label("Nothing reported yet, the engine reports once per second while the simulation runs");
+            return;
+        };
+        ui.label(format!("{} entities, about {} in components and maps", latest.entities, format_bytes(latest.total_bytes())));
+        ui.label(format!("{} events buffered for the next repaint, at most {} events waiting at a repaint", latest.buffered_events, self.reported_backlog))
+            .on_hover_text("A backlog that keeps growing means the gui can't keep up with the engine");
+        ui.separator();
+        let previous_count = |name: &str| self.previous.as_ref().and_then(|previous| previous.components.iter().find(|(component, _, _)| *component == name)).map(|(_, count, _)| *count);
+        egui::Grid::new("diagnostics_components").striped(true).num_columns(4).show(ui, |ui| {
+            ui.strong("Component");
+            ui.strong("Entities");
+            ui.strong("Change");
+            ui.strong("Memory");
+            ui.end_row();
+            for (name, count, bytes) in &latest.components {
+                ui.label(*name);
+                ui.label(count.to_string());
+                match previous_count(name).map(|previous| *count as i64 - previous as i64) {
+                    Some(change) if change > 0 => ui.colored_label(Color32::LIGHT_RED, format!("+{}", change)),
+                    Some(change) => ui.label(change.to_string()),
+                    None => ui.label(""),
+                };
+                ui.label(format_bytes(*bytes));
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        egui::Grid::new("diagnostics_maps").striped(true).num_columns(2).show(ui, |ui| {
+            ui.strong("Map");
+            ui.strong("Memory");
+            ui.end_row();
+            for (name, bytes) in &latest.maps {
+                ui.label(*name);
+                ui.label(format_bytes(*bytes));
+                ui.end_row();
+            }
+        });
+        ui.label("Memory is a rough estimate, what the components point to, like the brains of the snakes, is not included.");
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        bytes if bytes >= 1 << 20 => format!("{:.1} MB", bytes as f32 / (1 << 20) as f32),
+        bytes if bytes >= 1 << 10 => format!("{:.1} KB", bytes as f32 / (1 << 10) as f32),
+        bytes => format!("{} B", bytes),
+    }
+}
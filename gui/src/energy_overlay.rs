@@ -0,0 +1,38 @@
+use eframe::emath::{self, Pos2, Rect};
+use eframe::epaint::Color32;
+use egui::{Shape, Stroke};
+use hex_brains_engine::energy_grid::EnergyGrid;
+use crate::{cell_size, Config};
+
+// blocks are split into this many levels of energy, the borders between levels are drawn as contour lines
+const BANDS: f32 = 5.0;
+
+// Shades every block by its share of the richest block, with contour lines around regions of similar energy
+pub fn energy_overlay(grid: &EnergyGrid, to_screen: &emath::RectTransform, config: &Config) -> Vec<Shape> {
+    let max = grid.max();
+    if max <= 0.0 {
+        return vec![];
+    }
+    let level = |column: usize, row: usize| grid.energy(column, row) / max;
+    let band = |column: usize, row: usize| (level(column, row) * BANDS).min(BANDS - 1.0) as u32;
+    // blocks at the edges are cut off where the world ends
+    let corner = |column: usize, row: usize| to_screen * Pos2 {
+        x: (column * grid.cell).min(config.columns) as f32 * cell_size(config),
+        y: (row * grid.cell).min(config.rows) as f32 * cell_size(config),
+    };
+    let contour = Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 255, 255, 120));
+    let mut shapes = vec![];
+    for row in 0..grid.rows {
+        for column in 0..grid.columns {
+            let alpha = (level(column, row) * 120.0) as u8;
+            shapes.push(Shape::rect_filled(Rect::from_two_pos(corner(column, row), corner(column + 1, row + 1)), 0.0, Color32::from_rgba_unmultiplied(255, 200, 0, alpha)));
+            if column + 1 < grid.columns && band(column, row) != band(column + 1, row) {
+                shapes.push(Shape::line_segment([corner(column + 1, row), corner(column + 1, row + 1)], contour));
+            }
+            if row + 1 < grid.rows && band(column, row) != band(column, row + 1) {
+                shapes.push(Shape::line_segment([corner(column, row + 1), corner(column + 1, row + 1)], contour));
+            }
+        }
+    }
+    shapes
+}
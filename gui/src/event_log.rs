@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use egui::{ScrollArea, TextEdit, Ui};
+use hex_brains_engine::simulation::{DeathCause, SimulationEvent, SimulationEventKind};
+
+const MAX_LOG_ENTRIES: usize = 5_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EventCategory {
+    Births,
+    Deaths,
+    Species,
+    Records,
+    Engine,
+}
+
+impl EventCategory {
+    pub const ALL: [EventCategory; 5] = [EventCategory::Births, EventCategory::Deaths, EventCategory::Species, EventCategory::Records, EventCategory::Engine];
+
+    fn label(&self) -> &'static str {
+        match self {
+            EventCategory::Births => "Births",
+            EventCategory::Deaths => "Deaths",
+            EventCategory::Species => "Species",
+            EventCategory::Records => "Records",
+            EventCategory::Engine => "Engine",
+        }
+    }
+}
+
+pub struct LogEntry {
+    pub frame: Option<u32>,
+    pub category: EventCategory,
+    pub message: String,
+}
+
+pub struct EventLog {
+    entries: VecDeque<LogEntry>,
+    shown_categories: [bool; EventCategory::ALL.len()],
+    text_filter: String,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog {
+            entries: VecDeque::new(),
+            // births are very frequent, so they are hidden until asked for
+            shown_categories: [false, true, true, true, true],
+            text_filter: String::new(),
+        }
+    }
+
+    pub fn add_simulation_events(&mut self, events: Vec<SimulationEvent>) {
+        for event in events {
+            let (category, message) = describe(&event.kind);
+            self.push(LogEntry { frame: Some(event.frame), category, message });
+        }
+    }
+
+    pub fn add_engine_message(&mut self, message: String) {
+        self.push(LogEntry { frame: None, category: EventCategory::Engine, message });
+    }
+
+    fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() >= MAX_LOG_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    fn is_shown(&self, entry: &LogEntry) -> bool {
+        self.shown_categories[entry.category as usize] && (self.text_filter.is_empty() || entry.message.contains(self.text_filter.as_str()))
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            for category in EventCategory::ALL {
+                ui.checkbox(&mut self.shown_categories[category as usize], category.label());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.add(TextEdit::singleline(&mut self.text_filter).desired_width(150.0));
+            if ui.button("Clear log").clicked() {
+                self.entries.clear();
+            }
+        });
+        ui.separator();
+        let shown: Vec<&LogEntry> = self.entries.iter().filter(|entry| self.is_shown(entry)).collect();
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, shown.len(), |ui, row_range| {
+                for entry in &shown[row_range] {
+                    match entry.frame {
+                        Some(frame) => ui.label(format!("[{}] {}", frame, entry.message)),
+                        None => ui.label(&entry.message),
+                    };
+                }
+            });
+    }
+}
+
+fn describe(kind: &SimulationEventKind) -> (EventCategory, String) {
+    match kind {
+        SimulationEventKind::SnakeBorn { snake, generation, mutations } => {
+            (EventCategory::Births, format!("Snake {:?} was born (generation {}, {} mutations)", snake, generation, mutations))
+        }
+        SimulationEventKind::SnakeDied { snake, cause, age, generation } => {
+            let cause = match cause {
+                DeathCause::Starvation => "starved",
+                DeathCause::Collision => "collided with a solid",
+                DeathCause::Culled => "was culled to keep the population under its limit",
+                DeathCause::Disease => "died of disease",
+                DeathCause::OldAge => "died of old age",
+            };
+            (EventCategory::Deaths, format!("Snake {:?} {} at age {} (generation {})", snake, cause, age, generation))
+        }
+        SimulationEventKind::SpecieCreated { specie } => {
+            (EventCategory::Species, format!("Specie {} emerged", specie))
+        }
+        SimulationEventKind::SpecieExtinct { specie } => {
+            (EventCategory::Species, format!("Specie {} went extinct", specie))
+        }
+        SimulationEventKind::LifespanRecord { snake, age } => {
+            (EventCategory::Records, format!("Snake {:?} set a new lifespan record of {}", snake, age))
+        }
+        SimulationEventKind::EntityLimitReached { limit, count, max } => {
+            (EventCategory::Engine, format!("Warning: {:?} limit reached ({} of {})", limit, count, max))
+        }
+    }
+}
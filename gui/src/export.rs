@@ -0,0 +1,268 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use eframe::emath::Pos2;
+use eframe::epaint::Color32;
+use egui::Ui;
+use image::{Rgba, RgbaImage};
+use hex_brains_engine::draw::HexCells;
+use hex_brains_engine::simulation::RunMetadata;
+use crate::{hex_center, hex_color, world_size, cell_size, Config};
+use crate::palette::SpeciesColors;
+use crate::run_info::format_duration;
+
+const RUN_FILE: &str = "run.toml";
+
+type FrameSaver = (Sender<(PathBuf, RgbaImage)>, JoinHandle<()>);
+
+pub struct TimeLapseSettings {
+    pub every_n_frames: u32,
+    pub directory: String,
+    pub image_size: u32,
+    pub stitch_video: bool,
+    pub video_fps: u32,
+}
+
+impl Default for TimeLapseSettings {
+    fn default() -> Self {
+        TimeLapseSettings {
+            every_n_frames: 100,
+            directory: "timelapse".to_string(),
+            image_size: 800,
+            stitch_video: false,
+            video_fps: 30,
+        }
+    }
+}
+
+pub struct TimeLapse {
+    pub settings: TimeLapseSettings,
+    recording: bool,
+    frames_since_capture: u32,
+    captured: u32,
+    // the run being recorded, also written into the stitched video
+    run: Option<RunMetadata>,
+    // frames are saved in order on one thread, which ends once the sender is dropped
+    frames: Option<FrameSaver>,
+    // waits for the last frames of a stopped recording and stitches them
+    finishing: Option<JoinHandle<()>>,
+    status_sender: Sender<String>,
+    status_receiver: Receiver<String>,
+    status: String,
+}
+
+impl TimeLapse {
+    pub fn new() -> Self {
+        let (status_sender, status_receiver) = channel();
+        TimeLapse {
+            settings: TimeLapseSettings::default(),
+            recording: false,
+            frames_since_capture: 0,
+            captured: 0,
+            run: None,
+            frames: None,
+            finishing: None,
+            status_sender,
+            status_receiver,
+            status: String::new(),
+        }
+    }
+
+    pub fn on_frames_simulated(&mut self, updates_done: u32) {
+        if self.recording {
+            self.frames_since_capture += updates_done;
+        }
+    }
+
+    pub fn on_draw_data(&mut self, hexes: &HexCells, config: &Config, species_colors: &SpeciesColors) {
+        if !self.recording || self.frames_since_capture < self.settings.every_n_frames {
+            return;
+        }
+        self.frames_since_capture = 0;
+        let image = render_hexes(hexes, config, species_colors, self.settings.image_size);
+        let path = frame_path(Path::new(&self.settings.directory), self.captured);
+        self.captured += 1;
+        if let Some((frames, _)) = &self.frames {
+            let _ = frames.send((path, image));
+        }
+    }
+
+    fn start(&mut self, run: Option<RunMetadata>) {
+        if self.finishing.as_ref().is_some_and(|finishing| !finishing.is_finished()) {
+            self.status = "Still saving the last recording".to_string();
+            return;
+        }
+        if let Err(error) = std::fs::create_dir_all(&self.settings.directory) {
+            self.status = format!("Unable to create {}: {}", self.settings.directory, error);
+            return;
+        }
+        // frames of an earlier, longer recording would end up in the video
+        if let Err(error) = remove_frames(Path::new(&self.settings.directory)) {
+            self.status = error;
+            return;
+        }
+        // the frames can't carry text, so the run they show is described next to them
+        if let Some(run) = &run {
+            let path = Path::new(&self.settings.directory).join(RUN_FILE);
+            let saved = toml::to_string(run).map_err(|error| error.to_string()).and_then(|content| std::fs::write(&path, content).map_err(|error| error.to_string()));
+            if let Err(error) = saved {
+                self.status = format!("Unable to save {}: {}", path.display(), error);
+                return;
+            }
+        }
+        let (frames, frames_receiver) = channel::<(PathBuf, RgbaImage)>();
+        let status_sender = self.status_sender.clone();
+        // encoding big pngs takes a while, so the gui thread only renders
+        let saver = thread::spawn(move || {
+            for (path, image) in frames_receiver {
+                if let Err(error) = image.save(&path) {
+                    let _ = status_sender.send(format!("Unable to save {}: {}", path.display(), error));
+                }
+            }
+        });
+        self.frames = Some((frames, saver));
+        self.recording = true;
+        self.captured = 0;
+        self.run = run;
+        // the first frame is captured immediately
+        self.frames_since_capture = self.settings.every_n_frames;
+        self.status = format!("Recording to {}", self.settings.directory);
+    }
+
+    // The video describes the run as it is when the recording stops
+    fn stop(&mut self, run: Option<RunMetadata>) {
+        self.recording = false;
+        if run.is_some() {
+            self.run = run;
+        }
+        self.status = format!("Captured {} frames", self.captured);
+        let Some((frames, saver)) = self.frames.take() else {
+            return;
+        };
+        drop(frames);
+        let stitch = self.settings.stitch_video && self.captured > 0;
+        let directory = PathBuf::from(&self.settings.directory);
+        let fps = self.settings.video_fps;
+        let comment = self.run.as_ref().map(describe_run);
+        let status_sender = self.status_sender.clone();
+        self.finishing = Some(thread::spawn(move || {
+            // ffmpeg only starts once every frame is written
+            let _ = saver.join();
+            if stitch {
+                let _ = status_sender.send(stitch_video(&directory, fps, comment));
+            }
+        }));
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, run: Option<RunMetadata>) {
+        while let Ok(status) = self.status_receiver.try_recv() {
+            self.status = status;
+        }
+        ui.add_enabled_ui(!self.recording, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Capture every");
+                ui.add(egui::DragValue::new(&mut self.settings.every_n_frames).speed(1.0).clamp_range(1..=u32::MAX));
+                ui.label("frames");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Directory");
+                ui.text_edit_singleline(&mut self.settings.directory);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Image size");
+                ui.add(egui::DragValue::new(&mut self.settings.image_size).speed(10.0).clamp_range(64..=8192)).on_hover_text("Longer side of the images, the other side follows the shape of the world");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.settings.stitch_video, "Stitch into video with ffmpeg");
+                ui.label("FPS");
+                ui.add(egui::DragValue::new(&mut self.settings.video_fps).speed(1.0).clamp_range(1..=120));
+            });
+        });
+        ui.horizontal(|ui| {
+            if !self.recording && ui.button("Start recording").clicked() {
+                self.start(run);
+            } else if self.recording && ui.button("Stop recording").clicked() {
+                self.stop(run);
+            }
+            if self.recording {
+                ui.label(format!("{} frames captured", self.captured));
+            }
+        });
+        ui.label(&self.status);
+    }
+}
+
+fn frame_path(directory: &Path, index: u32) -> PathBuf {
+    directory.join(format!("frame_{:06}.png", index))
+}
+
+fn remove_frames(directory: &Path) -> Result<(), String> {
+    let entries = std::fs::read_dir(directory).map_err(|error| format!("Unable to read {}: {}", directory.display(), error))?;
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        if name.starts_with("frame_") && name.ends_with(".png") {
+            std::fs::remove_file(&path).map_err(|error| format!("Unable to remove {}: {}", path.display(), error))?;
+        }
+    }
+    Ok(())
+}
+
+fn describe_run(run: &RunMetadata) -> String {
+    format!("hex_brains {} seed {} config {} started {} running for {}", run.engine_version, run.seed, run.config_hash, run.started_at, format_duration(run.active_millis))
+}
+
+fn stitch_video(directory: &Path, fps: u32, comment: Option<String>) -> String {
+    let output = directory.join("timelapse.mp4");
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-y")
+        .arg("-framerate").arg(fps.to_string())
+        .arg("-i").arg(directory.join("frame_%06d.png"))
+        .arg("-pix_fmt").arg("yuv420p");
+    if let Some(comment) = comment {
+        command.arg("-metadata").arg(format!("comment={}", comment));
+    }
+    let result = command.arg(&output).output();
+    match result {
+        Ok(result) if result.status.success() => format!("Video saved to {}", output.display()),
+        Ok(result) => format!("ffmpeg failed: {}", String::from_utf8_lossy(&result.stderr).lines().last().unwrap_or("")),
+        Err(error) => format!("Unable to run ffmpeg: {}", error),
+    }
+}
+
+// Uses the layout of draw_hexes, but paints into an image instead of the screen, the size is the longer side of the image
+pub fn render_hexes(hexes: &HexCells, config: &Config, species_colors: &SpeciesColors, size: u32) -> RgbaImage {
+    let background = config.bg_color.color;
+    // the sides are kept even, the yuv420p video needs them like that
+    let world = world_size(config) * size as f32 / 2.0;
+    let (width, height) = ((world.x.round() as u32).max(1) * 2, (world.y.round() as u32).max(1) * 2);
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([background.r(), background.g(), background.b(), 255]));
+    let radius = cell_size(config) / 2.0;
+    for hex in hexes.hexes().filter(|hex| config.layers.shows(&hex.hex_type)) {
+        let center = hex_center(&Pos2 { x: hex.x as f32, y: hex.y as f32 }, config);
+        fill_circle(&mut image, center.x * size as f32, center.y * size as f32, radius * size as f32, hex_color(&hex.hex_type, config, species_colors));
+    }
+    image
+}
+
+fn fill_circle(image: &mut RgbaImage, center_x: f32, center_y: f32, radius: f32, color: Color32) {
+    let min_x = (center_x - radius).floor().max(0.0) as u32;
+    let max_x = ((center_x + radius).ceil() as u32).min(image.width());
+    let min_y = (center_y - radius).floor().max(0.0) as u32;
+    let max_y = ((center_y + radius).ceil() as u32).min(image.height());
+    // egui colors are premultiplied, so blending is src + dst * (1 - src_alpha)
+    let inverse_alpha = 1.0 - color.a() as f32 / 255.0;
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let dx = x as f32 + 0.5 - center_x;
+            let dy = y as f32 + 0.5 - center_y;
+            if dx * dx + dy * dy > radius * radius {
+                continue;
+            }
+            let pixel = image.get_pixel_mut(x, y);
+            let blend = |src: u8, dst: u8| (src as f32 + dst as f32 * inverse_alpha).min(255.0) as u8;
+            *pixel = Rgba([blend(color.r(), pixel[0]), blend(color.g(), pixel[1]), blend(color.b(), pixel[2]), 255]);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::VecDeque;
+use eframe::emath;
+use eframe::emath::{Pos2, Rect, Vec2};
+use eframe::epaint::{Color32, CubicBezierShape};
+use egui::{Align2, FontFamily, FontId, Frame, Rounding, Sense, Shape, Stroke, Ui};
+use hex_brains_engine::simulation::{Compartment, EnergyFlows, Stats};
+
+const WINDOWS: [u32; 4] = [100, 1_000, 10_000, 100_000];
+// widest band in the diagram, the others are scaled to it
+const MAX_BAND_WIDTH: f32 = 0.12;
+const NODE_SIZE: Vec2 = Vec2 { x: 0.12, y: 0.16 };
+const COMPARTMENTS: [Compartment; 5] = [Compartment::Soil, Compartment::Plants, Compartment::Meat, Compartment::Stomachs, Compartment::Snakes];
+
+// Food goes from left to right, the bands of dead snakes and rotting meat flow back
+fn node_position(compartment: Compartment) -> Pos2 {
+    match compartment {
+        Compartment::Soil => Pos2 { x: 0.12, y: 0.5 },
+        Compartment::Plants => Pos2 { x: 0.4, y: 0.22 },
+        Compartment::Meat => Pos2 { x: 0.4, y: 0.78 },
+        Compartment::Stomachs => Pos2 { x: 0.68, y: 0.5 },
+        Compartment::Snakes => Pos2 { x: 0.9, y: 0.5 },
+    }
+}
+
+fn node_color(compartment: Compartment) -> Color32 {
+    match compartment {
+        Compartment::Soil => Color32::BROWN,
+        Compartment::Plants => Color32::GREEN,
+        Compartment::Meat => Color32::RED,
+        Compartment::Stomachs => Color32::KHAKI,
+        Compartment::Snakes => Color32::LIGHT_BLUE,
+    }
+}
+
+// Cumulative flows sent with the stats, the diagram shows how much moved during the chosen window
+pub struct FoodWeb {
+    history: VecDeque<(u32, EnergyFlows)>,
+    window: u32,
+}
+
+impl FoodWeb {
+    pub fn new() -> Self {
+        FoodWeb { history: VecDeque::new(), window: WINDOWS[1] }
+    }
+
+    pub fn record(&mut self, stats: &Stats) {
+        if let Some((frame, _)) = self.history.back() {
+            if *frame == stats.frame {
+                return;
+            }
+            if *frame > stats.frame {
+                self.history.clear();
+            }
+        }
+        self.history.push_back((stats.frame, stats.energy_flows));
+        // one sample older than the longest window is enough to measure it
+        let oldest_needed = stats.frame.saturating_sub(WINDOWS[WINDOWS.len() - 1]);
+        while self.history.len() > 1 && self.history[1].0 <= oldest_needed {
+            self.history.pop_front();
+        }
+    }
+
+    fn window_flows(&self) -> Option<(u32, EnergyFlows)> {
+        let (last_frame, last) = self.history.back()?;
+        let start = last_frame.saturating_sub(self.window);
+        let (first_frame, first) = self.history.iter().find(|(frame, _)| *frame >= start)?;
+        Some((last_frame - first_frame, last.since(first)))
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Window:");
+            for window in WINDOWS {
+                ui.selectable_value(&mut self.window, window, format!("{} frames", window));
+            }
+        });
+        let Some((frames, flows)) = self.window_flows() else {
+            ui.label("No flows recorded yet");
+            return;
+        };
+        ui.label(format!("Food matter moved during the last {} frames, {:.0}% of the digested food was meat", frames, flows.carnivory() * 100.0));
+        let links = flows.links();
+        let max_amount = links.iter().map(|(_, _, amount)| *amount).fold(0.0, f32::max);
+        Frame::canvas(ui.style()).show(ui, |ui| {
+            let desired_size = Vec2 { x: ui.available_width(), y: ui.available_width() / 2.0 };
+            let (response, painter) = ui.allocate_painter(desired_size, Sense::hover());
+            let to_screen = emath::RectTransform::from_to(Rect::from_min_size(Pos2::ZERO, Vec2 { x: 1.0, y: 1.0 }), response.rect);
+            let height = response.rect.height();
+            let font = FontId::new(12.0, FontFamily::Monospace);
+            for (from, to, amount) in links {
+                if amount <= 0.0 || max_amount <= 0.0 {
+                    continue;
+                }
+                let start = node_position(from) + Vec2 { x: NODE_SIZE.x / 2.0, y: 0.0 };
+                let end = node_position(to) - Vec2 { x: NODE_SIZE.x / 2.0, y: 0.0 };
+                // bands going back to the left loop below the nodes instead of crossing them
+                let bend = if end.x > start.x { Vec2 { x: (end.x - start.x) / 2.0, y: 0.0 } } else { Vec2 { x: 0.15, y: 0.2 } };
+                let points = [start, start + bend, end + Vec2 { x: -bend.x, y: bend.y }, end].map(|point| to_screen * point);
+                let width = (amount / max_amount * MAX_BAND_WIDTH * height).max(1.0);
+                painter.add(Shape::CubicBezier(CubicBezierShape::from_points_stroke(points, false, Color32::TRANSPARENT, Stroke::new(width, node_color(from).gamma_multiply(0.5)))));
+                let middle = CubicBezierShape::from_points_stroke(points, false, Color32::TRANSPARENT, Stroke::NONE).sample(0.5);
+                painter.text(middle, Align2::CENTER_CENTER, format!("{:.0}", amount), font.clone(), Color32::WHITE);
+            }
+            for compartment in COMPARTMENTS {
+                let rect = to_screen.transform_rect(Rect::from_center_size(node_position(compartment), NODE_SIZE));
+                painter.rect_filled(rect, Rounding::same(4.0), node_color(compartment));
+                painter.text(rect.center(), Align2::CENTER_CENTER, format!("{:?}", compartment), font.clone(), Color32::BLACK);
+            }
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use eframe::emath::{Pos2, Rect, Vec2};
+use eframe::epaint::Color32;
+use egui::{Frame, Sense, Stroke, Ui};
+use hex_brains_engine::genome_distances::{DistanceMatrix, GenomeLabel, Merge};
+use crate::palette::SpeciesColors;
+
+const DENDROGRAM_HEIGHT: f32 = 0.3;
+const STRIP_HEIGHT: f32 = 6.0;
+
+// Distances between the species leaders and some of their members, clustered so genomes that belong together form blocks
+pub struct GenomeDistances {
+    matrix: Option<DistanceMatrix>,
+    merges: Vec<Merge>,
+    order: Vec<usize>,
+    members_per_species: usize,
+}
+
+impl GenomeDistances {
+    pub fn new() -> Self {
+        GenomeDistances { matrix: None, merges: vec![], order: vec![], members_per_species: 3 }
+    }
+
+    pub fn record(&mut self, matrix: DistanceMatrix) {
+        self.merges = matrix.cluster();
+        self.order = matrix.leaf_order(&self.merges);
+        self.matrix = Some(matrix);
+    }
+
+    pub fn members_per_species(&self) -> usize {
+        self.members_per_species
+    }
+
+    // Returns true when new distances were requested
+    pub fn ui(&mut self, ui: &mut Ui, species_colors: &SpeciesColors) -> bool {
+        let measure = ui.horizontal(|ui| {
+            let measure = ui.button("Measure").on_hover_text("Measures the distances in the current world").clicked();
+            ui.label("with up to");
+            ui.add(egui::DragValue::new(&mut self.members_per_species).clamp_range(0..=20));
+            ui.label("members of every specie");
+            measure
+        }).inner;
+        let Some(matrix) = &self.matrix else {
+            ui.label("Nothing measured yet");
+            return measure;
+        };
+        if matrix.is_empty() {
+            ui.label("There were no species to measure");
+            return measure;
+        }
+        let species = matrix.genomes.iter().filter(|genome| genome.snake.is_none()).count();
+        ui.label(format!("{} genomes of {} species, species threshold {:.2}", matrix.len(), species, matrix.threshold));
+        let (close_strangers, far_relatives) = threshold_misfits(matrix);
+        ui.label(format!("{:.0}% of the pairs from different species are closer than the threshold, {:.0}% of the pairs from the same specie are further apart", close_strangers * 100.0, far_relatives * 100.0))
+            .on_hover_text("Species are only compared with their leader, a high share of either suggests a different threshold");
+        Frame::canvas(ui.style()).show(ui, |ui| {
+            let width = ui.available_width().min(600.0);
+            let (response, painter) = ui.allocate_painter(Vec2 { x: width, y: width * (1.0 + DENDROGRAM_HEIGHT) + STRIP_HEIGHT }, Sense::hover());
+            let origin = response.rect.min;
+            let cell = width / matrix.len() as f32;
+            let base = width * DENDROGRAM_HEIGHT;
+            let max_distance = self.merges.iter().map(|merge| merge.distance).fold(matrix.threshold, f32::max).max(f32::EPSILON);
+            let height_of = |distance: f32| origin.y + base - distance / max_distance * (base - 4.0);
+            // where every cluster sits, leaves first and then the merges in order
+            let mut positions: Vec<Pos2> = vec![Pos2::ZERO; matrix.len()];
+            for (column, genome) in self.order.iter().enumerate() {
+                positions[*genome] = Pos2 { x: origin.x + (column as f32 + 0.5) * cell, y: origin.y + base };
+            }
+            let line = Stroke::new(1.0, Color32::LIGHT_GRAY);
+            for merge in &self.merges {
+                let (left, right) = (positions[merge.left], positions[merge.right]);
+                let y = height_of(merge.distance);
+                painter.line_segment([left, Pos2 { x: left.x, y }], line);
+                painter.line_segment([right, Pos2 { x: right.x, y }], line);
+                painter.line_segment([Pos2 { x: left.x, y }, Pos2 { x: right.x, y }], line);
+                positions.push(Pos2 { x: (left.x + right.x) / 2.0, y });
+            }
+            let threshold = height_of(matrix.threshold);
+            painter.line_segment([Pos2 { x: origin.x, y: threshold }, Pos2 { x: origin.x + width, y: threshold }], Stroke::new(1.0, Color32::YELLOW));
+            let top = origin.y + base + STRIP_HEIGHT;
+            for (column, genome) in self.order.iter().enumerate() {
+                let x = origin.x + column as f32 * cell;
+                painter.rect_filled(Rect::from_min_size(Pos2 { x, y: origin.y + base }, Vec2 { x: cell, y: STRIP_HEIGHT }), 0.0, species_colors.color(matrix.genomes[*genome].specie));
+                for (row, other) in self.order.iter().enumerate() {
+                    let rect = Rect::from_min_size(Pos2 { x, y: top + row as f32 * cell }, Vec2 { x: cell, y: cell });
+                    painter.rect_filled(rect, 0.0, distance_color(matrix.distance(*genome, *other), matrix.threshold));
+                }
+            }
+            if let Some(pointer) = response.hover_pos().filter(|pointer| pointer.y >= top) {
+                let column = ((pointer.x - origin.x) / cell) as usize;
+                let row = ((pointer.y - top) / cell) as usize;
+                if let (Some(genome), Some(other)) = (self.order.get(column), self.order.get(row)) {
+                    response.on_hover_text(format!("{} and {}: {:.3}", describe(&matrix.genomes[*genome]), describe(&matrix.genomes[*other]), matrix.distance(*genome, *other)));
+                }
+            }
+        });
+        ui.label("The yellow line is the species threshold, clusters joined below it are as close as the members of a specie.");
+        measure
+    }
+}
+
+// Close pairs are green, pairs at the threshold are yellow and pairs twice as far are red
+fn distance_color(distance: f32, threshold: f32) -> Color32 {
+    let share = (distance / (2.0 * threshold).max(f32::EPSILON)).min(1.0);
+    let (red, green) = if share < 0.5 { (share * 2.0, 1.0) } else { (1.0, 2.0 - share * 2.0) };
+    Color32::from_rgb((red * 200.0) as u8, (green * 200.0) as u8, 40)
+}
+
+fn describe(genome: &GenomeLabel) -> String {
+    match genome.snake {
+        Some(snake) => format!("snake {} of specie {}", snake, genome.specie),
+        None => format!("leader of specie {}", genome.specie),
+    }
+}
+
+// Shares of the pairs of different species closer than the threshold and of the same specie further apart
+fn threshold_misfits(matrix: &DistanceMatrix) -> (f32, f32) {
+    let (mut strangers, mut close_strangers, mut relatives, mut far_relatives) = (0, 0, 0, 0);
+    for row in 0..matrix.len() {
+        for column in row + 1..matrix.len() {
+            let close = matrix.distance(row, column) < matrix.threshold;
+            if matrix.genomes[row].specie == matrix.genomes[column].specie {
+                relatives += 1;
+                far_relatives += usize::from(!close);
+            } else {
+                strangers += 1;
+                close_strangers += usize::from(close);
+            }
+        }
+    }
+    let share = |part: usize, total: usize| if total == 0 { 0.0 } else { part as f32 / total as f32 };
+    (share(close_strangers, strangers), share(far_relatives, relatives))
+}
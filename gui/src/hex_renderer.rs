@@ -0,0 +1,117 @@
+use eframe::emath::{Pos2, Rect};
+use eframe::epaint::Color32;
+use eframe::glow;
+use eframe::glow::HasContext;
+
+// center followed by the premultiplied color
+const INSTANCE_FLOATS: usize = 6;
+
+const VERTEX_SHADER: &str = r#"
+    layout (location = 0) in vec2 a_center;
+    layout (location = 1) in vec4 a_color;
+    uniform vec2 u_origin;
+    uniform vec2 u_size;
+    uniform float u_radius;
+    out vec2 v_local;
+    out vec4 v_color;
+    const vec2 corners[4] = vec2[4](vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(-1.0, 1.0), vec2(1.0, 1.0));
+    void main() {
+        v_local = corners[gl_VertexID];
+        v_color = a_color;
+        vec2 position = (a_center + v_local * u_radius - u_origin) / u_size;
+        gl_Position = vec4(position.x * 2.0 - 1.0, 1.0 - position.y * 2.0, 0.0, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+    precision mediump float;
+    in vec2 v_local;
+    in vec4 v_color;
+    out vec4 out_color;
+    void main() {
+        if (dot(v_local, v_local) > 1.0) {
+            discard;
+        }
+        out_color = v_color;
+    }
+"#;
+
+// Draws every hex as an instance of one quad with the circle cut out in the fragment shader, a lot cheaper than a shape per hex
+pub struct HexRenderer {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    instances: glow::Buffer,
+}
+
+pub fn push_instance(instances: &mut Vec<f32>, center: Pos2, color: Color32) {
+    let [r, g, b, a] = color.to_array();
+    instances.extend_from_slice(&[center.x, center.y, r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0]);
+}
+
+impl HexRenderer {
+    // Fails when the shaders don't compile on this driver, the gui then keeps drawing shapes
+    pub fn new(gl: &glow::Context) -> Result<Self, String> {
+        let shader_version = if cfg!(target_arch = "wasm32") { "#version 300 es" } else { "#version 330" };
+        unsafe {
+            let program = gl.create_program()?;
+            let mut shaders = vec![];
+            for (shader_type, source) in [(glow::VERTEX_SHADER, VERTEX_SHADER), (glow::FRAGMENT_SHADER, FRAGMENT_SHADER)] {
+                let shader = gl.create_shader(shader_type)?;
+                gl.shader_source(shader, &format!("{}\n{}", shader_version, source));
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    return Err(gl.get_shader_info_log(shader));
+                }
+                gl.attach_shader(program, shader);
+                shaders.push(shader);
+            }
+            gl.link_program(program);
+            if !gl.get_program_link_status(program) {
+                return Err(gl.get_program_info_log(program));
+            }
+            for shader in shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+            let vertex_array = gl.create_vertex_array()?;
+            let instances = gl.create_buffer()?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(instances));
+            let stride = (INSTANCE_FLOATS * std::mem::size_of::<f32>()) as i32;
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 2, glow::FLOAT, false, stride, 0);
+            gl.vertex_attrib_divisor(0, 1);
+            gl.enable_vertex_attrib_array(1);
+            gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, stride, 2 * std::mem::size_of::<f32>() as i32);
+            gl.vertex_attrib_divisor(1, 1);
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            Ok(HexRenderer { program, vertex_array, instances })
+        }
+    }
+
+    pub fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.instances);
+        }
+    }
+
+    // Centers and radius are in the normalized coordinates of draw_hexes, visible is the part of the world filling the viewport
+    pub fn paint(&self, gl: &glow::Context, instances: &[f32], radius: f32, visible: Rect) {
+        let bytes: Vec<u8> = instances.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        unsafe {
+            gl.use_program(Some(self.program));
+            gl.uniform_2_f32(gl.get_uniform_location(self.program, "u_origin").as_ref(), visible.min.x, visible.min.y);
+            gl.uniform_2_f32(gl.get_uniform_location(self.program, "u_size").as_ref(), visible.width(), visible.height());
+            gl.uniform_1_f32(gl.get_uniform_location(self.program, "u_radius").as_ref(), radius);
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.instances));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, &bytes, glow::STREAM_DRAW);
+            gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, (instances.len() / INSTANCE_FLOATS) as i32);
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::collections::VecDeque;
+use hex_brains_engine::simulation::SimulationConfig;
+
+const MAX_HISTORY: usize = 100;
+
+// Bounded undo/redo stacks of the settings, a drag is recorded as a single edit once the pointer is released
+pub struct ConfigHistory {
+    undo: VecDeque<SimulationConfig>,
+    redo: Vec<SimulationConfig>,
+    // None until the first config is recorded
+    current: Option<SimulationConfig>,
+}
+
+impl ConfigHistory {
+    pub fn new() -> Self {
+        ConfigHistory { undo: VecDeque::new(), redo: vec![], current: None }
+    }
+
+    pub fn record(&mut self, config: SimulationConfig, editing: bool) {
+        if editing || self.current.as_ref() == Some(&config) {
+            return;
+        }
+        if let Some(previous) = self.current.replace(config) {
+            if self.undo.len() == MAX_HISTORY {
+                self.undo.pop_front();
+            }
+            self.undo.push_back(previous);
+            self.redo.clear();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self) -> Option<SimulationConfig> {
+        let previous = self.undo.pop_back()?;
+        self.redo.extend(self.current.replace(previous.clone()));
+        Some(previous)
+    }
+
+    pub fn redo(&mut self) -> Option<SimulationConfig> {
+        let next = self.redo.pop()?;
+        self.undo.extend(self.current.replace(next.clone()));
+        Some(next)
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use hex_brains_engine::draw::HexCells;
+use hex_brains_engine::simulation::HexType;
+
+// longer gaps between states are pauses, the heads should not crawl through them
+const MAX_INTERVAL: Duration = Duration::from_secs(1);
+
+fn is_head(hex_type: &HexType) -> bool {
+    matches!(hex_type, HexType::SnakeHead { .. } | HexType::ControlledHead)
+}
+
+// Hexes touching the given one, every second row is shifted right like in the engine, moves over the world edge are not animated
+fn neighbours(x: usize, y: usize) -> [(i64, i64); 6] {
+    let (x, y) = (x as i64, y as i64);
+    let shift = if y % 2 == 0 { 0 } else { -1 };
+    [(x - 1, y), (x + 1, y), (x + shift, y - 1), (x + shift + 1, y - 1), (x + shift, y + 1), (x + shift + 1, y + 1)]
+}
+
+// The gui only knows what is drawn on every hex, so a head that appears next to where the same kind of head disappeared is assumed to have moved there
+pub struct Interpolation {
+    heads: HashMap<(usize, usize), HexType>,
+    // current hex of a head and the hex it came from
+    moves: HashMap<(usize, usize), (usize, usize)>,
+    received: Instant,
+    interval: Duration,
+}
+
+impl Interpolation {
+    pub fn new() -> Self {
+        Interpolation { heads: HashMap::new(), moves: HashMap::new(), received: Instant::now(), interval: Duration::ZERO }
+    }
+
+    pub fn on_new_state(&mut self, hexes: &HexCells) {
+        let heads: HashMap<(usize, usize), HexType> = hexes.hexes().filter(|hex| is_head(&hex.hex_type)).map(|hex| ((hex.x, hex.y), hex.hex_type)).collect();
+        self.moves = heads.iter()
+            .filter(|(position, hex_type)| self.heads.get(position) != Some(hex_type))
+            .filter_map(|((x, y), hex_type)| {
+                neighbours(*x, *y).into_iter()
+                    .filter(|(x, y)| *x >= 0 && *y >= 0)
+                    .map(|(x, y)| (x as usize, y as usize))
+                    .find(|origin| self.heads.get(origin) == Some(hex_type) && heads.get(origin) != Some(hex_type))
+                    .map(|origin| ((*x, *y), origin))
+            })
+            .collect();
+        self.heads = heads;
+        self.interval = self.received.elapsed().min(MAX_INTERVAL);
+        self.received = Instant::now();
+    }
+
+    // 0 right after a state arrived and 1 once the next one is expected
+    pub fn progress(&self) -> f32 {
+        if self.interval.is_zero() {
+            return 1.0;
+        }
+        (self.received.elapsed().as_secs_f32() / self.interval.as_secs_f32()).min(1.0)
+    }
+
+    pub fn origin(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        self.moves.get(&(x, y)).copied()
+    }
+}
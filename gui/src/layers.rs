@@ -0,0 +1,74 @@
+use egui::{Sense, Ui};
+use hex_brains_engine::dna::SegmentType;
+use hex_brains_engine::simulation::HexType;
+use crate::{hex_color, Config};
+use crate::palette::SpeciesColors;
+
+// Parts of the world that can be hidden, walls, water and everything else are always drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layers {
+    pub food: bool,
+    pub heads: bool,
+    pub segments: bool,
+    pub scents: bool,
+    // food and snake energy summed up in blocks, sent by the simulation every few frames
+    pub energy: bool,
+    // the legend is drawn over the world, it is not part of exported images
+    pub legend: bool,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers { food: true, heads: true, segments: true, scents: true, energy: false, legend: false }
+    }
+}
+
+impl Layers {
+    pub fn shows(&self, hex_type: &HexType) -> bool {
+        match hex_type {
+            HexType::Food | HexType::Meat => self.food,
+            HexType::SnakeHead { .. } | HexType::ControlledHead | HexType::Signal { .. } => self.heads,
+            HexType::Segment { .. } => self.segments,
+            HexType::Scent { .. } => self.scents,
+            _ => true,
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.label("Show");
+        ui.checkbox(&mut self.food, "Food");
+        ui.checkbox(&mut self.heads, "Heads");
+        ui.checkbox(&mut self.segments, "Segments");
+        ui.checkbox(&mut self.scents, "Scents");
+        ui.checkbox(&mut self.energy, "Energy").on_hover_text("Shades the world by the energy in food and snakes, with contour lines around regions of similar energy");
+        ui.checkbox(&mut self.legend, "Legend").on_hover_text("Explains the colors of the hexes in the corner of the world");
+    }
+}
+
+// Swatches are taken from hex_color, so the legend follows the chosen colors
+pub fn legend_ui(ui: &mut Ui, config: &Config, species_colors: &SpeciesColors) {
+    let entries = [
+        ("Head, in the color of its specie", HexType::SnakeHead { specie: 0 }),
+        ("Controlled head", HexType::ControlledHead),
+        ("Tail and walls", HexType::SnakeTail),
+        ("Muscle", HexType::Segment { segment_type: SegmentType::muscle() }),
+        ("Solid", HexType::Segment { segment_type: SegmentType::solid() }),
+        ("Solar", HexType::Segment { segment_type: SegmentType::solar() }),
+        ("Stomach", HexType::Segment { segment_type: SegmentType::stomach() }),
+        ("Fin", HexType::Segment { segment_type: SegmentType::fin() }),
+        ("Eye", HexType::Segment { segment_type: SegmentType::eye() }),
+        ("Nose", HexType::Segment { segment_type: SegmentType::nose() }),
+        ("Plant", HexType::Food),
+        ("Meat", HexType::Meat),
+        ("Scent", HexType::Scent { value: 1.0 }),
+        ("Minerals", HexType::Mineral),
+        ("Water", HexType::Water),
+    ];
+    for (name, hex_type) in entries {
+        ui.horizontal(|ui| {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), Sense::hover());
+            ui.painter().circle_filled(rect.center(), 6.0, hex_color(&hex_type, config, species_colors));
+            ui.label(name);
+        });
+    }
+}
@@ -0,0 +1,93 @@
+use egui::{ScrollArea, Ui};
+use hex_brains_engine::dna::{MutationType, Trait};
+use hex_brains_engine::lineage::{AncestryRecord, MutationKind};
+use hex_brains_engine::simulation::{MutationStats, OperatorStats};
+
+const OPERATORS: [MutationKind; 11] = [
+    MutationKind::ConnectionFlip,
+    MutationKind::WeightPerturbation,
+    MutationKind::WeightReset,
+    MutationKind::Prune,
+    MutationKind::Dna(MutationType::AddGene),
+    MutationKind::Dna(MutationType::RemoveGene),
+    MutationKind::Dna(MutationType::ChangeSegmentType),
+    MutationKind::Dna(MutationType::ChangeJump),
+    MutationKind::Trait(Trait::MaxEnergy),
+    MutationKind::Trait(Trait::DigestionSpeed),
+    MutationKind::Trait(Trait::Mobility),
+];
+
+pub fn mutation_name(mutation: &MutationKind) -> &'static str {
+    match mutation {
+        MutationKind::ConnectionFlip => "connection flipped",
+        MutationKind::WeightPerturbation => "weight perturbed",
+        MutationKind::WeightReset => "weight reset",
+        MutationKind::Prune => "network pruned",
+        MutationKind::Dna(MutationType::AddGene) => "dna gene added",
+        MutationKind::Dna(MutationType::RemoveGene) => "dna gene removed",
+        MutationKind::Dna(MutationType::ChangeSegmentType) => "dna segment changed",
+        MutationKind::Dna(MutationType::ChangeJump) => "dna jump changed",
+        MutationKind::Trait(Trait::MaxEnergy) => "max energy trait changed",
+        MutationKind::Trait(Trait::DigestionSpeed) => "digestion speed trait changed",
+        MutationKind::Trait(Trait::Mobility) => "mobility trait changed",
+    }
+}
+
+// Ancestors of the inspected snake, newest first
+pub fn lineage_ui(ui: &mut Ui, ancestors: &[AncestryRecord]) {
+    let Some(snake) = ancestors.first() else {
+        ui.label("Click on a snake to see its ancestors");
+        return;
+    };
+    ui.label(format!("Snake {} has {} known ancestors", snake.id, ancestors.len() - 1));
+    ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+        egui::Grid::new("lineage").striped(true).num_columns(5).show(ui, |ui| {
+            ui.label("Snake");
+            ui.label("Generation");
+            ui.label("Born at");
+            ui.label("Specie");
+            ui.label("Mutations at birth");
+            ui.end_row();
+            for record in ancestors {
+                ui.label(record.id.to_string());
+                ui.label(record.generation.to_string());
+                ui.label(record.birth_frame.to_string());
+                ui.label(record.specie.map_or("-".to_string(), |specie| specie.to_string()));
+                let mutations: Vec<&str> = record.mutations.iter().map(mutation_name).collect();
+                ui.label(if mutations.is_empty() { "none".to_string() } else { mutations.join(", ") });
+                ui.end_row();
+            }
+        });
+    });
+}
+
+// Every operator next to the offspring born without mutations, lifespans and food only count the offspring that already died
+pub fn mutation_stats_ui(ui: &mut Ui, stats: &MutationStats) {
+    let baseline = stats.unmutated;
+    egui::Grid::new("mutation_stats").striped(true).num_columns(6).show(ui, |ui| {
+        ui.label("Operator");
+        ui.label("Fired");
+        ui.label("Died");
+        ui.label("Mean lifespan");
+        ui.label("Mean food eaten");
+        ui.label("Compared to unmutated");
+        ui.end_row();
+        let row = |ui: &mut Ui, name: &str, operator: &OperatorStats| {
+            ui.label(name);
+            ui.label(operator.fired.to_string());
+            ui.label(operator.deaths.to_string());
+            ui.label(format!("{:.0}", operator.mean_lifespan()));
+            ui.label(format!("{:.1}", operator.mean_food_eaten()));
+            if operator.deaths > 0 && baseline.deaths > 0 {
+                ui.label(format!("{:+.0} lifespan, {:+.1} food", operator.mean_lifespan() - baseline.mean_lifespan(), operator.mean_food_eaten() - baseline.mean_food_eaten()));
+            } else {
+                ui.label("-");
+            }
+            ui.end_row();
+        };
+        row(ui, "none", &baseline);
+        for operator in OPERATORS {
+            row(ui, mutation_name(&operator), &stats.operators.get(&operator).copied().unwrap_or_default());
+        }
+    });
+}
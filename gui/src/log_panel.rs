@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Write};
+use std::sync::{Arc, Mutex};
+use egui::{Color32, ScrollArea, TextEdit, Ui};
+use tracing::{Event, Level, Subscriber};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+use tracing_subscriber::registry::LookupSpan;
+use hex_brains_engine::logging::LogLevel;
+
+const MAX_LOG_LINES: usize = 2_000;
+
+pub struct LogLine {
+    level: Level,
+    // the spans the event happened in, outermost first
+    spans: String,
+    target: String,
+    message: String,
+}
+
+type LogLines = Arc<Mutex<VecDeque<LogLine>>>;
+
+// Message first, the other fields follow as name=value
+#[derive(Default)]
+struct FieldsVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for FieldsVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, "{}{}={:?}", if self.fields.is_empty() { "" } else { " " }, field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, "{}{}={}", if self.fields.is_empty() { "" } else { " " }, field.name(), value);
+        }
+    }
+}
+
+// Fields of a span, formatted once when it is created
+struct SpanFields(String);
+
+// Keeps the latest events for the log window, the level filter of the subscriber decides what arrives here
+pub struct LogCapture {
+    lines: LogLines,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for LogCapture {
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        let mut visitor = FieldsVisitor::default();
+        attributes.record(&mut visitor);
+        if let Some(span) = context.span(id) {
+            span.extensions_mut().insert(SpanFields(visitor.fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, context: Context<'_, S>) {
+        let mut visitor = FieldsVisitor::default();
+        event.record(&mut visitor);
+        let mut spans = String::new();
+        for span in context.event_scope(event).into_iter().flat_map(|scope| scope.from_root()) {
+            let _ = match span.extensions().get::<SpanFields>() {
+                Some(SpanFields(fields)) if !fields.is_empty() => write!(spans, "{}{{{}}}: ", span.name(), fields),
+                _ => write!(spans, "{}: ", span.name()),
+            };
+        }
+        let message = if visitor.fields.is_empty() { visitor.message } else { format!("{} {}", visitor.message, visitor.fields) };
+        let line = LogLine { level: *event.metadata().level(), spans, target: event.metadata().target().to_string(), message };
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() >= MAX_LOG_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+pub struct LogPanel {
+    lines: LogLines,
+    level: LogLevel,
+    text_filter: String,
+}
+
+impl LogPanel {
+    // The panel starts at the level the subscriber was created with
+    pub fn new(level: LogLevel) -> (Self, LogCapture) {
+        let lines = LogLines::default();
+        (LogPanel { lines: Arc::clone(&lines), level, text_filter: String::new() }, LogCapture { lines })
+    }
+
+    // Returns the level picked by the user, the engine applies it to the whole process
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<LogLevel> {
+        let mut picked = None;
+        ui.horizontal(|ui| {
+            ui.label("Level");
+            egui::ComboBox::from_id_source("log_level")
+                .selected_text(format!("{:?}", self.level))
+                .show_ui(ui, |ui| {
+                    for level in LogLevel::ALL {
+                        if ui.selectable_value(&mut self.level, level, format!("{:?}", level)).clicked() {
+                            picked = Some(level);
+                        }
+                    }
+                }).response.on_hover_text("Debug and trace slow the simulation down a lot, the running simulation switches the level");
+            ui.label("Filter");
+            ui.add(TextEdit::singleline(&mut self.text_filter).desired_width(150.0));
+            if ui.button("Clear log").clicked() {
+                self.lines.lock().unwrap().clear();
+            }
+        });
+        ui.separator();
+        let lines = self.lines.lock().unwrap();
+        let shown: Vec<&LogLine> = lines.iter().filter(|line| self.text_filter.is_empty() || line.message.contains(self.text_filter.as_str()) || line.spans.contains(self.text_filter.as_str())).collect();
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .stick_to_bottom(true)
+            .show_rows(ui, row_height, shown.len(), |ui, row_range| {
+                for line in &shown[row_range] {
+                    ui.colored_label(level_color(&line.level), format!("{} {}{}: {}", line.level, line.spans, line.target, line.message));
+                }
+            });
+        picked
+    }
+}
+
+fn level_color(level: &Level) -> Color32 {
+    match *level {
+        Level::ERROR => Color32::RED,
+        Level::WARN => Color32::YELLOW,
+        Level::INFO => Color32::LIGHT_GRAY,
+        _ => Color32::GRAY,
+    }
+}
@@ -1,313 +1,455 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+mod audio;
+mod body_plan;
+mod energy_overlay;
+mod debugger;
+mod diagnostics;
+mod event_log;
+mod log_panel;
+mod export;
+mod food_web;
+mod genome_distances;
+mod hex_renderer;
+mod history;
+mod interpolation;
+mod layers;
+mod lineage;
+mod mutation_preview;
+mod network_view;
+mod palette;
+mod performance;
+mod presets;
+mod scenarios;
+mod run_info;
+mod statistics;
+mod viewport;
+mod world_tab;
+
+use std::path::Path;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::Receiver;
 use std::thread;
+use std::thread::JoinHandle;
 use std::time::Instant;
 use bevy_ecs::prelude::*;
 use eframe::{egui, emath};
-use eframe::emath::{Pos2, Rect, Vec2};
-use eframe::epaint::{Color32, Fonts};
-use egui::{Align2, FontDefinitions, FontFamily, FontId, Frame, Key, Response, ScrollArea, Sense, Shape, Stroke, Ui};
+use eframe::emath::{Pos2, Vec2};
+use eframe::epaint::Color32;
+use egui::{Align2, Frame, Key, Modifiers, PointerButton, Sense, Shape, Stroke, Ui};
 use egui::epaint::CircleShape;
 use egui::Shape::Circle;
-use tracing::{info, Level};
-use tracing_subscriber::fmt;
-use hex_brains_engine::core::{Food, Snake, Position, Solid, ScentMap, Scent};
+use tracing_subscriber::{fmt, reload};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use hex_brains_engine::arena::Genome;
+use hex_brains_engine::draw::HexCells;
+use hex_brains_engine::energy_grid::EnergyGrid;
+use hex_brains_engine::events::{EventCapacity, EventSender};
+use hex_brains_engine::snapshot::WorldSnapshot;
+use hex_brains_engine::core::{Controlled, RngResource, Direction, Specie, Decision, FoodMap, Snake, Position, Solid, ScentMap, TemperatureMap, Trail, OUTPUT_NAMES, SENSOR_NAMES};
 use hex_brains_engine::dna::SegmentType;
-use hex_brains_engine::neural;
-use hex_brains_engine::neural::{ConnectionGene, NodeGene, NodeType};
-use hex_brains_engine::simulation::{Simulation, EngineEvent, EngineCommand, EngineState, EngineEvents, Hex, HexType, SimulationConfig, Stats, MutationConfig};
+use hex_brains_engine::logging::{install_level_setter, LogLevel};
+use hex_brains_engine::minerals::MineralMap;
+use hex_brains_engine::water::WaterMap;
+use hex_brains_engine::nests::Nest;
+use hex_brains_engine::obstacles::{MovementPattern, Obstacle};
+use hex_brains_engine::simulation::{Simulation, EngineEvent, EngineCommand, EngineState, EngineEvents, Hex, HexType, SimulationConfig, Stats, MutationConfig, AgingCurve, CollisionPolicy, FoodControl, FoodDistribution, FoodKind, TemperatureLayout, OverpopulationPolicy, Region, WallLayout, WaterLayout};
 use hex_brains_engine::simulation_manager::simulate_batch;
+use crate::audio::AudioFeedback;
+use crate::event_log::EventLog;
+use crate::log_panel::LogPanel;
+use crate::hex_renderer::{push_instance, HexRenderer};
+use crate::interpolation::Interpolation;
+use crate::body_plan::body_plan_ui;
+use crate::energy_overlay::energy_overlay;
+use crate::layers::{legend_ui, Layers};
+use crate::export::TimeLapse;
+use crate::lineage::{lineage_ui, mutation_stats_ui};
+use crate::mutation_preview::MutationPreview;
+use crate::network_view::NetworkView;
+use crate::palette::SpeciesColors;
+use crate::performance::Performance;
+use crate::presets::Presets;
+use crate::run_info::run_info_ui;
+use crate::scenarios::ScenarioDialog;
+use crate::viewport::Viewport;
+use crate::world_tab::WorldTab;
 
 fn main() {
-    let mut native_options = eframe::NativeOptions::default();
-    native_options.initial_window_size = Some(Vec2 { x: 1200.0, y: 1200.0 });
-    fmt()
-        .with_max_level(Level::INFO)
+    let native_options = eframe::NativeOptions {
+        initial_window_size: Some(Vec2 { x: 1200.0, y: 1200.0 }),
+        ..Default::default()
+    };
+    // the level can be changed from the log window, the events are printed and shown there
+    let (level_filter, level_handle) = reload::Layer::new(LevelFilter::INFO);
+    let (log_panel, log_capture) = LogPanel::new(LogLevel::Info);
+    tracing_subscriber::registry()
+        .with(level_filter)
+        .with(fmt::layer())
+        .with(log_capture)
         .init();
-    let (engine_commands_sender, engine_commands_receiver) = std::sync::mpsc::channel();
-    let (engine_events_sender, engine_events_receiver) = std::sync::mpsc::channel();
-    eframe::run_native("My egui App", native_options, Box::new(|cc| {
-        Box::new(MyEguiApp::new(cc, engine_commands_sender, engine_events_sender, engine_events_receiver, engine_commands_receiver))
+    install_level_setter(move |level| {
+        let _ = level_handle.modify(|filter| *filter = LevelFilter::from_level(level.as_tracing()));
+    });
+    let _ = eframe::run_native("My egui App", native_options, Box::new(|cc| {
+        Box::new(MyEguiApp::new(cc, log_panel))
     }));
 }
 
-fn create_simulation_config(columns: usize, rows: usize, add_walls: bool) -> SimulationConfig {
+fn create_simulation_config(columns: usize, rows: usize, wall_layout: WallLayout, wall_seed: u64, perimeter_walls: bool, water_layout: WaterLayout, water_seed: u64) -> SimulationConfig {
     SimulationConfig {
         rows,
         columns,
-        add_walls,
-        create_scents: false,
-        scent_diffusion_rate: 0.25,
-        scent_dispersion_per_step: 150.0,
-        starting_snakes: 10,
-        starting_food: 100,
-        food_per_step: 2,
-        plant_matter_per_segment: 100.0,
-        wait_cost: 1.0,
-        move_cost: 10.0,
-        new_segment_cost: 100.0,
-        size_to_split: 10,
-        species_threshold: 0.2,
-        mutation: MutationConfig::default(),
-        snake_max_age: 2_000,
-        meat_energy_content: 5.0,
-        plant_energy_content: 1.0,
+        wall_layout,
+        wall_seed,
+        perimeter_walls,
+        water_layout,
+        water_seed,
+        ..SimulationConfig::default()
     }
 }
 
-fn start_simulation(engine_events_sender: &Sender<EngineEvent>, engine_commands_receiver: Arc<Mutex<Receiver<EngineCommand>>>, context: egui::Context, config: Config) {
-    let simulation_config = create_simulation_config(config.columns, config.rows, config.add_walls);
-    let mut simulation = Simulation::new("Main".to_string(), engine_events_sender.clone(), Some(Arc::clone(&engine_commands_receiver)), simulation_config);
+fn start_simulation(engine_events_sender: &EventSender, engine_commands_receiver: Arc<Mutex<Receiver<EngineCommand>>>, context: egui::Context, config: Config, name: String, seed: Option<u64>) -> JoinHandle<()> {
+    let simulation_config = create_simulation_config(config.columns, config.rows, config.wall_layout, config.wall_seed, config.perimeter_walls, config.water_layout, config.water_seed);
+    let mut simulation = Simulation::new(name, engine_events_sender.clone(), Some(Arc::clone(&engine_commands_receiver)), simulation_config);
     let egui_context = EguiEcsContext {
         context,
     };
     simulation.insert_resource(egui_context);
     simulation.insert_resource(config);
-    simulation.insert_resource(EngineState {
-        repaint_needed: false,
-        speed_limit: Some(0.1),
-        running: true,
-        frames_left: 0.0,
-        frames: 0,
-        updates_done: 0,
-        ignore_speed_limit: false,
-        finished: false,
-    });
+    if let Some(seed) = seed {
+        simulation.insert_resource(RngResource::from_seed(seed));
+    }
+    simulation.insert_resource(EngineState { speed_limit: Some(0.1), ..EngineState::default() });
     simulation.add_system(draw_simulation.run_if(should_draw_simulation));
+    simulation.add_system(send_energy_grid.run_if(should_draw_simulation));
     thread::spawn(move || {
         simulation.run();
-    });
+    })
+}
+
+// Only sent every few frames, the overlay shows where energy gathers and doesn't need to follow every move
+fn send_energy_grid(engine_events: Res<EngineEvents>, mut sent_at: Local<Option<u32>>, snakes: Query<(&Snake, &Position)>, food_map: Res<FoodMap>, config: Res<SimulationConfig>, engine_state: Res<EngineState>) {
+    hex_brains_engine::profile_system!();
+    if sent_at.is_some_and(|frame| engine_state.frames.abs_diff(frame) < ENERGY_GRID_INTERVAL) {
+        return;
+    }
+    *sent_at = Some(engine_state.frames);
+    let grid = EnergyGrid::new(ENERGY_GRID_CELL, &food_map, snakes.iter().map(|(snake, position)| (position, snake.energy.energy)), &config);
+    let _ = engine_events.events.lock().unwrap().send(EngineEvent::EnergyGrid { grid: Box::new(grid) });
 }
 
-fn draw_simulation(mut engine_events: ResMut<EngineEvents>, positions: Query<&Position>, scents: Query<(Entity, &Scent)>, scent_map: Res<ScentMap>, heads: Query<(Entity, &Snake)>, solids: Query<(Entity, &Solid), Without<SegmentType>>, segments: Query<(Entity, &SegmentType), With<SegmentType>>, food: Query<(Entity, &Food)>, stats: Res<Stats>) {
-    puffin::profile_function!();
-    let all_hexes: Vec<Hex> = solids.iter().map(|(solid, _)| {
+// Food and scents are drawn straight from their maps, so they show up the same with or without food entities
+#[allow(clippy::too_many_arguments)]
+fn draw_simulation(engine_events: ResMut<EngineEvents>, mut sent_hexes: Local<HexCells>, positions: Query<&Position>, scent_map: Res<ScentMap>, food_map: Res<FoodMap>, mineral_map: Res<MineralMap>, water_map: Res<WaterMap>, heads: Query<(Entity, &Snake, Has<Controlled>)>, trails: Query<(&Trail, &Snake)>, nests: Query<(&Nest, &Position)>, solids: Query<(Entity, &Solid), Without<SegmentType>>, segments: Query<(Entity, &SegmentType), With<SegmentType>>, stats: Res<Stats>, temperature_map: Res<TemperatureMap>, engine_state: Res<EngineState>) {
+    hex_brains_engine::profile_system!();
+    if temperature_map.is_changed() {
+        let _ = engine_events.events.lock().unwrap().send(EngineEvent::TemperatureMap { columns: temperature_map.map.width, rows: temperature_map.map.height, temperatures: temperature_map.map.map.clone() });
+    }
+    // water and trails come first so everything else is drawn over them
+    let all_hexes = water_map.map.iter().filter(|(_, water)| **water).map(|(position, _)| {
+        Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Water }
+    }).chain(trails.iter().flat_map(|(trail, snake)| {
+        let length = trail.positions.len() as f32;
+        trail.positions.iter().enumerate().map(move |(index, position)| {
+            Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Trail { specie: snake.species.unwrap_or(0), fade: (index + 1) as f32 / length } }
+        })
+    })).chain(nests.iter().map(|(nest, position)| {
+        Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Nest { specie: nest.specie.unwrap_or(0) } }
+    })).chain(solids.iter().map(|(solid, _)| {
         let position = positions.get(solid).unwrap();
         Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::SnakeTail }
-    }).chain(food.iter().map(|(food_id, food)| {
-        let position = positions.get(food_id).unwrap();
-        if food.is_meat() {
-            Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Meat }
-        } else {
-            Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Food }
-        }
-    })).chain(heads.iter().map(|(head, snake)| {
+    })).chain(mineral_map.map.iter().filter(|(_, deposit)| **deposit > 0.0).map(|(position, _)| {
+        Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Mineral }
+    })).chain(food_map.map.iter().filter(|(_, food)| food.contains_food()).map(|(position, food)| {
+        let hex_type = if food.is_meat() { HexType::Meat } else { HexType::Food };
+        Hex { x: position.x as usize, y: position.y as usize, hex_type }
+    })).chain(heads.iter().map(|(head, snake, controlled)| {
         let position = positions.get(head).unwrap();
-        Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::SnakeHead { specie: snake.species.unwrap_or(0) } }
+        let hex_type = if controlled { HexType::ControlledHead } else { HexType::SnakeHead { specie: snake.species.unwrap_or(0) } };
+        Hex { x: position.x as usize, y: position.y as usize, hex_type }
     })).chain(segments.iter().map(|(segment_id, segment_type)| {
         let position = positions.get(segment_id).unwrap();
         Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Segment { segment_type: segment_type.clone() } }
-    })).chain(scents.iter().map(|(scent, _)| {
-        let position = positions.get(scent).unwrap();
-        let value = scent_map.map.get(position);
+    })).chain(heads.iter().filter(|(_, snake, _)| snake.signal > 0.0).map(|(head, snake, _)| {
+        let position = positions.get(head).unwrap();
+        Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Signal { value: snake.signal } }
+    })).chain(scent_map.map.iter().filter(|(_, value)| **value > 0.0).map(|(position, value)| {
         Hex { x: position.x as usize, y: position.y as usize, hex_type: HexType::Scent { value: *value } }
-    })).collect();
-    engine_events.events.lock().unwrap().send(EngineEvent::DrawData { hexes: all_hexes, stats: stats.clone() });
+    }));
+    // only the hexes that changed since the last frame travel to the gui
+    let delta = sent_hexes.update(HexCells::from_hexes(food_map.map.width, food_map.map.height, all_hexes));
+    let _ = engine_events.events.lock().unwrap().send(EngineEvent::DrawData { delta, stats: Box::new(stats.clone()), frame: engine_state.frames });
 }
 
-fn draw_neural_network(ui: &mut Ui, fonts: &Fonts, specie_id: u32, nodes: &Vec<&NodeGene>, connections: &Vec<&ConnectionGene>) {
-    Frame::canvas(ui.style()).show(ui, |ui| {
-        let (mut response, _) =
-            ui.allocate_painter(ui.available_size_before_wrap(), Sense::drag());
-
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.square_proportions()),
-            response.rect,
-        );
+// the energy overlay sums up blocks of this many hexes along each side and is refreshed every this many frames
+const ENERGY_GRID_CELL: usize = 8;
+const ENERGY_GRID_INTERVAL: u32 = 20;
 
-        let input_nodes = nodes.iter().filter(|node| node.node_type == neural::NodeType::Input).collect::<Vec<_>>();
-        let output_nodes = nodes.iter().filter(|node| node.node_type == neural::NodeType::Output).collect::<Vec<_>>();
+const GENOMES_DIRECTORY: &str = "genomes";
+const SNAPSHOTS_DIRECTORY: &str = "snapshots";
 
-        let specie_marker = Circle(CircleShape {
-            center: to_screen * Pos2 { x: 0.05, y: 0.05 },
-            radius: 0.02 * response.rect.height(), // Using the normalized radius for the screen
-            fill: u32_to_color(specie_id),
-            stroke: Default::default(),
-        });
+// Snapshots can be compared with the diff command of the cli
+fn save_snapshot(snapshot: &WorldSnapshot) -> String {
+    let path = Path::new(SNAPSHOTS_DIRECTORY).join(format!("frame_{}.toml", snapshot.frame));
+    let saved = snapshot.to_toml().and_then(|content| std::fs::create_dir_all(SNAPSHOTS_DIRECTORY).and_then(|_| std::fs::write(&path, content)).map_err(|error| error.to_string()));
+    match saved {
+        Ok(()) => format!("Saved snapshot {}", path.display()),
+        Err(error) => format!("Unable to save snapshot {}: {}", path.display(), error),
+    }
+}
 
-        let input_colors = vec![Color32::LIGHT_GRAY, Color32::DARK_GRAY, Color32::KHAKI, Color32::KHAKI, Color32::KHAKI, Color32::YELLOW, Color32::YELLOW, Color32::YELLOW, Color32::RED, Color32::RED, Color32::RED, Color32::LIGHT_RED, Color32::LIGHT_RED, Color32::LIGHT_RED, Color32::YELLOW, Color32::RED, Color32::BLUE, Color32::GRAY];
-
-        let input_node_shapes: Vec<Shape> = input_nodes.iter().enumerate().map(|(index, node)| {
-            let position = get_node_position(index, NodeType::Input);
-            let screen_position = to_screen * position;
-            // let text = Shape::text(&fonts, screen_position, Align2::LEFT_CENTER, "Hello worlds", FontId::new(26.0, FontFamily::Monospace), Color32::WHITE);
-            let circle = Circle(CircleShape {
-                center: screen_position,
-                radius: 0.02 * response.rect.height(), // Using the normalized radius for the screen
-                fill: input_colors[index],
-                stroke: Default::default(),
-            });
-            circle
-        }).collect();
-        let output_node_shapes: Vec<Shape> = output_nodes.iter().enumerate().map(|(index, node)| {
-            let position = get_node_position(index, NodeType::Output);
-            let screen_position = to_screen * position;
-
-            Circle(CircleShape {
-                center: screen_position,
-                radius: 0.02 * response.rect.height(), // Using the normalized radius for the screen
-                fill: Color32::LIGHT_RED,
-                stroke: Default::default(),
-            })
-        }).collect();
-        let connection_shapes: Vec<Shape> = connections.iter().map(|connection| {
-            let from_node = connection.in_node;
-            let to_node = connection.out_node - input_nodes.len();
-            let from_position = get_node_position(from_node, NodeType::Input);
-            let to_position = get_node_position(to_node, NodeType::Output);
-            let from_screen_position = to_screen * from_position;
-            let to_screen_position = to_screen * to_position;
-            let color = if connection.weight > 0.0 {
-                Color32::LIGHT_GREEN
-            } else {
-                Color32::LIGHT_RED
-            };
-            Shape::line_segment(
-                [from_screen_position, to_screen_position],
-                Stroke::new(connection.weight.abs() / 30.0 * response.rect.height(), color),
-            )
-        }).collect();
-        let painter = ui.painter();
-        let input_node_names = vec![
-            "bias",
-            "chaos",
-            "scent front",
-            "scent left",
-            "scent right",
-            "plant v. front",
-            "plant v. left",
-            "plant v. right",
-            "meat v. front",
-            "meat v. left",
-            "meat v. right",
-            "solid v. front",
-            "solid v. left",
-            "solid v. right",
-            "plant food level",
-            "meat food level",
-            "energy level",
-            "age level"];
-        let output_node_names = vec!["move forward", "move left", "move right", "wait"];
-        painter.extend(vec![specie_marker]);
-        painter.extend(connection_shapes);
-        painter.extend(input_node_shapes);
-        painter.extend(output_node_shapes);
-        input_node_names.iter().enumerate().for_each(|(i, name)| {
-            painter.text(to_screen * (get_node_position(i, NodeType::Input) - Vec2{ x: 0.05, y: 0.0 }), Align2::RIGHT_CENTER, name, FontId::new(12.0, FontFamily::Monospace), Color32::WHITE);
-        });
-        output_node_names.iter().enumerate().for_each(|(i, name)| {
-            painter.text(to_screen * (get_node_position(i, NodeType::Output) + Vec2{ x: 0.05, y: 0.0 }), Align2::LEFT_CENTER, name, FontId::new(12.0, FontFamily::Monospace), Color32::WHITE);
-        });
-        response
-    });
+fn save_genome(specie: &Specie) -> String {
+    let genome = Genome { network: specie.leader_network.clone(), dna: specie.leader_dna.clone() };
+    let path = Path::new(GENOMES_DIRECTORY).join(format!("specie_{}.toml", specie.id));
+    match std::fs::create_dir_all(GENOMES_DIRECTORY).and_then(|_| std::fs::write(&path, genome.to_toml())) {
+        Ok(()) => format!("Saved {}", path.display()),
+        Err(error) => format!("Unable to save {}: {}", path.display(), error),
+    }
 }
 
-fn get_node_position(index: usize, node_type: NodeType) -> Pos2 {
-    match node_type {
-        NodeType::Input => {
-            Pos2 { x: 0.25, y: 0.1 + index as f32 * 0.075 }
-        }
-        NodeType::Hidden => {
-            Pos2 { x: 0.5, y: 0.1 + index as f32 * 0.075 }
-        }
-        NodeType::Output => {
-            Pos2 { x: 0.85, y: 0.1 + index as f32 * 0.4 }
-        }
+fn show_config_problems(ui: &mut Ui, problems: &[String]) {
+    for problem in problems {
+        ui.colored_label(Color32::RED, format!("{}, the simulation uses the closest valid value", problem));
     }
 }
 
-fn draw_hexes(ui: &mut Ui, hexes: &Vec<Hex>, config: &Config) {
+// Returns the hex that was clicked and the button used, while painting every hex the primary button is held down on, the renderer draws all hexes on the gpu when the gui runs on glow
+#[allow(clippy::too_many_arguments)]
+fn draw_hexes(ui: &mut Ui, hexes: &HexCells, temperatures: Option<&[f32]>, energy_grid: Option<&EnergyGrid>, interpolation: Option<&Interpolation>, renderer: Option<&Arc<Mutex<HexRenderer>>>, viewport: &mut Viewport, config: &Config, species_colors: &SpeciesColors, painting: bool) -> Option<((usize, usize), PointerButton)> {
     Frame::canvas(ui.style()).fill(config.bg_color.color).show(ui, |ui| {
         let (mut response, _) =
-            ui.allocate_painter(ui.available_size_before_wrap(), Sense::drag());
-
-        let to_screen = emath::RectTransform::from_to(
-            Rect::from_min_size(Pos2::ZERO, response.rect.square_proportions()),
-            response.rect,
-        );
-
-        // let from_screen = to_screen.inverse();
-        let segment_alpha = 0.8;
-        let muscle_color = with_alpha(Color32::LIGHT_RED, segment_alpha);
-        let solid_color = with_alpha(Color32::BROWN, segment_alpha);
-        let solar_color = with_alpha(Color32::LIGHT_BLUE, segment_alpha);
-        let stomach_color = with_alpha(Color32::LIGHT_GREEN, segment_alpha);
-
-        let shapes: Vec<Shape> = hexes.iter().map(|hex| {
-            let position = Pos2 { x: hex.x as f32, y: hex.y as f32 };
-            let color = match &hex.hex_type {
-                HexType::SnakeHead { specie } => u32_to_color(*specie),
-                HexType::SnakeTail => config.tail_color.color,
-                HexType::Food => config.food_color.color,
-                HexType::Meat => Color32::RED,
-                HexType::Scent { value } => with_alpha(config.scent_color.color, config.scent_color.color.a() as f32 * value),
-                HexType::Segment { segment_type } => {
-                    match &segment_type {
-                        SegmentType::Muscle(_) => muscle_color,
-                        SegmentType::Solid(_) => solid_color,
-                        SegmentType::Solar(_) => solar_color,
-                        SegmentType::Stomach(_) => stomach_color,
+            ui.allocate_painter(ui.available_size_before_wrap(), Sense::click_and_drag());
+        // the primary button is taken by the brush
+        let pan_button = if painting { PointerButton::Secondary } else { PointerButton::Primary };
+        let cell = cell_size(config);
+        viewport.handle_input(ui, &response, world_size(config), config.rows.max(config.columns) as f32, pan_button);
+        let visible = viewport.visible_rect(response.rect.square_proportions(), world_size(config));
+        let to_screen = emath::RectTransform::from_to(visible, response.rect);
+
+        // hexes smaller than a few pixels are merged into blocks of their average color
+        let hex_pixels = to_screen.scale().y * cell;
+        let block = (MIN_HEX_PIXELS / hex_pixels).ceil().max(1.0) as usize;
+        let radius = block as f32 * cell / 2.0;
+        let columns = visible_cells(visible.min.x / cell, visible.max.x / cell, config.columns, block);
+        let rows = visible_cells(visible.min.y / cell, visible.max.y / cell, config.rows, block);
+        // temperatures are stored row by row and only drawn when they match the world size
+        let temperatures = temperatures.filter(|temperatures| temperatures.len() == config.columns * config.rows);
+        let mut circles: Vec<(Pos2, Color32)> = vec![];
+        let progress = interpolation.map_or(1.0, Interpolation::progress);
+        if block == 1 {
+            for y in rows {
+                for x in columns.clone() {
+                    let position = Pos2 { x: x as f32, y: y as f32 };
+                    if let Some(temperatures) = temperatures {
+                        circles.push((hex_center(&position, config), temperature_color(temperatures[y * config.columns + x])));
+                    }
+                    for hex_type in hexes.cell(x, y).iter().filter(|hex_type| config.layers.shows(hex_type)) {
+                        // moved heads slide from their previous hex until the next state is expected
+                        let center = match interpolation.and_then(|interpolation| interpolation.origin(x, y)) {
+                            Some((from_x, from_y)) => hex_center(&Pos2 { x: from_x as f32, y: from_y as f32 }, config).lerp(hex_center(&position, config), progress),
+                            None => hex_center(&position, config),
+                        };
+                        circles.push((center, hex_color(hex_type, config, species_colors)));
                     }
                 }
-            };
-            transform_to_circle(&position, &to_screen, &response, &config, color)
-        }).collect();
-
-        // let positions: Vec<Pos2> = (0..config.columns)
-        //     .flat_map(|x| (0..config.rows).map(move |y| Pos2 { x: x as f32, y: y as f32 }))
-        //     .collect();
-        let positions = vec![];
-
-        let mut ground: Vec<Shape> = positions.iter().map(|position| {
-            transform_to_circle(position, &to_screen, &response, &config, config.bg_color.color)
-        }).collect();
-        ground.extend(shapes);
+            }
+            if progress < 1.0 {
+                ui.ctx().request_repaint();
+            }
+        } else {
+            for y in rows.step_by(block) {
+                for x in columns.clone().step_by(block) {
+                    let cells: Vec<(usize, usize)> = (y..(y + block).min(config.rows)).flat_map(|y| (x..(x + block).min(config.columns)).map(move |x| (x, y))).collect();
+                    let center = Pos2 { x: (x as f32 + block as f32 / 2.0) * cell, y: (y as f32 + block as f32 / 2.0) * cell };
+                    if let Some(temperatures) = temperatures {
+                        let mean = cells.iter().map(|(x, y)| temperatures[y * config.columns + x]).sum::<f32>() / cells.len() as f32;
+                        circles.push((center, temperature_color(mean)));
+                    }
+                    // empty hexes count as transparent, so sparse blocks fade out
+                    let colors: Vec<Color32> = cells.iter().map(|(x, y)| hexes.cell(*x, *y).iter().rev().find(|hex_type| config.layers.shows(hex_type)).map_or(Color32::TRANSPARENT, |hex_type| hex_color(hex_type, config, species_colors))).collect();
+                    if colors.iter().any(|color| *color != Color32::TRANSPARENT) {
+                        circles.push((center, average_color(&colors)));
+                    }
+                }
+            }
+        }
         response.mark_changed();
-        let painter = ui.painter();
-        painter.extend(ground);
-        response
-    });
+        if let Some(renderer) = renderer {
+            let mut instances = Vec::with_capacity(circles.len() * 6);
+            for (center, color) in circles {
+                push_instance(&mut instances, center, color);
+            }
+            let renderer = Arc::clone(renderer);
+            ui.painter().add(egui::PaintCallback {
+                rect: response.rect,
+                callback: Arc::new(eframe::egui_glow::CallbackFn::new(move |_info, painter| {
+                    renderer.lock().unwrap().paint(painter.gl(), &instances, radius, visible);
+                })),
+            });
+        } else {
+            let shapes: Vec<Shape> = circles.into_iter().map(|(center, color)| circle_at(center, radius, &to_screen, color)).collect();
+            ui.painter().extend(shapes);
+        }
+        if let Some(grid) = energy_grid {
+            ui.painter().extend(energy_overlay(grid, &to_screen, config));
+        }
+        let primary_used = if painting { response.is_pointer_button_down_on() && ui.input(|input| input.pointer.primary_down()) } else { response.clicked() };
+        let button = if primary_used { Some(PointerButton::Primary) } else if response.secondary_clicked() { Some(PointerButton::Secondary) } else { None };
+        let hex = response.interact_pointer_pos().and_then(|pointer| hex_at(pointer, &to_screen, config));
+        button.zip(hex).map(|(button, hex)| (hex, button))
+    }).inner
+}
+
+// hexes are merged into blocks until they are at least this big on the screen
+const MIN_HEX_PIXELS: f32 = 2.0;
+
+// Rows or columns between the edges of the view given in cells, starting at a multiple of the block so blocks don't jump while panning
+fn visible_cells(from: f32, to: f32, cells: usize, block: usize) -> std::ops::Range<usize> {
+    let first = (from.floor() as i64 - 1).clamp(0, cells as i64) as usize;
+    let last = (to.ceil() as i64 + 1).clamp(0, cells as i64) as usize;
+    (first - first % block)..last
+}
+
+fn average_color(colors: &[Color32]) -> Color32 {
+    let mut sums = [0u32; 4];
+    for color in colors {
+        for (sum, channel) in sums.iter_mut().zip(color.to_array()) {
+            *sum += channel as u32;
+        }
+    }
+    let [r, g, b, a] = sums.map(|sum| (sum / colors.len() as u32) as u8);
+    Color32::from_rgba_premultiplied(r, g, b, a)
+}
+
+// Inverse of hex_center
+fn hex_at(screen_position: Pos2, to_screen: &emath::RectTransform, config: &Config) -> Option<(usize, usize)> {
+    let cell = cell_size(config);
+    let normalized = to_screen.inverse() * screen_position;
+    let row = (normalized.y / cell - 0.5).round();
+    if row < 0.0 || row >= config.rows as f32 {
+        return None;
+    }
+    let offset = if row as i32 % 2 == 0 { 0.5 } else { 0.0 };
+    let column = (normalized.x / cell - offset - 0.5).round();
+    (column >= 0.0 && column < config.columns as f32).then_some((column as usize, row as usize))
+}
+
+fn hex_color(hex_type: &HexType, config: &Config, species_colors: &SpeciesColors) -> Color32 {
+    let segment_alpha = 0.8;
+    match hex_type {
+        HexType::SnakeHead { specie } => species_colors.color(*specie),
+        HexType::ControlledHead => Color32::WHITE,
+        HexType::SnakeTail => config.tail_color.color,
+        HexType::Food => config.food_color.color,
+        HexType::Meat => Color32::RED,
+        HexType::Trail { specie, fade } => with_alpha(species_colors.color(*specie), 0.5 * fade),
+        HexType::Signal { value } => with_alpha(Color32::WHITE, 0.6 * value),
+        HexType::Nest { specie } => with_alpha(species_colors.color(*specie), 0.35),
+        HexType::Mineral => Color32::DARK_GRAY,
+        HexType::Water => Color32::DARK_BLUE,
+        HexType::Scent { value } => with_alpha(config.scent_color.color, config.scent_color.color.a() as f32 * value),
+        HexType::Segment { segment_type } => {
+            match &segment_type {
+                SegmentType::Muscle(_) => with_alpha(Color32::LIGHT_RED, segment_alpha),
+                SegmentType::Solid(_) => with_alpha(Color32::BROWN, segment_alpha),
+                SegmentType::Solar(_) => with_alpha(Color32::LIGHT_BLUE, segment_alpha),
+                SegmentType::Stomach(_) => with_alpha(Color32::LIGHT_GREEN, segment_alpha),
+                SegmentType::Fin(_) => with_alpha(Color32::LIGHT_YELLOW, segment_alpha),
+                SegmentType::Eye(_) => with_alpha(Color32::WHITE, segment_alpha),
+                SegmentType::Nose(_) => with_alpha(Color32::GOLD, segment_alpha),
+            }
+        }
+    }
+}
+
+fn temperature_color(temperature: f32) -> Color32 {
+    let hot = (temperature.clamp(0.0, 1.0) * 255.0) as u8;
+    Color32::from_rgba_unmultiplied(hot, 0, 255 - hot, 60)
 }
 
 fn with_alpha(color: Color32, alpha: f32) -> Color32 {
     Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), (alpha * 256.0) as u8)
 }
 
-fn transform_to_circle(game_position: &Pos2, to_screen: &emath::RectTransform, response: &Response, config: &Config, color: Color32) -> Shape {
-    // Radius is based on window's dimensions and the desired number of circles.
-    let radius = 1.0 / (2.0 * config.rows as f32);
+// Every hex takes the same space whatever the shape of the world, its longer side is one unit long
+fn cell_size(config: &Config) -> f32 {
+    1.0 / config.columns.max(config.rows) as f32
+}
+
+// Normalized size of the whole world
+fn world_size(config: &Config) -> Vec2 {
+    Vec2 { x: config.columns as f32, y: config.rows as f32 } * cell_size(config)
+}
+
+// Normalized center of the hex at the game position
+fn hex_center(game_position: &Pos2, config: &Config) -> Pos2 {
+    let cell = cell_size(config);
 
     // Offset every second row
-    let offset = if game_position.y as i32 % 2 == 0 { radius } else { 0.0 };
+    let offset = if game_position.y as i32 % 2 == 0 { 0.5 } else { 0.0 };
 
     // Normalize the game position
-    let normalized_position = Pos2 {
-        x: game_position.x / config.columns as f32 + offset + radius,
-        y: game_position.y / config.rows as f32 + radius,
-    };
+    Pos2 {
+        x: (game_position.x + offset + 0.5) * cell,
+        y: (game_position.y + 0.5) * cell,
+    }
+}
 
+fn circle_at(normalized_position: Pos2, radius: f32, to_screen: &emath::RectTransform, color: Color32) -> Shape {
     // Convert normalized position to screen position
     let screen_position = to_screen * normalized_position;
 
     Circle(CircleShape {
         center: screen_position,
-        radius: radius * response.rect.height(), // Using the normalized radius for the screen
+        radius: radius * to_screen.scale().y, // Using the normalized radius for the screen
         fill: color,
         stroke: Default::default(),
     })
 }
 
 fn should_draw_simulation(engine_state: Res<EngineState>) -> bool {
-    engine_state.repaint_needed
+    engine_state.repaint_needed && engine_state.draw_due()
 }
 
 #[derive(Resource)]
+#[allow(dead_code)]
 struct EguiEcsContext {
     context: egui::Context,
 }
 
+const DEFAULT_TRAIL_LENGTH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Brush {
+    Off,
+    Wall,
+    Erase,
+    Plant,
+    Meat,
+    // places one snake per click
+    Snake,
+}
+
+impl Brush {
+    const ALL: [Brush; 6] = [Brush::Off, Brush::Wall, Brush::Erase, Brush::Plant, Brush::Meat, Brush::Snake];
+
+    // only walls and food are painted while the button is held down
+    fn paints(self) -> bool {
+        !matches!(self, Brush::Off | Brush::Snake)
+    }
+
+    fn command(self, x: usize, y: usize, genome: Option<&Genome>) -> Option<EngineCommand> {
+        match self {
+            Brush::Off => None,
+            Brush::Snake => Some(EngineCommand::CreateSnakeAt { position: (x, y), genome: genome.cloned().map(Box::new) }),
+            Brush::Wall => Some(EngineCommand::SetSolid { x, y, solid: true }),
+            Brush::Erase => Some(EngineCommand::SetSolid { x, y, solid: false }),
+            Brush::Plant => Some(EngineCommand::AddFoodAt { x, y, kind: FoodKind::Plant }),
+            Brush::Meat => Some(EngineCommand::AddFoodAt { x, y, kind: FoodKind::Meat }),
+        }
+    }
+}
+
 #[derive(Resource, Clone, Copy)]
 struct Config {
     rows: usize,
@@ -316,304 +458,1026 @@ struct Config {
     scent_color: Stroke,
     food_color: Stroke,
     tail_color: Stroke,
-    add_walls: bool,
+    wall_layout: WallLayout,
+    wall_seed: u64,
+    perimeter_walls: bool,
+    water_layout: WaterLayout,
+    water_seed: u64,
+    // slides the snake heads between two drawn states, only changes how the world looks
+    smooth_movement: bool,
+    // shapes are only drawn by egui when this is off or the gpu renderer is not available
+    gpu_rendering: bool,
+    layers: Layers,
+    // how far the engine may run ahead of the gui, applied when the next simulation starts
+    event_capacity: EventCapacity,
 }
 
 struct MyEguiApp {
-    text: String,
-    total_frames: usize,
+    event_log: EventLog,
+    log_panel: LogPanel,
+    audio: AudioFeedback,
+    time_lapse: TimeLapse,
+    performance: Performance,
+    presets: Presets,
+    scenarios: ScenarioDialog,
+    // only there when the gui runs on glow
+    hex_renderer: Option<Arc<Mutex<HexRenderer>>>,
+    // every tab runs its own simulation, the toolbar and the windows act on the active one
+    tabs: Vec<WorldTab>,
+    active_tab: usize,
+    // used to name new tabs, names are never reused
+    tabs_created: usize,
+    draw_interval: u32,
     last_frame: Instant,
-    engine_commands_sender: Sender<EngineCommand>,
-    engine_events_sender: Sender<EngineEvent>,
-    engine_events_receiver: Receiver<EngineEvent>,
-    engine_commands_receiver: Arc<Mutex<Receiver<EngineCommand>>>,
-    can_draw_frame: bool,
-    config: Config,
-    hexes: Vec<Hex>,
-    updates_last_second: u32,
-    last_second: Instant,
-    frames_last_second: u32,
-    frames_per_second: u32,
-    updates_per_second: u32,
-    stats: Stats,
+    show_temperature: bool,
     show_simulation_settings: bool,
     show_mutation_settings: bool,
     show_species: bool,
     show_info: bool,
-    simulation_config: SimulationConfig,
-    simulation_running: bool,
+    show_events: bool,
+    show_log: bool,
+    show_sound_settings: bool,
+    show_export: bool,
+    show_statistics: bool,
+    show_food_web: bool,
+    show_performance: bool,
+    show_diagnostics: bool,
+    show_genome_distances: bool,
+    show_lineage: bool,
+    show_debugger: bool,
     show_networks: bool,
-    selected_network: u32,
-    fonts: Fonts,
+    show_mutation_preview: bool,
+    show_scenarios: bool,
+    // what a click or a drag on the world does to the hexes under the pointer
+    brush: Brush,
+    // the genome placed by the snake brush together with where it came from, random genes without it
+    placed_genome: Option<(String, Genome)>,
+    genome_status: String,
+    network_view: NetworkView,
+    mutation_preview: MutationPreview,
+    // text pasted by the user, either a share code or the toml of a saved genome
+    pasted_genome: String,
+    pasted_copies: usize,
 }
 
 impl MyEguiApp {
-    fn new(cc: &eframe::CreationContext<'_>, engine_commands_sender: Sender<EngineCommand>, engine_events_sender: Sender<EngineEvent>, engine_events_receiver: Receiver<EngineEvent>, engine_commands_receiver: Receiver<EngineCommand>) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, log_panel: LogPanel) -> Self {
         // Customize egui here with cc.egui_ctx.set_fonts and cc.egui_ctx.set_visuals.
         // Restore app state using cc.storage (requires the "persistence" feature).
         // Use the cc.gl (a glow::Context) to create graphics shaders and buffers that you can use
         // for e.g. egui::PaintCallback.
+        let config = Config {
+            rows: 100,
+            columns: 100,
+            bg_color: Stroke::new(1.0, Color32::LIGHT_GREEN),
+            scent_color: Stroke::new(1.0, Color32::from_rgba_unmultiplied(0xAD, 0xD8, 0xE6, 50)),
+            tail_color: Stroke::new(1.0, Color32::LIGHT_RED),
+            food_color: Stroke::new(1.0, Color32::YELLOW),
+            wall_layout: WallLayout::None,
+            wall_seed: 0,
+            water_layout: WaterLayout::None,
+            water_seed: 0,
+            perimeter_walls: false,
+            smooth_movement: false,
+            gpu_rendering: true,
+            layers: Layers::default(),
+            event_capacity: EventCapacity::default(),
+        };
+        let simulation_config = SimulationConfig {
+            rows: 100,
+            columns: 100,
+            create_scents: false,
+            scent_diffusion_rate: 0.2,
+            scent_dispersion_per_step: 30.0,
+            starting_snakes: 0,
+            starting_food: 0,
+            food_per_step: 2,
+            plant_matter_per_segment: 100.0,
+            wait_cost: 1.0,
+            move_cost: 10.0,
+            new_segment_cost: 100.0,
+            size_to_split: 12,
+            species_threshold: 0.2,
+            mutation: MutationConfig::default(),
+            background_stats: true,
+            snake_max_age: 2_000,
+            meat_energy_content: 5.0,
+            plant_energy_content: 1.0,
+            ..SimulationConfig::default()
+        };
         Self {
-            text: String::new(),
-            total_frames: 0,
-            updates_last_second: 0,
-            frames_last_second: 0,
-            frames_per_second: 0,
-            updates_per_second: 0,
+            event_log: EventLog::new(),
+            log_panel,
+            audio: AudioFeedback::new(),
+            time_lapse: TimeLapse::new(),
+            performance: Performance::new(),
+            presets: Presets::new(),
+            scenarios: ScenarioDialog::new(),
+            hex_renderer: cc.gl.as_ref().and_then(|gl| HexRenderer::new(gl).ok()).map(|renderer| Arc::new(Mutex::new(renderer))),
+            tabs: vec![WorldTab::new("World 1".to_string(), config, simulation_config)],
+            active_tab: 0,
+            tabs_created: 1,
+            draw_interval: 1,
             last_frame: Instant::now(),
-            last_second: Instant::now(),
-            engine_commands_sender,
-            engine_events_sender,
-            engine_events_receiver,
-            engine_commands_receiver: Arc::new(Mutex::new(engine_commands_receiver)),
-            config: Config {
-                rows: 100,
-                columns: 100,
-                bg_color: Stroke::new(1.0, Color32::LIGHT_GREEN),
-                scent_color: Stroke::new(1.0, Color32::from_rgba_unmultiplied(0xAD, 0xD8, 0xE6, 50)),
-                tail_color: Stroke::new(1.0, Color32::LIGHT_RED),
-                food_color: Stroke::new(1.0, Color32::YELLOW),
-                add_walls: false,
-            },
-            simulation_config: SimulationConfig {
-                rows: 100,
-                columns: 100,
-                create_scents: false,
-                scent_diffusion_rate: 0.2,
-                scent_dispersion_per_step: 30.0,
-                starting_snakes: 0,
-                starting_food: 0,
-                food_per_step: 2,
-                plant_matter_per_segment: 100.0,
-                wait_cost: 1.0,
-                move_cost: 10.0,
-                new_segment_cost: 100.0,
-                size_to_split: 12,
-                species_threshold: 0.2,
-                add_walls: false,
-                mutation: MutationConfig::default(),
-                snake_max_age: 2_000,
-                meat_energy_content: 5.0,
-                plant_energy_content: 1.0,
-            },
-            can_draw_frame: true,
-            stats: Stats::default(),
-            hexes: vec![],
+            show_temperature: false,
             show_simulation_settings: false,
             show_mutation_settings: false,
             show_species: false,
             show_networks: false,
+            show_mutation_preview: false,
+            show_scenarios: false,
             show_info: false,
-            simulation_running: false,
-            selected_network: 0,
-            fonts: Fonts::new(1.0, 2 * 1024, FontDefinitions::default()),
+            show_events: false,
+            show_log: false,
+            show_sound_settings: false,
+            show_export: false,
+            show_statistics: false,
+            show_food_web: false,
+            show_performance: false,
+            show_diagnostics: false,
+            show_genome_distances: false,
+            show_lineage: false,
+            show_debugger: false,
+            brush: Brush::Off,
+            placed_genome: None,
+            genome_status: String::new(),
+            network_view: NetworkView::new(),
+            mutation_preview: MutationPreview::new(),
+            pasted_genome: String::new(),
+            pasted_copies: 5,
+        }
+    }
+
+    // New worlds start with the settings of the active one
+    fn add_tab(&mut self) {
+        self.tabs_created += 1;
+        let active = &self.tabs[self.active_tab];
+        let mut tab = WorldTab::new(format!("World {}", self.tabs_created), active.config, active.simulation_config.clone());
+        tab.species_colors = SpeciesColors::new(active.species_colors.palette, active.species_colors.remap);
+        self.tabs.push(tab);
+        self.active_tab = self.tabs.len() - 1;
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        let mut tab = self.tabs.remove(index);
+        tab.stop_simulation();
+        if self.active_tab >= index && self.active_tab > 0 {
+            self.active_tab -= 1;
+        }
+    }
+
+    fn tabs_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let mut closed = None;
+            for (index, tab) in self.tabs.iter().enumerate() {
+                let label = if tab.simulation_running { tab.name.clone() } else { format!("{} (stopped)", tab.name) };
+                if ui.selectable_label(index == self.active_tab, label).clicked() {
+                    self.active_tab = index;
+                }
+                if self.tabs.len() > 1 && ui.small_button("x").on_hover_text("Stops the simulation and closes the tab").clicked() {
+                    closed = Some(index);
+                }
+                ui.separator();
+            }
+            if let Some(index) = closed {
+                self.close_tab(index);
+            }
+            if ui.button("New world").on_hover_text("Adds a tab for another simulation, it starts with the settings of the active tab").clicked() {
+                self.add_tab();
+            }
+        });
+    }
+
+    // Background tabs keep their history up to date, only the active one is heard and recorded in the time-lapse
+    fn process_events(&mut self) {
+        for (index, tab) in self.tabs.iter_mut().enumerate() {
+            let active = index == self.active_tab;
+            let mut received = 0;
+            tab.engine_events_receiver.try_iter().for_each(|result| {
+                received += 1;
+                match result {
+                    EngineEvent::SimulationFinished { steps, name, duration, paused } => {
+                        self.event_log.add_engine_message(format!("Simulation {} finished in {} steps in {} ms ({} ms paused)", name, steps, duration, paused));
+                    }
+                    EngineEvent::FrameDrawn { updates_left, updates_done, clock } => {
+                        tab.run_clock = clock;
+                        tab.status = format!("{:.1} updates left, {} updates done", updates_left, updates_done);
+                        tab.can_draw_frame = true;
+                        tab.total_frames += 1;
+                        tab.updates_last_second += updates_done;
+                        tab.frames_last_second += 1;
+                        if active {
+                            self.time_lapse.on_frames_simulated(updates_done);
+                        }
+                    }
+                    EngineEvent::DrawData { delta, stats, frame } => {
+                        tab.simulation_frame = frame;
+                        tab.hexes.apply(delta);
+                        if tab.config.smooth_movement {
+                            tab.interpolation.on_new_state(&tab.hexes);
+                        }
+                        if active {
+                            self.time_lapse.on_draw_data(&tab.hexes, &tab.config, &tab.species_colors);
+                        }
+                        tab.statistics.record(&stats);
+                        tab.food_web.record(&stats);
+                        tab.species_colors.update(&stats.species);
+                        tab.stats = *stats;
+                    }
+                    EngineEvent::SimulationEvents { events } => {
+                        if active {
+                            self.audio.on_events(&events);
+                            self.event_log.add_simulation_events(events);
+                        }
+                    }
+                    EngineEvent::TemperatureMap { temperatures, .. } => {
+                        tab.temperatures = temperatures;
+                    }
+                    EngineEvent::EnergyGrid { grid } => {
+                        tab.energy_grid = Some(*grid);
+                    }
+                    EngineEvent::Ancestry { ancestors } => {
+                        tab.ancestors = ancestors;
+                        self.show_lineage = true;
+                    }
+                    EngineEvent::Snapshot { snapshot } => {
+                        self.event_log.add_engine_message(save_snapshot(&snapshot));
+                    }
+                    EngineEvent::SystemTimings { timings } => {
+                        self.performance.record(timings);
+                    }
+                    EngineEvent::Diagnostics { diagnostics } => {
+                        tab.diagnostics.record(*diagnostics);
+                    }
+                    EngineEvent::Thoughts { snake, frame, trace } => {
+                        tab.debugger.record(snake, frame, *trace);
+                    }
+                    EngineEvent::RunStarted { metadata } => {
+                        tab.run = Some(metadata);
+                    }
+                    EngineEvent::GenomeDistances { matrix } => {
+                        tab.genome_distances.record(*matrix);
+                    }
+                }
+            });
+            tab.diagnostics.on_events_received(received);
+            tab.update_rates();
         }
     }
 }
 
 impl eframe::App for MyEguiApp {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn on_exit(&mut self, gl: Option<&eframe::glow::Context>) {
+        for tab in &mut self.tabs {
+            tab.stop_simulation();
+        }
+        if let (Some(renderer), Some(gl)) = (&self.hex_renderer, gl) {
+            renderer.lock().unwrap().destroy(gl);
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         puffin::profile_scope!("gui::update");
         if puffin::are_scopes_on() {
             puffin_egui::profiler_window(ctx);
             puffin::GlobalProfiler::lock().new_frame();
         }
-        self.engine_events_receiver.try_iter().for_each(|result| {
-            match result {
-                EngineEvent::SimulationFinished { steps, name, duration } => {
-                    self.text.push_str(&format!("\nSimulation {} finished in {} steps in {} ms", name, steps, duration));
-                }
-                EngineEvent::FrameDrawn { updates_left, updates_done } => {
-                    self.text = format!("{:.1} updates left, {} updates done", updates_left, updates_done);
-                    self.can_draw_frame = true;
-                    self.total_frames += 1;
-                    self.updates_last_second += updates_done;
-                    self.frames_last_second += 1;
-                }
-                EngineEvent::DrawData { hexes, stats } => {
-                    self.hexes = hexes;
-                    self.stats = stats;
-                }
-            }
+        self.process_events();
+        egui::TopBottomPanel::top("world_tabs").show(ctx, |ui| {
+            self.tabs_ui(ui);
         });
-        if self.last_second.elapsed().as_millis() > 1000 {
-            self.last_second = Instant::now();
-            self.updates_per_second = self.updates_last_second;
-            self.frames_per_second = self.frames_last_second;
-            self.updates_last_second = 0;
-            self.frames_last_second = 0;
-        }
+        let tab = &mut self.tabs[self.active_tab];
+        // values can still be out of range when they come from a preset file, the engine clamps them
+        let config_problems = tab.simulation_config.validate();
         egui::Window::new("Environment Settings").open(&mut self.show_simulation_settings).show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label("Size");
-                ui.add_enabled(!self.simulation_running, egui::DragValue::new(&mut self.config.columns).speed(1.0));
-                self.config.rows = self.config.columns;
-                self.simulation_config.rows = self.config.rows;
-                self.simulation_config.columns = self.config.columns;
+                ui.label("Columns");
+                let mut size_changed = ui.add(egui::DragValue::new(&mut tab.config.columns).speed(1.0).clamp_range(4..=1000)).changed();
+                ui.label("Rows");
+                size_changed |= ui.add(egui::DragValue::new(&mut tab.config.rows).speed(1.0).clamp_range(4..=1000)).on_hover_text("Odd rows break the hex pattern where the world wraps from the bottom to the top").changed();
+                tab.simulation_config.rows = tab.config.rows;
+                tab.simulation_config.columns = tab.config.columns;
+                if size_changed && tab.simulation_running {
+                    tab.engine_commands_sender.send(EngineCommand::ResizeWorld(tab.config.rows, tab.config.columns)).unwrap();
+                }
+            });
+            ui.add_enabled_ui(!tab.simulation_running, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Walls");
+                    egui::ComboBox::from_id_source("wall_layout")
+                        .selected_text(format!("{:?}", tab.config.wall_layout))
+                        .show_ui(ui, |ui| {
+                            for layout in WallLayout::ALL {
+                                ui.selectable_value(&mut tab.config.wall_layout, layout, format!("{:?}", layout));
+                            }
+                        });
+                    ui.label("Seed");
+                    ui.add(egui::DragValue::new(&mut tab.config.wall_seed).speed(1.0));
+                    ui.checkbox(&mut tab.config.perimeter_walls, "Perimeter").on_hover_text("Walls along the edges, snakes can't cross over to the other side");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Water");
+                    egui::ComboBox::from_id_source("water_layout")
+                        .selected_text(format!("{:?}", tab.config.water_layout))
+                        .show_ui(ui, |ui| {
+                            for layout in WaterLayout::ALL {
+                                ui.selectable_value(&mut tab.config.water_layout, layout, format!("{:?}", layout));
+                            }
+                        });
+                    ui.label("Seed");
+                    ui.add(egui::DragValue::new(&mut tab.config.water_seed).speed(1.0));
+                }).response.on_hover_text("Only snakes with a fin can swim through water, enable fins in the mutation settings so they can evolve");
             });
             ui.horizontal(|ui| {
-                ui.add_enabled(!self.simulation_running, egui::Checkbox::new(&mut self.config.add_walls, "Add walls"));
+                ui.label("Swimming cost");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.water_move_cost).speed(0.01).clamp_range(0.0..=2.0));
+            }).response.on_hover_text("Multiplier of the move cost for every hex a snake swims through");
+            ui.add_enabled_ui(tab.simulation_running, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Brush");
+                    for brush in Brush::ALL {
+                        ui.selectable_value(&mut self.brush, brush, format!("{:?}", brush));
+                    }
+                }).response.on_hover_text("Paints walls or food into the running world or places snakes, the right button pans while the brush is on. A right click drops plants, with shift meat");
+                if self.brush == Brush::Snake {
+                    ui.horizontal(|ui| {
+                        match &self.placed_genome {
+                            Some((source, _)) => ui.label(format!("Placing {}", source)),
+                            None => ui.label("Placing snakes with random genes, pick a genome in the networks window"),
+                        };
+                        if self.placed_genome.is_some() && ui.button("Random genes").clicked() {
+                            self.placed_genome = None;
+                        }
+                    });
+                }
             });
             ui.horizontal(|ui| {
                 ui.label("Food per step");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.food_per_step).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.food_per_step).speed(1.0));
+                ui.label("controlled by");
+                egui::ComboBox::from_id_source("food_control")
+                    .selected_text(format!("{:?}", tab.simulation_config.food_control))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut tab.simulation_config.food_control, FoodControl::Off, "Off");
+                        ui.selectable_value(&mut tab.simulation_config.food_control, FoodControl::Population, "Population");
+                        ui.selectable_value(&mut tab.simulation_config.food_control, FoodControl::Energy, "Energy");
+                    }).response.on_hover_text("Raises the food per step while the population or the snake energy is below the target and lowers it above");
+            });
+            ui.add_enabled_ui(tab.simulation_config.food_control != FoodControl::Off, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Target");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_control_target).speed(1.0).clamp_range(1.0..=1_000_000.0));
+                    ui.label("Gain");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_control_gain).speed(0.05).clamp_range(0.0..=100.0)).on_hover_text("How much the food per step follows the current distance to the target");
+                    ui.label("Integral gain");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_control_integral_gain).speed(0.001).clamp_range(0.0..=100.0)).on_hover_text("How much the food per step follows the distance summed up over time, removes a lasting offset but overshoots when too high");
+                    ui.label("Max food per step");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_control_max_rate).speed(1.0).clamp_range(0..=10_000));
+                    ui.label(format!("now {:.1}", tab.stats.food_per_step));
+                });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Food distribution");
+                egui::ComboBox::from_id_source("food_distribution")
+                    .selected_text(format!("{:?}", tab.simulation_config.food_distribution))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut tab.simulation_config.food_distribution, FoodDistribution::Uniform, "Uniform");
+                        ui.selectable_value(&mut tab.simulation_config.food_distribution, FoodDistribution::Patches, "Patches");
+                        ui.selectable_value(&mut tab.simulation_config.food_distribution, FoodDistribution::Oases, "Oases");
+                        ui.selectable_value(&mut tab.simulation_config.food_distribution, FoodDistribution::Ring, "Ring");
+                        ui.selectable_value(&mut tab.simulation_config.food_distribution, FoodDistribution::Gradient, "Gradient");
+                    });
+            });
+            let distribution = tab.simulation_config.food_distribution;
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(matches!(distribution, FoodDistribution::Patches | FoodDistribution::Oases), |ui| {
+                    ui.label("Patches");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_patches).speed(1.0).clamp_range(1..=100));
+                });
+                ui.add_enabled_ui(matches!(distribution, FoodDistribution::Patches | FoodDistribution::Oases | FoodDistribution::Ring), |ui| {
+                    ui.label("Spread");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_patch_radius).speed(1.0).clamp_range(0..=100));
+                });
+                ui.add_enabled_ui(distribution == FoodDistribution::Patches, |ui| {
+                    ui.label("Drift");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.food_patch_drift).speed(0.01).clamp_range(0.0..=10.0));
+                });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Food rate per quadrant (NW, NE, SW, SE)");
+                for rate in tab.simulation_config.food_region_rates.iter_mut() {
+                    ui.add(egui::DragValue::new(rate).speed(0.05).clamp_range(0.0..=10.0));
+                }
             });
             ui.horizontal(|ui| {
                 ui.label("Energy per segment");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.plant_matter_per_segment).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.plant_matter_per_segment).speed(1.0).clamp_range(0.0..=f32::MAX));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Temperature");
+                egui::ComboBox::from_id_source("temperature_layout")
+                    .selected_text(format!("{:?}", tab.simulation_config.temperature_layout))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut tab.simulation_config.temperature_layout, TemperatureLayout::Uniform, "Uniform");
+                        ui.selectable_value(&mut tab.simulation_config.temperature_layout, TemperatureLayout::Gradient, "Gradient");
+                        ui.selectable_value(&mut tab.simulation_config.temperature_layout, TemperatureLayout::Noise, "Noise");
+                    });
+                ui.label("Strength");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.temperature_strength).speed(0.01).clamp_range(0.0..=1.0));
+                ui.checkbox(&mut self.show_temperature, "Show");
+            }).response.on_hover_text("Heat makes moving more expensive but boosts energy production of segments, cold does the opposite");
+            ui.horizontal(|ui| {
+                let mut show_trails = tab.simulation_config.trail_length > 0;
+                if ui.checkbox(&mut show_trails, "Trails").changed() {
+                    tab.simulation_config.trail_length = if show_trails { DEFAULT_TRAIL_LENGTH } else { 0 };
+                }
+                ui.label("Length");
+                ui.add_enabled(show_trails, egui::DragValue::new(&mut tab.simulation_config.trail_length).speed(1.0).clamp_range(1..=1000));
+            }).response.on_hover_text("Fading trails behind every snake head show how the snakes move, e.g. circling or following walls");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.energy_transfer_enabled, "Energy transfer");
+                ui.label("Amount");
+                ui.add_enabled(tab.simulation_config.energy_transfer_enabled, egui::DragValue::new(&mut tab.simulation_config.energy_transfer_amount).speed(1.0).clamp_range(0.0..=f32::MAX));
+            }).response.on_hover_text("Snakes can use an extra brain output to give energy to the snake in front of them, which allows feeding offspring or parasitism to evolve");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.backward_move_enabled, "Backward moves");
+                ui.checkbox(&mut tab.simulation_config.sprint_enabled, "Sprint");
+            }).response.on_hover_text("Extra brain outputs for crawling one hex backward and for sprinting two hexes forward at twice the energy cost, only snakes created afterwards get them");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.nests_enabled, "Nests");
+                ui.add_enabled_ui(tab.simulation_config.nests_enabled, |ui| {
+                    ui.label("Cost");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.nest_cost).speed(1.0).clamp_range(0.0..=f32::MAX));
+                    ui.label("Radius");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.nest_radius).speed(0.1).clamp_range(0..=50));
+                    ui.label("Feeding");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.nest_feeding).speed(0.01).clamp_range(0.0..=f32::MAX));
+                    ui.label("Lifetime");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.nest_lifetime).speed(10.0).clamp_range(1..=u32::MAX));
+                });
+            }).response.on_hover_text("Snakes can use an extra brain output to build a nest for energy, it feeds the snakes of their specie within the radius every frame until it crumbles, only snakes created afterwards get the output");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.disease_enabled, "Disease");
+                ui.add_enabled_ui(tab.simulation_config.disease_enabled, |ui| {
+                    ui.label("Transmission");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.disease_transmission).speed(0.01).clamp_range(0.0..=1.0));
+                    ui.label("Virulence");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.disease_virulence).speed(0.01).clamp_range(0.0..=f32::MAX));
+                    ui.label("Duration");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.disease_duration).speed(10.0).clamp_range(1..=u32::MAX));
+                    ui.label("Immunity");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.disease_immunity).speed(10.0).clamp_range(0..=u32::MAX));
+                    ui.label("Outbreaks");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.disease_outbreak_chance).speed(0.0001).clamp_range(0.0..=1.0));
+                });
+            }).response.on_hover_text("A pathogen passes between snakes when a head touches another snake with the transmission chance, sick snakes pay the virulence as extra energy per segment every frame until they recover or starve, recovered ones stay immune for a while and outbreaks make random healthy snakes sick");
+            ui.horizontal(|ui| {
+                ui.label("Crowding");
+                ui.label("Radius");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.crowding_radius).speed(0.1).clamp_range(0..=10));
+                ui.label("Capacity");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.crowding_capacity).speed(0.1).clamp_range(0..=1000));
+                ui.label("Penalty");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.crowding_penalty).speed(0.001).clamp_range(0.0..=f32::MAX));
+            }).response.on_hover_text("Snakes with more segments of other snakes within the radius around their head than the capacity pay the penalty in energy for each of them every frame, a penalty of 0 turns crowding off");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.minerals_enabled, "Minerals");
+                ui.add_enabled_ui(tab.simulation_config.minerals_enabled, |ui| {
+                    ui.label("Per step");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.minerals_per_step).speed(0.1).clamp_range(0..=1000));
+                    ui.label("Deposit");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.mineral_deposit).speed(0.1).clamp_range(0.0..=f32::MAX));
+                    ui.label("Carried");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.max_minerals_carried).speed(0.1).clamp_range(0.0..=f32::MAX));
+                    ui.label("Solid cost");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.solid_segment_minerals).speed(0.1).clamp_range(0.0..=f32::MAX));
+                    ui.label("Vision");
+                    ui.add(egui::DragValue::new(&mut tab.simulation_config.mineral_vision_range).speed(0.1).clamp_range(1..=50));
+                });
+            }).response.on_hover_text("Mineral deposits show up on free hexes, snakes pick them up with their head until they carry the most they can and need the solid cost for every solid segment they grow, two sensors see deposits ahead and tell how much is carried");
+            ui.horizontal(|ui| {
+                ui.label("Speed factor");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.speed_factor).speed(0.1).clamp_range(0.0..=5.0));
+            }).response.on_hover_text("Scales how fast snakes gain move potential, above 1 the most mobile snakes act several times per frame while sluggish ones still wait for their turn");
+            ui.horizontal(|ui| {
+                ui.label("Action temperature");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.action_temperature).speed(0.01).clamp_range(0.0..=10.0));
+            }).response.on_hover_text("0 always takes the strongest brain output, higher values let weaker outputs win sometimes, which keeps fresh brains from doing the same thing over and over");
+            ui.collapsing("Statistics regions", |ui| {
+                ui.label("Edges are shares of the world width and height");
+                let mut removed = None;
+                egui::Grid::new("regions").num_columns(6).show(ui, |ui| {
+                    for label in ["Name", "Left", "Top", "Right", "Bottom", ""] {
+                        ui.label(label);
+                    }
+                    ui.end_row();
+                    for (index, region) in tab.simulation_config.regions.iter_mut().enumerate() {
+                        ui.add(egui::TextEdit::singleline(&mut region.name).desired_width(100.0));
+                        ui.add(egui::DragValue::new(&mut region.left).speed(0.01).clamp_range(0.0..=1.0));
+                        ui.add(egui::DragValue::new(&mut region.top).speed(0.01).clamp_range(0.0..=1.0));
+                        ui.add(egui::DragValue::new(&mut region.right).speed(0.01).clamp_range(region.left..=1.0));
+                        ui.add(egui::DragValue::new(&mut region.bottom).speed(0.01).clamp_range(region.top..=1.0));
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(index) = removed {
+                    tab.simulation_config.regions.remove(index);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add region").clicked() {
+                        let name = format!("Region {}", tab.simulation_config.regions.len() + 1);
+                        tab.simulation_config.regions.push(Region::new(&name, 0.25, 0.25, 0.75, 0.75));
+                    }
+                    if ui.button("Quadrants").clicked() {
+                        tab.simulation_config.regions = Region::quadrants();
+                    }
+                });
+            });
+            ui.add_enabled_ui(!tab.simulation_running, |ui| {
+                ui.collapsing("Moving obstacles", |ui| {
+                    ui.label("Lines of hexes running east, the first hex is given as shares of the world width and height");
+                    let mut removed = None;
+                    egui::Grid::new("obstacles").num_columns(8).show(ui, |ui| {
+                        for label in ["", "X", "Y", "Length", "Period", "Direction", "Distance", ""] {
+                            ui.label(label);
+                        }
+                        ui.end_row();
+                        for (index, obstacle) in tab.simulation_config.obstacles.iter_mut().enumerate() {
+                            ui.label(match obstacle.pattern {
+                                MovementPattern::Gate { .. } => "Gate",
+                                MovementPattern::Slide { .. } => "Slide",
+                            });
+                            ui.add(egui::DragValue::new(&mut obstacle.x).speed(0.01).clamp_range(0.0..=1.0));
+                            ui.add(egui::DragValue::new(&mut obstacle.y).speed(0.01).clamp_range(0.0..=1.0));
+                            ui.add(egui::DragValue::new(&mut obstacle.length).speed(1.0).clamp_range(1..=1000));
+                            match &mut obstacle.pattern {
+                                MovementPattern::Gate { period } => {
+                                    ui.add(egui::DragValue::new(period).speed(1.0).clamp_range(1..=10000));
+                                    ui.label("");
+                                    ui.label("");
+                                }
+                                MovementPattern::Slide { direction, distance, period } => {
+                                    ui.add(egui::DragValue::new(period).speed(1.0).clamp_range(1..=10000));
+                                    egui::ComboBox::from_id_source(("obstacle_direction", index))
+                                        .selected_text(format!("{:?}", direction))
+                                        .show_ui(ui, |ui| {
+                                            for option in Direction::ALL {
+                                                let text = format!("{:?}", option);
+                                                ui.selectable_value(direction, option, text);
+                                            }
+                                        });
+                                    ui.add(egui::DragValue::new(distance).speed(1.0).clamp_range(1..=1000));
+                                }
+                            }
+                            if ui.button("Remove").clicked() {
+                                removed = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some(index) = removed {
+                        tab.simulation_config.obstacles.remove(index);
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Add gate").clicked() {
+                            tab.simulation_config.obstacles.push(Obstacle::gate(0.25, 0.5, 10, 100));
+                        }
+                        if ui.button("Add slide").clicked() {
+                            tab.simulation_config.obstacles.push(Obstacle::slide(0.25, 0.25, 5, Direction::SouthEast, 10, 10));
+                        }
+                    });
+                });
+            }).response.on_hover_text("Gates close and open every period frames, slides move one hex every period frames and come back after the distance, both wait while a snake is in the way");
+            ui.horizontal(|ui| {
+                ui.label("Diet tradeoff");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.diet_tradeoff_strength).speed(0.01).clamp_range(0.0..=1.0));
+                ui.label("Curve exponent");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.diet_tradeoff_exponent).speed(0.05).clamp_range(0.1..=10.0));
+            }).response.on_hover_text("Share of plant digestion lost by a body made only of stomachs, the exponent shapes the loss over the stomach share of the body");
+            ui.horizontal(|ui| {
+                ui.label("Meat decay rate");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.meat_decay_rate).speed(0.0001).clamp_range(0.0..=1.0));
+                ui.label("Composted part");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.meat_compost_ratio).speed(0.01).clamp_range(0.0..=1.0));
             });
             ui.horizontal(|ui| {
                 ui.label("Wait cost");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.wait_cost).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.wait_cost).speed(1.0).clamp_range(0.0..=f32::MAX));
             });
             ui.horizontal(|ui| {
                 ui.label("Move cost");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.move_cost).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.move_cost).speed(1.0).clamp_range(0.0..=f32::MAX));
             });
+            ui.horizontal(|ui| {
+                ui.label("Collisions");
+                egui::ComboBox::from_id_source("collision_policy")
+                    .selected_text(format!("{:?}", tab.simulation_config.collision_policy))
+                    .show_ui(ui, |ui| {
+                        for policy in CollisionPolicy::ALL {
+                            ui.selectable_value(&mut tab.simulation_config.collision_policy, policy, format!("{:?}", policy));
+                        }
+                    });
+                ui.label("Bounce cost");
+                ui.add_enabled(tab.simulation_config.collision_policy == CollisionPolicy::Bounce, egui::DragValue::new(&mut tab.simulation_config.bounce_cost).speed(1.0).clamp_range(0.0..=f32::MAX));
+            }).response.on_hover_text("Snakes moving into something solid either die or bounce off, staying where they were and losing the bounce cost on top of the move");
+            ui.horizontal(|ui| {
+                ui.label("Starvation grace");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.starvation_grace).speed(1.0).clamp_range(0..=1000));
+                ui.label("Efficiency while starving");
+                ui.add_enabled(tab.simulation_config.starvation_grace > 0, egui::DragValue::new(&mut tab.simulation_config.starving_efficiency).speed(0.01).clamp_range(0.01..=1.0));
+            }).response.on_hover_text("Frames a snake survives below zero energy before it starves. Starving snakes can't act, produce less and pay more upkeep, their hunger input grows until they die");
             ui.horizontal(|ui| {
                 ui.label("New segment energy cost");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.new_segment_cost).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.new_segment_cost).speed(1.0).clamp_range(0.0..=f32::MAX));
             });
             ui.horizontal(|ui| {
                 ui.label("Size to split");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.size_to_split).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.size_to_split).speed(1.0).clamp_range(2..=usize::MAX));
             });
+            ui.horizontal(|ui| {
+                ui.label("Splits from age");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.maturity_age).speed(10.0).clamp_range(0..=u32::MAX));
+                ui.label("until age");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.menopause_age).speed(10.0).clamp_range(0..=u32::MAX));
+            }).response.on_hover_text("Young snakes have to mature before they can split and old ones stop splitting, 0 turns either limit off");
             ui.horizontal(|ui| {
                 ui.label("Aging starts at");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.snake_max_age).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.snake_max_age).speed(1.0).clamp_range(1..=u32::MAX));
             });
+            ui.horizontal(|ui| {
+                ui.label("Aging rate");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.aging_rate).speed(0.1).clamp_range(0..=1_000));
+                ui.label("Aging curve");
+                egui::ComboBox::from_id_source("aging_curve")
+                    .selected_text(format!("{:?}", tab.simulation_config.aging_curve))
+                    .show_ui(ui, |ui| {
+                        for curve in AgingCurve::ALL {
+                            ui.selectable_value(&mut tab.simulation_config.aging_curve, curve, format!("{:?}", curve));
+                        }
+                    });
+            }).response.on_hover_text("Age gained every tick and how the efficiency of snakes falls once they are older than where aging starts");
+            ui.horizontal(|ui| {
+                ui.label("Old age mortality");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.old_age_mortality).speed(0.001).clamp_range(0.0..=1.0));
+                ui.label("Max lifespan");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.max_lifespan).speed(10.0).clamp_range(0..=u32::MAX));
+            }).response.on_hover_text("Chance per frame that a snake at the lowest efficiency dies of old age, it follows the aging curve so snakes that just started aging rarely die. Snakes always die at the max lifespan, 0 turns it off");
+            ui.horizontal(|ui| {
+                ui.label("Ticks per day");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.ticks_per_day).speed(1.0).clamp_range(1..=u32::MAX));
+                ui.label("Days per year");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.days_per_year).speed(1.0).clamp_range(1..=u32::MAX));
+            }).response.on_hover_text("Every simulated frame is one tick, only changes how the time is shown");
             ui.horizontal(|ui| {
                 ui.label("Species coloring threshold");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.species_threshold).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.species_threshold).speed(0.01).clamp_range(0.0..=f32::MAX));
             });
-            ui.add(egui::Checkbox::new(&mut self.simulation_config.create_scents, "Create smell (low performance, memory leaks)"));
+            ui.horizontal(|ui| {
+                ui.label("Species distance: excess");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.excess_gene_coefficient).speed(0.01).clamp_range(0.0..=f32::MAX));
+                ui.label("disjoint");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.disjoint_gene_coefficient).speed(0.01).clamp_range(0.0..=f32::MAX));
+                ui.label("weights");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.weight_difference_coefficient).speed(0.01).clamp_range(0.0..=f32::MAX));
+            }).response.on_hover_text("Coefficients of the excess genes, disjoint genes and average weight difference when comparing a newborn to a specie leader");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.species_quotas_enabled, "Species quotas");
+                ui.label("Population");
+                ui.add_enabled(tab.simulation_config.species_quotas_enabled, egui::DragValue::new(&mut tab.simulation_config.quota_population).speed(1.0).clamp_range(1..=usize::MAX));
+                ui.add_enabled(tab.simulation_config.species_quotas_enabled, egui::Checkbox::new(&mut tab.simulation_config.protect_elites, "Protect elites"));
+            }).response.on_hover_text("Species can only split up to their share of the population, shares follow the food eaten per member. Protected elites, the best eater of each specie, don't starve before they get old");
+            ui.horizontal(|ui| {
+                ui.label("Max snakes");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.max_snakes).speed(10.0).clamp_range(1..=usize::MAX));
+                ui.label("segments");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.max_segments).speed(100.0).clamp_range(1..=usize::MAX));
+                ui.label("food");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.max_food).speed(100.0).clamp_range(1..=usize::MAX));
+                ui.label("smells");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.max_scents).speed(100.0).clamp_range(1..=usize::MAX));
+            }).response.on_hover_text("Food and smells stop spawning at their limit, the policy decides what happens to snakes and segments");
+            ui.horizontal(|ui| {
+                ui.label("Overpopulation policy");
+                egui::ComboBox::from_id_source("overpopulation_policy")
+                    .selected_text(format!("{:?}", tab.simulation_config.overpopulation_policy))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut tab.simulation_config.overpopulation_policy, OverpopulationPolicy::BlockSplits, "BlockSplits");
+                        ui.selectable_value(&mut tab.simulation_config.overpopulation_policy, OverpopulationPolicy::CullWeakest, "CullWeakest");
+                    });
+            });
+            ui.add(egui::Checkbox::new(&mut tab.simulation_config.create_scents, "Create smell (low performance, memory leaks)"));
             ui.horizontal(|ui| {
                 ui.label("Smell diffusion rate");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.scent_diffusion_rate).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.scent_diffusion_rate).speed(0.01).clamp_range(0.0..=1.0));
             });
             ui.horizontal(|ui| {
                 ui.label("Smell dispersion rate per step");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.scent_dispersion_per_step).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.scent_dispersion_per_step).speed(1.0).clamp_range(0.0..=f32::MAX));
             });
+            show_config_problems(ui, &config_problems);
         });
         egui::Window::new("Mutation Settings").open(&mut self.show_mutation_settings).show(ctx, |ui| {
             ui.label("Senses:");
             ui.horizontal(|ui| {
-                ui.checkbox(&mut self.simulation_config.mutation.chaos_input_enabled, "Chaos gene");
+                ui.checkbox(&mut tab.simulation_config.mutation.chaos_input_enabled, "Chaos gene");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.memory_input_enabled, "Memory of last action");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.compass_input_enabled, "Compass");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.food_direction_enabled, "Direction to nearest food");
+                ui.label("Range");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.food_direction_range).speed(1.0).clamp_range(1..=20));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.touch_input_enabled, "Touch of neighbouring hexes");
             });
             ui.horizontal(|ui| {
-                ui.checkbox(&mut self.simulation_config.mutation.scent_sensing_enabled, "Food smelling");
+                ui.checkbox(&mut tab.simulation_config.mutation.signaling_enabled, "Signaling");
+            }).response.on_hover_text("Snakes set a signal with an extra output, it glows on their heads and other snakes see it when their front obstacle vision hits them");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.fins_enabled, "Fins");
+            }).response.on_hover_text("Fins can show up in the dna, a snake with a fin can swim through water");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.sense_segments_enabled, "Eyes and noses");
+                ui.label("Vision per eye");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.eye_vision_bonus).speed(1.0).clamp_range(0..=10));
+                ui.label("Smell per nose");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.nose_smell_bonus).speed(1.0).clamp_range(0..=10));
+            }).response.on_hover_text("Eyes and noses can show up in the dna, they cost energy every frame, every eye adds hexes to all vision ranges and every nose lets the snake smell scents further away");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut tab.simulation_config.mutation.scent_sensing_enabled, "Food smelling");
             });
             ui.horizontal(|ui| {
-                ui.checkbox(&mut self.simulation_config.mutation.plant_vision_enabled, "Plant vision");
+                ui.checkbox(&mut tab.simulation_config.mutation.plant_vision_enabled, "Plant vision");
             });
             ui.horizontal(|ui| {
                 ui.label("Front range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.plant_vision_front_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.plant_vision_front_range).speed(1.0).clamp_range(1..=u32::MAX));
                 ui.label("Left range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.plant_vision_left_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.plant_vision_left_range).speed(1.0).clamp_range(1..=u32::MAX));
                 ui.label("Right range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.plant_vision_right_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.plant_vision_right_range).speed(1.0).clamp_range(1..=u32::MAX));
             });
             ui.horizontal(|ui| {
-                ui.checkbox(&mut self.simulation_config.mutation.meat_vision_enabled, "Meat vision");
+                ui.checkbox(&mut tab.simulation_config.mutation.meat_vision_enabled, "Meat vision");
             });
             ui.horizontal(|ui| {
                 ui.label("Front range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.meat_vision_front_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.meat_vision_front_range).speed(1.0).clamp_range(1..=u32::MAX));
                 ui.label("Left range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.meat_vision_left_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.meat_vision_left_range).speed(1.0).clamp_range(1..=u32::MAX));
                 ui.label("Right range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.meat_vision_right_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.meat_vision_right_range).speed(1.0).clamp_range(1..=u32::MAX));
             });
             ui.horizontal(|ui| {
-                ui.checkbox(&mut self.simulation_config.mutation.obstacle_vision_enabled, "Obstacle vision");
+                ui.checkbox(&mut tab.simulation_config.mutation.obstacle_vision_enabled, "Obstacle vision");
             });
             ui.horizontal(|ui| {
                 ui.label("Front range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.obstacle_vision_front_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.obstacle_vision_front_range).speed(1.0).clamp_range(1..=u32::MAX));
                 ui.label("Left range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.obstacle_vision_left_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.obstacle_vision_left_range).speed(1.0).clamp_range(1..=u32::MAX));
                 ui.label("Right range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.obstacle_vision_right_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.obstacle_vision_right_range).speed(1.0).clamp_range(1..=u32::MAX));
             });
             ui.label("Mutation settings:");
             ui.horizontal(|ui| {
                 ui.label("Weights perturbation chance");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.weight_perturbation_chance).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.weight_perturbation_chance).speed(0.01).clamp_range(0.0..=1.0));
             });
             ui.horizontal(|ui| {
                 ui.label("Weights perturbation range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.weight_perturbation_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.weight_perturbation_range).speed(0.01).clamp_range(0.0..=f32::MAX));
             });
-            ui.add(egui::Checkbox::new(&mut self.simulation_config.mutation.perturb_disabled_connections, "Perturb disabled connections"));
+            ui.add(egui::Checkbox::new(&mut tab.simulation_config.mutation.perturb_disabled_connections, "Perturb disabled connections"));
             ui.horizontal(|ui| {
                 ui.label("Weights reset chance");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.weight_reset_chance).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.weight_reset_chance).speed(0.01).clamp_range(0.0..=1.0));
             });
             ui.horizontal(|ui| {
                 ui.label("Weights reset range");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.weight_reset_range).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.weight_reset_range).speed(0.01).clamp_range(0.0..=f32::MAX));
             });
-            ui.add(egui::Checkbox::new(&mut self.simulation_config.mutation.perturb_disabled_connections, "Perturb reset connections"));
+            ui.add(egui::Checkbox::new(&mut tab.simulation_config.mutation.perturb_disabled_connections, "Perturb reset connections"));
             ui.horizontal(|ui| {
                 ui.label("Connection flip chance");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.connection_flip_chance).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.connection_flip_chance).speed(0.01).clamp_range(0.0..=1.0));
             });
             ui.horizontal(|ui| {
                 ui.label("Dna mutation chance");
-                ui.add(egui::DragValue::new(&mut self.simulation_config.mutation.dna_mutation_chance).speed(1.0));
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.dna_mutation_chance).speed(0.01).clamp_range(0.0..=1.0));
             });
+            ui.horizontal(|ui| {
+                ui.label("Network pruning chance");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.prune_chance).speed(0.01).clamp_range(0.0..=1.0));
+            }).response.on_hover_text("Removes disabled connections and hidden nodes that can't reach an output from the network of a newborn");
+            ui.horizontal(|ui| {
+                ui.label("Action temperature mutation range");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.action_temperature_mutation_range).speed(0.01).clamp_range(0.0..=10.0));
+            }).response.on_hover_text("Above 0 every snake evolves its own action temperature, starting from the one in the environment settings");
+            ui.horizontal(|ui| {
+                ui.label("Trait mutation chance");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.trait_mutation_chance).speed(0.01).clamp_range(0.0..=1.0));
+                ui.label("Range");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.mutation.trait_mutation_range).speed(0.01).clamp_range(0.0..=1.0));
+            }).response.on_hover_text("Chance that offspring get one metabolism trait scaled by up to the range, e.g. 0.1 is up to 10% more or less");
+            let mutation = &mut tab.simulation_config.mutation;
+            for (name, range) in [("Max energy", &mut mutation.max_energy_range), ("Digestion speed", &mut mutation.digestion_speed_range), ("Mobility", &mut mutation.mobility_range)] {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} multiplier from", name));
+                    ui.add(egui::DragValue::new(&mut range.0).speed(0.01).clamp_range(0.01..=100.0));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut range.1).speed(0.01).clamp_range(0.01..=100.0));
+                });
+            }
+            show_config_problems(ui, &config_problems);
+            ui.collapsing("Operator statistics", |ui| {
+                mutation_stats_ui(ui, &tab.stats.mutations);
+            });
+        });
+        egui::Window::new("Species").open(&mut self.show_species).show(ctx, |ui| {
+            tab.species_colors.ui(ui, &tab.stats.species, &mut tab.selected_network);
+            if let Some(selected_specie) = tab.stats.species.species.iter().find(|specie| specie.id == tab.selected_network) {
+                ui.separator();
+                body_plan_ui(ui, selected_specie.id, &selected_specie.leader_dna, tab.simulation_config.size_to_split, &tab.config, &tab.species_colors);
+            }
         });
-        egui::Window::new("Species").open(&mut self.show_species).show(ctx, |ui| {});
         egui::Window::new("Networks").open(&mut self.show_networks).show(ctx, |ui| {
-            let specie_ids = &self.stats.species.species.iter().map(|specie| specie.id).collect::<Vec<u32>>();
-            if specie_ids.len() == 0 {
+            ui.collapsing("Paste genome", |ui| {
+                ui.add(egui::TextEdit::multiline(&mut self.pasted_genome).desired_rows(2).hint_text("Paste a copied genome here"));
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.pasted_copies).speed(0.1).clamp_range(1..=100));
+                    if ui.add_enabled(tab.simulation_running, egui::Button::new("Spawn copies")).clicked() {
+                        self.genome_status = match Genome::from_share_code(&self.pasted_genome) {
+                            Ok(genome) => {
+                                tab.engine_commands_sender.send(EngineCommand::SpawnGenome(Box::new(genome), self.pasted_copies)).unwrap();
+                                format!("Spawned {} snakes from the pasted genome", self.pasted_copies)
+                            }
+                            Err(error) => format!("Unable to read the pasted genome: {}", error),
+                        };
+                    }
+                    if ui.button("Place").on_hover_text("Places snakes with the pasted genome wherever the world is clicked").clicked() {
+                        self.genome_status = match Genome::from_share_code(&self.pasted_genome) {
+                            Ok(genome) => {
+                                self.placed_genome = Some(("the pasted genome".to_string(), genome));
+                                self.brush = Brush::Snake;
+                                "Click the world to place snakes".to_string()
+                            }
+                            Err(error) => format!("Unable to read the pasted genome: {}", error),
+                        };
+                    }
+                    ui.label(&self.genome_status);
+                });
+            });
+            let specie_ids = &tab.stats.species.species.iter().map(|specie| specie.id).collect::<Vec<u32>>();
+            if specie_ids.is_empty() {
                 ui.label("No networks yet");
                 return;
             }
-            let selected_specie_in_list = specie_ids.contains(&self.selected_network);
+            let selected_specie_in_list = specie_ids.contains(&tab.selected_network);
             if !selected_specie_in_list {
-                self.selected_network = specie_ids[0];
+                tab.selected_network = specie_ids[0];
             }
             ui.horizontal(|ui| {
                 egui::ComboBox::from_label("Specie")
-                    .selected_text(format!("{:?}", self.selected_network))
+                    .selected_text(format!("{:?}", tab.selected_network))
                     .show_ui(ui, |ui| {
                         for specie_id in specie_ids {
-                            ui.selectable_value(&mut self.selected_network, *specie_id, format!("{:?}", specie_id));
+                            ui.selectable_value(&mut tab.selected_network, *specie_id, format!("{:?}", specie_id));
                         }
                     });
                 if ui.button("Next").clicked() {
-                    self.selected_network = specie_ids[(specie_ids.iter().position(|id| *id == self.selected_network).unwrap() + 1) % specie_ids.len()];
+                    tab.selected_network = specie_ids[(specie_ids.iter().position(|id| *id == tab.selected_network).unwrap() + 1) % specie_ids.len()];
                 }
                 if ui.button("Previous").clicked() {
-                    self.selected_network = specie_ids[(specie_ids.iter().position(|id| *id == self.selected_network).unwrap() + specie_ids.len() - 1) % specie_ids.len()];
+                    tab.selected_network = specie_ids[(specie_ids.iter().position(|id| *id == tab.selected_network).unwrap() + specie_ids.len() - 1) % specie_ids.len()];
                 }
             });
             ui.collapsing("Information", |ui| {
                 ui.label("Green connections mean that the weight is positive, red connections mean that the weight is negative. The thicker the connection, the higher the weight.");
+                ui.label("Hidden nodes are placed in columns by how many nodes are before them, drag any node to move it");
                 ui.label("Positive weight means the snake wants to do the given action if it encounters this sensory input.");
                 ui.label("Bias is a constant value of 1.0, chaos is a random number from range 0.0 .. 1.0 generated each tick");
+                ui.label("Moved forward, turned left and turned right remember the last action, move blocked is 1.0 when the snake wanted to move but was too slow to do so");
+                ui.label("Facing north/east is the absolute heading, food ahead/to the left points to the nearest food within smelling range (both 0.0 if there is none)");
                 ui.label("Network cost is the energy it takes each turn to 'think'");
 
                 ui.horizontal(|ui| {
-                    ui.label(
-                        r#"Input Nodes:
-                    "#);
-                    ui.label(
-                        r#"Output Nodes
-                    Move Forward
-                    Move Left
-                    Move Right
-                    Wait"#);
+                    ui.label(format!("Input Nodes:\n{}", SENSOR_NAMES.join("\n")));
+                    ui.label(format!("Output Nodes:\n{}", OUTPUT_NAMES.join("\n")));
                 });
             });
-            if let Some(selected_specie) = self.stats.species.species.iter().find(|specie| specie.id == self.selected_network) {
-                ui.label(format!("Network run cost: {}", selected_specie.leader_network.run_cost()));
-                draw_neural_network(ui, &self.fonts, selected_specie.id, &selected_specie.leader_network.get_nodes(), &selected_specie.leader_network.get_active_connections());
+            if let Some(selected_specie) = tab.stats.species.species.iter().find(|specie| specie.id == tab.selected_network) {
+                ui.horizontal(|ui| {
+                    let size = selected_specie.leader_network.effective_size();
+                    ui.label(format!("Network run cost: {} ({} nodes, {} connections in use)", selected_specie.leader_network.run_cost(), size.nodes, size.connections));
+                    let traits = selected_specie.leader_dna.traits;
+                    ui.label(format!("Traits: max energy x{:.2}, digestion x{:.2}, mobility x{:.2}", traits.max_energy, traits.digestion_speed, traits.mobility));
+                    if ui.button("Save genome").on_hover_text("Saves the leader of this specie to the genomes directory, e.g. for the arena of the cli").clicked() {
+                        self.genome_status = save_genome(selected_specie);
+                    }
+                    if ui.button("Copy genome").on_hover_text("Copies the leader of this specie as a single line that can be pasted into another simulation").clicked() {
+                        let genome = Genome { network: selected_specie.leader_network.clone(), dna: selected_specie.leader_dna.clone() };
+                        ui.output_mut(|output| output.copied_text = genome.to_share_code());
+                        self.genome_status = format!("Copied the leader of specie {}", selected_specie.id);
+                    }
+                    if ui.button("Place leader").on_hover_text("Places copies of the leader wherever the world is clicked").clicked() {
+                        let genome = Genome { network: selected_specie.leader_network.clone(), dna: selected_specie.leader_dna.clone() };
+                        self.placed_genome = Some((format!("the leader of specie {}", selected_specie.id), genome));
+                        self.brush = Brush::Snake;
+                    }
+                    if ui.button("Preview mutations").on_hover_text("Shows children of the leader mutated with the current mutation settings").clicked() {
+                        self.mutation_preview.set_parent(selected_specie.id, Genome { network: selected_specie.leader_network.clone(), dna: selected_specie.leader_dna.clone() });
+                        self.show_mutation_preview = true;
+                    }
+                    ui.label(&self.genome_status);
+                });
+                body_plan_ui(ui, selected_specie.id, &selected_specie.leader_dna, tab.simulation_config.size_to_split, &tab.config, &tab.species_colors);
+                ui.collapsing("Output activations", |ui| {
+                    ui.label("Mean value of every output over all members of the specie, shows how it tends to act beyond what its network looks like");
+                    tab.statistics.outputs_ui(ui, selected_specie.id);
+                });
+                self.network_view.ui(ui, selected_specie.id, &selected_specie.leader_network, tab.species_colors.color(selected_specie.id));
             }
         });
+        egui::Window::new("Mutation preview").open(&mut self.show_mutation_preview).show(ctx, |ui| {
+            self.mutation_preview.ui(ui, &tab.simulation_config.mutation, &tab.species_colors);
+        });
+        let mut scenario = None;
+        egui::Window::new("New simulation").open(&mut self.show_scenarios).show(ctx, |ui| {
+            scenario = self.scenarios.ui(ui);
+        });
+        if let Some(scenario) = scenario {
+            self.show_scenarios = false;
+            tab.start_scenario(ctx, self.draw_interval, scenario);
+        }
+        egui::Window::new("Lineage").open(&mut self.show_lineage).show(ctx, |ui| {
+            lineage_ui(ui, &tab.ancestors);
+        });
+        let mut step_requested = false;
+        egui::Window::new("Debugger").open(&mut self.show_debugger).show(ctx, |ui| {
+            step_requested = tab.debugger.ui(ui);
+        });
+        if step_requested {
+            tab.engine_commands_sender.send(EngineCommand::AdvanceOneFrame).unwrap();
+        }
+        egui::Window::new("Events").open(&mut self.show_events).show(ctx, |ui| {
+            self.event_log.ui(ui);
+        });
+        let mut picked_level = None;
+        egui::Window::new("Log").open(&mut self.show_log).show(ctx, |ui| {
+            picked_level = self.log_panel.ui(ui);
+        });
+        if let Some(level) = picked_level {
+            tab.engine_commands_sender.send(EngineCommand::SetLogLevel(level)).unwrap();
+        }
+        egui::Window::new("Sound").open(&mut self.show_sound_settings).show(ctx, |ui| {
+            self.audio.ui(ui);
+        });
+        egui::Window::new("Statistics").open(&mut self.show_statistics).show(ctx, |ui| {
+            tab.statistics.ui(ui, &tab.species_colors);
+        });
+        egui::Window::new("Food web").open(&mut self.show_food_web).show(ctx, |ui| {
+            tab.food_web.ui(ui);
+        });
+        let mut distances_requested = false;
+        egui::Window::new("Genome distances").open(&mut self.show_genome_distances).show(ctx, |ui| {
+            distances_requested = tab.genome_distances.ui(ui, &tab.species_colors);
+        });
+        if distances_requested {
+            tab.engine_commands_sender.send(EngineCommand::MeasureGenomeDistances(tab.genome_distances.members_per_species())).unwrap();
+        }
+        egui::Window::new("Performance").open(&mut self.show_performance).show(ctx, |ui| {
+            ui.label(format!("{} updates/s, {} frames/s", tab.updates_per_second, tab.frames_per_second));
+            ui.horizontal(|ui| {
+                ui.label("Draw every");
+                if ui.add(egui::DragValue::new(&mut self.draw_interval).speed(0.1).clamp_range(1..=100)).changed() {
+                    tab.engine_commands_sender.send(EngineCommand::SetDrawInterval(self.draw_interval)).unwrap();
+                }
+                ui.label("frames at high speed");
+            }).response.on_hover_text("Above one update per frame the world is only sent to the gui every few frames, which leaves more time for updates");
+            ui.horizontal(|ui| {
+                ui.label("Statistics every");
+                ui.add(egui::DragValue::new(&mut tab.simulation_config.stats_interval).speed(1.0).clamp_range(1..=10_000));
+                ui.label("frames");
+            }).response.on_hover_text("Every calculation of the statistics is one sample in the plots, fewer samples cost less time and memory");
+            ui.checkbox(&mut tab.simulation_config.background_stats, "Background statistics").on_hover_text("The statistics are summed up on another thread, so the simulation doesn't wait for them, they show up a few frames later");
+            ui.checkbox(&mut tab.simulation_config.batched_thinking, "Batched thinking").on_hover_text("Snakes whose networks only differ in their weights, like most members of a specie, think together, the decisions stay exactly the same");
+            self.performance.ui(ui);
+        });
+        egui::Window::new("Diagnostics").open(&mut self.show_diagnostics).show(ctx, |ui| {
+            tab.diagnostics.ui(ui);
+        });
+        egui::Window::new("Time-lapse export").open(&mut self.show_export).show(ctx, |ui| {
+            self.time_lapse.ui(ui, tab.run_metadata());
+        });
         egui::Window::new("Info").open(&mut self.show_info).show(ctx, |ui| {
             ui.label("Press 's' to add one snake");
             ui.label("Press 'a' to stop simulation and advance one frame (useful for debug)");
@@ -621,10 +1485,25 @@ impl eframe::App for MyEguiApp {
             ui.label("Press '-' to decrease speed");
             ui.label("Press 'tab' to ignore speed limit");
             ui.label("Press 'p' to pause/resume");
+            ui.label("Use the arrow keys to steer the snake added with 'Play snake', down stops it");
+            ui.label("Click on a snake to see its ancestors in the lineage window");
+            ui.label("Press Ctrl+Z to undo and Ctrl+Shift+Z to redo settings changes");
             ui.label("All enabled settings take effect immediately");
             ui.label("To change disabled settings, stop the simulation first");
+            ui.label("Every tab at the top runs its own world, the buttons, keys and windows act on the selected one");
+            ui.separator();
+            ui.heading("Run");
+            run_info_ui(ui, tab.run_metadata().as_ref());
         });
-        self.engine_commands_sender.send(EngineCommand::UpdateSimulationConfig(self.simulation_config.clone())).unwrap();
+        if !ctx.wants_keyboard_input() {
+            let (redo, undo) = ctx.input_mut(|i| (i.consume_key(Modifiers::COMMAND | Modifiers::SHIFT, Key::Z), i.consume_key(Modifiers::COMMAND, Key::Z)));
+            if let Some(config) = if redo { tab.history.redo() } else if undo { tab.history.undo() } else { None } {
+                tab.apply_config(config);
+            }
+        }
+        let editing = ctx.input(|i| i.pointer.any_down()) || ctx.wants_keyboard_input();
+        tab.history.record(tab.current_config(), editing);
+        tab.engine_commands_sender.send(EngineCommand::UpdateSimulationConfig(Box::new(tab.simulation_config.clone()))).unwrap();
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 if ui.button("Start profiling").clicked() {
@@ -633,17 +1512,8 @@ impl eframe::App for MyEguiApp {
                 if ui.button("Simulate Batch").clicked() {
                     let simulations = (0..64)
                         .map(|i| {
-                            let mut result = Simulation::new(format!("Simulation {}", i), self.engine_events_sender.clone(), None, create_simulation_config(self.config.columns, self.config.rows, false));
-                            result.insert_resource(EngineState {
-                                repaint_needed: false,
-                                speed_limit: None,
-                                running: true,
-                                frames_left: 0.0,
-                                frames: 0,
-                                updates_done: 0,
-                                finished: false,
-                                ignore_speed_limit: false,
-                            });
+                            let mut result = Simulation::new(format!("Simulation {}", i), tab.engine_events_sender.clone(), None, create_simulation_config(tab.config.columns, tab.config.rows, WallLayout::None, 0, false, WaterLayout::None, 0));
+                            result.insert_resource(EngineState::default());
                             result
                         })
                         .collect();
@@ -652,39 +1522,63 @@ impl eframe::App for MyEguiApp {
                     });
                 }
                 if ui.button("Create Snakes").on_hover_text("Click to add 10 snakes. Press 's' to add one snake").clicked() {
-                    self.engine_commands_sender.send(EngineCommand::CreateSnakes(10)).unwrap();
-                }
-                ui.label(format!("Total : {} ({:.1}ms/frame)", self.total_frames, (Instant::now().duration_since(self.last_frame)).as_millis()));
-                ui.label(format!("FPS : {:.1}", self.frames_per_second));
-                ui.label(format!("UPS : {}", self.updates_per_second));
-                ui.label(format!("Speed : x{:.1}", self.updates_per_second as f32 / self.frames_per_second as f32));
-                ui.label(format!("Oldest snake : {}", self.stats.oldest_snake));
-                ui.label(format!("Max generation : {}", self.stats.max_generation));
-                ui.label(format!("Max mutations : {}", self.stats.max_mutations));
-                ui.label(format!("Snakes/segments : {}/{}", self.stats.total_snakes, self.stats.total_segments));
-                ui.label(format!("Food : {}", self.stats.total_food));
-                ui.label(format!("Species : {}", self.stats.species.species.len()));
-                ui.label(format!("Scents : {}", self.stats.total_scents));
-                ui.label(format!("Entities : {}", self.stats.total_entities));
-                ui.label(format!("Plants/Meat : {}/{}", self.stats.total_plants, self.stats.total_meat));
-                ui.label(format!("Stomachs: P/M: {}/{}", self.stats.total_plants_in_stomachs, self.stats.total_meat_in_stomachs));
-                ui.label(format!("Total snake energy : {}", self.stats.total_snake_energy));
-                ui.label(format!("Total energy : {}", self.stats.total_energy));
-            });
-            ui.horizontal(|ui| {
-                egui::stroke_ui(ui, &mut self.config.bg_color, "Background Color");
-                egui::stroke_ui(ui, &mut self.config.scent_color, "Scent Color");
-                egui::stroke_ui(ui, &mut self.config.tail_color, "Tail Color");
-                egui::stroke_ui(ui, &mut self.config.food_color, "Food Color");
-            });
-            ui.horizontal(|ui| {
-                if ui.add_enabled(!self.simulation_running, egui::Button::new("Start simulation")).clicked() {
-                    start_simulation(&self.engine_events_sender, Arc::clone(&self.engine_commands_receiver), ctx.clone(), self.config);
-                    self.simulation_running = true;
-                }
-                if ui.button("Stop simulation").clicked() {
-                    self.engine_commands_sender.send(EngineCommand::StopSimulation).unwrap();
-                    self.simulation_running = false;
+                    tab.engine_commands_sender.send(EngineCommand::CreateSnakes(10)).unwrap();
+                }
+                if ui.add_enabled(tab.simulation_running, egui::Button::new("Play snake")).on_hover_text("Adds a white headed snake steered with the arrow keys").clicked() {
+                    tab.engine_commands_sender.send(EngineCommand::SpawnControlledSnake).unwrap();
+                }
+                ui.label(format!("Total : {} ({:.1}ms/frame)", tab.total_frames, (Instant::now().duration_since(self.last_frame)).as_millis()));
+                ui.label(format!("Time : {}", tab.simulation_frame));
+                ui.label(format!("FPS : {:.1}", tab.frames_per_second));
+                ui.label(format!("UPS : {}", tab.updates_per_second));
+                ui.label(format!("Speed : x{:.1}", tab.updates_per_second as f32 / tab.frames_per_second as f32));
+                ui.label(format!("Oldest snake : {}", tab.stats.oldest_snake));
+                ui.label(format!("Max generation : {}", tab.stats.max_generation));
+                ui.label(format!("Max mutations : {}", tab.stats.max_mutations));
+                ui.label(format!("Snakes/segments : {}/{}", tab.stats.total_snakes, tab.stats.total_segments));
+                ui.label(format!("Food : {}", tab.stats.total_food));
+                ui.label(format!("Species : {}", tab.stats.species.species.len()));
+                ui.label(format!("Scents : {}", tab.stats.total_scents));
+                ui.label(format!("Nests (built) : {} ({})", tab.stats.total_nests, tab.stats.nests_built));
+                ui.label(format!("Infected : {} ({} infections, {} recovered, {} died)", tab.stats.infected_snakes, tab.stats.infections, tab.stats.recoveries, tab.stats.disease_deaths));
+                ui.label(format!("Crowded : {}", tab.stats.crowded_snakes));
+                ui.label(format!("Minerals : {:.0} ({:.0} deposited, {:.0} collected)", tab.stats.total_minerals, tab.stats.minerals_deposited, tab.stats.minerals_collected));
+                ui.label(format!("Entities : {}", tab.stats.total_entities));
+                ui.label(format!("Plants/Meat : {}/{}", tab.stats.total_plants, tab.stats.total_meat));
+                ui.label(format!("Stomachs: P/M: {}/{}", tab.stats.total_plants_in_stomachs, tab.stats.total_meat_in_stomachs));
+                ui.label(format!("Decayed meat/composted : {:.0}/{:.0}", tab.stats.total_decayed_meat, tab.stats.total_composted_plants));
+                ui.label(format!("Total snake energy : {}", tab.stats.total_snake_energy));
+                ui.label(format!("Total energy : {}", tab.stats.total_energy));
+            });
+            ui.horizontal(|ui| {
+                egui::stroke_ui(ui, &mut tab.config.bg_color, "Background Color");
+                egui::stroke_ui(ui, &mut tab.config.scent_color, "Scent Color");
+                egui::stroke_ui(ui, &mut tab.config.tail_color, "Tail Color");
+                egui::stroke_ui(ui, &mut tab.config.food_color, "Food Color");
+                ui.add_enabled(self.hex_renderer.is_some(), egui::Checkbox::new(&mut tab.config.gpu_rendering, "GPU rendering")).on_hover_text("Draws all hexes in one instanced call, needed for very large worlds").on_disabled_hover_text("Not available, the gui does not run on OpenGL or the shaders did not compile");
+                ui.checkbox(&mut tab.config.smooth_movement, "Smooth movement").on_hover_text("Animates the snake heads between the drawn states, looks best at low speeds");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Event queue");
+                ui.add(egui::DragValue::new(&mut tab.config.event_capacity.events).clamp_range(1..=100_000).suffix(" events")).on_hover_text("The engine waits for the gui once that many events were not picked up yet");
+                ui.add(egui::DragValue::new(&mut tab.config.event_capacity.frames).clamp_range(1..=16).suffix(" frames")).on_hover_text("Drawn frames the gui did not pick up yet are merged into the newest ones, so a stalled gui never holds more than that many");
+                ui.label("(applied with the next start)");
+            });
+            ui.horizontal(|ui| {
+                tab.config.layers.ui(ui);
+            }).response.on_hover_text("Hidden layers are left out of the exported images and videos too");
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!tab.simulation_running, egui::Button::new("Start simulation")).clicked() {
+                    tab.start_simulation(ctx, self.draw_interval);
+                }
+                if ui.add_enabled(tab.simulation_running, egui::Button::new("Restart simulation")).on_hover_text("Starts a new world with the current size and walls").clicked() {
+                    tab.start_simulation(ctx, self.draw_interval);
+                }
+                if ui.add_enabled(tab.simulation_running, egui::Button::new("Stop simulation")).clicked() {
+                    tab.stop_simulation();
+                }
+                if ui.button("New simulation…").on_hover_text("Starts one of a few curated worlds with a single click").clicked() {
+                    self.show_scenarios = !self.show_scenarios;
                 }
                 if ui.button("Environment").clicked() {
                     self.show_simulation_settings = !self.show_simulation_settings;
@@ -698,55 +1592,126 @@ impl eframe::App for MyEguiApp {
                 if ui.button("Networks").clicked() {
                     self.show_networks = !self.show_networks;
                 }
+                if ui.button("Statistics").clicked() {
+                    self.show_statistics = !self.show_statistics;
+                }
+                if ui.button("Food web").clicked() {
+                    self.show_food_web = !self.show_food_web;
+                }
+                if ui.button("Genome distances").on_hover_text("How far apart the species are, to judge the species threshold").clicked() {
+                    self.show_genome_distances = !self.show_genome_distances;
+                }
+                if ui.button("Performance").clicked() {
+                    self.show_performance = !self.show_performance;
+                }
+                if ui.button("Diagnostics").on_hover_text("Entity counts and memory of the world, to notice leaks").clicked() {
+                    self.show_diagnostics = !self.show_diagnostics;
+                }
+                if ui.button("Species").on_hover_text("Colors of the living species and the palette they are drawn with").clicked() {
+                    self.show_species = !self.show_species;
+                }
+                if ui.button("Lineage").on_hover_text("Click on a snake to see its ancestors").clicked() {
+                    self.show_lineage = !self.show_lineage;
+                }
+                if ui.button("Debugger").on_hover_text("Click on a snake to see what it senses and thinks each frame").clicked() {
+                    self.show_debugger = !self.show_debugger;
+                }
+                if ui.button("Events").clicked() {
+                    self.show_events = !self.show_events;
+                }
+                if ui.button("Log").on_hover_text("What the engine logs, each system logs inside its own span").clicked() {
+                    self.show_log = !self.show_log;
+                }
+                if ui.button("Sound").clicked() {
+                    self.show_sound_settings = !self.show_sound_settings;
+                }
+                if ui.button("Export").clicked() {
+                    self.show_export = !self.show_export;
+                }
+                if ui.add_enabled(tab.simulation_running, egui::Button::new("Snapshot")).on_hover_text("Saves a summary of the world to the snapshots directory").clicked() {
+                    tab.engine_commands_sender.send(EngineCommand::TakeSnapshot).unwrap();
+                }
                 if ui.button("Info").clicked() {
                     self.show_info = !self.show_info;
                 }
+                ui.label(&tab.status);
+                ui.label(tab.stats.time.calendar(&tab.simulation_config).to_string()).on_hover_text(format!("{} ticks", tab.stats.time.ticks));
             });
-            draw_hexes(ui, &self.hexes, &self.config);
-            ScrollArea::vertical()
-                .auto_shrink([false; 2])
-                .stick_to_bottom(true)
-                .show(ui, |ui| {
-                    ui.label(&self.text);
+            ui.horizontal(|ui| {
+                ui.label("Preset");
+                let current = tab.current_config();
+                if let Some(preset) = self.presets.ui(ui, &current) {
+                    tab.apply_config(preset);
+                }
+                ui.separator();
+                if ui.add_enabled(tab.history.can_undo(), egui::Button::new("Undo")).on_hover_text("Ctrl+Z").clicked() {
+                    if let Some(config) = tab.history.undo() {
+                        tab.apply_config(config);
+                    }
+                }
+                if ui.add_enabled(tab.history.can_redo(), egui::Button::new("Redo")).on_hover_text("Ctrl+Shift+Z").clicked() {
+                    if let Some(config) = tab.history.redo() {
+                        tab.apply_config(config);
+                    }
+                }
+            });
+            let temperatures = if self.show_temperature { Some(tab.temperatures.as_slice()) } else { None };
+            let energy_grid = tab.energy_grid.as_ref().filter(|_| tab.config.layers.energy);
+            let interpolation = if tab.config.smooth_movement { Some(&tab.interpolation) } else { None };
+            let renderer = self.hex_renderer.as_ref().filter(|_| tab.config.gpu_rendering);
+            let painting = self.brush.paints() && tab.simulation_running;
+            let placing = self.brush != Brush::Off && tab.simulation_running;
+            let command = match draw_hexes(ui, &tab.hexes, temperatures, energy_grid, interpolation, renderer, &mut tab.viewport, &tab.config, &tab.species_colors, painting) {
+                Some(((x, y), PointerButton::Secondary)) if tab.simulation_running => {
+                    let kind = if ctx.input(|i| i.modifiers.shift) { FoodKind::Meat } else { FoodKind::Plant };
+                    Some(EngineCommand::AddFoodAt { x, y, kind })
+                }
+                Some(((x, y), PointerButton::Primary)) if placing => self.brush.command(x, y, self.placed_genome.as_ref().map(|(_, genome)| genome)),
+                Some(((x, y), PointerButton::Primary)) => Some(EngineCommand::InspectSnakeAt(x, y)),
+                _ => None,
+            };
+            if let Some(command) = command {
+                tab.engine_commands_sender.send(command).unwrap();
+            }
+            if tab.config.layers.legend {
+                egui::Area::new("legend").anchor(Align2::LEFT_BOTTOM, Vec2 { x: 10.0, y: -10.0 }).show(ctx, |ui| {
+                    Frame::popup(ui.style()).show(ui, |ui| legend_ui(ui, &tab.config, &tab.species_colors));
                 });
+            }
 
+            let steering = [(Key::ArrowUp, Decision::MoveForward), (Key::ArrowLeft, Decision::MoveLeft), (Key::ArrowRight, Decision::MoveRight), (Key::ArrowDown, Decision::Wait)];
+            for (key, decision) in steering {
+                if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(key)) {
+                    tab.engine_commands_sender.send(EngineCommand::SetControlledDecision(decision)).unwrap();
+                }
+            }
             if ctx.input(|i| i.key_pressed(Key::PlusEquals)) {
-                self.engine_commands_sender.send(EngineCommand::IncreaseSpeed).unwrap();
+                tab.engine_commands_sender.send(EngineCommand::IncreaseSpeed).unwrap();
             }
             if ctx.input(|i| i.key_pressed(Key::Minus)) {
-                self.engine_commands_sender.send(EngineCommand::DecreaseSpeed).unwrap();
+                tab.engine_commands_sender.send(EngineCommand::DecreaseSpeed).unwrap();
             }
             if ctx.input(|i| i.key_pressed(Key::Tab)) {
-                self.engine_commands_sender.send(EngineCommand::IgnoreSpeedLimit).unwrap();
+                tab.engine_commands_sender.send(EngineCommand::IgnoreSpeedLimit).unwrap();
             }
             if ctx.input(|i| i.key_pressed(Key::P)) {
-                self.engine_commands_sender.send(EngineCommand::FlipRunningState).unwrap();
+                tab.engine_commands_sender.send(EngineCommand::FlipRunningState).unwrap();
             }
             if ctx.input(|i| i.key_pressed(Key::S)) {
-                self.engine_commands_sender.send(EngineCommand::CreateSnakes(1)).unwrap();
+                tab.engine_commands_sender.send(EngineCommand::CreateSnakes(1)).unwrap();
             }
             if ctx.input(|i| i.key_pressed(Key::A)) {
-                self.engine_commands_sender.send(EngineCommand::AdvanceOneFrame).unwrap();
+                tab.engine_commands_sender.send(EngineCommand::AdvanceOneFrame).unwrap();
             }
         });
-        if self.can_draw_frame {
+        // background tabs are drawn as well, so their statistics keep growing while another tab is shown
+        if self.tabs.iter().any(|tab| tab.can_draw_frame) {
             ctx.request_repaint();
-            self.can_draw_frame = false;
         }
         self.last_frame = Instant::now();
-        self.engine_commands_sender.send(EngineCommand::RepaintRequested);
+        for tab in &mut self.tabs {
+            tab.can_draw_frame = false;
+            let _ = tab.engine_commands_sender.send(EngineCommand::RepaintRequested);
+        }
     }
 }
-
-
-fn u32_to_color(u: u32) -> Color32 {
-    let mut hasher = DefaultHasher::new();
-    u.hash(&mut hasher);
-    let hash = hasher.finish();
-
-    let r = (hash >> 16) as u8;
-    let g = (hash >> 8) as u8;
-    let b = hash as u8;
-
-    Color32::from_rgb(r, g, b)
-}
\ No newline at end of file
@@ -0,0 +1,86 @@
+use eframe::emath::Vec2;
+use egui::Ui;
+use hex_brains_engine::arena::Genome;
+use hex_brains_engine::core::RngResource;
+use hex_brains_engine::lineage::MutationKind;
+use hex_brains_engine::simulation::MutationConfig;
+use crate::lineage::mutation_name;
+use crate::network_view::NetworkView;
+use crate::palette::SpeciesColors;
+
+const MAX_VARIANTS: usize = 16;
+const CELL_SIZE: Vec2 = Vec2 { x: 280.0, y: 220.0 };
+
+// Children of one genome mutated with the current settings, shows what the mutation chances do before a world runs with them
+pub struct MutationPreview {
+    // the specie the genome leads
+    parent: Option<(u32, Genome)>,
+    variants: Vec<(Genome, Vec<MutationKind>)>,
+    // settings the variants were generated with, changed settings generate them again
+    generated_with: Option<(MutationConfig, usize, u64)>,
+    count: usize,
+    columns: usize,
+    seed: u64,
+    network_view: NetworkView,
+}
+
+impl MutationPreview {
+    pub fn new() -> Self {
+        MutationPreview { parent: None, variants: vec![], generated_with: None, count: 6, columns: 3, seed: 0, network_view: NetworkView::new() }
+    }
+
+    pub fn set_parent(&mut self, specie: u32, genome: Genome) {
+        self.parent = Some((specie, genome));
+        self.generated_with = None;
+    }
+
+    // The same seed gives the same variants, so only the settings make a difference between two generations
+    fn generate(&mut self, mutation: &MutationConfig) {
+        let Some((_, parent)) = &self.parent else {
+            return;
+        };
+        let mut rng = RngResource::from_seed(self.seed);
+        self.variants = (0..self.count).map(|_| parent.mutate(mutation, &mut rng.rng)).collect();
+        self.generated_with = Some((*mutation, self.count, self.seed));
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, mutation: &MutationConfig, species_colors: &SpeciesColors) {
+        ui.horizontal(|ui| {
+            ui.label("Variants");
+            ui.add(egui::DragValue::new(&mut self.count).speed(1.0).clamp_range(1..=MAX_VARIANTS));
+            ui.label("Columns");
+            ui.add(egui::DragValue::new(&mut self.columns).speed(1.0).clamp_range(1..=MAX_VARIANTS));
+            if ui.button("New variants").on_hover_text("Mutates the genome again with another seed").clicked() {
+                self.seed += 1;
+            }
+        });
+        if self.generated_with != Some((*mutation, self.count, self.seed)) {
+            self.generate(mutation);
+        }
+        let Some((specie, parent)) = &self.parent else {
+            ui.label("Pick a specie in the networks window and press 'Preview mutations'");
+            return;
+        };
+        let parent_cost = parent.network.run_cost();
+        ui.label(format!("Leader of specie {}, network run cost {:.2}", specie, parent_cost));
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("mutation_preview").show(ui, |ui| {
+                for (index, (genome, mutations)) in self.variants.iter().enumerate() {
+                    ui.allocate_ui(CELL_SIZE, |ui| {
+                        ui.vertical(|ui| {
+                            let cost = genome.network.run_cost();
+                            let size = genome.network.effective_size();
+                            ui.label(format!("Run cost {:.2} ({:+.2}), {} connections in use", cost, cost - parent_cost, size.connections));
+                            let names: Vec<&str> = mutations.iter().map(mutation_name).collect();
+                            ui.label(if names.is_empty() { "Unchanged".to_string() } else { names.join(", ") });
+                            self.network_view.ui(ui, *specie, &genome.network, species_colors.color(*specie));
+                        });
+                    });
+                    if (index + 1) % self.columns == 0 {
+                        ui.end_row();
+                    }
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use eframe::emath;
+use eframe::emath::{Pos2, Rect, Vec2};
+use eframe::epaint::{Color32, CubicBezierShape};
+use egui::{Align2, FontFamily, FontId, Frame, Sense, Shape, Stroke, Ui};
+use egui::epaint::CircleShape;
+use egui::Shape::Circle;
+use hex_brains_engine::core::{OUTPUT_NAMES, SENSOR_NAMES};
+use hex_brains_engine::neural::{NeuralNetwork, NodeType};
+
+// left and right edge of the columns, the rest is left for the names
+const FIRST_COLUMN: f32 = 0.25;
+const LAST_COLUMN: f32 = 0.85;
+const TOP: f32 = 0.1;
+const BOTTOM: f32 = 0.95;
+// how close a click has to be to pick up a node
+const GRAB_DISTANCE: f32 = 0.03;
+
+const INPUT_COLORS: [Color32; 33] = [Color32::LIGHT_GRAY, Color32::DARK_GRAY, Color32::KHAKI, Color32::KHAKI, Color32::KHAKI, Color32::YELLOW, Color32::YELLOW, Color32::YELLOW, Color32::RED, Color32::RED, Color32::RED, Color32::LIGHT_RED, Color32::LIGHT_RED, Color32::LIGHT_RED, Color32::YELLOW, Color32::RED, Color32::BLUE, Color32::GRAY, Color32::LIGHT_BLUE, Color32::LIGHT_BLUE, Color32::LIGHT_BLUE, Color32::BROWN, Color32::WHITE, Color32::WHITE, Color32::GREEN, Color32::GREEN, Color32::LIGHT_RED, Color32::LIGHT_RED, Color32::LIGHT_RED, Color32::LIGHT_GREEN, Color32::LIGHT_GREEN, Color32::LIGHT_GREEN, Color32::WHITE];
+
+// Nodes dragged by the user keep their place for every specie until the layout is reset
+pub struct NetworkView {
+    moved: HashMap<(u32, usize), Pos2>,
+    dragged: Option<usize>,
+}
+
+// Nodes of a column spread evenly from top to bottom
+fn spread(index: usize, count: usize) -> f32 {
+    TOP + (index as f32 + 0.5) * (BOTTOM - TOP) / count as f32
+}
+
+// Inputs on the left, outputs on the right and hidden nodes in columns by their depth, each column sorted by the average height of its sources
+fn layered_positions(network: &NeuralNetwork) -> Vec<Pos2> {
+    let nodes = network.get_nodes();
+    let layers = network.node_layers();
+    let last_layer = layers.iter().copied().max().unwrap_or(1).max(1);
+    let mut positions = vec![Pos2::ZERO; nodes.len()];
+    let connections = network.get_active_connections();
+    for layer in 0..=last_layer {
+        let x = FIRST_COLUMN + (LAST_COLUMN - FIRST_COLUMN) * layer as f32 / last_layer as f32;
+        let mut column: Vec<(usize, f32)> = (0..nodes.len()).filter(|index| layers[*index] == layer).map(|index| {
+            let sources: Vec<f32> = connections.iter().filter(|connection| connection.out_node == index && layers[connection.in_node] < layer).map(|connection| positions[connection.in_node].y).collect();
+            let height = if nodes[index].node_type == NodeType::Hidden && !sources.is_empty() { sources.iter().sum::<f32>() / sources.len() as f32 } else { index as f32 };
+            (index, height)
+        }).collect();
+        // inputs and outputs keep their order so the names stay next to them
+        column.sort_by(|(_, first), (_, second)| first.total_cmp(second));
+        let count = column.len();
+        for (row, (index, _)) in column.into_iter().enumerate() {
+            positions[index] = Pos2 { x, y: spread(row, count) };
+        }
+    }
+    positions
+}
+
+impl NetworkView {
+    pub fn new() -> Self {
+        NetworkView { moved: HashMap::new(), dragged: None }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, specie_id: u32, network: &NeuralNetwork, specie_color: Color32) {
+        if self.moved.keys().any(|(specie, _)| *specie == specie_id) && ui.button("Reset layout").clicked() {
+            self.moved.retain(|(specie, _), _| *specie != specie_id);
+        }
+        let nodes = network.get_nodes();
+        let mut positions = layered_positions(network);
+        for (index, position) in positions.iter_mut().enumerate() {
+            if let Some(moved) = self.moved.get(&(specie_id, index)) {
+                *position = *moved;
+            }
+        }
+        Frame::canvas(ui.style()).show(ui, |ui| {
+            let (response, painter) = ui.allocate_painter(ui.available_size_before_wrap(), Sense::drag());
+            let to_screen = emath::RectTransform::from_to(
+                Rect::from_min_size(Pos2::ZERO, response.rect.square_proportions()),
+                response.rect,
+            );
+            let pointer = response.interact_pointer_pos().map(|pointer| to_screen.inverse() * pointer);
+            if response.drag_started() {
+                self.dragged = pointer.and_then(|pointer| {
+                    positions.iter().enumerate()
+                        .map(|(index, position)| (index, position.distance(pointer)))
+                        .filter(|(_, distance)| *distance < GRAB_DISTANCE)
+                        .min_by(|(_, first), (_, second)| first.total_cmp(second))
+                        .map(|(index, _)| index)
+                });
+            }
+            if let (Some(index), Some(pointer)) = (self.dragged, pointer) {
+                if response.dragged() {
+                    positions[index] = pointer;
+                    self.moved.insert((specie_id, index), pointer);
+                }
+            }
+            if response.drag_released() {
+                self.dragged = None;
+            }
+            let height = response.rect.height();
+
+            let specie_marker = Circle(CircleShape {
+                center: to_screen * Pos2 { x: 0.05, y: 0.05 },
+                radius: 0.02 * height,
+                fill: specie_color,
+                stroke: Default::default(),
+            });
+            // curves leave and enter the nodes horizontally, connections going back loop around the columns between
+            let connection_shapes: Vec<Shape> = network.get_active_connections().iter().map(|connection| {
+                let from = positions[connection.in_node];
+                let to = positions[connection.out_node];
+                let bend = ((to.x - from.x) / 3.0).abs().max(0.05);
+                let color = if connection.weight > 0.0 { Color32::LIGHT_GREEN } else { Color32::LIGHT_RED };
+                let points = [from, from + Vec2 { x: bend, y: 0.0 }, to - Vec2 { x: bend, y: 0.0 }, to].map(|point| to_screen * point);
+                Shape::CubicBezier(CubicBezierShape::from_points_stroke(points, false, Color32::TRANSPARENT, Stroke::new(connection.weight.abs() / 30.0 * height, color)))
+            }).collect();
+            let (mut inputs, mut outputs) = (0, 0);
+            let node_shapes: Vec<Shape> = nodes.iter().enumerate().map(|(index, node)| {
+                let (radius, fill) = match node.node_type {
+                    NodeType::Input => {
+                        inputs += 1;
+                        (0.012, INPUT_COLORS.get(inputs - 1).copied().unwrap_or(Color32::GRAY))
+                    }
+                    NodeType::Hidden => (0.015, Color32::LIGHT_GRAY),
+                    NodeType::Output => {
+                        outputs += 1;
+                        (0.02, Color32::LIGHT_RED)
+                    }
+                };
+                Circle(CircleShape { center: to_screen * positions[index], radius: radius * height, fill, stroke: Default::default() })
+            }).collect();
+            painter.extend(vec![specie_marker]);
+            painter.extend(connection_shapes);
+            painter.extend(node_shapes);
+            let (mut inputs, mut outputs) = (0, 0);
+            for (index, node) in nodes.iter().enumerate() {
+                let (name, offset, align) = match node.node_type {
+                    NodeType::Input => {
+                        inputs += 1;
+                        (SENSOR_NAMES.get(inputs - 1).copied().unwrap_or(""), -0.03, Align2::RIGHT_CENTER)
+                    }
+                    NodeType::Output => {
+                        outputs += 1;
+                        (OUTPUT_NAMES.get(outputs - 1).copied().unwrap_or(""), 0.03, Align2::LEFT_CENTER)
+                    }
+                    NodeType::Hidden => continue,
+                };
+                painter.text(to_screen * (positions[index] + Vec2 { x: offset, y: 0.0 }), align, name, FontId::new(12.0, FontFamily::Monospace), Color32::WHITE);
+            }
+        });
+    }
+}
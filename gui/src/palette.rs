@@ -0,0 +1,146 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use egui::{Color32, Ui};
+use hex_brains_engine::core::Species;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    // a color hashed from the id, neighbouring species can end up looking the same
+    Random,
+    // strong colors that are easy to tell apart
+    Vivid,
+    // the colorblind safe palette of Okabe and Ito
+    OkabeIto,
+    // the colorblind safe bright palette of Paul Tol
+    TolBright,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [Palette::Random, Palette::Vivid, Palette::OkabeIto, Palette::TolBright];
+
+    fn colors(self) -> &'static [Color32] {
+        match self {
+            Palette::Random => &[],
+            Palette::Vivid => &VIVID,
+            Palette::OkabeIto => &OKABE_ITO,
+            Palette::TolBright => &TOL_BRIGHT,
+        }
+    }
+
+    // Once every color is taken the palette repeats a bit darker each time
+    fn color(self, index: usize) -> Color32 {
+        let colors = self.colors();
+        if colors.is_empty() {
+            return hashed_color(index as u32);
+        }
+        let color = colors[index % colors.len()];
+        let shade = 1.0 - 0.25 * ((index / colors.len()) % 3) as f32;
+        let [r, g, b, _] = color.to_array().map(|channel| (channel as f32 * shade) as u8);
+        Color32::from_rgb(r, g, b)
+    }
+}
+
+const VIVID: [Color32; 12] = [
+    Color32::from_rgb(230, 25, 75),
+    Color32::from_rgb(60, 180, 75),
+    Color32::from_rgb(255, 225, 25),
+    Color32::from_rgb(67, 99, 216),
+    Color32::from_rgb(245, 130, 49),
+    Color32::from_rgb(145, 30, 180),
+    Color32::from_rgb(66, 212, 244),
+    Color32::from_rgb(240, 50, 230),
+    Color32::from_rgb(191, 239, 69),
+    Color32::from_rgb(250, 190, 212),
+    Color32::from_rgb(70, 153, 144),
+    Color32::from_rgb(220, 190, 255),
+];
+
+// black is left out, it would vanish on the dark background
+const OKABE_ITO: [Color32; 7] = [
+    Color32::from_rgb(230, 159, 0),
+    Color32::from_rgb(86, 180, 233),
+    Color32::from_rgb(0, 158, 115),
+    Color32::from_rgb(240, 228, 66),
+    Color32::from_rgb(0, 114, 178),
+    Color32::from_rgb(213, 94, 0),
+    Color32::from_rgb(204, 121, 167),
+];
+
+const TOL_BRIGHT: [Color32; 7] = [
+    Color32::from_rgb(68, 119, 170),
+    Color32::from_rgb(102, 204, 238),
+    Color32::from_rgb(34, 136, 51),
+    Color32::from_rgb(204, 187, 68),
+    Color32::from_rgb(238, 102, 119),
+    Color32::from_rgb(170, 51, 119),
+    Color32::from_rgb(187, 187, 187),
+];
+
+fn hashed_color(u: u32) -> Color32 {
+    let mut hasher = DefaultHasher::new();
+    u.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let r = (hash >> 16) as u8;
+    let g = (hash >> 8) as u8;
+    let b = hash as u8;
+
+    Color32::from_rgb(r, g, b)
+}
+
+// Colors of the species in one world, remapping hands the colors of the palette out to the living species instead of going by their ids
+pub struct SpeciesColors {
+    pub palette: Palette,
+    pub remap: bool,
+    slots: HashMap<u32, usize>,
+}
+
+impl SpeciesColors {
+    pub fn new(palette: Palette, remap: bool) -> Self {
+        SpeciesColors { palette, remap, slots: HashMap::new() }
+    }
+
+    pub fn color(&self, specie: u32) -> Color32 {
+        let index = if self.remap { self.slots.get(&specie).copied().unwrap_or(specie as usize) } else { specie as usize };
+        self.palette.color(index)
+    }
+
+    // Living species keep their slot, new ones take the lowest free slots in the order of their ids, so the same run always gets the same colors
+    pub fn update(&mut self, species: &Species) {
+        self.slots.retain(|specie, _| species.species.iter().any(|living| living.id == *specie));
+        let mut new_species: Vec<u32> = species.species.iter().map(|specie| specie.id).filter(|specie| !self.slots.contains_key(specie)).collect();
+        new_species.sort();
+        for specie in new_species {
+            let slot = (0..).find(|slot| !self.slots.values().any(|taken| taken == slot)).unwrap();
+            self.slots.insert(specie, slot);
+        }
+    }
+
+    // The palette settings and a swatch for every living specie, clicking a specie selects it
+    pub fn ui(&mut self, ui: &mut Ui, species: &Species, selected: &mut u32) {
+        ui.horizontal(|ui| {
+            ui.label("Palette");
+            egui::ComboBox::from_id_source("species_palette")
+                .selected_text(format!("{:?}", self.palette))
+                .show_ui(ui, |ui| {
+                    for palette in Palette::ALL {
+                        ui.selectable_value(&mut self.palette, palette, format!("{:?}", palette));
+                    }
+                }).response.on_hover_text("Okabe Ito and Tol Bright stay distinguishable with the common kinds of color blindness");
+            ui.checkbox(&mut self.remap, "Remap").on_hover_text("Living species get the colors of the palette one after another, so no two of them share a color while there are enough colors");
+        });
+        ui.separator();
+        let mut living: Vec<(u32, usize)> = species.species.iter().map(|specie| (specie.id, specie.members.len())).collect();
+        living.sort();
+        egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+            for (specie, members) in living {
+                ui.horizontal(|ui| {
+                    let (rect, _) = ui.allocate_exact_size(egui::vec2(16.0, 16.0), egui::Sense::hover());
+                    ui.painter().rect_filled(rect, 2.0, self.color(specie));
+                    ui.selectable_value(selected, specie, format!("Specie {} ({} snakes)", specie, members));
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,51 @@
+use egui::Ui;
+use hex_brains_engine::profiling::SystemTiming;
+
+pub struct Performance {
+    timings: Vec<SystemTiming>,
+}
+
+impl Performance {
+    pub fn new() -> Self {
+        Performance { timings: vec![] }
+    }
+
+    pub fn record(&mut self, timings: Vec<SystemTiming>) {
+        self.timings = timings;
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = "system,calls_per_second,ms_per_second,average_ms\n".to_string();
+        for timing in &self.timings {
+            csv.push_str(&format!("{},{:.1},{:.3},{:.4}\n", timing.name, timing.calls_per_second, timing.ms_per_second, timing.average_ms));
+        }
+        csv
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        if self.timings.is_empty() {
+            ui.label("No timings yet, they are reported once per second while the simulation runs");
+            return;
+        }
+        if ui.button("Copy as CSV").clicked() {
+            let csv = self.to_csv();
+            ui.output_mut(|output| output.copied_text = csv);
+        }
+        ui.separator();
+        egui::Grid::new("system_timings").striped(true).num_columns(4).show(ui, |ui| {
+            ui.strong("System");
+            ui.strong("Calls/s");
+            ui.strong("ms/s");
+            ui.strong("ms/call");
+            ui.end_row();
+            for timing in &self.timings {
+                ui.label(timing.name);
+                ui.label(format!("{:.0}", timing.calls_per_second));
+                ui.label(format!("{:.2}", timing.ms_per_second));
+                ui.label(format!("{:.3}", timing.average_ms));
+                ui.end_row();
+            }
+        });
+        ui.label("Systems of batch simulations running at the same time are included in the totals.");
+    }
+}
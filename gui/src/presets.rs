@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+use egui::Ui;
+use hex_brains_engine::simulation::{FoodDistribution, SimulationConfig};
+
+const PRESETS_DIRECTORY: &str = "presets";
+
+fn builtin_presets() -> Vec<(String, SimulationConfig)> {
+    let default = SimulationConfig::default();
+    vec![
+        ("Default".to_string(), default.clone()),
+        ("Predator world".to_string(), SimulationConfig {
+            plant_energy_content: 0.5,
+            meat_energy_content: 10.0,
+            meat_decay_rate: 0.0,
+            ..default.clone()
+        }),
+        ("Scarcity world".to_string(), SimulationConfig {
+            starting_food: 20,
+            food_per_step: 1,
+            food_distribution: FoodDistribution::Oases,
+            food_patches: 3,
+            ..default.clone()
+        }),
+        ("Big map".to_string(), SimulationConfig {
+            rows: 300,
+            columns: 300,
+            starting_snakes: 50,
+            starting_food: 1000,
+            food_per_step: 20,
+            ..default
+        }),
+    ]
+}
+
+fn preset_path(name: &str) -> PathBuf {
+    Path::new(PRESETS_DIRECTORY).join(format!("{}.toml", name))
+}
+
+fn load_preset(name: &str) -> Result<SimulationConfig, String> {
+    let path = preset_path(name);
+    let content = std::fs::read_to_string(&path).map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    SimulationConfig::from_toml(&content).map_err(|error| format!("Invalid preset {}: {}", path.display(), error))
+}
+
+fn save_preset(name: &str, config: &SimulationConfig) -> Result<(), String> {
+    std::fs::create_dir_all(PRESETS_DIRECTORY).map_err(|error| format!("Unable to create {}: {}", PRESETS_DIRECTORY, error))?;
+    let content = config.to_toml()?;
+    let path = preset_path(name);
+    std::fs::write(&path, content).map_err(|error| format!("Unable to write {}: {}", path.display(), error))
+}
+
+// Names of the presets saved in the presets directory, a missing directory just means no saved presets
+fn saved_preset_names() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(PRESETS_DIRECTORY) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+pub struct Presets {
+    saved: Vec<String>,
+    selected: String,
+    new_name: String,
+    status: String,
+}
+
+impl Presets {
+    pub fn new() -> Self {
+        Presets {
+            saved: saved_preset_names(),
+            selected: "Default".to_string(),
+            new_name: String::new(),
+            status: String::new(),
+        }
+    }
+
+    // Shows the preset dropdown and the save controls, returns the config of a newly selected preset
+    pub fn ui(&mut self, ui: &mut Ui, config: &SimulationConfig) -> Option<SimulationConfig> {
+        let mut loaded = None;
+        egui::ComboBox::from_id_source("presets")
+            .selected_text(&self.selected)
+            .show_ui(ui, |ui| {
+                for (name, preset) in builtin_presets() {
+                    if ui.selectable_label(self.selected == name, &name).clicked() {
+                        loaded = Some(preset);
+                        self.selected = name;
+                    }
+                }
+                ui.separator();
+                for name in &self.saved {
+                    if ui.selectable_label(self.selected == *name, name).clicked() {
+                        match load_preset(name) {
+                            Ok(preset) => {
+                                loaded = Some(preset);
+                                self.selected = name.clone();
+                            }
+                            Err(error) => self.status = error,
+                        }
+                    }
+                }
+            });
+        ui.add(egui::TextEdit::singleline(&mut self.new_name).hint_text("Preset name").desired_width(100.0));
+        let valid_name = !self.new_name.trim().is_empty() && !self.new_name.contains(['/', '\\', '.']);
+        if ui.add_enabled(valid_name, egui::Button::new("Save preset")).clicked() {
+            let name = self.new_name.trim().to_string();
+            match save_preset(&name, config) {
+                Ok(()) => {
+                    self.status = format!("Saved preset {}", name);
+                    self.saved = saved_preset_names();
+                    self.selected = name;
+                    self.new_name.clear();
+                }
+                Err(error) => self.status = error,
+            }
+        }
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+        loaded
+    }
+}
@@ -0,0 +1,62 @@
+use egui::Ui;
+use hex_brains_engine::simulation::RunMetadata;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+// Date and time in UTC, the days are turned into a civil date like in Howard Hinnant's civil_from_days
+pub fn format_utc(seconds: u64) -> String {
+    let days = (seconds / SECONDS_PER_DAY) as i64;
+    let time = seconds % SECONDS_PER_DAY;
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, time / 3_600, time % 3_600 / 60, time % 60)
+}
+
+pub fn format_duration(millis: u64) -> String {
+    let seconds = millis / 1_000;
+    match seconds {
+        seconds if seconds >= 3_600 => format!("{}h {:02}m {:02}s", seconds / 3_600, seconds % 3_600 / 60, seconds % 60),
+        seconds if seconds >= 60 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{:.1}s", millis as f32 / 1_000.0),
+    }
+}
+
+pub fn run_info_ui(ui: &mut Ui, run: Option<&RunMetadata>) {
+    let Some(run) = run else {
+        ui.label("No simulation running");
+        return;
+    };
+    egui::Grid::new("run_info").num_columns(2).show(ui, |ui| {
+        ui.label("Seed");
+        ui.label(run.seed.to_string());
+        ui.end_row();
+        ui.label("Config hash");
+        ui.label(&run.config_hash);
+        ui.end_row();
+        ui.label("Started");
+        ui.label(format_utc(run.started_at));
+        ui.end_row();
+        ui.label("Engine version");
+        ui.label(&run.engine_version);
+        ui.end_row();
+        ui.label("Frames");
+        ui.label(run.frame.to_string());
+        ui.end_row();
+        ui.label("Running for");
+        ui.label(format_duration(run.active_millis)).on_hover_text("Wall clock time spent simulating, pauses are left out");
+        ui.end_row();
+        ui.label("Paused for");
+        ui.label(format_duration(run.paused_millis)).on_hover_text("Including the time the engine waited for the gui to catch up");
+        ui.end_row();
+    });
+    if ui.button("Copy").on_hover_text("Copies the run metadata as toml, to paste next to results").clicked() {
+        ui.output_mut(|output| output.copied_text = toml::to_string(run).unwrap_or_default());
+    }
+}
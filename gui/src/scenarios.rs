@@ -0,0 +1,133 @@
+use egui::Ui;
+use hex_brains_engine::arena::Genome;
+use hex_brains_engine::core::ActionSet;
+use hex_brains_engine::dna::{Dna, Gene, SegmentType, Traits};
+use hex_brains_engine::neural::NeuralNetworkBuilder;
+use hex_brains_engine::simulation::{FoodDistribution, SimulationConfig, WallLayout};
+
+// A world to start with a single click, the seed makes every start of it begin the same way
+#[derive(Clone)]
+pub struct Scenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub config: SimulationConfig,
+    pub seed: u64,
+    // snakes with random genes added at the start
+    pub random_snakes: usize,
+    // hand made genomes and how many snakes start with each of them
+    pub genomes: Vec<(Genome, usize)>,
+}
+
+// Muscles to move and stomachs to digest in turns
+fn dna(segments: &[SegmentType]) -> Dna {
+    let genes = segments.iter().enumerate().map(|(id, segment_type)| Gene { segment_type: segment_type.clone(), id, jump: (id + 1) % segments.len() }).collect();
+    Dna { genes, current_gene: 0, traits: Traits::default() }
+}
+
+// Turns towards the plants it sees and keeps going straight otherwise
+fn grazer(config: &SimulationConfig) -> Genome {
+    let network = NeuralNetworkBuilder::for_snake(ActionSet::from_config(config).outputs())
+        .connect("Bias", "Move forward", 0.3)
+        .connect("Plants front", "Move forward", 2.0)
+        .connect("Plants left", "Move left", 2.0)
+        .connect("Plants right", "Move right", 2.0)
+        .connect("Obstacle front", "Move right", 1.5)
+        .build()
+        .expect("the grazer only uses nodes of every snake");
+    Genome { network, dna: dna(&[SegmentType::muscle(), SegmentType::stomach()]) }
+}
+
+// Chases the meat it sees, snakes leave meat behind when they die
+fn hunter(config: &SimulationConfig) -> Genome {
+    let network = NeuralNetworkBuilder::for_snake(ActionSet::from_config(config).outputs())
+        .connect("Bias", "Move forward", 0.5)
+        .connect("Meat front", "Move forward", 2.5)
+        .connect("Meat left", "Move left", 2.5)
+        .connect("Meat right", "Move right", 2.5)
+        .connect("Obstacle front", "Move left", 1.5)
+        .build()
+        .expect("the hunter only uses nodes of every snake");
+    Genome { network, dna: dna(&[SegmentType::muscle(), SegmentType::muscle(), SegmentType::stomach()]) }
+}
+
+// Keeps a wall on its left, the oldest way through a maze
+fn wall_follower(config: &SimulationConfig) -> Genome {
+    let network = NeuralNetworkBuilder::for_snake(ActionSet::from_config(config).outputs())
+        .connect("Bias", "Move forward", 0.5)
+        .connect("Bias", "Move left", 0.2)
+        .connect("Obstacle front", "Move right", 2.0)
+        .connect("Obstacle left", "Move forward", 1.0)
+        .connect("Food ahead", "Move forward", 1.0)
+        .build()
+        .expect("the wall follower only uses nodes of every snake");
+    Genome { network, dna: dna(&[SegmentType::muscle(), SegmentType::stomach()]) }
+}
+
+pub fn scenarios() -> Vec<Scenario> {
+    let default = SimulationConfig::default();
+    let garden = SimulationConfig { rows: 80, columns: 80, starting_food: 800, food_per_step: 8, food_distribution: FoodDistribution::Patches, food_patches: 6, ..default.clone() };
+    let arena = SimulationConfig { rows: 60, columns: 60, perimeter_walls: true, plant_energy_content: 0.5, meat_energy_content: 10.0, meat_decay_rate: 0.0, ..default.clone() };
+    let maze = SimulationConfig { rows: 60, columns: 60, wall_layout: WallLayout::Maze, wall_seed: 7, starting_food: 300, food_per_step: 3, ..default.clone() };
+    let desert = SimulationConfig { rows: 100, columns: 100, starting_food: 20, food_per_step: 1, food_distribution: FoodDistribution::Oases, food_patches: 3, ..default };
+    vec![
+        Scenario {
+            name: "Herbivore garden",
+            description: "Lush drifting patches of plants and grazers that know how to find them, watch the species spread over the garden.",
+            genomes: vec![(grazer(&garden), 20)],
+            config: garden,
+            seed: 1,
+            random_snakes: 10,
+        },
+        Scenario {
+            name: "Predator arena",
+            description: "A walled arena where plants are barely worth eating and meat never rots, grazers share it with hunters.",
+            genomes: vec![(grazer(&arena), 15), (hunter(&arena), 10)],
+            config: arena,
+            seed: 2,
+            random_snakes: 0,
+        },
+        Scenario {
+            name: "Maze challenge",
+            description: "Corridors everywhere, wall followers against random snakes, see who finds the food in the dead ends.",
+            genomes: vec![(wall_follower(&maze), 15)],
+            config: maze,
+            seed: 3,
+            random_snakes: 30,
+        },
+        Scenario {
+            name: "Scarcity desert",
+            description: "A big empty world with three oases that barely feed anyone, only thrifty snakes last.",
+            genomes: vec![],
+            config: desert,
+            seed: 4,
+            random_snakes: 40,
+        },
+    ]
+}
+
+// The new simulation dialog, one scenario is picked and started at a time
+pub struct ScenarioDialog {
+    scenarios: Vec<Scenario>,
+    selected: usize,
+}
+
+impl ScenarioDialog {
+    pub fn new() -> Self {
+        ScenarioDialog { scenarios: scenarios(), selected: 0 }
+    }
+
+    // Returns the scenario to start
+    pub fn ui(&mut self, ui: &mut Ui) -> Option<Scenario> {
+        ui.label("Starts the active tab over with a curated world, its settings replace the current ones.");
+        ui.separator();
+        for (index, scenario) in self.scenarios.iter().enumerate() {
+            ui.radio_value(&mut self.selected, index, scenario.name);
+        }
+        ui.separator();
+        let scenario = &self.scenarios[self.selected];
+        ui.label(scenario.description);
+        let snakes = scenario.random_snakes + scenario.genomes.iter().map(|(_, copies)| copies).sum::<usize>();
+        ui.label(format!("{}x{} hexes, {} snakes, seed {}", scenario.config.columns, scenario.config.rows, snakes, scenario.seed));
+        ui.button("Start").clicked().then(|| scenario.clone())
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::VecDeque;
+use egui::Ui;
+use egui_plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints};
+use hex_brains_engine::core::OUTPUT_NAMES;
+use hex_brains_engine::simulation::{BehaviorStats, Histogram, RegionStats, SpecieStats, Stats};
+use crate::palette::SpeciesColors;
+
+// the newest samples are kept as they arrive, older ones only every few samples so long runs still fit
+const RECENT_SAMPLES: usize = 2_500;
+const OLDER_SAMPLES: usize = 2_500;
+// one in this many samples leaving the recent ones is kept
+const OLDER_RESOLUTION: usize = 10;
+
+type RegionValue = fn(&RegionStats) -> f64;
+
+pub struct StatsSample {
+    pub frame: u32,
+    pub species: Vec<SpecieStats>,
+    pub behavior: BehaviorStats,
+    pub regions: Vec<RegionStats>,
+}
+
+// Samples of the whole run in a bounded amount of memory, whenever the older samples are full every other one of them is dropped
+struct StatsHistory {
+    older: VecDeque<StatsSample>,
+    recent: VecDeque<StatsSample>,
+    // samples that left the recent ones so far
+    aged: usize,
+    // keeps doubling with every thinning of the older samples
+    older_stride: usize,
+}
+
+impl StatsHistory {
+    fn new() -> Self {
+        StatsHistory { older: VecDeque::new(), recent: VecDeque::new(), aged: 0, older_stride: OLDER_RESOLUTION }
+    }
+
+    fn push(&mut self, sample: StatsSample) {
+        self.recent.push_back(sample);
+        if self.recent.len() <= RECENT_SAMPLES {
+            return;
+        }
+        let aged = self.recent.pop_front().unwrap();
+        if self.aged.is_multiple_of(self.older_stride) {
+            self.older.push_back(aged);
+        }
+        self.aged += 1;
+        if self.older.len() > OLDER_SAMPLES {
+            self.older = self.older.drain(..).step_by(2).collect();
+            self.older_stride *= 2;
+            self.aged = 0;
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = StatsHistory::new();
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &StatsSample> {
+        self.older.iter().chain(self.recent.iter())
+    }
+
+    fn back(&self) -> Option<&StatsSample> {
+        self.recent.back()
+    }
+
+    fn len(&self) -> usize {
+        self.older.len() + self.recent.len()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StatisticsTab {
+    Population,
+    Energy,
+    Behavior,
+    Regions,
+    Distributions,
+}
+
+pub struct Statistics {
+    stats_history: StatsHistory,
+    distributions: Vec<(&'static str, Histogram)>,
+    tab: StatisticsTab,
+    stacked: bool,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics {
+            stats_history: StatsHistory::new(),
+            distributions: vec![],
+            tab: StatisticsTab::Population,
+            stacked: false,
+        }
+    }
+
+    // stats are only recalculated every few frames, but are sent with every repaint
+    pub fn record(&mut self, stats: &Stats) {
+        if let Some(last) = self.stats_history.back() {
+            if last.frame == stats.frame {
+                return;
+            }
+            if last.frame > stats.frame {
+                // a new simulation was started
+                self.stats_history.clear();
+            }
+        }
+        self.stats_history.push(StatsSample { frame: stats.frame, species: stats.species_stats.clone(), behavior: stats.behavior, regions: stats.regions.clone() });
+        self.distributions = vec![
+            ("Age", stats.age_distribution.clone()),
+            ("Energy", stats.energy_distribution.clone()),
+            ("Segments", stats.segment_distribution.clone()),
+            ("Active connections", stats.connection_distribution.clone()),
+        ];
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, species_colors: &SpeciesColors) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.tab, StatisticsTab::Population, "Species population");
+            ui.selectable_value(&mut self.tab, StatisticsTab::Energy, "Species mean energy");
+            ui.selectable_value(&mut self.tab, StatisticsTab::Behavior, "Behavior");
+            ui.selectable_value(&mut self.tab, StatisticsTab::Regions, "Regions");
+            ui.selectable_value(&mut self.tab, StatisticsTab::Distributions, "Distributions");
+        });
+        ui.separator();
+        match self.tab {
+            StatisticsTab::Population => {
+                ui.checkbox(&mut self.stacked, "Stacked");
+                self.species_plot(ui, "species_population", self.stacked, species_colors, |specie| specie.population as f64);
+            }
+            StatisticsTab::Energy => {
+                self.species_plot(ui, "species_energy", false, species_colors, |specie| specie.mean_energy as f64);
+            }
+            StatisticsTab::Behavior => {
+                self.behavior_ui(ui);
+                return;
+            }
+            StatisticsTab::Regions => {
+                self.regions_ui(ui);
+                return;
+            }
+            StatisticsTab::Distributions => {
+                self.distributions_ui(ui);
+                return;
+            }
+        }
+        ui.label("Only species that are currently alive are plotted, colors match the snake heads on the map.");
+    }
+
+    // Mean output activations of the members over time, e.g. a specie leaning on waiting or on turning left
+    pub fn outputs_ui(&self, ui: &mut Ui, specie_id: u32) {
+        let samples: Vec<(u32, &SpecieStats)> = self.stats_history.iter().filter_map(|sample| {
+            sample.species.iter().find(|specie| specie.id == specie_id).map(|specie| (sample.frame, specie))
+        }).collect();
+        if samples.is_empty() {
+            ui.label("No statistics of this specie yet");
+            return;
+        }
+        Plot::new("specie_outputs").legend(Legend::default()).height(200.0).include_y(0.0).include_y(1.0).show(ui, |plot_ui| {
+            for (index, name) in OUTPUT_NAMES.iter().enumerate() {
+                // outputs the networks don't have yet are left out
+                let points: PlotPoints = samples.iter().filter_map(|(frame, specie)| specie.mean_outputs.get(index).map(|output| [*frame as f64, *output as f64])).collect();
+                plot_ui.line(Line::new(points).name(*name));
+            }
+        });
+    }
+
+    fn behavior_ui(&self, ui: &mut Ui) {
+        let series = |value: &dyn Fn(&BehaviorStats) -> f32| -> PlotPoints {
+            self.stats_history.iter().map(|sample| [sample.frame as f64, value(&sample.behavior) as f64]).collect()
+        };
+        ui.label("Share of decisions");
+        Plot::new("decision_shares").legend(Legend::default()).height(200.0).include_y(0.0).include_y(1.0).show(ui, |plot_ui| {
+            for (index, name) in ["Forward", "Left", "Right", "Wait", "Transfer", "Backward", "Sprint", "Nest"].iter().enumerate() {
+                plot_ui.line(Line::new(series(&|behavior| behavior.decision_shares[index])).name(*name));
+            }
+        });
+        ui.label("Movement and collisions");
+        Plot::new("movement").legend(Legend::default()).height(200.0).include_y(0.0).show(ui, |plot_ui| {
+            plot_ui.line(Line::new(series(&|behavior| behavior.mean_displacement)).name("Mean displacement per 100 frames"));
+            plot_ui.line(Line::new(series(&|behavior| behavior.collision_rate)).name("Collisions per 1000 decisions"));
+        });
+    }
+
+    fn regions_ui(&self, ui: &mut Ui) {
+        // regions are matched by name, so renamed or removed regions just end their lines
+        let names: Vec<String> = self.stats_history.back().map(|sample| sample.regions.iter().map(|region| region.name.clone()).collect()).unwrap_or_default();
+        if names.is_empty() {
+            ui.label("No regions are defined in the environment settings");
+            return;
+        }
+        let plots: [(&str, &str, RegionValue); 3] = [
+            ("Snakes", "region_snakes", |region| region.snakes as f64),
+            ("Food", "region_food", |region| region.food as f64),
+            ("Deaths per 100 frames", "region_deaths", |region| region.deaths as f64),
+        ];
+        for (label, id, value) in plots {
+            ui.label(label);
+            Plot::new(id).legend(Legend::default()).height(150.0).include_y(0.0).show(ui, |plot_ui| {
+                for name in &names {
+                    let points: PlotPoints = self.stats_history.iter().filter_map(|sample| {
+                        sample.regions.iter().find(|region| region.name == *name).map(|region| [sample.frame as f64, value(region)])
+                    }).collect();
+                    plot_ui.line(Line::new(points).name(name));
+                }
+            });
+        }
+    }
+
+    fn distributions_ui(&self, ui: &mut Ui) {
+        if self.distributions.is_empty() {
+            ui.label("No statistics yet");
+            return;
+        }
+        ui.label("Distribution of all living snakes at the last statistics update.");
+        egui::Grid::new("distributions").num_columns(2).show(ui, |ui| {
+            for (index, (name, histogram)) in self.distributions.iter().enumerate() {
+                ui.vertical(|ui| {
+                    ui.label(*name);
+                    histogram_plot(ui, name, histogram);
+                });
+                if index % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+
+    fn species_plot(&self, ui: &mut Ui, id: &str, stacked: bool, species_colors: &SpeciesColors, value: impl Fn(&SpecieStats) -> f64) {
+        let alive: Vec<u32> = self.stats_history.back().map(|sample| sample.species.iter().map(|specie| specie.id).collect()).unwrap_or_default();
+        let series: Vec<Vec<[f64; 2]>> = alive.iter().map(|specie_id| {
+            self.stats_history.iter().filter_map(|sample| {
+                let specie = sample.species.iter().find(|specie| specie.id == *specie_id);
+                match specie {
+                    Some(specie) => Some([sample.frame as f64, value(specie)]),
+                    // stacked series need a value for every sample to add up correctly
+                    None if stacked => Some([sample.frame as f64, 0.0]),
+                    None => None,
+                }
+            }).collect()
+        }).collect();
+        Plot::new(id).legend(Legend::default()).height(300.0).show(ui, |plot_ui| {
+            if stacked {
+                let mut cumulative = vec![0.0; self.stats_history.len()];
+                let stacked_series: Vec<Vec<[f64; 2]>> = series.iter().map(|points| {
+                    points.iter().zip(cumulative.iter_mut()).map(|(point, total)| {
+                        *total += point[1];
+                        [point[0], *total]
+                    }).collect()
+                }).collect();
+                // the highest band is painted first so that lower ones cover it
+                for (specie_id, points) in alive.iter().zip(stacked_series).rev() {
+                    plot_ui.line(Line::new(PlotPoints::from(points)).color(species_colors.color(*specie_id)).fill(0.0).name(format!("Specie {}", specie_id)));
+                }
+            } else {
+                for (specie_id, points) in alive.iter().zip(series) {
+                    plot_ui.line(Line::new(PlotPoints::from(points)).color(species_colors.color(*specie_id)).name(format!("Specie {}", specie_id)));
+                }
+            }
+        });
+    }
+}
+
+fn histogram_plot(ui: &mut Ui, id: &str, histogram: &Histogram) {
+    let bars: Vec<Bar> = histogram.counts.iter().enumerate().map(|(bucket, count)| {
+        let center = histogram.bucket_start(bucket) + histogram.bucket_width / 2.0;
+        Bar::new(center as f64, *count as f64).width(histogram.bucket_width as f64)
+    }).collect();
+    Plot::new(id).width(250.0).height(150.0).allow_drag(false).allow_zoom(false).show(ui, |plot_ui| {
+        plot_ui.bar_chart(BarChart::new(bars));
+    });
+}
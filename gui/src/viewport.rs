@@ -0,0 +1,50 @@
+use eframe::emath::{Pos2, Rect, Vec2};
+use egui::{PointerButton, Response, Ui};
+
+// zoom factor per scrolled point
+const ZOOM_SPEED: f32 = 0.002;
+
+// Part of the world shown in the canvas, in the normalized coordinates of draw_hexes where the longer side of the world is one unit
+pub struct Viewport {
+    zoom: f32,
+    // none keeps the whole world centered whatever the size of the canvas
+    center: Option<Pos2>,
+}
+
+impl Viewport {
+    pub fn new() -> Self {
+        Viewport { zoom: 1.0, center: None }
+    }
+
+    // Without any zoom the whole world fits into the canvas
+    pub fn visible_rect(&self, proportions: Vec2, world: Vec2) -> Rect {
+        let center = self.center.unwrap_or((world / 2.0).to_pos2());
+        let fit = (world.x / proportions.x).max(world.y / proportions.y);
+        Rect::from_center_size(center, proportions * fit / self.zoom)
+    }
+
+    // Scrolling zooms around the pointer, dragging with the pan button pans and a double click shows the whole world again
+    pub fn handle_input(&mut self, ui: &Ui, response: &Response, world: Vec2, max_zoom: f32, pan_button: PointerButton) {
+        if response.double_clicked() {
+            *self = Viewport::new();
+            return;
+        }
+        let visible = self.visible_rect(response.rect.square_proportions(), world);
+        let scale = response.rect.height() / visible.height();
+        if response.dragged_by(pan_button) {
+            self.center = Some(visible.center() - response.drag_delta() / scale);
+        }
+        let scroll = if response.hovered() { ui.input(|input| input.scroll_delta.y) } else { 0.0 };
+        if scroll == 0.0 {
+            return;
+        }
+        let zoom = (self.zoom * (scroll * ZOOM_SPEED).exp()).clamp(1.0, max_zoom.max(1.0));
+        if let Some(pointer) = response.hover_pos() {
+            // the world under the pointer stays where it is
+            let anchor = visible.min + (pointer - response.rect.min) / scale;
+            let center = self.center.unwrap_or(visible.center());
+            self.center = Some(anchor + (center - anchor) * self.zoom / zoom);
+        }
+        self.zoom = zoom;
+    }
+}
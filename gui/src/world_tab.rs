@@ -0,0 +1,206 @@
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use hex_brains_engine::draw::HexCells;
+use hex_brains_engine::energy_grid::EnergyGrid;
+use hex_brains_engine::events::{event_channel, EventCapacity, EventReceiver, EventSender};
+use hex_brains_engine::lineage::AncestryRecord;
+use hex_brains_engine::simulation::{EngineCommand, RunClock, RunMetadata, SimulationConfig, Stats};
+use crate::debugger::Debugger;
+use crate::diagnostics::DiagnosticsPanel;
+use crate::food_web::FoodWeb;
+use crate::genome_distances::GenomeDistances;
+use crate::history::ConfigHistory;
+use crate::interpolation::Interpolation;
+use crate::palette::{Palette, SpeciesColors};
+use crate::scenarios::Scenario;
+use crate::statistics::Statistics;
+use crate::viewport::Viewport;
+use crate::{start_simulation, Config};
+
+// One simulation with its own thread, channels, settings and everything the gui remembers about it
+pub struct WorldTab {
+    pub name: String,
+    pub status: String,
+    pub statistics: Statistics,
+    pub debugger: Debugger,
+    pub diagnostics: DiagnosticsPanel,
+    pub food_web: FoodWeb,
+    pub genome_distances: GenomeDistances,
+    pub history: ConfigHistory,
+    pub total_frames: usize,
+    // simulation time of the last drawn state
+    pub simulation_frame: u32,
+    // sent by the engine when the simulation starts
+    pub run: Option<RunMetadata>,
+    // sent along with every drawn frame
+    pub run_clock: RunClock,
+    pub engine_commands_sender: Sender<EngineCommand>,
+    pub engine_events_sender: EventSender,
+    pub engine_events_receiver: EventReceiver,
+    // what the event channel was created with, changed capacities take effect with the next simulation
+    event_capacity: EventCapacity,
+    engine_commands_receiver: Arc<Mutex<Receiver<EngineCommand>>>,
+    pub can_draw_frame: bool,
+    pub config: Config,
+    pub hexes: HexCells,
+    pub interpolation: Interpolation,
+    pub viewport: Viewport,
+    pub temperatures: Vec<f32>,
+    pub energy_grid: Option<EnergyGrid>,
+    pub updates_last_second: u32,
+    pub last_second: Instant,
+    pub frames_last_second: u32,
+    pub frames_per_second: u32,
+    pub updates_per_second: u32,
+    pub stats: Stats,
+    // the inspected snake followed by its ancestors
+    pub ancestors: Vec<AncestryRecord>,
+    pub species_colors: SpeciesColors,
+    pub simulation_config: SimulationConfig,
+    pub simulation_running: bool,
+    // only the next start draws its random numbers from it, restarts are random again
+    seed: Option<u64>,
+    simulation_thread: Option<JoinHandle<()>>,
+    pub selected_network: u32,
+}
+
+impl WorldTab {
+    pub fn new(name: String, config: Config, simulation_config: SimulationConfig) -> Self {
+        let (engine_commands_sender, engine_commands_receiver) = channel();
+        let (engine_events_sender, engine_events_receiver) = event_channel(config.event_capacity);
+        WorldTab {
+            name,
+            status: String::new(),
+            statistics: Statistics::new(),
+            debugger: Debugger::new(),
+            diagnostics: DiagnosticsPanel::new(),
+            food_web: FoodWeb::new(),
+            genome_distances: GenomeDistances::new(),
+            history: ConfigHistory::new(),
+            total_frames: 0,
+            simulation_frame: 0,
+            run: None,
+            run_clock: RunClock::default(),
+            engine_commands_sender,
+            engine_events_sender,
+            engine_events_receiver,
+            event_capacity: config.event_capacity,
+            engine_commands_receiver: Arc::new(Mutex::new(engine_commands_receiver)),
+            can_draw_frame: true,
+            config,
+            hexes: HexCells::default(),
+            interpolation: Interpolation::new(),
+            viewport: Viewport::new(),
+            temperatures: vec![],
+            energy_grid: None,
+            updates_last_second: 0,
+            last_second: Instant::now(),
+            frames_last_second: 0,
+            frames_per_second: 0,
+            updates_per_second: 0,
+            stats: Stats::default(),
+            ancestors: vec![],
+            species_colors: SpeciesColors::new(Palette::Vivid, true),
+            simulation_config,
+            simulation_running: false,
+            seed: None,
+            simulation_thread: None,
+            selected_network: 0,
+        }
+    }
+
+    // The world settings live in the gui config until the simulation starts
+    pub fn current_config(&self) -> SimulationConfig {
+        SimulationConfig { rows: self.config.rows, columns: self.config.columns, wall_layout: self.config.wall_layout, wall_seed: self.config.wall_seed, perimeter_walls: self.config.perimeter_walls, water_layout: self.config.water_layout, water_seed: self.config.water_seed, ..self.simulation_config.clone() }
+    }
+
+    // Metadata of the running world with the settings and frame as the gui currently knows them
+    pub fn run_metadata(&self) -> Option<RunMetadata> {
+        let mut run = self.run.clone()?;
+        run.update(&self.current_config(), self.simulation_frame, &self.run_clock);
+        Some(run)
+    }
+
+    // Waits for the simulation thread to finish its step, so nothing from the old world reaches the next one
+    pub fn stop_simulation(&mut self) {
+        if let Some(thread) = self.simulation_thread.take() {
+            self.engine_commands_sender.send(EngineCommand::StopSimulation).unwrap();
+            // the simulation may be waiting for room in a full event queue
+            while !thread.is_finished() {
+                self.engine_events_receiver.try_iter().for_each(drop);
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let _ = thread.join();
+        }
+        self.simulation_running = false;
+    }
+
+    // Everything shown about the world is dropped, the new simulation may even have a different size
+    pub fn start_simulation(&mut self, ctx: &egui::Context, draw_interval: u32) {
+        self.stop_simulation();
+        self.hexes = HexCells::default();
+        self.interpolation = Interpolation::new();
+        self.stats = Stats::default();
+        self.statistics = Statistics::new();
+        self.temperatures = vec![];
+        self.energy_grid = None;
+        self.ancestors = vec![];
+        self.species_colors = SpeciesColors::new(self.species_colors.palette, self.species_colors.remap);
+        self.debugger = Debugger::new();
+        self.diagnostics = DiagnosticsPanel::new();
+        self.food_web = FoodWeb::new();
+        self.genome_distances = GenomeDistances::new();
+        self.simulation_frame = 0;
+        self.run = None;
+        self.run_clock = RunClock::default();
+        if self.event_capacity != self.config.event_capacity {
+            self.event_capacity = self.config.event_capacity;
+            (self.engine_events_sender, self.engine_events_receiver) = event_channel(self.event_capacity);
+        }
+        self.simulation_thread = Some(start_simulation(&self.engine_events_sender, Arc::clone(&self.engine_commands_receiver), ctx.clone(), self.config, self.name.clone(), self.seed.take()));
+        self.engine_commands_sender.send(EngineCommand::SetDrawInterval(draw_interval)).unwrap();
+        self.simulation_running = true;
+    }
+
+    // Settings, seed and starting snakes of the scenario replace the running world
+    pub fn start_scenario(&mut self, ctx: &egui::Context, draw_interval: u32, scenario: Scenario) {
+        self.stop_simulation();
+        self.apply_config(scenario.config);
+        self.seed = Some(scenario.seed);
+        self.start_simulation(ctx, draw_interval);
+        // the snakes are created with the settings of the scenario
+        self.engine_commands_sender.send(EngineCommand::UpdateSimulationConfig(Box::new(self.simulation_config.clone()))).unwrap();
+        self.engine_commands_sender.send(EngineCommand::CreateSnakes(scenario.random_snakes)).unwrap();
+        for (genome, copies) in scenario.genomes {
+            self.engine_commands_sender.send(EngineCommand::SpawnGenome(Box::new(genome), copies)).unwrap();
+        }
+    }
+
+    // Walls and water only change when the next simulation starts, the size is applied right away
+    pub fn apply_config(&mut self, config: SimulationConfig) {
+        let resized = config.rows != self.config.rows || config.columns != self.config.columns;
+        self.config.rows = config.rows;
+        self.config.columns = config.columns;
+        self.config.wall_layout = config.wall_layout;
+        self.config.wall_seed = config.wall_seed;
+        self.config.perimeter_walls = config.perimeter_walls;
+        self.config.water_layout = config.water_layout;
+        self.config.water_seed = config.water_seed;
+        self.simulation_config = config;
+        if resized && self.simulation_running {
+            self.engine_commands_sender.send(EngineCommand::ResizeWorld(self.config.rows, self.config.columns)).unwrap();
+        }
+    }
+
+    pub fn update_rates(&mut self) {
+        if self.last_second.elapsed().as_millis() > 1000 {
+            self.last_second = Instant::now();
+            self.updates_per_second = self.updates_last_second;
+            self.frames_per_second = self.frames_last_second;
+            self.updates_last_second = 0;
+            self.frames_last_second = 0;
+        }
+    }
+}